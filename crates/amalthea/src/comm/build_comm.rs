@@ -0,0 +1,157 @@
+// @generated
+
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *--------------------------------------------------------------------------------------------*/
+
+//
+// AUTO-GENERATED from build.json; do not edit.
+//
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The outcome of a build
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BuildResult {
+	/// Whether the build succeeded
+	pub success: bool,
+
+	/// The exit status of the build process
+	pub status: i64,
+
+	/// The number of compiler errors
+	pub errors: i64,
+
+	/// The number of compiler warnings
+	pub warnings: i64
+}
+
+/// Possible values for Source in Output
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, strum_macros::Display)]
+pub enum BuildOutputSource {
+	#[serde(rename = "stdout")]
+	#[strum(to_string = "stdout")]
+	Stdout,
+
+	#[serde(rename = "stderr")]
+	#[strum(to_string = "stderr")]
+	Stderr
+}
+
+/// Possible values for Severity in Diagnostic
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, strum_macros::Display)]
+pub enum BuildDiagnosticSeverity {
+	#[serde(rename = "error")]
+	#[strum(to_string = "error")]
+	Error,
+
+	#[serde(rename = "warning")]
+	#[strum(to_string = "warning")]
+	Warning,
+
+	#[serde(rename = "note")]
+	#[strum(to_string = "note")]
+	Note
+}
+
+/// Parameters for the Build method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BuildParams {
+	/// The path of the package to build
+	pub path: String,
+
+	/// Whether to install the package in the user library. Otherwise the
+	/// package is installed in a temporary library, which checks that it
+	/// compiles.
+	pub install: bool,
+}
+
+/// Parameters for the Output method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct OutputParams {
+	/// The stream of the build process the line was written to
+	pub source: BuildOutputSource,
+
+	/// The line of output, without its trailing newline
+	pub line: String,
+}
+
+/// Parameters for the Diagnostic method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DiagnosticParams {
+	/// The path of the source file, as reported by the compiler
+	pub file: String,
+
+	/// The 1-based line of the diagnostic
+	pub line: i64,
+
+	/// The 1-based column of the diagnostic, if reported
+	pub column: Option<i64>,
+
+	/// The severity of the diagnostic
+	pub severity: BuildDiagnosticSeverity,
+
+	/// The compiler message
+	pub message: String,
+}
+
+/**
+ * Backend RPC request types for the build comm
+ */
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", content = "params")]
+pub enum BuildBackendRequest {
+	/// Build a package
+	///
+	/// Builds and installs the package, streaming the output of the build
+	/// with `output` events and compiler diagnostics with `diagnostic`
+	/// events. Replies once the build has finished.
+	#[serde(rename = "build")]
+	Build(BuildParams),
+
+}
+
+/**
+ * Backend RPC Reply types for the build comm
+ */
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", content = "result")]
+pub enum BuildBackendReply {
+	/// The outcome of the build
+	BuildReply(BuildResult),
+
+}
+
+/**
+ * Frontend RPC request types for the build comm
+ */
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", content = "params")]
+pub enum BuildFrontendRequest {
+}
+
+/**
+ * Frontend RPC Reply types for the build comm
+ */
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", content = "result")]
+pub enum BuildFrontendReply {
+}
+
+/**
+ * Frontend events for the build comm
+ */
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", content = "params")]
+pub enum BuildFrontendEvent {
+	/// A line of output of the build process
+	#[serde(rename = "output")]
+	Output(OutputParams),
+
+	/// A compiler error, warning, or note parsed from the build output
+	#[serde(rename = "diagnostic")]
+	Diagnostic(DiagnosticParams),
+
+}
+
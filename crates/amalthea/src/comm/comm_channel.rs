@@ -35,6 +35,9 @@ pub enum Comm {
     /// The Positron frontend.
     Ui,
 
+    /// The output of package builds.
+    Build,
+
     /// Some other comm with a custom name.
     Other(String),
 }
@@ -51,10 +54,45 @@ pub enum CommMsg {
     /// used for events.
     Data(Value),
 
+    /// A data message whose delivery should be acknowledged. The first value
+    /// is the unique ID of the receipt and the second value is the data. When
+    /// sent by the back end, the receipt comes back on the comm's incoming
+    /// channel as a `CommMsg::Receipt`.
+    DataWithReceipt(String, Value),
+
+    /// A delivery receipt for a message, identified by its receipt ID.
+    Receipt(String),
+
     // A message indicating that the comm channel should be closed.
     Close,
 }
 
+/// The field of a comm message's data requesting a delivery receipt.
+pub const COMM_RECEIPT_ID_KEY: &str = "receipt_id";
+
+/// The method of delivery receipts on the wire. Dunder-named so it can't
+/// collide with the methods of the comms themselves.
+pub const COMM_RECEIPT_METHOD: &str = "__receipt__";
+
+/// The wire representation of a delivery receipt, shaped like the events of
+/// Positron comms.
+pub fn comm_receipt_json(receipt_id: &str) -> Value {
+    serde_json::json!({
+        "method": COMM_RECEIPT_METHOD,
+        "params": { COMM_RECEIPT_ID_KEY: receipt_id }
+    })
+}
+
+/// The receipt ID of a delivery receipt received on the wire, if `data` is
+/// one.
+pub fn comm_receipt_id(data: &Value) -> Option<String> {
+    if data.get("method")?.as_str()? != COMM_RECEIPT_METHOD {
+        return None;
+    }
+    let id = data.get("params")?.get(COMM_RECEIPT_ID_KEY)?.as_str()?;
+    Some(id.to_string())
+}
+
 impl MessageType for UiFrontendRequest {
     fn message_type() -> String {
         String::from("rpc_request")
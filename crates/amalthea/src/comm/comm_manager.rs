@@ -12,10 +12,13 @@ use crossbeam::channel::Select;
 use crossbeam::channel::Sender;
 use log::info;
 use log::warn;
+use serde_json::Value;
 use stdext::result::ResultOrLog;
 use stdext::spawn;
 
+use crate::comm::comm_channel::comm_receipt_json;
 use crate::comm::comm_channel::CommMsg;
+use crate::comm::comm_channel::COMM_RECEIPT_ID_KEY;
 use crate::comm::event::CommInfo;
 use crate::comm::event::CommManagerEvent;
 use crate::comm::event::CommManagerInfoReply;
@@ -33,6 +36,10 @@ pub struct CommManager {
     iopub_tx: Sender<IOPubMessage>,
     comm_event_rx: Receiver<CommManagerEvent>,
     pending_rpcs: HashMap<String, JupyterHeader>,
+
+    /// Receipts requested by the back end that the frontend hasn't sent yet,
+    /// mapped to the ID of the comm that requested them
+    pending_receipts: HashMap<String, String>,
 }
 
 impl CommManager {
@@ -63,6 +70,7 @@ impl CommManager {
             comm_event_rx,
             open_comms: Vec::<CommSocket>::new(),
             pending_rpcs: HashMap::<String, JupyterHeader>::new(),
+            pending_receipts: HashMap::<String, String>::new(),
         }
     }
 
@@ -139,7 +147,19 @@ impl CommManager {
                         let comm = self.open_comms.get(index).unwrap();
                         log::trace!("Comm manager: Sending message to comm '{}'", comm.comm_name);
 
+                        let (msg, receipt_id) = take_receipt_request(msg);
                         comm.incoming_tx.send(msg).unwrap();
+
+                        // The message is delivered, acknowledge it if the
+                        // frontend asked us to
+                        if let Some(receipt_id) = receipt_id {
+                            self.iopub_tx
+                                .send(IOPubMessage::CommMsgEvent(CommWireMsg {
+                                    comm_id: comm_id.clone(),
+                                    data: comm_receipt_json(&receipt_id),
+                                }))
+                                .unwrap();
+                        }
                     } else {
                         log::warn!(
                             "Received message for unknown comm channel {}: {:?}",
@@ -149,8 +169,34 @@ impl CommManager {
                     }
                 },
 
+                // A receipt was received from the frontend; relay it to the
+                // comm that requested it
+                CommManagerEvent::Receipt(comm_id, receipt_id) => {
+                    if self.pending_receipts.remove(&receipt_id).is_none() {
+                        // Likely requested by a comm that has been closed
+                        // since, in which case nobody is waiting for it
+                        log::trace!(
+                            "Discarding receipt {receipt_id} for closed or unknown comm {comm_id}"
+                        );
+                        return;
+                    }
+
+                    let comm = self
+                        .open_comms
+                        .iter()
+                        .find(|comm_socket| comm_socket.comm_id == comm_id);
+
+                    if let Some(comm) = comm {
+                        comm.incoming_tx
+                            .send(CommMsg::Receipt(receipt_id))
+                            .or_log_error("Failed to send receipt to comm.");
+                    }
+                },
+
                 // A Comm was closed; attempt to remove it from the set of open comms
                 CommManagerEvent::Closed(comm_id) => {
+                    self.discard_pending_receipts(&comm_id);
+
                     // Find the index of the comm in the vector
                     let index = self
                         .open_comms
@@ -246,13 +292,67 @@ impl CommManager {
                     }
                 },
 
-                CommMsg::Close => IOPubMessage::CommClose(CommClose {
+                // The comm wants to know when the frontend has received this
+                // message. The receipt ID is sent along with the data.
+                CommMsg::DataWithReceipt(receipt_id, mut data) => {
+                    match data.as_object_mut() {
+                        Some(object) => {
+                            object.insert(
+                                String::from(COMM_RECEIPT_ID_KEY),
+                                Value::String(receipt_id.clone()),
+                            );
+                            self.pending_receipts
+                                .insert(receipt_id, comm_socket.comm_id.clone());
+                        },
+                        None => {
+                            log::warn!(
+                                "Can't request a receipt for non-object data on comm '{}'",
+                                comm_socket.comm_name
+                            );
+                        },
+                    }
+                    IOPubMessage::CommMsgEvent(CommWireMsg {
+                        comm_id: comm_socket.comm_id.clone(),
+                        data,
+                    })
+                },
+
+                // The comm acknowledges a message from the frontend
+                CommMsg::Receipt(receipt_id) => IOPubMessage::CommMsgEvent(CommWireMsg {
                     comm_id: comm_socket.comm_id.clone(),
+                    data: comm_receipt_json(&receipt_id),
                 }),
+
+                CommMsg::Close => {
+                    let comm_id = comm_socket.comm_id.clone();
+                    self.discard_pending_receipts(&comm_id);
+                    IOPubMessage::CommClose(CommClose { comm_id })
+                },
             };
 
             // Deliver the message to the frontend
             self.iopub_tx.send(msg).unwrap();
         }
     }
+
+    /// Forget the receipts requested by a comm that is closing. Receipts that
+    /// still come back for them are discarded.
+    fn discard_pending_receipts(&mut self, comm_id: &str) {
+        self.pending_receipts.retain(|_, id| id != comm_id);
+    }
+}
+
+/// Strip the receipt request from a message received from the frontend,
+/// returning the message and the ID of the requested receipt
+fn take_receipt_request(msg: CommMsg) -> (CommMsg, Option<String>) {
+    let CommMsg::Rpc(id, mut data) = msg else {
+        return (msg, None);
+    };
+
+    let receipt_id = data
+        .as_object_mut()
+        .and_then(|object| object.remove(COMM_RECEIPT_ID_KEY))
+        .and_then(|value| value.as_str().map(String::from));
+
+    (CommMsg::Rpc(id, data), receipt_id)
 }
@@ -138,7 +138,19 @@ pub struct FormatOptions {
 	pub max_value_length: i64,
 
 	/// Thousands separator string
-	pub thousands_sep: Option<String>
+	pub thousands_sep: Option<String>,
+
+	/// Decimal separator string. The session's `OutDec` option is used when
+	/// unset.
+	pub decimal_sep: Option<String>,
+
+	/// Number of significant digits to display for numbers, instead of a
+	/// fixed number of decimal places
+	pub significant_digits: Option<i64>,
+
+	/// Format for dates and date-times, in the format of `strftime()`. The
+	/// format of the column's class is used when unset.
+	pub date_format: Option<String>
 }
 
 /// The schema for a table-like object
@@ -636,7 +648,11 @@ pub struct ColumnSelection {
 	pub column_index: i64,
 
 	/// Union of selection specifications for array_selection
-	pub spec: ArraySelection
+	pub spec: ArraySelection,
+
+	/// Formatting options for this column, overriding the formatting options
+	/// of the request
+	pub format_options: Option<FormatOptions>
 }
 
 /// Possible values for ColumnDisplayType
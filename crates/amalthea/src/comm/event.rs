@@ -26,6 +26,10 @@ pub enum CommManagerEvent {
     /// An RPC was received from the frontend
     PendingRpc(JupyterHeader),
 
+    /// A delivery receipt was received from the frontend; the first value is
+    /// the comm ID, and the second value is the receipt ID.
+    Receipt(String, String),
+
     /// A Comm was closed
     Closed(String),
 
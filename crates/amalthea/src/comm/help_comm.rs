@@ -22,16 +22,67 @@ pub enum ShowHelpKind {
 	#[strum(to_string = "markdown")]
 	Markdown,
 
+	#[serde(rename = "text")]
+	#[strum(to_string = "text")]
+	Text,
+
 	#[serde(rename = "url")]
 	#[strum(to_string = "url")]
 	Url
 }
 
+/// Possible values for ContentFormat in ShowHelpTopic
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, strum_macros::Display)]
+pub enum ShowHelpTopicContentFormat {
+	#[serde(rename = "url")]
+	#[strum(to_string = "url")]
+	Url,
+
+	#[serde(rename = "html")]
+	#[strum(to_string = "html")]
+	Html,
+
+	#[serde(rename = "markdown")]
+	#[strum(to_string = "markdown")]
+	Markdown,
+
+	#[serde(rename = "text")]
+	#[strum(to_string = "text")]
+	Text
+}
+
+/// Possible values for AssetLinks in ShowHelpTopic
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, strum_macros::Display)]
+pub enum ShowHelpTopicAssetLinks {
+	#[serde(rename = "relative")]
+	#[strum(to_string = "relative")]
+	Relative,
+
+	#[serde(rename = "absolute")]
+	#[strum(to_string = "absolute")]
+	Absolute,
+
+	#[serde(rename = "inline")]
+	#[strum(to_string = "inline")]
+	Inline
+}
+
 /// Parameters for the ShowHelpTopic method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct ShowHelpTopicParams {
 	/// The help topic to show
 	pub topic: String,
+
+	/// How the help content is delivered in the Show Help notification.
+	/// Defaults to a URL served by the help server. Other formats are
+	/// rendered from the topic's Rd by the backend.
+	pub content_format: Option<ShowHelpTopicContentFormat>,
+
+	/// How links to the assets of HTML help content, such as the stylesheet
+	/// and figures, are delivered. Defaults to links relative to the help
+	/// server. Absolute links point to the help server, inlined assets make
+	/// the content self-contained.
+	pub asset_links: Option<ShowHelpTopicAssetLinks>,
 }
 
 /// Parameters for the ShowHelp method.
@@ -62,6 +113,14 @@ pub enum HelpBackendRequest {
 	#[serde(rename = "show_help_topic")]
 	ShowHelpTopic(ShowHelpTopicParams),
 
+	/// Restart the help server
+	///
+	/// Stops and restarts the R help server, e.g. when it can no longer serve
+	/// help pages after a port conflict. Help URLs requested while the server
+	/// restarts fail and can be requested again.
+	#[serde(rename = "restart_help_server")]
+	RestartHelpServer,
+
 }
 
 /**
@@ -74,6 +133,9 @@ pub enum HelpBackendReply {
 	/// Help notification.
 	ShowHelpTopicReply(bool),
 
+	/// The port of the restarted help server.
+	RestartHelpServerReply(i64),
+
 }
 
 /**
@@ -10,6 +10,8 @@
 // https://github.com/rust-lang/rustfmt/issues/5080
 
 pub mod base_comm;
+#[rustfmt::skip]
+pub mod build_comm;
 pub mod comm_channel;
 pub mod comm_manager;
 #[rustfmt::skip]
@@ -93,6 +93,14 @@ pub struct RenderParams {
 	pub format: RenderFormat,
 }
 
+/// Parameters for the GetPlotSvg method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GetPlotSvgParams {
+	/// The requested size of the plot. If not provided, the plot will be
+	/// rendered at the default size of the SVG device.
+	pub size: Option<PlotSize>,
+}
+
 /**
  * Backend RPC request types for the plot comm
  */
@@ -113,6 +121,14 @@ pub enum PlotBackendRequest {
 	#[serde(rename = "render")]
 	Render(RenderParams),
 
+	/// Render a plot as SVG
+	///
+	/// Re-renders the plot through an SVG device and returns the SVG
+	/// document. Unlike the SVG format of `render`, the text isn't
+	/// base64-encoded, which makes it suitable for vector export.
+	#[serde(rename = "get_plot_svg")]
+	GetPlotSvg(GetPlotSvgParams),
+
 }
 
 /**
@@ -127,6 +143,9 @@ pub enum PlotBackendReply {
 	/// A rendered plot
 	RenderReply(PlotResult),
 
+	/// The SVG document of the plot
+	GetPlotSvgReply(String),
+
 }
 
 /**
@@ -97,6 +97,62 @@ pub struct Range {
 	pub end: Position
 }
 
+/// Possible values for Status in StartupPackage
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, strum_macros::Display)]
+pub enum StartupPackageStatus {
+	#[serde(rename = "attached")]
+	#[strum(to_string = "attached")]
+	Attached,
+
+	#[serde(rename = "not_installed")]
+	#[strum(to_string = "not_installed")]
+	NotInstalled,
+
+	#[serde(rename = "failed")]
+	#[strum(to_string = "failed")]
+	Failed
+}
+
+/// Possible values for Severity in ShowNotification
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, strum_macros::Display)]
+pub enum NotificationSeverity {
+	#[serde(rename = "info")]
+	#[strum(to_string = "info")]
+	Info,
+
+	#[serde(rename = "warning")]
+	#[strum(to_string = "warning")]
+	Warning,
+
+	#[serde(rename = "error")]
+	#[strum(to_string = "error")]
+	Error
+}
+
+/// Possible values for Status in TestResult
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, strum_macros::Display)]
+pub enum TestResultStatus {
+	#[serde(rename = "passed")]
+	#[strum(to_string = "passed")]
+	Passed,
+
+	#[serde(rename = "failed")]
+	#[strum(to_string = "failed")]
+	Failed,
+
+	#[serde(rename = "skipped")]
+	#[strum(to_string = "skipped")]
+	Skipped,
+
+	#[serde(rename = "error")]
+	#[strum(to_string = "error")]
+	Error,
+
+	#[serde(rename = "load_error")]
+	#[strum(to_string = "load_error")]
+	LoadError
+}
+
 /// Parameters for the CallMethod method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct CallMethodParams {
@@ -107,6 +163,13 @@ pub struct CallMethodParams {
 	pub params: Vec<Param>,
 }
 
+/// Parameters for the Pong method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PongParams {
+	/// The sequence number of the `ping` being answered
+	pub seq: i64,
+}
+
 /// Parameters for the Busy method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct BusyParams {
@@ -144,6 +207,16 @@ pub struct ShowMessageParams {
 	pub message: String,
 }
 
+/// Parameters for the ShowNotification method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ShowNotificationParams {
+	/// The text of the notification
+	pub message: String,
+
+	/// The severity of the notification
+	pub severity: NotificationSeverity,
+}
+
 /// Parameters for the ShowQuestion method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct ShowQuestionParams {
@@ -170,6 +243,33 @@ pub struct ShowDialogParams {
 	pub message: String,
 }
 
+/// Parameters for the ShowFilePicker method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ShowFilePickerParams {
+	/// The title of the dialog
+	pub title: String,
+
+	/// Whether several files can be chosen
+	pub multiple: bool,
+
+	/// Whether to choose folders instead of files
+	pub directory: bool,
+
+	/// Whether the chosen file doesn't need to exist, e.g. to save to a new
+	/// file
+	pub new: bool,
+}
+
+/// Parameters for the ChooseRestart method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ChooseRestartParams {
+	/// The message of the error
+	pub message: String,
+
+	/// The names of the restarts the user can choose from
+	pub restarts: Vec<String>,
+}
+
 /// Parameters for the PromptState method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct PromptStateParams {
@@ -277,6 +377,107 @@ pub struct ShowHtmlFileParams {
 	pub height: i64,
 }
 
+/// Parameters for the DevContext method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DevContextParams {
+	/// The name of the package under development
+	pub package: String,
+
+	/// Whether the session entered (true) or exited (false) the development
+	/// context of the package
+	pub active: bool,
+}
+
+/// Parameters for the StartupPackage method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct StartupPackageParams {
+	/// The name of the package
+	pub package: String,
+
+	/// Whether the package was attached, isn't installed, or failed to attach
+	pub status: StartupPackageStatus,
+
+	/// The error message when the package failed to attach
+	pub message: Option<String>,
+}
+
+/// Parameters for the TestResult method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TestResultParams {
+	/// The path of the test file or directory being run
+	pub path: String,
+
+	/// The test file the result comes from
+	pub file: Option<String>,
+
+	/// The description of the test, empty for load errors
+	pub test: String,
+
+	/// Whether the test passed, failed, was skipped, or errored. Load errors
+	/// are reported for test files that fail before their tests can run.
+	pub status: TestResultStatus,
+
+	/// The failure, error, or skip message
+	pub message: Option<String>,
+
+	/// The 1-based line of the failing expectation or of the test
+	pub line: Option<i64>,
+}
+
+/// Parameters for the StatusStart method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct StatusStartParams {
+	/// The identifier of the status, unique within the session
+	pub id: String,
+
+	/// The text to show along with the spinner, e.g. "Fitting model..."
+	pub label: String,
+
+	/// The identifier of the enclosing status, if any
+	pub parent: Option<String>,
+}
+
+/// Parameters for the StatusEnd method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct StatusEndParams {
+	/// The identifier of the status
+	pub id: String,
+
+	/// Whether the operation ended with an error
+	pub error: bool,
+}
+
+/// Parameters for the Progress method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProgressParams {
+	/// The identifier of the operation, unique within the session
+	pub id: String,
+
+	/// The text to show along with the progress bar, e.g. "Downloading
+	/// data.csv"
+	pub label: String,
+
+	/// The amount of work done so far, e.g. the number of bytes downloaded
+	pub value: f64,
+
+	/// The total amount of work, if known. Without a total, the frontend
+	/// shows the value without a percentage.
+	pub total: Option<f64>,
+
+	/// The units of the value and the total, e.g. "bytes"
+	pub units: String,
+
+	/// Whether the operation has finished
+	pub done: bool,
+}
+
+/// Parameters for the Ping method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PingParams {
+	/// The sequence number of the heartbeat, echoed back by `pong`
+	pub seq: i64,
+}
+
 /**
  * Backend RPC request types for the ui comm
  */
@@ -291,6 +492,13 @@ pub enum UiBackendRequest {
 	#[serde(rename = "call_method")]
 	CallMethod(CallMethodParams),
 
+	/// Answer a heartbeat
+	///
+	/// Sent by the frontend in response to a `ping` event, to let the
+	/// backend know that it is still connected
+	#[serde(rename = "pong")]
+	Pong(PongParams),
+
 }
 
 /**
@@ -302,6 +510,9 @@ pub enum UiBackendReply {
 	/// The method result
 	CallMethodReply(CallMethodResult),
 
+	/// Reply for the pong method (no result)
+	PongReply(),
+
 }
 
 /**
@@ -374,6 +585,21 @@ pub enum UiFrontendRequest {
 	#[serde(rename = "last_active_editor_context")]
 	LastActiveEditorContext,
 
+	/// Show a file picker
+	///
+	/// Use this to let the user choose files or folders with a native file
+	/// dialog. The result is null if the user cancelled the dialog.
+	#[serde(rename = "show_file_picker")]
+	ShowFilePicker(ShowFilePickerParams),
+
+	/// Choose a restart
+	///
+	/// Use this to let the user choose one of the restarts available when an
+	/// error is signalled. The result is null if the user declined, in which
+	/// case the error is handled as usual.
+	#[serde(rename = "choose_restart")]
+	ChooseRestart(ChooseRestartParams),
+
 }
 
 /**
@@ -412,6 +638,12 @@ pub enum UiFrontendReply {
 	/// Editor metadata
 	LastActiveEditorContextReply(Option<EditorContext>),
 
+	/// The chosen paths, or null if the user cancelled the dialog
+	ShowFilePickerReply(Option<Vec<String>>),
+
+	/// The name of the chosen restart, or null if the user declined
+	ChooseRestartReply(Option<String>),
+
 }
 
 /**
@@ -439,6 +671,11 @@ pub enum UiFrontendEvent {
 	#[serde(rename = "show_message")]
 	ShowMessage(ShowMessageParams),
 
+	/// Show a non-blocking notification, e.g. to let the user know that a
+	/// long running job has finished
+	#[serde(rename = "show_notification")]
+	ShowNotification(ShowNotificationParams),
+
 	/// Languages like R allow users to change the way their prompts look.
 	/// This event signals a change in the prompt configuration.
 	#[serde(rename = "prompt_state")]
@@ -466,6 +703,43 @@ pub enum UiFrontendEvent {
 	#[serde(rename = "show_html_file")]
 	ShowHtmlFile(ShowHtmlFileParams),
 
+	/// This event signals that a package under development was loaded in the
+	/// session, e.g. with `devtools::load_all()`, or unloaded.
+	#[serde(rename = "dev_context")]
+	DevContext(DevContextParams),
+
+	/// This event reports the outcome of attaching one of the packages
+	/// configured to be attached at startup.
+	#[serde(rename = "startup_package")]
+	StartupPackage(StartupPackageParams),
+
+	/// This event reports the result of a single test while a test run
+	/// started with the `runTests` method is in progress.
+	#[serde(rename = "test_result")]
+	TestResult(TestResultParams),
+
+	/// This event signals the start of a long running operation, so the
+	/// frontend can show its label along with a spinner until the matching
+	/// `status_end` event.
+	#[serde(rename = "status_start")]
+	StatusStart(StatusStartParams),
+
+	/// This event signals the end of an operation started with
+	/// `status_start`.
+	#[serde(rename = "status_end")]
+	StatusEnd(StatusEndParams),
+
+	/// This event reports the progress of a long running operation, such as
+	/// a download. Events with the same `id` update the same progress bar.
+	#[serde(rename = "progress")]
+	Progress(ProgressParams),
+
+	/// A heartbeat, sent periodically when the backend is configured to
+	/// detect a frontend that went away without closing the comm. The
+	/// frontend answers with the `pong` method.
+	#[serde(rename = "ping")]
+	Ping(PingParams),
+
 }
 
 /**
@@ -486,6 +760,8 @@ pub fn ui_frontend_reply_from_value(
 		UiFrontendRequest::WorkspaceFolder => Ok(UiFrontendReply::WorkspaceFolderReply(serde_json::from_value(reply)?)),
 		UiFrontendRequest::ModifyEditorSelections(_) => Ok(UiFrontendReply::ModifyEditorSelectionsReply()),
 		UiFrontendRequest::LastActiveEditorContext => Ok(UiFrontendReply::LastActiveEditorContextReply(serde_json::from_value(reply)?)),
+		UiFrontendRequest::ShowFilePicker(_) => Ok(UiFrontendReply::ShowFilePickerReply(serde_json::from_value(reply)?)),
+		UiFrontendRequest::ChooseRestart(_) => Ok(UiFrontendReply::ChooseRestartReply(serde_json::from_value(reply)?)),
 	}
 }
 
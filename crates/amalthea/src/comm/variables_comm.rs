@@ -44,6 +44,37 @@ pub struct FormattedVariable {
 	pub content: String
 }
 
+/// The display properties of a variable, or the error raised while
+/// resolving it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct VariableDescription {
+	/// The path to the variable, as an array of access keys.
+	pub path: Vec<String>,
+
+	/// A formatted, human-readable representation of the variable's value
+	pub display_value: Option<String>,
+
+	/// A short, human-readable representation of the variable's type
+	pub display_type: Option<String>,
+
+	/// Whether the variable has child variables
+	pub has_children: Option<bool>,
+
+	/// The kind of value the variable represents
+	pub kind: Option<VariableKind>,
+
+	/// The error raised while resolving the variable, if any. The display
+	/// properties are unset in that case.
+	pub error: Option<String>
+}
+
+/// The text lines R produces when printing a variable.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FormattedValue {
+	/// The lines of printed output
+	pub lines: Vec<String>
+}
+
 /// A single variable in the runtime.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Variable {
@@ -85,6 +116,14 @@ pub struct Variable {
 	/// variable's value
 	pub is_truncated: bool,
 
+	/// True if the variable is an active binding, whose value is computed by
+	/// a function each time it is accessed. The value of active bindings is
+	/// never evaluated by the runtime.
+	pub is_active_binding: bool,
+
+	/// True if the variable's binding is locked and can't be reassigned
+	pub is_locked: bool,
+
 	/// The time the variable was created or updated, in milliseconds since
 	/// the epoch, or 0 if unknown.
 	pub updated_time: i64
@@ -102,6 +141,18 @@ pub enum ClipboardFormatFormat {
 	TextPlain
 }
 
+/// Possible values for SortOrder in SetSortOrder
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, strum_macros::Display)]
+pub enum SetSortOrderSortOrder {
+	#[serde(rename = "name")]
+	#[strum(to_string = "name")]
+	Name,
+
+	#[serde(rename = "definition")]
+	#[strum(to_string = "definition")]
+	Definition
+}
+
 /// Possible values for Kind in Variable
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, strum_macros::Display)]
 pub enum VariableKind {
@@ -189,6 +240,24 @@ pub struct ClipboardFormatParams {
 	pub format: ClipboardFormatFormat,
 }
 
+/// Parameters for the FormatValue method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FormatValueParams {
+	/// The path to the variable to format, as an array of access keys.
+	pub path: Vec<String>,
+
+	/// The console width, in characters, to use when printing the variable
+	pub width: i64,
+}
+
+/// Parameters for the Describe method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DescribeParams {
+	/// The paths to the variables to describe, each as an array of access
+	/// keys.
+	pub paths: Vec<Vec<String>>,
+}
+
 /// Parameters for the View method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct ViewParams {
@@ -196,6 +265,14 @@ pub struct ViewParams {
 	pub path: Vec<String>,
 }
 
+/// Parameters for the SetSortOrder method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SetSortOrderParams {
+	/// The order of the variables: by name, or in the order they were
+	/// defined in the session
+	pub sort_order: SetSortOrderSortOrder,
+}
+
 /// Parameters for the Update method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct UpdateParams {
@@ -227,6 +304,14 @@ pub struct RefreshParams {
 	pub version: i64,
 }
 
+/// Parameters for the Cleared method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ClearedParams {
+	/// The version of the view (incremented with each update), or 0 if the
+	/// backend doesn't track versions.
+	pub version: i64,
+}
+
 /**
  * Backend RPC request types for the variables comm
  */
@@ -264,6 +349,21 @@ pub enum VariablesBackendRequest {
 	#[serde(rename = "clipboard_format")]
 	ClipboardFormat(ClipboardFormatParams),
 
+	/// Format a variable as it would print
+	///
+	/// Requests the text output R would produce when printing a variable at
+	/// the given console width.
+	#[serde(rename = "format_value")]
+	FormatValue(FormatValueParams),
+
+	/// Describe a batch of variables
+	///
+	/// Returns the display value, display type, whether it has children, and
+	/// kind of each variable, in a single round-trip. A variable that can't
+	/// be resolved gets an error without failing the batch.
+	#[serde(rename = "describe")]
+	Describe(DescribeParams),
+
 	/// Request a viewer for a variable
 	///
 	/// Request that the runtime open a data viewer to display the data in a
@@ -271,6 +371,13 @@ pub enum VariablesBackendRequest {
 	#[serde(rename = "view")]
 	View(ViewParams),
 
+	/// Set the order of the variables
+	///
+	/// Sets the order in which variables are listed and returns the variables
+	/// in that order. Variables are sorted by name by default.
+	#[serde(rename = "set_sort_order")]
+	SetSortOrder(SetSortOrderParams),
+
 }
 
 /**
@@ -294,9 +401,19 @@ pub enum VariablesBackendReply {
 	/// An object formatted for copying to the clipboard.
 	ClipboardFormatReply(FormattedVariable),
 
+	/// The text lines R produces when printing a variable.
+	FormatValueReply(FormattedValue),
+
+	/// The descriptions of the variables, in the order of the requested
+	/// paths.
+	DescribeReply(Vec<VariableDescription>),
+
 	/// The ID of the viewer that was opened.
 	ViewReply(String),
 
+	/// A view containing a list of variables in the session.
+	SetSortOrderReply(VariableList),
+
 }
 
 /**
@@ -330,5 +447,11 @@ pub enum VariablesFrontendEvent {
 	#[serde(rename = "refresh")]
 	Refresh(RefreshParams),
 
+	/// All variables in the current session have been removed, e.g. because
+	/// the environment was cleared. The frontend should reset the state of
+	/// its view, like expanded variables and selections.
+	#[serde(rename = "cleared")]
+	Cleared(ClearedParams),
+
 }
 
@@ -9,14 +9,20 @@ use assert_matches::assert_matches;
 use rand::Rng;
 use serde_json::Value;
 
+use crate::comm::base_comm::JsonRpcReply;
+use crate::comm::base_comm::JsonRpcResult;
+use crate::comm::ui_comm::UiFrontendRequest;
 use crate::connection_file::ConnectionFile;
 use crate::registration_file::RegistrationFile;
 use crate::session::Session;
 use crate::socket::socket::Socket;
+use crate::wire::comm_open::CommOpen;
 use crate::wire::execute_input::ExecuteInput;
 use crate::wire::execute_request::ExecuteRequest;
 use crate::wire::handshake_reply::HandshakeReply;
 use crate::wire::input_reply::InputReply;
+use crate::wire::input_request::InputRequest;
+use crate::wire::interrupt_request::InterruptRequest;
 use crate::wire::jupyter_message::JupyterMessage;
 use crate::wire::jupyter_message::Message;
 use crate::wire::jupyter_message::ProtocolMessage;
@@ -36,12 +42,15 @@ pub struct DummyConnection {
 }
 
 pub struct DummyFrontend {
-    pub _control_socket: Socket,
+    pub control_socket: Socket,
     pub shell_socket: Socket,
     pub iopub_socket: Socket,
     pub stdin_socket: Socket,
     pub heartbeat_socket: Socket,
     session: Session,
+
+    /// Whether comm messages received on IOPub are skipped
+    skip_iopub_comm_msgs: bool,
 }
 
 pub struct ExecuteRequestOptions {
@@ -132,7 +141,7 @@ impl DummyFrontend {
         // the Jupyter specification, these must share a ZeroMQ identity.
         let shell_id = rand::thread_rng().gen::<[u8; 16]>();
 
-        let _control_socket = Socket::new(
+        let control_socket = Socket::new(
             connection.session.clone(),
             connection.ctx.clone(),
             String::from("Control"),
@@ -197,12 +206,13 @@ impl DummyFrontend {
         });
 
         Self {
-            _control_socket,
+            control_socket,
             shell_socket,
             iopub_socket,
             stdin_socket,
             heartbeat_socket,
             session: connection.session,
+            skip_iopub_comm_msgs: false,
         }
     }
 
@@ -220,9 +230,33 @@ impl DummyFrontend {
             user_expressions: serde_json::Value::Null,
             allow_stdin: options.allow_stdin,
             stop_on_error: false,
+            supported_mimetypes: None,
+            package_context: None,
         })
     }
 
+    /// Sends a Jupyter message on the Control socket; returns the ID of the newly
+    /// created message
+    pub fn send_control<T: ProtocolMessage>(&self, msg: T) -> String {
+        Self::send(&self.control_socket, &self.session, msg)
+    }
+
+    pub fn send_interrupt_request(&self) -> String {
+        self.send_control(InterruptRequest {})
+    }
+
+    /// Sends a `CommOpen` message on the Shell socket; returns the ID of the
+    /// new comm
+    pub fn send_comm_open(&self, target_name: &str, data: Value) -> String {
+        let comm_id = uuid::Uuid::new_v4().to_string();
+        self.send_shell(CommOpen {
+            comm_id: comm_id.clone(),
+            target_name: String::from(target_name),
+            data,
+        });
+        comm_id
+    }
+
     /// Sends a Jupyter message on the Stdin socket
     pub fn send_stdin<T: ProtocolMessage>(&self, msg: T) {
         Self::send(&self.stdin_socket, &self.session, msg);
@@ -249,14 +283,33 @@ impl DummyFrontend {
         panic!("Timeout while expecting message on socket {}", socket.name);
     }
 
+    /// Receives a Jupyter message from the Control socket
+    pub fn recv_control(&self) -> Message {
+        Self::recv(&self.control_socket)
+    }
+
     /// Receives a Jupyter message from the Shell socket
     pub fn recv_shell(&self) -> Message {
         Self::recv(&self.shell_socket)
     }
 
-    /// Receives a Jupyter message from the IOPub socket
+    /// Receives a Jupyter message from the IOPub socket. Comm messages are
+    /// skipped after `skip_iopub_comm_msgs()` has been called.
     pub fn recv_iopub(&self) -> Message {
-        Self::recv(&self.iopub_socket)
+        loop {
+            let msg = Self::recv(&self.iopub_socket);
+            if self.skip_iopub_comm_msgs && matches!(msg, Message::CommMsg(_)) {
+                continue;
+            }
+            return msg;
+        }
+    }
+
+    /// Skip the comm messages received on IOPub from now on. Comms like the UI
+    /// comm send events at any time, e.g. busy events around each execution,
+    /// which would otherwise interleave with the messages a test expects.
+    pub fn skip_iopub_comm_msgs(&mut self) {
+        self.skip_iopub_comm_msgs = true;
     }
 
     /// Receives a Jupyter message from the Stdin socket
@@ -286,6 +339,15 @@ impl DummyFrontend {
         })
     }
 
+    /// Receive from Control and assert `InterruptReply` message
+    pub fn recv_control_interrupt_reply(&self) {
+        let msg = self.recv_control();
+
+        assert_matches!(msg, Message::InterruptReply(data) => {
+            assert_eq!(data.content.status, Status::Ok);
+        });
+    }
+
     /// Receive from IOPub and assert Busy message
     pub fn recv_iopub_busy(&self) -> () {
         let msg = self.recv_iopub();
@@ -327,6 +389,16 @@ impl DummyFrontend {
         })
     }
 
+    /// Receive from IOPub and assert DisplayData message. Returns the MIME
+    /// bundle.
+    pub fn recv_iopub_display_data(&self) -> Value {
+        let msg = self.recv_iopub();
+
+        assert_matches!(msg, Message::DisplayData(data) => {
+            data.content.data
+        })
+    }
+
     pub fn recv_iopub_stream_stdout(&self, expect: &str) {
         self.recv_iopub_stream(expect, Stream::Stdout)
     }
@@ -335,6 +407,16 @@ impl DummyFrontend {
         self.recv_iopub_stream(expect, Stream::Stderr)
     }
 
+    /// Receive from IOPub and assert `CommMsg` message, even if comm messages
+    /// are skipped. Returns the comm ID and the data.
+    pub fn recv_iopub_comm_msg(&self) -> (String, Value) {
+        let msg = Self::recv(&self.iopub_socket);
+
+        assert_matches!(msg, Message::CommMsg(data) => {
+            (data.content.comm_id, data.content.data)
+        })
+    }
+
     pub fn recv_iopub_comm_close(&self) -> String {
         let msg = self.recv_iopub();
 
@@ -392,10 +474,16 @@ impl DummyFrontend {
     /// Receive from Stdin and assert `InputRequest` message.
     /// Returns the `prompt`.
     pub fn recv_stdin_input_request(&self) -> String {
+        self.recv_stdin_input_request_content().prompt
+    }
+
+    /// Receive from Stdin and assert `InputRequest` message, returning its
+    /// full content
+    pub fn recv_stdin_input_request_content(&self) -> InputRequest {
         let msg = self.recv_stdin();
 
         assert_matches!(msg, Message::InputRequest(data) => {
-            data.content.prompt
+            data.content
         })
     }
 
@@ -404,6 +492,21 @@ impl DummyFrontend {
         self.send_stdin(InputReply { value })
     }
 
+    /// Receive from Stdin and assert a request of the UI comm to the
+    /// frontend. Returns the request.
+    pub fn recv_stdin_comm_request(&self) -> UiFrontendRequest {
+        let msg = self.recv_stdin();
+
+        assert_matches!(msg, Message::CommRequest(data) => {
+            data.content
+        })
+    }
+
+    /// Send back the result of a UI comm request over Stdin
+    pub fn send_stdin_comm_reply(&self, result: Value) {
+        self.send_stdin(JsonRpcReply::Result(JsonRpcResult { result }))
+    }
+
     /// Receives a (raw) message from the heartbeat socket
     pub fn recv_heartbeat(&self) -> zmq::Message {
         let mut msg = zmq::Message::new();
@@ -420,6 +523,10 @@ impl DummyFrontend {
     pub fn assert_no_incoming(&mut self) {
         let mut has_incoming = false;
 
+        if self.skip_iopub_comm_msgs {
+            self.flush_iopub_comm_msgs();
+        }
+
         if self.iopub_socket.has_incoming_data().unwrap() {
             has_incoming = true;
             Self::flush_incoming("IOPub", &self.iopub_socket);
@@ -442,6 +549,18 @@ impl DummyFrontend {
         }
     }
 
+    /// Discards the comm messages at the front of the IOPub queue
+    fn flush_iopub_comm_msgs(&self) {
+        while self.iopub_socket.has_incoming_data().unwrap() {
+            let msg = Self::recv(&self.iopub_socket);
+            if !matches!(msg, Message::CommMsg(_)) {
+                eprintln!("IOPub has incoming data:");
+                dbg!(msg);
+                panic!("Sockets must be empty on exit (see details above)");
+            }
+        }
+    }
+
     fn flush_incoming(name: &str, socket: &Socket) {
         eprintln!("{name} has incoming data:");
 
@@ -30,6 +30,7 @@ use crate::socket::control::Control;
 use crate::socket::heartbeat::Heartbeat;
 use crate::socket::iopub::IOPub;
 use crate::socket::iopub::IOPubMessage;
+use crate::socket::shell::ExecuteQueue;
 use crate::socket::shell::Shell;
 use crate::socket::socket::Socket;
 use crate::socket::stdin::StdInRequest;
@@ -66,6 +67,8 @@ pub fn connect(
     lsp_handler: Option<Arc<Mutex<dyn ServerHandler>>>,
     dap_handler: Option<Arc<Mutex<dyn ServerHandler>>>,
     stream_behavior: StreamBehavior,
+    // Bound on the execute requests waiting on the shell socket
+    execute_queue: ExecuteQueue,
     iopub_tx: Sender<IOPubMessage>,
     iopub_rx: Receiver<IOPubMessage>,
     comm_manager_tx: Sender<CommManagerEvent>,
@@ -113,6 +116,7 @@ pub fn connect(
             shell_handler,
             lsp_handler,
             dap_handler,
+            execute_queue,
         )
     });
 
@@ -330,6 +334,7 @@ fn shell_thread(
     shell_handler: Box<dyn ShellHandler>,
     lsp_handler: Option<Arc<Mutex<dyn ServerHandler>>>,
     dap_handler: Option<Arc<Mutex<dyn ServerHandler>>>,
+    execute_queue: ExecuteQueue,
 ) -> Result<(), Error> {
     let mut shell = Shell::new(
         socket,
@@ -338,6 +343,7 @@ fn shell_thread(
         shell_handler,
         lsp_handler,
         dap_handler,
+        execute_queue,
     );
     shell.listen();
     Ok(())
@@ -23,6 +23,12 @@ pub trait ControlHandler: Send {
         msg: &ShutdownRequest,
     ) -> Result<ShutdownReply, Exception>;
 
+    /// Called once the reply to a shutdown request has been sent. Kernels
+    /// should start terminating from here rather than from
+    /// `handle_shutdown_request()`, otherwise exiting the process races with
+    /// the delivery of the reply.
+    async fn handle_shutdown(&self, _msg: &ShutdownRequest) {}
+
     /// Handles a request to interrupt the kernel. This message is forwarded
     /// from the Control socket.
     ///
@@ -5,10 +5,17 @@
  *
  */
 
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
 use crossbeam::channel::Receiver;
 use crossbeam::channel::Sender;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use serde_json::Value;
 
 use crate::comm::base_comm::json_rpc_error;
 use crate::comm::base_comm::JsonRpcErrorCode;
@@ -44,7 +51,8 @@ pub struct CommSocket {
 
     /// The other side of the channel receiving messages from the back end. This
     /// `Sender` is passed to the back end of the comm channel so that it can
-    /// send messages to the frontend.
+    /// send messages to the frontend. Messages sent directly through it
+    /// bypass the backpressure policy.
     pub outgoing_tx: Sender<CommMsg>,
 
     /// The channel that will accept messages from the frontend and relay them
@@ -53,8 +61,41 @@ pub struct CommSocket {
 
     /// The other side of the channel receiving messages from the frontend
     pub incoming_rx: Receiver<CommMsg>,
+
+    /// How `send_outgoing()` behaves when the outgoing channel is full.
+    pub backpressure: CommBackpressure,
+
+    /// Number of outgoing messages dropped because of backpressure. Shared
+    /// between clones of the socket.
+    dropped: Arc<AtomicU64>,
 }
 
+/**
+ * Describes how outgoing messages are queued when the back end produces them
+ * faster than the frontend consumes them.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CommBackpressure {
+    /// The outgoing queue grows without bound. This is the default and is
+    /// appropriate for low-volume comms.
+    #[default]
+    Unbounded,
+
+    /// `send_outgoing()` waits while the outgoing queue holds `capacity`
+    /// messages or more, for at most `timeout`, after which the message is
+    /// dropped. The timeout ensures producers running on the R main thread
+    /// never block indefinitely.
+    Block { capacity: usize, timeout: Duration },
+
+    /// When the outgoing queue holds `capacity` messages or more,
+    /// `send_outgoing()` drops the oldest queued events to make room.
+    DropOldest { capacity: usize },
+}
+
+/// How often a producer blocked by `CommBackpressure::Block` checks whether
+/// the frontend has caught up.
+const BLOCK_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
 /**
  * Describes the identity of the comm's initiator. This is used to determine
  * whether the comm is owned by the frontend or the back end.
@@ -85,6 +126,28 @@ impl CommSocket {
      *    member of the Comm enum.
      */
     pub fn new(initiator: CommInitiator, comm_id: String, comm_name: String) -> Self {
+        Self::new_with_backpressure(initiator, comm_id, comm_name, CommBackpressure::default())
+    }
+
+    /**
+     * Create a new CommSocket whose outgoing events follow the given
+     * backpressure policy. See `new()` for the other arguments.
+     *
+     * - `backpressure`: How `send_outgoing()` behaves when the outgoing
+     *   queue is full.
+     *
+     * The policy only applies to events sent with `send_outgoing()`. RPC
+     * replies and messages sent with a receipt are never blocked nor dropped,
+     * so they don't count towards the capacity when they are sent, and the
+     * channel itself is unbounded. There is at most one reply per request, so
+     * replies can't grow the queue without bound.
+     */
+    pub fn new_with_backpressure(
+        initiator: CommInitiator,
+        comm_id: String,
+        comm_name: String,
+        backpressure: CommBackpressure,
+    ) -> Self {
         let (outgoing_tx, outgoing_rx) = crossbeam::channel::unbounded();
         let (incoming_tx, incoming_rx) = crossbeam::channel::unbounded();
 
@@ -96,6 +159,97 @@ impl CommSocket {
             outgoing_rx,
             incoming_tx,
             incoming_rx,
+            backpressure,
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /**
+     * Send a message to the frontend, applying the comm's backpressure
+     * policy. Prefer this over `outgoing_tx` for high-volume traffic.
+     *
+     * Returns `false` if the message was dropped, either because the channel
+     * is disconnected or because of backpressure.
+     */
+    pub fn send_outgoing(&self, message: CommMsg) -> bool {
+        match self.backpressure {
+            CommBackpressure::Unbounded => (),
+
+            CommBackpressure::Block { capacity, timeout } => {
+                let deadline = Instant::now() + timeout;
+                while self.outgoing_tx.len() >= capacity {
+                    if Instant::now() >= deadline {
+                        self.notify_dropped();
+                        return false;
+                    }
+                    std::thread::sleep(BLOCK_POLL_INTERVAL);
+                }
+            },
+
+            CommBackpressure::DropOldest { capacity } => {
+                // Make room by dropping the oldest events. Replies and
+                // messages with a receipt are requeued instead, which is
+                // harmless since the frontend matches them by ID. Give up
+                // once every queued message has been requeued.
+                let mut requeued = 0;
+                while self.outgoing_tx.len() >= capacity && requeued < self.outgoing_tx.len() {
+                    match self.outgoing_rx.try_recv() {
+                        Ok(message @ (CommMsg::Rpc(..) | CommMsg::DataWithReceipt(..))) => {
+                            self.send_unchecked(message);
+                            requeued += 1;
+                        },
+                        Ok(_) => self.notify_dropped(),
+                        // The frontend consumed the queue in the meantime
+                        Err(_) => break,
+                    }
+                }
+            },
+        }
+
+        self.outgoing_tx.send(message).is_ok()
+    }
+
+    /// Send a message to the frontend regardless of the backpressure policy
+    fn send_unchecked(&self, message: CommMsg) {
+        if let Err(err) = self.outgoing_tx.send(message) {
+            log::error!(
+                "Can't send message on comm '{}' ({}): {err}",
+                self.comm_name,
+                self.comm_id
+            );
+        }
+    }
+
+    /**
+     * Send data to the frontend and request a delivery receipt, for critical
+     * messages whose loss would leave the frontend in an inconsistent state.
+     *
+     * Returns the ID of the receipt, which arrives on `incoming_rx` as a
+     * `CommMsg::Receipt` once the frontend has acknowledged the message.
+     * Receipts that arrive after the comm is closed are discarded. The
+     * message bypasses the backpressure policy.
+     */
+    pub fn send_with_receipt(&self, data: Value) -> String {
+        let receipt_id = uuid::Uuid::new_v4().to_string();
+        self.send_unchecked(CommMsg::DataWithReceipt(receipt_id.clone(), data));
+        receipt_id
+    }
+
+    /// The number of outgoing messages dropped so far because of
+    /// backpressure.
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn notify_dropped(&self) {
+        // Only log the first drop to avoid flooding the log when a
+        // frontend can't keep up
+        if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+            log::warn!(
+                "Dropping outgoing messages on comm '{}' ({}): the frontend isn't keeping up",
+                self.comm_name,
+                self.comm_id
+            );
         }
     }
 
@@ -106,7 +260,9 @@ impl CommSocket {
      * - `request_handler`: The comm's handler for requests.
      *
      * Returns `false` if `message` is not an RPC. Otherwise returns `true`.
-     * Requests that could not be handled cause an RPC error response.
+     * Requests that could not be handled cause an RPC error response. The
+     * response bypasses the backpressure policy, so the frontend always gets
+     * a reply.
      */
     pub fn handle_request<Reqs, Reps>(
         &self,
@@ -156,9 +312,7 @@ impl CommSocket {
             ),
         };
 
-        let response = CommMsg::Rpc(id, json);
-
-        self.outgoing_tx.send(response).unwrap();
+        self.send_unchecked(CommMsg::Rpc(id, json));
         true
     }
 }
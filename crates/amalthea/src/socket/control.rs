@@ -134,17 +134,16 @@ impl Control {
             }
         );
 
-        // TODO: This currently races with the R thread exiting the
-        // REPL and calling `exit()`. Odds are that this is never sent.
-        // We should send this from the `R_CleanUp()` frontend method
-        // once we implement it.
         unwrap!(
             req.send_reply(reply, &self.socket),
             Err(err) => {
-                log::error!("Failed to reply to interrupt request: {err:?}");
+                log::error!("Failed to reply to shutdown request: {err:?}");
             }
         );
 
+        // Now that the reply is on its way, let the kernel terminate
+        block_on(control_handler.handle_shutdown(&req.content));
+
         Ok(())
     }
 
@@ -5,12 +5,18 @@
  *
  */
 
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::LazyLock;
 use std::time::Duration;
+use std::time::Instant;
 
 use crossbeam::channel::tick;
 use crossbeam::channel::Receiver;
 use crossbeam::channel::Sender;
 use crossbeam::select;
+use serde_json::json;
 
 use crate::session::Session;
 use crate::wire::comm_close::CommClose;
@@ -61,6 +67,48 @@ pub struct IOPub {
     /// this avoids a message sequence of <stdout, stderr, stdout> getting
     /// accidentally sent to the frontend as <stdout, stdout, stderr>.
     buffer: StreamBuffer,
+
+    /// Sequence number of the next message, used when tracing is enabled
+    sequence: AtomicU64,
+}
+
+/// Whether to stamp outgoing IOPub messages with a monotonic sequence number
+/// and a timestamp in their metadata, so that frontend logs can be correlated
+/// with kernel logs. Enabled by setting the `ARK_IOPUB_TRACE` environment
+/// variable, or at runtime with `set_iopub_tracing()`. Clients that don't
+/// expect this are unaffected since metadata is a free-form dict.
+static IOPUB_TRACING: LazyLock<AtomicBool> =
+    LazyLock::new(|| AtomicBool::new(std::env::var("ARK_IOPUB_TRACE").is_ok()));
+
+pub fn set_iopub_tracing(enabled: bool) {
+    IOPUB_TRACING.store(enabled, Ordering::Relaxed);
+}
+
+fn iopub_tracing() -> bool {
+    IOPUB_TRACING.load(Ordering::Relaxed)
+}
+
+/// How long, in milliseconds, a line of stream output that isn't terminated
+/// by a newline is held back before it is flushed anyway. Complete lines are
+/// flushed at every tick of the stream buffer, and partial lines at the
+/// latest when the kernel returns to a prompt. Without this timeout, a
+/// progress bar that redraws itself with `\r` would never show up. Set with
+/// the `ARK_STREAM_PARTIAL_LINE_TIMEOUT` environment variable, or at runtime
+/// with `set_stream_partial_line_timeout()`.
+static STREAM_PARTIAL_LINE_TIMEOUT: LazyLock<AtomicU64> = LazyLock::new(|| {
+    let timeout = std::env::var("ARK_STREAM_PARTIAL_LINE_TIMEOUT")
+        .ok()
+        .and_then(|timeout| timeout.parse().ok())
+        .unwrap_or(200);
+    AtomicU64::new(timeout)
+});
+
+pub fn set_stream_partial_line_timeout(timeout: Duration) {
+    STREAM_PARTIAL_LINE_TIMEOUT.store(timeout.as_millis() as u64, Ordering::Relaxed);
+}
+
+fn stream_partial_line_timeout() -> Duration {
+    Duration::from_millis(STREAM_PARTIAL_LINE_TIMEOUT.load(Ordering::Relaxed))
 }
 
 /// Enumeration of possible channels that an IOPub message can be associated
@@ -86,6 +134,9 @@ pub enum IOPubMessage {
     DisplayData(DisplayData),
     UpdateDisplayData(UpdateDisplayData),
     Wait(Wait),
+    /// Flush the active stream, including a trailing partial line. Sent when
+    /// the kernel returns to a prompt.
+    Flush,
 }
 
 /// A special IOPub message used to block the sender until the IOPub queue has
@@ -117,6 +168,7 @@ impl IOPub {
             shell_context: None,
             control_context: None,
             buffer,
+            sequence: AtomicU64::new(0),
         }
     }
 
@@ -125,8 +177,8 @@ impl IOPub {
         // Begin by emitting the starting state
         self.emit_state(ExecutionState::Starting);
 
-        // Flush the active stream (either stdout or stderr) at regular
-        // intervals
+        // Flush the complete lines of the active stream (either stdout or
+        // stderr) at regular intervals
         let flush_interval = *StreamBuffer::interval();
         let flush_interval = tick(flush_interval);
 
@@ -158,7 +210,7 @@ impl IOPub {
                 },
                 recv(flush_interval) -> message => {
                     match message {
-                        Ok(_) => self.flush_stream(),
+                        Ok(_) => self.flush_stream_lines(),
                         Err(_) => unreachable!()
                     }
                 }
@@ -241,7 +293,14 @@ impl IOPub {
                     self.message_with_context(content, IOPubContextChannel::Shell),
                 ))
             },
-            IOPubMessage::Wait(content) => self.process_wait_request(content),
+            IOPubMessage::Wait(content) => {
+                self.flush_stream();
+                self.process_wait_request(content)
+            },
+            IOPubMessage::Flush => {
+                self.flush_stream();
+                Ok(())
+            },
         }
     }
 
@@ -281,6 +340,11 @@ impl IOPub {
 
     /// Create a message using the underlying socket with the given content. The
     /// parent message is assumed to be the current context.
+    ///
+    /// The message metadata names its originator so that clients sharing the
+    /// kernel can filter output by request. Output emitted outside of a
+    /// request, e.g. from a callback run while the kernel is idle, is
+    /// attributed to the session.
     fn message_with_context<T: ProtocolMessage>(
         &self,
         content: T,
@@ -290,7 +354,18 @@ impl IOPub {
             IOPubContextChannel::Control => &self.control_context,
             IOPubContextChannel::Shell => &self.shell_context,
         };
-        self.message_create(context.clone(), content)
+
+        let originator = match context {
+            Some(header) => json!({
+                "scope": "request",
+                "request_id": header.msg_id,
+            }),
+            None => json!({ "scope": "session" }),
+        };
+
+        let mut message = self.message_create(context.clone(), content);
+        message.metadata["originator"] = originator;
+        message
     }
 
     /// Create a message using the underlying socket with the given content and
@@ -309,7 +384,19 @@ impl IOPub {
         header: Option<JupyterHeader>,
         content: T,
     ) -> JupyterMessage<T> {
-        JupyterMessage::<T>::create(content, header, &self.session)
+        let mut message = JupyterMessage::<T>::create(content, header, &self.session);
+
+        if iopub_tracing() {
+            let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+            message.metadata = json!({
+                "trace": {
+                    "sequence": sequence,
+                    "timestamp": chrono::Utc::now().to_rfc3339(),
+                }
+            });
+        }
+
+        message
     }
 
     /// Forward a message on to the actual IOPub socket through the outbound channel
@@ -329,7 +416,27 @@ impl IOPub {
         }
 
         let content = self.buffer.drain();
+        self.forward_stream(content);
+    }
 
+    /// Flushes the complete lines of the active stream. A trailing partial
+    /// line stays in the buffer, unless it has been waiting for its newline
+    /// for longer than the partial line timeout.
+    fn flush_stream_lines(&mut self) {
+        if self
+            .buffer
+            .partial_line_expired(stream_partial_line_timeout())
+        {
+            self.flush_stream();
+            return;
+        }
+
+        if let Some(content) = self.buffer.drain_lines() {
+            self.forward_stream(content);
+        }
+    }
+
+    fn forward_stream(&self, content: StreamOutput) {
         let message =
             Message::Stream(self.message_with_context(content, IOPubContextChannel::Shell));
 
@@ -348,8 +455,8 @@ impl IOPub {
 
     /// Processes a `Stream` message by appending it to the stream buffer
     ///
-    /// The buffer will be flushed on the next tick interval unless it is
-    /// manually flushed before then.
+    /// The complete lines of the buffer will be flushed on the next tick
+    /// interval unless it is manually flushed before then.
     ///
     /// If this new message switches streams, then we flush the existing stream
     /// before switching.
@@ -400,6 +507,10 @@ impl IOPub {
 struct StreamBuffer {
     name: Stream,
     buffer: Vec<String>,
+
+    /// When the trailing line of the buffer, which isn't terminated by a
+    /// newline yet, started
+    partial_line_start: Option<Instant>,
 }
 
 impl StreamBuffer {
@@ -407,13 +518,49 @@ impl StreamBuffer {
         return StreamBuffer {
             name,
             buffer: Vec::new(),
+            partial_line_start: None,
         };
     }
 
     fn push(&mut self, message: String) {
+        if message.ends_with('\n') {
+            self.partial_line_start = None;
+        } else if message.contains('\n') || self.partial_line_start.is_none() {
+            // A new partial line starts after the last newline
+            if !message.is_empty() {
+                self.partial_line_start = Some(Instant::now());
+            }
+        }
+
         self.buffer.push(message);
     }
 
+    fn partial_line_expired(&self, timeout: Duration) -> bool {
+        self.partial_line_start
+            .is_some_and(|start| start.elapsed() >= timeout)
+    }
+
+    /// Drains the buffer up to and including its last newline
+    fn drain_lines(&mut self) -> Option<StreamOutput> {
+        let mut text = self.buffer.join("");
+        self.buffer.clear();
+
+        let Some(last_newline) = text.rfind('\n') else {
+            self.buffer.push(text);
+            return None;
+        };
+
+        let partial_line = text.split_off(last_newline + 1);
+        if !partial_line.is_empty() {
+            self.buffer.push(partial_line);
+        }
+
+        Some(StreamOutput {
+            name: self.name,
+            text,
+        })
+    }
+
     fn is_empty(&self) -> bool {
         self.buffer.is_empty()
     }
@@ -421,6 +568,7 @@ impl StreamBuffer {
     fn drain(&mut self) -> StreamOutput {
         let text = self.buffer.join("");
         self.buffer.clear();
+        self.partial_line_start = None;
 
         StreamOutput {
             name: self.name,
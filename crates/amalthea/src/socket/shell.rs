@@ -6,6 +6,7 @@
  */
 
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -16,6 +17,7 @@ use futures::executor::block_on;
 use serde_json::json;
 use stdext::result::ResultOrLog;
 
+use crate::comm::comm_channel::comm_receipt_id;
 use crate::comm::comm_channel::Comm;
 use crate::comm::comm_channel::CommMsg;
 use crate::comm::event::CommManagerEvent;
@@ -37,6 +39,8 @@ use crate::wire::comm_info_request::CommInfoRequest;
 use crate::wire::comm_msg::CommWireMsg;
 use crate::wire::comm_open::CommOpen;
 use crate::wire::exception::Exception;
+use crate::wire::execute_reply::ExecuteReply;
+use crate::wire::execute_request::ExecuteRequest;
 use crate::wire::header::JupyterHeader;
 use crate::wire::jupyter_message::JupyterMessage;
 use crate::wire::jupyter_message::Message;
@@ -47,6 +51,71 @@ use crate::wire::originator::Originator;
 use crate::wire::status::ExecutionState;
 use crate::wire::status::KernelStatus;
 
+/// What happens to the execute requests that exceed `ExecuteQueue::limit`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ExecuteQueuePolicy {
+    /// The newest requests are rejected with an error reply
+    #[default]
+    RejectNew,
+
+    /// The oldest requests are dropped with an error reply, in favour of
+    /// the newest ones
+    DropOldest,
+}
+
+impl ExecuteQueuePolicy {
+    pub fn parse(policy: &str) -> anyhow::Result<Self> {
+        match policy {
+            "reject-new" => Ok(Self::RejectNew),
+            "drop-oldest" => Ok(Self::DropOldest),
+            other => Err(anyhow::anyhow!(
+                "Unknown execute queue policy '{other}'. Expected `reject-new` or `drop-oldest`."
+            )),
+        }
+    }
+}
+
+/// Bound on the execute requests waiting for the execution of previous
+/// requests. Execution is sequential, so a client submitting requests faster
+/// than they run builds up a backlog.
+///
+/// Only automated requests count towards the limit and may be rejected, i.e.
+/// those that are silent or not stored in the history. Requests submitted by
+/// the user are never dropped.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ExecuteQueue {
+    /// The maximum number of pending automated requests, unbounded if `None`
+    pub limit: Option<usize>,
+
+    pub policy: ExecuteQueuePolicy,
+}
+
+impl ExecuteQueue {
+    /// The positions in `automated` of the requests to reject, given whether
+    /// each pending request is automated.
+    fn rejected(&self, automated: &[bool]) -> Vec<usize> {
+        let Some(limit) = self.limit else {
+            return vec![];
+        };
+
+        let candidates: Vec<usize> = automated
+            .iter()
+            .enumerate()
+            .filter_map(|(i, automated)| automated.then_some(i))
+            .collect();
+
+        let excess = candidates.len().saturating_sub(limit);
+        match self.policy {
+            ExecuteQueuePolicy::RejectNew => candidates[candidates.len() - excess..].to_vec(),
+            ExecuteQueuePolicy::DropOldest => candidates[..excess].to_vec(),
+        }
+    }
+}
+
+fn is_automated_execute_request(req: &ExecuteRequest) -> bool {
+    req.silent || !req.store_history
+}
+
 /// Wrapper for the Shell socket; receives requests for execution, etc. from the
 /// frontend and handles them or dispatches them to the execution thread.
 pub struct Shell {
@@ -67,6 +136,13 @@ pub struct Shell {
 
     /// Channel used to deliver comm events to the comm manager
     comm_manager_tx: Sender<CommManagerEvent>,
+
+    /// Bound on the pending execute requests
+    execute_queue: ExecuteQueue,
+
+    /// Messages read from the socket ahead of their processing, to enforce
+    /// `execute_queue`
+    pending: VecDeque<Message>,
 }
 
 impl Shell {
@@ -78,6 +154,7 @@ impl Shell {
     /// * `comm_changed_rx` - A channel that receives messages from the comm manager thread
     /// * `shell_handler` - The language's shell channel handler
     /// * `lsp_handler` - The language's LSP handler, if it supports LSP
+    /// * `execute_queue` - The bound on pending execute requests
     pub fn new(
         socket: Socket,
         iopub_tx: Sender<IOPubMessage>,
@@ -85,6 +162,7 @@ impl Shell {
         shell_handler: Box<dyn ShellHandler>,
         lsp_handler: Option<Arc<Mutex<dyn ServerHandler>>>,
         dap_handler: Option<Arc<Mutex<dyn ServerHandler>>>,
+        execute_queue: ExecuteQueue,
     ) -> Self {
         // Need a RefCell to allow handler methods to be mutable.
         // We only run one handler at a time so this is safe.
@@ -96,6 +174,8 @@ impl Shell {
             lsp_handler,
             dap_handler,
             comm_manager_tx,
+            execute_queue,
+            pending: VecDeque::new(),
         }
     }
 
@@ -103,14 +183,28 @@ impl Shell {
     pub fn listen(&mut self) {
         // Begin listening for shell messages
         loop {
-            log::trace!("Waiting for shell messages");
-            // Attempt to read the next message from the ZeroMQ socket
-            let message = match Message::read_from_socket(&self.socket) {
-                Ok(m) => m,
-                Err(err) => {
-                    log::warn!("Could not read message from shell socket: {err}");
-                    continue;
-                },
+            if self.pending.is_empty() {
+                log::trace!("Waiting for shell messages");
+                // Attempt to read the next message from the ZeroMQ socket
+                match Message::read_from_socket(&self.socket) {
+                    Ok(m) => self.pending.push_back(m),
+                    Err(err) => {
+                        log::warn!("Could not read message from shell socket: {err}");
+                        continue;
+                    },
+                };
+            }
+
+            // The messages received while the previous request was handled
+            // are still in the socket. Read them to see how many execute
+            // requests are pending.
+            if self.execute_queue.limit.is_some() {
+                self.read_incoming();
+                self.enforce_execute_queue();
+            }
+
+            let Some(message) = self.pending.pop_front() else {
+                continue;
             };
 
             // Handle the message; any failures while handling the messages are
@@ -122,6 +216,81 @@ impl Shell {
         }
     }
 
+    /// Reads the messages available on the socket without blocking
+    fn read_incoming(&mut self) {
+        loop {
+            match self.socket.has_incoming_data() {
+                Ok(true) => {},
+                Ok(false) => return,
+                Err(err) => {
+                    log::warn!("Could not poll shell socket: {err}");
+                    return;
+                },
+            }
+
+            match Message::read_from_socket(&self.socket) {
+                Ok(m) => self.pending.push_back(m),
+                Err(err) => log::warn!("Could not read message from shell socket: {err}"),
+            }
+        }
+    }
+
+    /// Rejects the pending execute requests beyond the limit of
+    /// `execute_queue`
+    fn enforce_execute_queue(&mut self) {
+        let automated: Vec<bool> = self
+            .pending
+            .iter()
+            .map(|message| match message {
+                Message::ExecuteRequest(req) => is_automated_execute_request(&req.content),
+                _ => false,
+            })
+            .collect();
+
+        let rejected = self.execute_queue.rejected(&automated);
+        if rejected.is_empty() {
+            return;
+        }
+
+        log::warn!(
+            "{} pending execute requests exceed the limit of {:?}, applying policy {:?}",
+            automated.iter().filter(|x| **x).count(),
+            self.execute_queue.limit,
+            self.execute_queue.policy
+        );
+
+        // Remove from the back so the positions stay valid
+        let mut rejected_messages = vec![];
+        for i in rejected.into_iter().rev() {
+            if let Some(message) = self.pending.remove(i) {
+                rejected_messages.push(message);
+            }
+        }
+        rejected_messages.reverse();
+
+        for message in rejected_messages {
+            if let Message::ExecuteRequest(req) = message {
+                if let Err(err) = self.reject_execute_request(req) {
+                    log::error!("Could not reject execute request: {err}");
+                }
+            }
+        }
+    }
+
+    fn reject_execute_request(&self, req: JupyterMessage<ExecuteRequest>) -> crate::Result<()> {
+        let exception = Exception {
+            ename: String::from("KernelBusy"),
+            evalue: String::from(
+                "The kernel is busy: too many execute requests are pending. The request was not run.",
+            ),
+            traceback: vec![],
+        };
+
+        self.handle_request::<_, ExecuteReply, _>(req, |_| {
+            Err(Error::ShellErrorExecuteReply(exception, 0))
+        })
+    }
+
     /// Process a message received from the front-end, optionally dispatching
     /// messages to the IOPub or execution threads
     fn process_message(&self, msg: Message) -> crate::Result<()> {
@@ -305,6 +474,15 @@ impl Shell {
     /// request from the frontend to deliver a message to a backend, often as
     /// the request side of a request/response pair.
     fn handle_comm_msg(&self, header: JupyterHeader, msg: &CommWireMsg) -> crate::Result<()> {
+        // Delivery receipts are not requests, they are routed to the comm
+        // that requested them
+        if let Some(receipt_id) = comm_receipt_id(&msg.data) {
+            self.comm_manager_tx
+                .send(CommManagerEvent::Receipt(msg.comm_id.clone(), receipt_id))
+                .unwrap();
+            return Ok(());
+        }
+
         // Store this message as a pending RPC request so that when the comm
         // responds, we can match it up
         self.comm_manager_tx
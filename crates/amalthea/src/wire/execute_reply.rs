@@ -23,6 +23,47 @@ pub struct ExecuteReply {
 
     /// Results for user expressions
     pub user_expressions: Value,
+
+    /// The variables bound by the executed code, if the kernel reports them.
+    /// Not part of the Jupyter protocol.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignment_targets: Option<AssignmentTargets>,
+
+    /// The resources used to execute the code, if the kernel reports them.
+    /// Not part of the Jupyter protocol.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ExecutionResources>,
+}
+
+/// Variables bound by the executed code, as determined from the code itself
+/// rather than by diffing the environment. Lets frontends refresh just these
+/// variables.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AssignmentTargets {
+    /// Names of the bound variables, in order of first assignment
+    pub names: Vec<String>,
+
+    /// Whether the code also binds or removes variables whose names can't be
+    /// determined from the code, e.g. `assign(name, value)`. Frontends should
+    /// then refresh all variables.
+    pub unknown: bool,
+}
+
+/// Resources used to execute a request, e.g. for notebooks to show how long
+/// each cell took.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExecutionResources {
+    /// Wall-clock time spent executing, in milliseconds. Time spent waiting
+    /// for the user to answer input requests, e.g. from `readline()`, is
+    /// excluded.
+    pub elapsed_ms: u64,
+
+    /// Change in the memory used by the interpreter during execution, in
+    /// bytes. Only reported when the kernel is asked to track allocations
+    /// since that can be expensive. Negative when more memory was released
+    /// than allocated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allocation_delta_bytes: Option<f64>,
 }
 
 impl MessageType for ExecuteReply {
@@ -33,6 +33,19 @@ pub struct ExecuteRequest {
     /// Whether the kernel should discard the execution queue if evaluating the
     /// code results in an error
     pub stop_on_error: bool,
+
+    /// The output mimetypes the frontend is able to render. If `None`, the
+    /// kernel computes all the representations it supports. This is an
+    /// extension to the Jupyter protocol.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supported_mimetypes: Option<Vec<String>>,
+
+    /// The name of a package in which the code should be evaluated, as if
+    /// it were part of the package. Unqualified names then resolve against
+    /// the package namespace first, including internal functions. This is an
+    /// extension to the Jupyter protocol.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package_context: Option<String>,
 }
 
 impl MessageType for ExecuteRequest {
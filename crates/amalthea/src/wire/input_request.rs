@@ -24,6 +24,13 @@ pub struct InputRequest {
     /// Whether the string being requested is a password (and should therefore
     /// be obscured)
     pub password: bool,
+
+    /// The options of a `menu()` style prompt, in order. The user answers
+    /// with the number of their selection. Not part of the Jupyter protocol,
+    /// frontends that know about it may render a picker instead of a text
+    /// box.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub choices: Option<Vec<String>>,
 }
 
 /// An input request originating from a Shell handler
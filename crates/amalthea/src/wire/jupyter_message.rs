@@ -7,6 +7,8 @@
 
 use serde::Deserialize;
 use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
 
 use super::display_data::DisplayData;
 use super::handshake_reply::HandshakeReply;
@@ -46,6 +48,7 @@ use crate::wire::is_complete_reply::IsCompleteReply;
 use crate::wire::is_complete_request::IsCompleteRequest;
 use crate::wire::kernel_info_request::KernelInfoRequest;
 use crate::wire::originator::Originator;
+use crate::wire::shutdown_reply::ShutdownReply;
 use crate::wire::shutdown_request::ShutdownRequest;
 use crate::wire::status::KernelStatus;
 use crate::wire::wire_message::WireMessage;
@@ -63,6 +66,9 @@ pub struct JupyterMessage<T> {
     /// not all messages have a parent.
     pub parent_header: Option<JupyterHeader>,
 
+    /// Free-form metadata attached to the message. Usually an empty dict.
+    pub metadata: Value,
+
     /// The body (payload) of the message
     pub content: T,
 }
@@ -101,6 +107,7 @@ pub enum Message {
     // Control
     InterruptReply(JupyterMessage<InterruptReply>),
     InterruptRequest(JupyterMessage<InterruptRequest>),
+    ShutdownReply(JupyterMessage<ShutdownReply>),
     ShutdownRequest(JupyterMessage<ShutdownRequest>),
     // Registration
     HandshakeRequest(JupyterMessage<HandshakeRequest>),
@@ -163,6 +170,7 @@ impl TryFrom<&Message> for WireMessage {
             Message::IsCompleteRequest(msg) => WireMessage::try_from(msg),
             Message::KernelInfoReply(msg) => WireMessage::try_from(msg),
             Message::KernelInfoRequest(msg) => WireMessage::try_from(msg),
+            Message::ShutdownReply(msg) => WireMessage::try_from(msg),
             Message::ShutdownRequest(msg) => WireMessage::try_from(msg),
             Message::Status(msg) => WireMessage::try_from(msg),
             Message::CommInfoReply(msg) => WireMessage::try_from(msg),
@@ -242,6 +250,9 @@ impl TryFrom<&WireMessage> for Message {
         if kind == ShutdownRequest::message_type() {
             return Ok(Message::ShutdownRequest(JupyterMessage::try_from(msg)?));
         }
+        if kind == ShutdownReply::message_type() {
+            return Ok(Message::ShutdownReply(JupyterMessage::try_from(msg)?));
+        }
         if kind == KernelStatus::message_type() {
             return Ok(Message::Status(JupyterMessage::try_from(msg)?));
         }
@@ -333,6 +344,7 @@ where
                 session.username.clone(),
             ),
             parent_header: parent,
+            metadata: json!({}),
             content,
         }
     }
@@ -351,6 +363,7 @@ where
                 session.username.clone(),
             ),
             parent_header: Some(originator.header),
+            metadata: json!({}),
             content,
         }
     }
@@ -413,6 +426,7 @@ where
                 session.username.clone(),
             ),
             parent_header: Some(self.header.clone()),
+            metadata: json!({}),
             content,
         }
     }
@@ -435,6 +449,7 @@ where
                 session.username.clone(),
             ),
             parent_header: Some(self.header.clone()),
+            metadata: json!({}),
             content: ErrorReply {
                 status: Status::Error,
                 exception,
@@ -11,7 +11,6 @@ use log::trace;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Serialize;
-use serde_json::json;
 use serde_json::value::Value;
 use sha2::Sha256;
 
@@ -347,6 +346,7 @@ impl<T: ProtocolMessage + DeserializeOwned> TryFrom<&WireMessage> for JupyterMes
             zmq_identities: msg.zmq_identities.clone(),
             header: msg.header.clone(),
             parent_header: msg.parent_header.clone(),
+            metadata: msg.metadata.clone(),
             content,
         })
     }
@@ -371,7 +371,7 @@ impl<T: ProtocolMessage> TryFrom<&JupyterMessage<T>> for WireMessage {
             zmq_identities: msg.zmq_identities.clone(),
             header: msg.header.clone(),
             parent_header: msg.parent_header.clone(),
-            metadata: json!({}),
+            metadata: msg.metadata.clone(),
             content,
         })
     }
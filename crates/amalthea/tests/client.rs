@@ -9,19 +9,29 @@ mod control;
 mod dummy_frontend;
 mod shell;
 
+use std::time::Duration;
+
+use amalthea::comm::comm_channel::comm_receipt_json;
 use amalthea::comm::comm_channel::CommMsg;
+use amalthea::comm::comm_channel::COMM_RECEIPT_ID_KEY;
 use amalthea::comm::event::CommManagerEvent;
 use amalthea::socket::comm::CommInitiator;
 use amalthea::socket::comm::CommSocket;
+use amalthea::socket::iopub::set_iopub_tracing;
+use amalthea::socket::shell::ExecuteQueue;
+use amalthea::socket::shell::ExecuteQueuePolicy;
 use amalthea::wire::comm_close::CommClose;
 use amalthea::wire::comm_info_reply::CommInfoTargetName;
 use amalthea::wire::comm_info_request::CommInfoRequest;
 use amalthea::wire::comm_msg::CommWireMsg;
 use amalthea::wire::comm_open::CommOpen;
+use amalthea::wire::execute_request::ExecuteRequest;
 use amalthea::wire::jupyter_message::Message;
 use amalthea::wire::kernel_info_request::KernelInfoRequest;
+use amalthea::wire::shutdown_request::ShutdownRequest;
 use amalthea::wire::status::ExecutionState;
 use assert_matches::assert_matches;
+use control::SHUTDOWN_STARTED;
 use dummy_frontend::DummyAmaltheaFrontend;
 use serde_json;
 
@@ -63,6 +73,42 @@ fn test_amalthea_execute_request() {
     frontend.recv_iopub_idle();
 }
 
+#[test]
+fn test_amalthea_iopub_tracing() {
+    let frontend = DummyAmaltheaFrontend::lock();
+
+    let sequence = |msg: &Message| -> u64 {
+        let metadata = match msg {
+            Message::Status(data) => &data.metadata,
+            Message::ExecuteInput(data) => &data.metadata,
+            Message::ExecuteResult(data) => &data.metadata,
+            _ => panic!("Unexpected IOPub message: {msg:?}"),
+        };
+        assert!(metadata["trace"]["timestamp"].is_string());
+        metadata["trace"]["sequence"].as_u64().unwrap()
+    };
+
+    set_iopub_tracing(true);
+
+    frontend.send_execute_request("42", Default::default());
+    let messages: Vec<Message> = (0..4).map(|_| frontend.recv_iopub()).collect();
+    frontend.recv_shell();
+
+    set_iopub_tracing(false);
+
+    // Busy, execute input, execute result, idle
+    let sequences: Vec<u64> = messages.iter().map(sequence).collect();
+    assert!(sequences.windows(2).all(|pair| pair[1] == pair[0] + 1));
+
+    // Without tracing, messages carry the usual empty metadata
+    frontend.send_shell(KernelInfoRequest {});
+    assert_matches!(frontend.recv_iopub(), Message::Status(data) => {
+        assert_eq!(data.metadata, serde_json::json!({}));
+    });
+    frontend.recv_shell();
+    frontend.recv_iopub_idle();
+}
+
 #[test]
 fn test_amalthea_input_request() {
     let frontend = DummyAmaltheaFrontend::lock();
@@ -87,6 +133,110 @@ fn test_amalthea_input_request() {
     frontend.recv_iopub_idle();
 }
 
+#[test]
+fn test_amalthea_execute_queue_limit() {
+    // The limit applies to the whole kernel, so use a kernel of our own
+    // rather than the shared one
+    let execute_queue = ExecuteQueue {
+        limit: Some(2),
+        policy: ExecuteQueuePolicy::RejectNew,
+    };
+    let mut frontend = DummyAmaltheaFrontend::dedicated(execute_queue);
+    let limit = execute_queue.limit.unwrap();
+
+    // Block the shell on a prompt while requests pile up
+    let code = "prompt";
+    frontend.send_execute_request(code, Default::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+    frontend.recv_stdin_input_request();
+
+    // Queue a second prompt ahead of the automated requests. The shell
+    // blocks on it before running any of them, so they have all reached the
+    // socket by the time the policy is applied once it returns.
+    frontend.send_execute_request(code, Default::default());
+
+    // Flood the kernel with automated requests, i.e. not stored in the history
+    let n = limit + 2;
+    let ids: Vec<String> = (0..n)
+        .map(|i| {
+            frontend.send_shell(ExecuteRequest {
+                code: format!("automated{i}"),
+                silent: false,
+                store_history: false,
+                user_expressions: serde_json::Value::Null,
+                allow_stdin: false,
+                stop_on_error: false,
+                supported_mimetypes: None,
+                package_context: None,
+            })
+        })
+        .collect();
+
+    frontend.send_stdin_input_reply(String::from("42"));
+    frontend.recv_iopub_stream_stdout("42");
+    assert_eq!(frontend.recv_iopub_execute_result(), "prompt");
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+    frontend.recv_iopub_idle();
+
+    frontend.recv_stdin_input_request();
+    frontend.send_stdin_input_reply(String::from("42"));
+
+    // Some requests might be rejected while the second prompt is pending, so
+    // poll the replies until all requests are accounted for
+    let mut rejected = vec![];
+    for _ in 0..=n {
+        match frontend.recv_shell() {
+            Message::ExecuteReply(_) => {},
+            Message::ExecuteReplyException(data) => {
+                assert_eq!(data.content.exception.ename, "KernelBusy");
+                rejected.push(data.parent_header.unwrap().msg_id);
+            },
+            msg => panic!("Unexpected shell message: {msg:?}"),
+        }
+    }
+
+    // The newest requests beyond the limit get a busy reply
+    assert_eq!(rejected, ids[limit..]);
+
+    // The others run in order, after the second prompt. Each request has a
+    // busy and an idle status, the executed ones also have an input and a
+    // result, and the prompt streams its reply.
+    let n_executed = limit + 1;
+    let n_iopub = 2 * (n + 1) + 2 * n_executed + 1;
+    let inputs: Vec<String> = (0..n_iopub)
+        .filter_map(|_| match frontend.recv_iopub() {
+            Message::ExecuteInput(data) => Some(data.content.code),
+            _ => None,
+        })
+        .collect();
+
+    let mut expected = vec![String::from(code)];
+    expected.extend((0..limit).map(|i| format!("automated{i}")));
+    assert_eq!(inputs, expected);
+
+    frontend.assert_no_incoming();
+}
+
+#[test]
+fn test_amalthea_shutdown_request() {
+    let frontend = DummyAmaltheaFrontend::lock();
+
+    frontend.send_control(ShutdownRequest { restart: true });
+    frontend.recv_iopub_busy();
+
+    // The kernel is blocked before it starts terminating, so the reply must
+    // have been sent first
+    assert_matches!(frontend.recv_control(), Message::ShutdownReply(reply) => {
+        assert!(reply.content.restart);
+    });
+    assert!(SHUTDOWN_STARTED.1.recv().unwrap());
+
+    frontend.recv_iopub_idle();
+}
+
 #[test]
 fn test_amalthea_heartbeat() {
     let frontend = DummyAmaltheaFrontend::lock();
@@ -295,3 +445,74 @@ fn test_amalthea_comm_open_from_kernel() {
         assert_eq!(msg.content.comm_id, test_comm_id);
     });
 }
+
+#[test]
+fn test_amalthea_comm_receipt() {
+    let frontend = DummyAmaltheaFrontend::lock();
+
+    let test_comm_id = String::from("test_comm_id_receipt");
+    let test_comm = CommSocket::new(
+        CommInitiator::BackEnd,
+        test_comm_id.clone(),
+        String::from("test_target"),
+    );
+
+    frontend
+        .comm_manager_tx
+        .send(CommManagerEvent::Opened(
+            test_comm.clone(),
+            serde_json::Value::Null,
+        ))
+        .unwrap();
+
+    assert_matches!(frontend.recv_iopub(), Message::CommOpen(msg) => {
+        assert_eq!(msg.content.comm_id, test_comm_id);
+    });
+
+    // Send a message requesting a receipt. The frontend gets the receipt ID
+    // along with the data.
+    let receipt_id = test_comm.send_with_receipt(serde_json::json!({ "method": "final" }));
+
+    let data = assert_matches!(frontend.recv_iopub(), Message::CommMsg(msg) => {
+        assert_eq!(msg.content.comm_id, test_comm_id);
+        msg.content.data
+    });
+    assert_eq!(data["method"], "final");
+    assert_eq!(data[COMM_RECEIPT_ID_KEY], receipt_id.as_str());
+
+    // Acknowledge the message. The receipt is relayed to the comm.
+    frontend.send_shell(CommWireMsg {
+        comm_id: test_comm_id.clone(),
+        data: comm_receipt_json(&receipt_id),
+    });
+    frontend.recv_iopub_busy();
+    frontend.recv_iopub_idle();
+
+    assert_matches!(
+        test_comm.incoming_rx.recv_timeout(Duration::from_secs(1)).unwrap(),
+        CommMsg::Receipt(id) => {
+            assert_eq!(id, receipt_id);
+        }
+    );
+
+    // A receipt that comes back after the comm is closed is discarded
+    let receipt_id = test_comm.send_with_receipt(serde_json::json!({ "method": "final" }));
+    assert_matches!(frontend.recv_iopub(), Message::CommMsg(_));
+
+    test_comm.outgoing_tx.send(CommMsg::Close).unwrap();
+    assert_matches!(frontend.recv_iopub(), Message::CommClose(msg) => {
+        assert_eq!(msg.content.comm_id, test_comm_id);
+    });
+
+    frontend.send_shell(CommWireMsg {
+        comm_id: test_comm_id.clone(),
+        data: comm_receipt_json(&receipt_id),
+    });
+    frontend.recv_iopub_busy();
+    frontend.recv_iopub_idle();
+
+    assert!(test_comm
+        .incoming_rx
+        .recv_timeout(Duration::from_millis(100))
+        .is_err());
+}
@@ -0,0 +1,221 @@
+/*
+ * comm_socket.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+use std::time::Instant;
+
+use amalthea::comm::base_comm::JsonRpcErrorCode;
+use amalthea::comm::base_comm::JsonRpcReply;
+use amalthea::comm::comm_channel::CommMsg;
+use amalthea::socket::comm::CommBackpressure;
+use amalthea::socket::comm::CommInitiator;
+use amalthea::socket::comm::CommSocket;
+use assert_matches::assert_matches;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+
+fn flood(socket: &CommSocket, n: usize) -> usize {
+    (0..n)
+        .filter(|i| socket.send_outgoing(CommMsg::Data(json!({ "i": i }))))
+        .count()
+}
+
+#[test]
+fn test_comm_socket_drop_oldest() {
+    let socket = CommSocket::new_with_backpressure(
+        CommInitiator::BackEnd,
+        String::from("test-comm-drop-oldest"),
+        String::from("test"),
+        CommBackpressure::DropOldest { capacity: 10 },
+    );
+
+    // Nobody consumes the messages. All sends succeed but the queue stays
+    // bounded.
+    assert_eq!(flood(&socket, 1000), 1000);
+    assert_eq!(socket.outgoing_rx.len(), 10);
+    assert_eq!(socket.dropped_count(), 990);
+
+    // The most recent messages are the ones that were kept
+    let kept: Vec<CommMsg> = socket.outgoing_rx.try_iter().collect();
+    assert_matches!(kept.first().unwrap(), CommMsg::Data(data) => {
+        assert_eq!(data, &json!({ "i": 990 }));
+    });
+    assert_matches!(kept.last().unwrap(), CommMsg::Data(data) => {
+        assert_eq!(data, &json!({ "i": 999 }));
+    });
+}
+
+#[test]
+fn test_comm_socket_block() {
+    let socket = CommSocket::new_with_backpressure(
+        CommInitiator::BackEnd,
+        String::from("test-comm-block"),
+        String::from("test"),
+        CommBackpressure::Block {
+            capacity: 10,
+            timeout: Duration::from_millis(10),
+        },
+    );
+
+    // The producer blocks for at most the timeout once the queue is full,
+    // then drops the incoming messages instead of waiting forever
+    let start = Instant::now();
+    assert_eq!(flood(&socket, 15), 10);
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    assert_eq!(socket.outgoing_rx.len(), 10);
+    assert_eq!(socket.dropped_count(), 5);
+
+    // The oldest messages are the ones that were kept
+    assert_matches!(socket.outgoing_rx.try_recv().unwrap(), CommMsg::Data(data) => {
+        assert_eq!(data, json!({ "i": 0 }));
+    });
+
+    // Once the consumer catches up, sends go through again
+    assert!(socket.send_outgoing(CommMsg::Data(json!({ "i": 15 }))));
+    assert_eq!(socket.dropped_count(), 5);
+}
+
+#[test]
+fn test_comm_socket_unbounded() {
+    let socket = CommSocket::new(
+        CommInitiator::BackEnd,
+        String::from("test-comm-unbounded"),
+        String::from("test"),
+    );
+
+    assert_eq!(socket.backpressure, CommBackpressure::Unbounded);
+    assert_eq!(flood(&socket, 1000), 1000);
+    assert_eq!(socket.outgoing_rx.len(), 1000);
+    assert_eq!(socket.dropped_count(), 0);
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", content = "params")]
+enum TestRequest {
+    #[serde(rename = "add")]
+    Add(Vec<i64>),
+
+    #[serde(rename = "fail")]
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "method", content = "result")]
+enum TestReply {
+    #[serde(rename = "add")]
+    AddReply(i64),
+}
+
+fn recv_reply(socket: &CommSocket, expected_id: &str) -> JsonRpcReply {
+    assert_matches!(socket.outgoing_rx.try_recv().unwrap(), CommMsg::Rpc(id, data) => {
+        assert_eq!(id, expected_id);
+        serde_json::from_value(data).unwrap()
+    })
+}
+
+#[test]
+fn test_comm_socket_handle_request() {
+    let socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-comm-handle-request"),
+        String::from("test"),
+    );
+
+    let handler = |req: TestRequest| -> anyhow::Result<TestReply> {
+        match req {
+            TestRequest::Add(values) => Ok(TestReply::AddReply(values.iter().sum())),
+            TestRequest::Fail => Err(anyhow::anyhow!("oh no")),
+        }
+    };
+
+    // The reply is sent with the ID of the request
+    let request = CommMsg::Rpc(
+        String::from("id-add"),
+        json!({ "method": "add", "params": [1, 2, 3] }),
+    );
+    assert!(socket.handle_request(request, handler));
+    assert_matches!(recv_reply(&socket, "id-add"), JsonRpcReply::Result(reply) => {
+        assert_eq!(reply.result, json!(6));
+    });
+
+    // Failing handlers and unknown methods cause structured errors
+    let request = CommMsg::Rpc(String::from("id-fail"), json!({ "method": "fail" }));
+    assert!(socket.handle_request(request, handler));
+    assert_matches!(recv_reply(&socket, "id-fail"), JsonRpcReply::Error(reply) => {
+        assert_eq!(reply.error.code, JsonRpcErrorCode::InternalError);
+        assert!(reply.error.message.contains("oh no"));
+    });
+
+    let request = CommMsg::Rpc(String::from("id-unknown"), json!({ "method": "unknown" }));
+    assert!(socket.handle_request(request, handler));
+    assert_matches!(recv_reply(&socket, "id-unknown"), JsonRpcReply::Error(reply) => {
+        assert_eq!(reply.error.code, JsonRpcErrorCode::MethodNotFound);
+    });
+
+    // Other messages are left to the caller
+    assert!(!socket.handle_request(CommMsg::Data(json!({})), handler));
+    assert!(socket.outgoing_rx.is_empty());
+}
+
+#[test]
+fn test_comm_socket_replies_bypass_backpressure() {
+    let handler = |req: TestRequest| -> anyhow::Result<TestReply> {
+        match req {
+            TestRequest::Add(values) => Ok(TestReply::AddReply(values.iter().sum())),
+            TestRequest::Fail => Err(anyhow::anyhow!("oh no")),
+        }
+    };
+    let request = || {
+        CommMsg::Rpc(
+            String::from("id-add"),
+            json!({ "method": "add", "params": [1, 2] }),
+        )
+    };
+
+    // Replying on a full queue doesn't wait for the frontend
+    let socket = CommSocket::new_with_backpressure(
+        CommInitiator::FrontEnd,
+        String::from("test-comm-block-reply"),
+        String::from("test"),
+        CommBackpressure::Block {
+            capacity: 2,
+            timeout: Duration::from_secs(60),
+        },
+    );
+    assert_eq!(flood(&socket, 2), 2);
+
+    let start = Instant::now();
+    assert!(socket.handle_request(request(), handler));
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(socket.outgoing_rx.len(), 3);
+    assert_eq!(socket.dropped_count(), 0);
+
+    // Flooding events after a reply drops the events but keeps the reply
+    let socket = CommSocket::new_with_backpressure(
+        CommInitiator::FrontEnd,
+        String::from("test-comm-drop-oldest-reply"),
+        String::from("test"),
+        CommBackpressure::DropOldest { capacity: 2 },
+    );
+    assert!(socket.handle_request(request(), handler));
+    let receipt_id = socket.send_with_receipt(json!({ "important": true }));
+    assert_eq!(flood(&socket, 100), 100);
+
+    let kept: Vec<CommMsg> = socket.outgoing_rx.try_iter().collect();
+    assert!(kept
+        .iter()
+        .any(|msg| matches!(msg, CommMsg::Rpc(id, _) if id == "id-add")));
+    assert!(kept
+        .iter()
+        .any(|msg| matches!(msg, CommMsg::DataWithReceipt(id, _) if id == &receipt_id)));
+    assert_matches!(kept.last().unwrap(), CommMsg::Data(data) => {
+        assert_eq!(data, &json!({ "i": 99 }));
+    });
+    assert_eq!(kept.len(), 3);
+}
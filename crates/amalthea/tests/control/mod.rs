@@ -5,6 +5,8 @@
  *
  */
 
+use std::sync::LazyLock;
+
 use amalthea::language::control_handler::ControlHandler;
 use amalthea::wire::exception::Exception;
 use amalthea::wire::interrupt_reply::InterruptReply;
@@ -12,6 +14,15 @@ use amalthea::wire::jupyter_message::Status;
 use amalthea::wire::shutdown_reply::ShutdownReply;
 use amalthea::wire::shutdown_request::ShutdownRequest;
 use async_trait::async_trait;
+use crossbeam::channel::bounded;
+use crossbeam::channel::Receiver;
+use crossbeam::channel::Sender;
+
+/// Rendezvous channel on which the dummy kernel announces that it would start
+/// terminating. Sending blocks until a test receives, which lets tests check
+/// what happened before that point.
+pub static SHUTDOWN_STARTED: LazyLock<(Sender<bool>, Receiver<bool>)> =
+    LazyLock::new(|| bounded(0));
 
 pub struct Control {}
 
@@ -28,6 +39,10 @@ impl ControlHandler for Control {
         })
     }
 
+    async fn handle_shutdown(&self, msg: &ShutdownRequest) {
+        SHUTDOWN_STARTED.0.send(msg.restart).unwrap();
+    }
+
     async fn handle_interrupt_request(&self) -> Result<InterruptReply, Exception> {
         // NYI
         Ok(InterruptReply { status: Status::Ok })
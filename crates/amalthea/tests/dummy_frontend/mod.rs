@@ -18,6 +18,7 @@ use amalthea::fixtures::dummy_frontend::DummyFrontend;
 use amalthea::kernel;
 use amalthea::kernel::StreamBehavior;
 use amalthea::socket::iopub::IOPubMessage;
+use amalthea::socket::shell::ExecuteQueue;
 use amalthea::socket::stdin::StdInRequest;
 use crossbeam::channel::bounded;
 use crossbeam::channel::unbounded;
@@ -46,10 +47,22 @@ impl DummyAmaltheaFrontend {
     }
 
     fn get_frontend() -> &'static Arc<Mutex<(DummyFrontend, Sender<CommManagerEvent>)>> {
-        AMALTHEA_FRONTEND.get_or_init(|| Arc::new(Mutex::new(DummyAmaltheaFrontend::init())))
+        AMALTHEA_FRONTEND.get_or_init(|| {
+            Arc::new(Mutex::new(DummyAmaltheaFrontend::init(
+                ExecuteQueue::default(),
+            )))
+        })
     }
 
-    fn init() -> (DummyFrontend, Sender<CommManagerEvent>) {
+    /// Starts a kernel of its own, for tests that change the state of the
+    /// kernel or depend on the timing of its shell socket. Unlike `lock()`,
+    /// the caller is responsible for checking the sockets are empty.
+    pub fn dedicated(execute_queue: ExecuteQueue) -> DummyFrontend {
+        let (frontend, _comm_manager_tx) = DummyAmaltheaFrontend::init(execute_queue);
+        frontend
+    }
+
+    fn init(execute_queue: ExecuteQueue) -> (DummyFrontend, Sender<CommManagerEvent>) {
         let connection = DummyConnection::new();
         let (connection_file, registration_file) = connection.get_connection_files();
 
@@ -67,8 +80,8 @@ impl DummyAmaltheaFrontend {
         ));
         let control = Arc::new(Mutex::new(control::Control {}));
 
-        // Initialize logging
-        env_logger::init();
+        // Initialize logging, once for all the kernels of the process
+        let _ = env_logger::try_init();
 
         // Perform kernel connection on its own thread to
         // avoid deadlocking as it waits for the `HandshakeReply`
@@ -85,6 +98,7 @@ impl DummyAmaltheaFrontend {
                     None,
                     None,
                     StreamBehavior::None,
+                    execute_queue,
                     iopub_tx,
                     iopub_rx,
                     comm_manager_tx,
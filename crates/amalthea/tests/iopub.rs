@@ -0,0 +1,78 @@
+/*
+ * iopub.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+
+use amalthea::session::Session;
+use amalthea::socket::iopub::set_stream_partial_line_timeout;
+use amalthea::socket::iopub::IOPub;
+use amalthea::socket::iopub::IOPubMessage;
+use amalthea::wire::jupyter_message::Message;
+use amalthea::wire::jupyter_message::OutboundMessage;
+use amalthea::wire::stream::Stream;
+use amalthea::wire::stream::StreamOutput;
+use assert_matches::assert_matches;
+use crossbeam::channel::unbounded;
+use crossbeam::channel::Receiver;
+
+fn recv_stream(outbound_rx: &Receiver<OutboundMessage>) -> String {
+    let message = outbound_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_matches!(message, OutboundMessage::IOPub(Message::Stream(data)) => {
+        data.content.text
+    })
+}
+
+fn send_stdout(iopub_tx: &crossbeam::channel::Sender<IOPubMessage>, text: &str) {
+    iopub_tx
+        .send(IOPubMessage::Stream(StreamOutput {
+            name: Stream::Stdout,
+            text: String::from(text),
+        }))
+        .unwrap();
+}
+
+#[test]
+fn test_iopub_stream_partial_lines() {
+    // Long enough to never expire while we check that partial lines are
+    // held back
+    set_stream_partial_line_timeout(Duration::from_secs(60));
+
+    let (iopub_tx, iopub_rx) = unbounded();
+    let (_inbound_tx, inbound_rx) = unbounded();
+    let (outbound_tx, outbound_rx) = unbounded();
+    let session = Session::create("").unwrap();
+
+    std::thread::spawn(move || {
+        let mut iopub = IOPub::new(iopub_rx, inbound_rx, outbound_tx, session);
+        iopub.listen();
+    });
+
+    // The starting state
+    let message = outbound_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_matches!(message, OutboundMessage::IOPub(Message::Status(_)));
+
+    // Complete lines are flushed at the next tick, the partial line is held
+    // back until its newline arrives
+    send_stdout(&iopub_tx, "one\ntw");
+    assert_eq!(recv_stream(&outbound_rx), "one\n");
+    assert!(outbound_rx
+        .recv_timeout(Duration::from_millis(300))
+        .is_err());
+
+    send_stdout(&iopub_tx, "o\nthr");
+    assert_eq!(recv_stream(&outbound_rx), "two\n");
+
+    // Returning to the prompt flushes the partial line
+    iopub_tx.send(IOPubMessage::Flush).unwrap();
+    assert_eq!(recv_stream(&outbound_rx), "thr");
+
+    // A progress line redrawn with `\r` never gets a newline but is still
+    // flushed once the timeout expires
+    set_stream_partial_line_timeout(Duration::from_millis(100));
+    send_stdout(&iopub_tx, "\r50%");
+    assert_eq!(recv_stream(&outbound_rx), "\r50%");
+}
@@ -74,6 +74,7 @@ impl Shell {
                 request: InputRequest {
                     prompt: String::from("Amalthea Echo> "),
                     password: false,
+                    choices: None,
                 },
             }))
         {
@@ -221,6 +222,8 @@ impl ShellHandler for Shell {
             status: Status::Ok,
             execution_count: self.execution_count,
             user_expressions: serde_json::Value::Null,
+            assignment_targets: None,
+            resources: None,
         })
     }
 
@@ -267,6 +270,9 @@ impl ShellHandler for Shell {
                     // sender as the response to the RPC, using the same ID.
                     comm.outgoing_tx.send(CommMsg::Rpc(id, val)).unwrap();
                 },
+                CommMsg::DataWithReceipt(_, _) | CommMsg::Receipt(_) => {
+                    // Receipts are handled by the comm manager
+                },
                 CommMsg::Close => {
                     // Close the channel and exit the thread.
                     break;
@@ -0,0 +1,471 @@
+//
+// ansi.rs
+//
+// Copyright (C) 2026 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::fmt::Write;
+
+/// How ANSI escape sequences in R's console output, e.g. the colours of
+/// crayon and cli, are forwarded to the frontend. Positron renders them, but
+/// plain Jupyter frontends may show them as garbage.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum AnsiMode {
+    /// Forward the escape sequences untouched
+    #[default]
+    Passthrough,
+
+    /// Remove the escape sequences
+    Strip,
+
+    /// Convert SGR sequences (colours and text styles) to HTML spans,
+    /// emitted as `display_data`. Other sequences are removed. Styled output
+    /// is coalesced until the stream is flushed, output without escape
+    /// sequences is streamed as usual.
+    Html,
+}
+
+impl AnsiMode {
+    pub fn parse(mode: &str) -> anyhow::Result<Self> {
+        match mode {
+            "passthrough" => Ok(Self::Passthrough),
+            "strip" => Ok(Self::Strip),
+            "html" => Ok(Self::Html),
+            other => Err(anyhow::anyhow!(
+                "Unknown ANSI mode '{other}'. Expected `passthrough`, `strip`, or `html`."
+            )),
+        }
+    }
+}
+
+/// The ANSI modes of stdout and stderr
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AnsiOutput {
+    pub stdout: AnsiMode,
+    pub stderr: AnsiMode,
+}
+
+impl AnsiOutput {
+    /// Parses either a single mode applied to both streams, e.g. `strip`, or
+    /// a comma separated list of modes per stream, e.g.
+    /// `stdout=html,stderr=strip`. Streams that aren't listed pass escape
+    /// sequences through.
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        if !spec.contains('=') {
+            let mode = AnsiMode::parse(spec.trim())?;
+            return Ok(Self {
+                stdout: mode,
+                stderr: mode,
+            });
+        }
+
+        let mut output = Self::default();
+
+        for item in spec.split(',').map(str::trim) {
+            let Some((stream, mode)) = item.split_once('=') else {
+                return Err(anyhow::anyhow!(
+                    "Can't parse ANSI mode '{item}'. Expected `stdout=MODE` or `stderr=MODE`."
+                ));
+            };
+            let mode = AnsiMode::parse(mode.trim())?;
+            match stream.trim() {
+                "stdout" => output.stdout = mode,
+                "stderr" => output.stderr = mode,
+                other => {
+                    return Err(anyhow::anyhow!(
+                        "Unknown stream '{other}'. Expected `stdout` or `stderr`."
+                    ))
+                },
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+const ESC: char = '\u{1b}';
+const BEL: char = '\u{07}';
+
+/// A piece of console output
+#[derive(Debug, PartialEq)]
+enum AnsiToken<'a> {
+    Text(&'a str),
+    /// The parameters of a Select Graphic Rendition sequence, `ESC [ ... m`
+    Sgr(&'a str),
+    /// Any other escape sequence, e.g. cursor movements or OSC 8 hyperlinks
+    Other,
+    /// An escape sequence cut off at the end of the text
+    Incomplete(&'a str),
+}
+
+/// Splits `text` into plain text and escape sequences
+fn ansi_tokens(text: &str) -> Vec<AnsiToken<'_>> {
+    let mut tokens = vec![];
+    let mut rest = text;
+
+    while let Some(start) = rest.find(ESC) {
+        if start > 0 {
+            tokens.push(AnsiToken::Text(&rest[..start]));
+        }
+
+        let sequence = &rest[start + ESC.len_utf8()..];
+        let mut chars = sequence.char_indices();
+
+        let end = match chars.next() {
+            // Control Sequence Introducer: parameter and intermediate bytes
+            // followed by a final byte in `@` to `~`
+            Some((_, '[')) => match chars.find(|(_, c)| ('@'..='~').contains(c)) {
+                Some((i, final_byte)) => {
+                    if final_byte == 'm' {
+                        tokens.push(AnsiToken::Sgr(&sequence[1..i]));
+                    } else {
+                        tokens.push(AnsiToken::Other);
+                    }
+                    Some(i + final_byte.len_utf8())
+                },
+                None => None,
+            },
+
+            // Operating System Command, terminated by BEL or `ESC \`
+            Some((_, ']')) => {
+                let bel = sequence.find(BEL).map(|i| i + BEL.len_utf8());
+                let st = sequence.find("\u{1b}\\").map(|i| i + 2);
+                let end = match (bel, st) {
+                    (Some(bel), Some(st)) => Some(bel.min(st)),
+                    (bel, st) => bel.or(st),
+                };
+                if end.is_some() {
+                    tokens.push(AnsiToken::Other);
+                }
+                end
+            },
+
+            // Two character sequences like `ESC 7`
+            Some((_, c)) => {
+                tokens.push(AnsiToken::Other);
+                Some(c.len_utf8())
+            },
+
+            None => None,
+        };
+
+        match end {
+            Some(end) => rest = &sequence[end..],
+            None => {
+                tokens.push(AnsiToken::Incomplete(&rest[start..]));
+                rest = "";
+                break;
+            },
+        }
+    }
+
+    if !rest.is_empty() {
+        tokens.push(AnsiToken::Text(rest));
+    }
+
+    tokens
+}
+
+/// Whether `text` contains escape sequences
+pub fn has_ansi(text: &str) -> bool {
+    text.contains(ESC)
+}
+
+/// Removes the escape sequences of `text`, including one cut off at the end
+pub fn strip_ansi(text: &str) -> String {
+    ansi_tokens(text)
+        .into_iter()
+        .filter_map(|token| match token {
+            AnsiToken::Text(text) => Some(text),
+            _ => None,
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct AnsiStyle {
+    bold: bool,
+    faint: bool,
+    italic: bool,
+    underline: bool,
+    foreground: Option<String>,
+    background: Option<String>,
+}
+
+impl AnsiStyle {
+    fn apply(&mut self, parameters: &str) {
+        let mut codes = parameters
+            .split(';')
+            .map(|code| code.parse::<u8>().unwrap_or(0));
+
+        // `ESC [ m` is a reset, like `ESC [ 0 m`
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                2 => self.faint = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => {
+                    self.bold = false;
+                    self.faint = false;
+                },
+                23 => self.italic = false,
+                24 => self.underline = false,
+                30..=37 => self.foreground = Some(ansi_color(code - 30)),
+                38 => self.foreground = extended_color(&mut codes),
+                39 => self.foreground = None,
+                40..=47 => self.background = Some(ansi_color(code - 40)),
+                48 => self.background = extended_color(&mut codes),
+                49 => self.background = None,
+                90..=97 => self.foreground = Some(ansi_color(code - 90 + 8)),
+                100..=107 => self.background = Some(ansi_color(code - 100 + 8)),
+                _ => (),
+            }
+        }
+    }
+
+    fn css(&self) -> String {
+        let mut css = String::new();
+        if self.bold {
+            css.push_str("font-weight:bold;");
+        }
+        if self.faint {
+            css.push_str("opacity:0.5;");
+        }
+        if self.italic {
+            css.push_str("font-style:italic;");
+        }
+        if self.underline {
+            css.push_str("text-decoration:underline;");
+        }
+        if let Some(color) = &self.foreground {
+            write!(css, "color:{color};").unwrap();
+        }
+        if let Some(color) = &self.background {
+            write!(css, "background-color:{color};").unwrap();
+        }
+        css
+    }
+}
+
+/// The 16 standard colours, with the values of the VS Code terminal
+const ANSI_COLORS: [&str; 16] = [
+    "#000000", "#cd3131", "#0dbc79", "#e5e510", "#2472c8", "#bc3fbc", "#11a8cd", "#e5e5e5",
+    "#666666", "#f14c4c", "#23d18b", "#f5f543", "#3b8eea", "#d670d6", "#29b8db", "#ffffff",
+];
+
+/// The colour of the 256 colour palette
+fn ansi_color(index: u8) -> String {
+    match index {
+        0..=15 => ANSI_COLORS[index as usize].to_string(),
+        16..=231 => {
+            let index = index - 16;
+            let level = |x: u8| if x == 0 { 0 } else { 55 + x * 40 };
+            let (r, g, b) = (level(index / 36), level(index / 6 % 6), level(index % 6));
+            format!("#{r:02x}{g:02x}{b:02x}")
+        },
+        232..=255 => {
+            let level = 8 + (index - 232) * 10;
+            format!("#{level:02x}{level:02x}{level:02x}")
+        },
+    }
+}
+
+/// The colour of `38;5;n` and `38;2;r;g;b` sequences, after the `38`
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<String> {
+    match codes.next()? {
+        5 => Some(ansi_color(codes.next()?)),
+        2 => {
+            let (r, g, b) = (codes.next()?, codes.next()?, codes.next()?);
+            Some(format!("#{r:02x}{g:02x}{b:02x}"))
+        },
+        _ => None,
+    }
+}
+
+fn escape_html(text: &str, html: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            c => html.push(c),
+        }
+    }
+}
+
+/// Converts the SGR sequences of `text` to HTML spans inside a `<pre>`
+/// block. Other escape sequences are removed.
+pub fn ansi_to_html(text: &str) -> String {
+    let mut buffer = AnsiHtmlBuffer::default();
+    buffer.push(text);
+    match buffer.flush() {
+        Some(output) => output.html,
+        None => String::from("<pre></pre>"),
+    }
+}
+
+/// Escape sequences longer than this are never terminated in practice, they
+/// are dropped rather than buffered forever
+const MAX_PARTIAL_SEQUENCE: usize = 256;
+
+/// Output of a stream converted to HTML
+#[derive(Debug, PartialEq)]
+pub struct AnsiHtml {
+    pub html: String,
+    pub plain: String,
+}
+
+/// Output of a stream in HTML mode, coalesced until it is flushed. R writes
+/// styled output in many small pieces, and a style or an escape sequence may
+/// span several writes, so the style and any partial sequence carry over
+/// from one write to the next and across flushes.
+#[derive(Debug, Default)]
+pub struct AnsiHtmlBuffer {
+    style: AnsiStyle,
+    partial: String,
+    html: String,
+    plain: String,
+}
+
+impl AnsiHtmlBuffer {
+    /// Whether `text` can be streamed as is, because nothing is buffered and
+    /// it isn't styled
+    pub fn is_plain(&self, text: &str) -> bool {
+        self.plain.is_empty() &&
+            self.partial.is_empty() &&
+            self.style == AnsiStyle::default() &&
+            !has_ansi(text)
+    }
+
+    pub fn push(&mut self, text: &str) {
+        let text = std::mem::take(&mut self.partial) + text;
+
+        for token in ansi_tokens(&text) {
+            match token {
+                AnsiToken::Text(text) => {
+                    let css = self.style.css();
+                    if css.is_empty() {
+                        escape_html(text, &mut self.html);
+                    } else {
+                        write!(self.html, "<span style=\"{css}\">").unwrap();
+                        escape_html(text, &mut self.html);
+                        self.html.push_str("</span>");
+                    }
+                    self.plain.push_str(text);
+                },
+                AnsiToken::Sgr(parameters) => self.style.apply(parameters),
+                AnsiToken::Other => (),
+                AnsiToken::Incomplete(sequence) => {
+                    if sequence.len() <= MAX_PARTIAL_SEQUENCE {
+                        self.partial = sequence.to_string();
+                    }
+                },
+            }
+        }
+    }
+
+    /// Takes the output written since the last flush, if any
+    pub fn flush(&mut self) -> Option<AnsiHtml> {
+        if self.plain.is_empty() {
+            return None;
+        }
+
+        let html = format!("<pre>{}</pre>", std::mem::take(&mut self.html));
+        let plain = std::mem::take(&mut self.plain);
+        Some(AnsiHtml { html, plain })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::ansi_to_html;
+    use crate::ansi::has_ansi;
+    use crate::ansi::strip_ansi;
+    use crate::ansi::AnsiHtmlBuffer;
+    use crate::ansi::AnsiMode;
+    use crate::ansi::AnsiOutput;
+
+    // `cli::col_red("error")` and a `cli::style_hyperlink()`
+    const RED: &str = "\u{1b}[31merror\u{1b}[39m: oops";
+    const LINK: &str = "see \u{1b}]8;;https://example.com\u{07}docs\u{1b}]8;;\u{07}!";
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi(RED), "error: oops");
+        assert_eq!(strip_ansi(LINK), "see docs!");
+        assert_eq!(strip_ansi("\u{1b}[1;38;5;208mbold\u{1b}[0m\n"), "bold\n");
+
+        // Cut off escape sequences are dropped
+        assert_eq!(strip_ansi("done\u{1b}[3"), "done");
+        assert_eq!(strip_ansi("done\u{1b}"), "done");
+    }
+
+    #[test]
+    fn test_ansi_preserved() {
+        let text = "plain <text> & more\n";
+        assert!(!has_ansi(text));
+        assert_eq!(strip_ansi(text), text);
+
+        assert!(has_ansi(RED));
+        assert_eq!(AnsiOutput::default().stdout, AnsiMode::Passthrough);
+    }
+
+    #[test]
+    fn test_ansi_to_html() {
+        assert_eq!(
+            ansi_to_html(RED),
+            "<pre><span style=\"color:#cd3131;\">error</span>: oops</pre>"
+        );
+        assert_eq!(
+            ansi_to_html("\u{1b}[1;48;2;255;0;0m<b>\u{1b}[22mx\u{1b}[m"),
+            "<pre><span style=\"font-weight:bold;background-color:#ff0000;\">&lt;b&gt;</span><span style=\"background-color:#ff0000;\">x</span></pre>"
+        );
+        assert_eq!(ansi_to_html(LINK), "<pre>see docs!</pre>");
+    }
+
+    #[test]
+    fn test_ansi_html_buffer() {
+        let mut buffer = AnsiHtmlBuffer::default();
+        assert!(buffer.is_plain("plain\n"));
+        assert!(!buffer.is_plain(RED));
+
+        // The style and the sequence cut off between writes carry over
+        buffer.push("\u{1b}[3");
+        assert!(!buffer.is_plain("x"));
+        buffer.push("1mred");
+        buffer.push(" still red");
+        let output = buffer.flush().unwrap();
+        assert_eq!(
+            output.html,
+            "<pre><span style=\"color:#cd3131;\">red</span><span style=\"color:#cd3131;\"> still red</span></pre>"
+        );
+        assert_eq!(output.plain, "red still red");
+
+        // Across flushes too, until the style is reset
+        assert!(buffer.flush().is_none());
+        buffer.push("again\u{1b}[39m done");
+        assert_eq!(
+            buffer.flush().unwrap().html,
+            "<pre><span style=\"color:#cd3131;\">again</span> done</pre>"
+        );
+        assert!(buffer.is_plain("plain\n"));
+    }
+
+    #[test]
+    fn test_ansi_output_parse() {
+        let output = AnsiOutput::parse("strip").unwrap();
+        assert_eq!(output.stdout, AnsiMode::Strip);
+        assert_eq!(output.stderr, AnsiMode::Strip);
+
+        let output = AnsiOutput::parse("stderr=html").unwrap();
+        assert_eq!(output.stdout, AnsiMode::Passthrough);
+        assert_eq!(output.stderr, AnsiMode::Html);
+
+        assert!(AnsiOutput::parse("colour").is_err());
+        assert!(AnsiOutput::parse("stdin=strip").is_err());
+    }
+}
@@ -0,0 +1,261 @@
+//
+// assignment_targets.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use amalthea::wire::execute_reply::AssignmentTargets;
+use ropey::Rope;
+use tree_sitter::Node;
+
+use crate::lsp::documents::Document;
+use crate::treesitter::node_text;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Functions that bind or remove variables whose names we can't determine
+/// from the call
+const OPAQUE_FUNCTIONS: &[&str] = &[
+    "delayedAssign",
+    "makeActiveBinding",
+    "list2env",
+    "load",
+    "source",
+    "sys.source",
+    "rm",
+    "remove",
+    "eval",
+    "evalq",
+    "eval.parent",
+    "do.call",
+];
+
+/// Find the variables bound by `code` when evaluated at top level, from its
+/// assignments (`<-`, `=`, `<<-`, `->`, `->>`), `for` loops, and `assign()`
+/// calls with a literal name. Function definitions are skipped since their
+/// assignments happen in a different environment.
+///
+/// This is a purely syntactic analysis. `unknown` is set when the code calls
+/// a function that binds variables through computed names, such as
+/// `assign(name, value)` or `load()`.
+pub(crate) fn assignment_targets(code: &str) -> AssignmentTargets {
+    let document = Document::new(code, None);
+
+    let mut targets = AssignmentTargets {
+        names: vec![],
+        unknown: false,
+    };
+    collect(&document.ast.root_node(), &document.contents, &mut targets);
+
+    targets
+}
+
+fn collect(node: &Node, contents: &Rope, targets: &mut AssignmentTargets) {
+    match node.node_type() {
+        NodeType::FunctionDefinition => return,
+
+        NodeType::BinaryOperator(op) => {
+            let fields = match op {
+                BinaryOperatorType::LeftAssignment |
+                BinaryOperatorType::LeftSuperAssignment |
+                BinaryOperatorType::EqualsAssignment => Some(("lhs", "rhs")),
+                BinaryOperatorType::RightAssignment | BinaryOperatorType::RightSuperAssignment => {
+                    Some(("rhs", "lhs"))
+                },
+                _ => None,
+            };
+
+            if let Some((target, value)) = fields {
+                if let Some(target) = node.child_by_field_name(target) {
+                    push_target(target_name(&target, contents), targets);
+                }
+
+                // Chained assignments like `x <- y <- 1`
+                if let Some(value) = node.child_by_field_name(value) {
+                    collect(&value, contents, targets);
+                }
+                return;
+            }
+        },
+
+        NodeType::ForStatement => {
+            if let Some(variable) = node.child_by_field_name("variable") {
+                push_target(target_name(&variable, contents), targets);
+            }
+        },
+
+        NodeType::Call => {
+            if let Some(function) = node.child_by_field_name("function") {
+                let function = node_text(&function, contents).unwrap_or_default();
+                let function = function.strip_prefix("base::").unwrap_or(&function);
+
+                if function == "assign" {
+                    push_target(assign_name(node, contents), targets);
+                } else if OPAQUE_FUNCTIONS.contains(&function) {
+                    targets.unknown = true;
+                }
+            }
+        },
+
+        _ => {},
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect(&child, contents, targets);
+    }
+}
+
+fn push_target(name: Option<String>, targets: &mut AssignmentTargets) {
+    match name {
+        Some(name) => {
+            if !targets.names.contains(&name) {
+                targets.names.push(name);
+            }
+        },
+        None => targets.unknown = true,
+    }
+}
+
+/// The variable bound by an assignment to `node`. Complex assignments like
+/// `x$a <- 1`, `x[1] <- 1`, or `names(x) <- value` bind the root object.
+fn target_name(node: &Node, contents: &Rope) -> Option<String> {
+    match node.node_type() {
+        NodeType::Identifier => {
+            let name = node_text(node, contents)?;
+            let name = match name.strip_prefix('`') {
+                Some(name) => name.strip_suffix('`').unwrap_or(name).to_string(),
+                None => name,
+            };
+            Some(name)
+        },
+        NodeType::String => string_value(node, contents),
+        NodeType::ExtractOperator(_) => target_name(&node.child_by_field_name("lhs")?, contents),
+        NodeType::Subset | NodeType::Subset2 => {
+            target_name(&node.child_by_field_name("function")?, contents)
+        },
+        NodeType::Call => {
+            let arguments = node.child_by_field_name("arguments")?;
+            let mut cursor = arguments.walk();
+            let first = arguments
+                .named_children(&mut cursor)
+                .find(|child| child.node_type() == NodeType::Argument)?;
+            target_name(&first.child_by_field_name("value")?, contents)
+        },
+        NodeType::ParenthesizedExpression => {
+            let mut cursor = node.walk();
+            let inner = node.named_children(&mut cursor).next()?;
+            target_name(&inner, contents)
+        },
+        _ => None,
+    }
+}
+
+/// The name bound by a call to `assign()`, the `x` argument when it is a
+/// string literal
+fn assign_name(node: &Node, contents: &Rope) -> Option<String> {
+    let arguments = node.child_by_field_name("arguments")?;
+
+    let mut cursor = arguments.walk();
+    let arguments: Vec<Node> = arguments
+        .named_children(&mut cursor)
+        .filter(|child| child.node_type() == NodeType::Argument)
+        .collect();
+
+    let is_named = |argument: &Node, name: &str| {
+        argument
+            .child_by_field_name("name")
+            .and_then(|node| node_text(&node, contents))
+            .is_some_and(|text| text == name)
+    };
+
+    // Named `x` argument, or else the first unnamed one
+    let argument = arguments
+        .iter()
+        .find(|argument| is_named(argument, "x"))
+        .or_else(|| {
+            arguments
+                .iter()
+                .find(|argument| argument.child_by_field_name("name").is_none())
+        })?;
+
+    let value = argument.child_by_field_name("value")?;
+    if value.node_type() != NodeType::String {
+        return None;
+    }
+
+    string_value(&value, contents)
+}
+
+fn string_value(node: &Node, contents: &Rope) -> Option<String> {
+    let mut cursor = node.walk();
+    let content = node
+        .named_children(&mut cursor)
+        .find(|child| child.node_type() == NodeType::StringContent)?;
+
+    // Escapes would need to be interpreted to get the name
+    if content.named_child_count() > 0 {
+        return None;
+    }
+
+    node_text(&content, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assignment_targets::assignment_targets;
+
+    fn names(code: &str) -> Vec<String> {
+        let targets = assignment_targets(code);
+        assert!(!targets.unknown, "Unexpected unknown targets in `{code}`");
+        targets.names
+    }
+
+    #[test]
+    fn test_assignment_targets() {
+        assert_eq!(names("x <- 1; y = 2; z <<- 3; 4 -> w"), vec![
+            "x", "y", "z", "w"
+        ]);
+        assert_eq!(names("x <- y <- 1\nx <- 2"), vec!["x", "y"]);
+        assert_eq!(names("assign('a', 1); assign(value = 2, x = \"b\")"), vec![
+            "a", "b"
+        ]);
+        assert_eq!(names("`my var` <- 1; 'quoted' <- 2"), vec![
+            "my var", "quoted"
+        ]);
+        assert_eq!(names("for (i in 1:2) total <- total + i"), vec![
+            "i", "total"
+        ]);
+        assert_eq!(names("suppressWarnings(df <- read.csv(path))"), vec!["df"]);
+    }
+
+    #[test]
+    fn test_assignment_targets_complex_assignments() {
+        assert_eq!(
+            names("x$a <- 1; y[[1]] <- 2; names(z) <- 'n'; w[1, ]$b <- 3"),
+            vec!["x", "y", "z", "w"]
+        );
+    }
+
+    #[test]
+    fn test_assignment_targets_skip_function_definitions() {
+        assert_eq!(names("f <- function() { local <- 1 }"), vec!["f"]);
+        assert_eq!(
+            names("lapply(1:2, function(i) x <- i)"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_assignment_targets_unknown() {
+        let targets = assignment_targets("x <- 1; assign(name, 2)");
+        assert_eq!(targets.names, vec!["x"]);
+        assert!(targets.unknown);
+
+        assert!(assignment_targets("load('data.RData')").unknown);
+        assert!(assignment_targets("rm(x)").unknown);
+        assert!(assignment_targets("assign(paste0('x', 1), 1)").unknown);
+    }
+}
@@ -0,0 +1,297 @@
+//
+// build_output.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+use amalthea::comm::build_comm::BuildBackendReply;
+use amalthea::comm::build_comm::BuildBackendRequest;
+use amalthea::comm::build_comm::BuildDiagnosticSeverity;
+use amalthea::comm::build_comm::BuildFrontendEvent;
+use amalthea::comm::build_comm::BuildOutputSource;
+use amalthea::comm::build_comm::BuildParams;
+use amalthea::comm::build_comm::BuildResult;
+use amalthea::comm::build_comm::DiagnosticParams;
+use amalthea::comm::build_comm::OutputParams;
+use amalthea::comm::comm_channel::CommMsg;
+use amalthea::socket::comm::CommSocket;
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use stdext::spawn;
+use uuid::Uuid;
+
+/// Diagnostics of gcc and clang, e.g. `init.c:12:5: error: expected ';'`. The
+/// column is optional and paths may start with a Windows drive letter.
+static DIAGNOSTIC_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(?P<file>(?:[A-Za-z]:)?[^:]+):(?P<line>\d+):(?:(?P<column>\d+):)?\s*(?P<severity>fatal error|error|warning|note):\s*(?P<message>.*)$",
+    )
+    .unwrap()
+});
+
+/// The build comm streams the output of package builds requested by the
+/// frontend, along with the compiler diagnostics parsed from that output.
+pub struct BuildComm {
+    comm: CommSocket,
+}
+
+impl BuildComm {
+    pub fn start(comm: CommSocket) {
+        spawn!("ark-build", move || {
+            let build = Self { comm };
+            build.execution_thread();
+        });
+    }
+
+    fn execution_thread(&self) {
+        loop {
+            let message = match self.comm.incoming_rx.recv() {
+                Ok(message) => message,
+                Err(err) => {
+                    log::warn!("Error receiving message from frontend: {err:?}");
+                    break;
+                },
+            };
+
+            if let CommMsg::Close = message {
+                break;
+            }
+
+            self.comm
+                .handle_request(message, |req| self.handle_rpc(req));
+        }
+
+        log::trace!("Build comm {} closed.", self.comm.comm_id);
+    }
+
+    fn handle_rpc(&self, message: BuildBackendRequest) -> anyhow::Result<BuildBackendReply> {
+        match message {
+            BuildBackendRequest::Build(params) => {
+                Ok(BuildBackendReply::BuildReply(self.build(params)?))
+            },
+        }
+    }
+
+    /// Builds the package with `R CMD INSTALL` in a separate process, so the
+    /// session stays responsive, and streams each line of output along with
+    /// the compiler diagnostics parsed from it. Unless `install` is set, the
+    /// package is installed into a temporary library that is removed
+    /// afterwards.
+    fn build(&self, params: BuildParams) -> anyhow::Result<BuildResult> {
+        let path = std::path::absolute(&params.path)?;
+        if !path.exists() {
+            return Err(anyhow!("Can't find package at '{}'.", path.display()));
+        }
+
+        let mut command = Command::new(r_path());
+        command.args(["CMD", "INSTALL", "--preclean"]);
+
+        let library = if params.install {
+            None
+        } else {
+            let library = std::env::temp_dir().join(format!("ark-build-{}", Uuid::new_v4()));
+            std::fs::create_dir_all(&library)?;
+            command
+                .arg("--no-test-load")
+                .arg(format!("--library={}", library.display()));
+            Some(library)
+        };
+
+        let result = self.run(command.arg(&path));
+
+        if let Some(library) = library {
+            if let Err(err) = std::fs::remove_dir_all(&library) {
+                log::warn!("Can't remove build library {}: {err}", library.display());
+            }
+        }
+
+        result
+    }
+
+    fn run(&self, command: &mut Command) -> anyhow::Result<BuildResult> {
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or(anyhow!("Can't read build output"))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or(anyhow!("Can't read build output"))?;
+
+        // Both streams are read concurrently so neither pipe can fill up and
+        // block the build
+        let (stdout_counts, stderr_counts) = std::thread::scope(|scope| {
+            let stdout = scope.spawn(|| self.stream(stdout, BuildOutputSource::Stdout));
+            let stderr = scope.spawn(|| self.stream(stderr, BuildOutputSource::Stderr));
+            (stdout.join(), stderr.join())
+        });
+        let stdout_counts = stdout_counts.map_err(|_| anyhow!("Build output reader panicked"))?;
+        let stderr_counts = stderr_counts.map_err(|_| anyhow!("Build output reader panicked"))?;
+
+        let status = child.wait()?;
+
+        Ok(BuildResult {
+            success: status.success(),
+            status: status.code().unwrap_or(-1) as i64,
+            errors: stdout_counts.errors + stderr_counts.errors,
+            warnings: stdout_counts.warnings + stderr_counts.warnings,
+        })
+    }
+
+    /// Streams the lines of `output` to the frontend until the build closes
+    /// it. Output isn't necessarily UTF-8, e.g. compiler messages in the
+    /// native encoding on Windows, so invalid sequences are replaced.
+    fn stream(&self, output: impl Read, source: BuildOutputSource) -> DiagnosticCounts {
+        let mut counts = DiagnosticCounts::default();
+
+        for line in BufReader::new(output).split(b'\n') {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    log::error!("Error reading build output: {err}");
+                    break;
+                },
+            };
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches('\r').to_string();
+
+            let diagnostic = parse_compiler_diagnostic(&line);
+
+            self.send_event(BuildFrontendEvent::Output(OutputParams {
+                source: source.clone(),
+                line,
+            }));
+
+            if let Some(diagnostic) = diagnostic {
+                match diagnostic.severity {
+                    BuildDiagnosticSeverity::Error => counts.errors += 1,
+                    BuildDiagnosticSeverity::Warning => counts.warnings += 1,
+                    BuildDiagnosticSeverity::Note => {},
+                }
+                self.send_event(BuildFrontendEvent::Diagnostic(diagnostic));
+            }
+        }
+
+        counts
+    }
+
+    fn send_event(&self, event: BuildFrontendEvent) {
+        let json = serde_json::to_value(event).unwrap();
+        if let Err(err) = self.comm.outgoing_tx.send(CommMsg::Data(json)) {
+            log::error!("Error sending build event to frontend: {err}");
+        }
+    }
+}
+
+#[derive(Default)]
+struct DiagnosticCounts {
+    errors: i64,
+    warnings: i64,
+}
+
+/// `R` of the R installation of the session, or else the one on the `PATH`
+fn r_path() -> PathBuf {
+    let name = if cfg!(windows) { "R.exe" } else { "R" };
+    match std::env::var("R_HOME") {
+        Ok(home) => Path::new(&home).join("bin").join(name),
+        Err(_) => PathBuf::from(name),
+    }
+}
+
+/// Parse a line of compiler output into a diagnostic.
+///
+/// Lines are parsed independently of each other, so the output of compilers
+/// running in parallel with `make -j` can be interleaved line by line.
+/// Context lines, such as `In file included from` or the source excerpts
+/// that follow a diagnostic, aren't diagnostics.
+pub fn parse_compiler_diagnostic(line: &str) -> Option<DiagnosticParams> {
+    let captures = DIAGNOSTIC_RE.captures(line.trim_end())?;
+
+    let severity = match &captures["severity"] {
+        "warning" => BuildDiagnosticSeverity::Warning,
+        "note" => BuildDiagnosticSeverity::Note,
+        _ => BuildDiagnosticSeverity::Error,
+    };
+
+    Some(DiagnosticParams {
+        file: captures["file"].to_string(),
+        line: captures["line"].parse().ok()?,
+        column: captures
+            .name("column")
+            .and_then(|column| column.as_str().parse().ok()),
+        severity,
+        message: captures["message"].to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use amalthea::comm::build_comm::BuildDiagnosticSeverity;
+
+    use crate::build_output::parse_compiler_diagnostic;
+
+    #[test]
+    fn test_parse_compiler_diagnostic() {
+        let diagnostic =
+            parse_compiler_diagnostic("init.c:12:5: error: expected ';' before 'return'").unwrap();
+        assert_eq!(diagnostic.file, "init.c");
+        assert_eq!(diagnostic.line, 12);
+        assert_eq!(diagnostic.column, Some(5));
+        assert_eq!(diagnostic.severity, BuildDiagnosticSeverity::Error);
+        assert_eq!(diagnostic.message, "expected ';' before 'return'");
+
+        let diagnostic =
+            parse_compiler_diagnostic("C:/pkg/src/init.c:3: warning: unused variable 'x'").unwrap();
+        assert_eq!(diagnostic.file, "C:/pkg/src/init.c");
+        assert_eq!(diagnostic.line, 3);
+        assert_eq!(diagnostic.column, None);
+        assert_eq!(diagnostic.severity, BuildDiagnosticSeverity::Warning);
+
+        let diagnostic =
+            parse_compiler_diagnostic("init.c:1:10: fatal error: 'Rcpp.h' file not found").unwrap();
+        assert_eq!(diagnostic.severity, BuildDiagnosticSeverity::Error);
+    }
+
+    #[test]
+    fn test_parse_compiler_diagnostic_interleaved_output() {
+        // Output of two compilers running in parallel
+        let output = [
+            "gcc -I\"/usr/share/R/include\" -fpic -c a.c -o a.o",
+            "gcc -I\"/usr/share/R/include\" -fpic -c b.c -o b.o",
+            "a.c: In function 'a':",
+            "b.c:2:3: warning: implicit declaration of function 'g'",
+            "a.c:4:10: error: 'y' undeclared (first use in this function)",
+            "    4 |   return y;",
+            "      |          ^",
+            "b.c:2:3: note: include '<stdlib.h>'",
+        ];
+
+        let diagnostics: Vec<_> = output
+            .iter()
+            .filter_map(|line| parse_compiler_diagnostic(line))
+            .map(|diagnostic| (diagnostic.file, diagnostic.line, diagnostic.severity))
+            .collect();
+
+        assert_eq!(diagnostics, vec![
+            (String::from("b.c"), 2, BuildDiagnosticSeverity::Warning),
+            (String::from("a.c"), 4, BuildDiagnosticSeverity::Error),
+            (String::from("b.c"), 2, BuildDiagnosticSeverity::Note),
+        ]);
+    }
+}
@@ -14,6 +14,7 @@ use amalthea::wire::shutdown_request::ShutdownRequest;
 use async_trait::async_trait;
 use crossbeam::channel::Sender;
 
+use crate::interface::request_shutdown;
 use crate::request::RRequest;
 
 pub struct Control {
@@ -39,24 +40,29 @@ impl ControlHandler for Control {
         // According to the Jupyter protocol we should block here until the
         // shutdown is complete. However AFAICS ipykernel doesn't wait
         // until complete shutdown before replying and instead just signals
-        // a shutdown via a global flag picked up by an event loop.
-
-        let status = if let Err(err) = self.r_request_tx.send(RRequest::Shutdown(msg.restart)) {
-            log::error!("Could not deliver shutdown request to execution thread: {err:?}");
-            Status::Error
-        } else {
-            Status::Ok
-        };
-
+        // a shutdown via a global flag picked up by an event loop. We do the
+        // same in `handle_shutdown()`, once the reply has been sent.
         Ok(ShutdownReply {
-            status,
+            status: Status::Ok,
             restart: msg.restart,
         })
     }
 
+    async fn handle_shutdown(&self, msg: &ShutdownRequest) {
+        if let Err(err) = self.r_request_tx.send(RRequest::Shutdown(msg.restart)) {
+            log::error!("Could not deliver shutdown request to execution thread: {err:?}");
+            return;
+        }
+
+        // In case R is busy evaluating code, interrupt it so it gets back to
+        // the prompt where the shutdown request is waiting
+        request_shutdown();
+    }
+
     async fn handle_interrupt_request(&self) -> Result<InterruptReply, Exception> {
         log::info!("Received interrupt request");
         crate::sys::control::handle_interrupt_request();
+        crate::signals::notify_interrupt();
         Ok(InterruptReply { status: Status::Ok })
     }
 }
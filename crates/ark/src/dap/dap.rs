@@ -23,6 +23,9 @@ use crate::dap::dap_server;
 use crate::request::RRequest;
 use crate::thread::RThreadSafe;
 
+/// Default maximum number of frames sent in response to a stack trace request
+pub const DEFAULT_MAX_FRAMES: usize = 50;
+
 #[derive(Debug, Copy, Clone)]
 pub enum DapBackendEvent {
     /// Event sent when a normal (non-browser) prompt marks the end of a
@@ -51,6 +54,11 @@ pub struct Dap {
     /// Current call stack
     pub stack: Option<Vec<FrameInfo>>,
 
+    /// Maximum number of frames sent in response to a stack trace request.
+    /// Set from the `ark.debugger_max_frames` option when the debugger
+    /// stops. Clients request deeper frames with `startFrame`.
+    pub max_frames: usize,
+
     /// Map of `source` -> `source_reference` used for frames that don't have
     /// associated files (i.e. no `srcref` attribute). The `source` is the key to
     /// ensure that we don't insert the same function multiple times, which would result
@@ -97,6 +105,7 @@ impl Dap {
             is_connected: false,
             backend_events_tx: None,
             stack: None,
+            max_frames: DEFAULT_MAX_FRAMES,
             fallback_sources: HashMap::new(),
             current_source_reference: 1,
             frame_id_to_variables_reference: HashMap::new(),
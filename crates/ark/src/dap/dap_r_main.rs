@@ -11,6 +11,7 @@ use std::sync::Mutex;
 use anyhow::anyhow;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
+use harp::object::r_null_or_try_into;
 use harp::object::RObject;
 use harp::protect::RProtect;
 use harp::r_string;
@@ -29,6 +30,7 @@ use libr::VECTOR_ELT;
 use stdext::log_error;
 
 use crate::dap::dap::DapBackendEvent;
+use crate::dap::dap::DEFAULT_MAX_FRAMES;
 use crate::dap::Dap;
 use crate::modules::ARK_ENVS;
 use crate::thread::RThreadSafe;
@@ -99,7 +101,10 @@ impl RMainDap {
 
     pub fn start_debug(&mut self, stack: Vec<FrameInfo>) {
         self.debugging = true;
+        let max_frames = max_frames_option();
+
         let mut dap = self.dap.lock().unwrap();
+        dap.max_frames = max_frames;
         dap.start_debug(stack)
     }
 
@@ -327,3 +332,16 @@ impl RMainDap {
         self.current_frame_info_id = 0;
     }
 }
+
+/// The `ark.debugger_max_frames` option, or the default when it is unset or
+/// isn't a positive number
+fn max_frames_option() -> usize {
+    let max_frames: Option<i32> = r_null_or_try_into(harp::get_option("ark.debugger_max_frames"))
+        .ok()
+        .flatten();
+
+    max_frames
+        .and_then(|max_frames| usize::try_from(max_frames).ok())
+        .filter(|max_frames| *max_frames > 0)
+        .unwrap_or(DEFAULT_MAX_FRAMES)
+}
@@ -263,6 +263,7 @@ impl<R: Read, W: Write> DapServer<R, W> {
     fn handle_initialize(&mut self, req: Request, _args: InitializeArguments) {
         let rsp = req.success(ResponseBody::Initialize(types::Capabilities {
             supports_restart_request: Some(true),
+            supports_delayed_stack_trace_loading: Some(true),
             ..Default::default()
         }));
         self.server.respond(rsp).unwrap();
@@ -337,36 +338,15 @@ impl<R: Read, W: Write> DapServer<R, W> {
 
     fn handle_stacktrace(&mut self, req: Request, args: StackTraceArguments) {
         let state = self.state.lock().unwrap();
-        let stack = &state.stack;
-        let fallback_sources = &state.fallback_sources;
-
-        let stack = match stack {
-            Some(stack) => stack
-                .into_iter()
-                .map(|frame| into_dap_frame(frame, fallback_sources))
-                .collect(),
-            _ => vec![],
-        };
-
-        // Slice the stack as requested
-        let n_usize = stack.len();
-        let start: usize = args.start_frame.unwrap_or(0).try_into().unwrap();
-        let start = std::cmp::min(start, n_usize);
-
-        let end = if let Some(levels) = args.levels {
-            let levels: usize = levels.try_into().unwrap();
-            std::cmp::min(start + levels, n_usize)
-        } else {
-            n_usize
-        };
-
-        let stack = stack[start..end].to_vec();
-        let n = stack.len().try_into().unwrap();
+        let stack = state.stack.as_deref().unwrap_or_default();
 
-        let rsp = req.success(ResponseBody::StackTrace(StackTraceResponse {
-            stack_frames: stack,
-            total_frames: Some(n),
-        }));
+        let rsp = req.success(ResponseBody::StackTrace(stack_trace_page(
+            stack,
+            &state.fallback_sources,
+            args.start_frame,
+            args.levels,
+            state.max_frames,
+        )));
 
         self.server.respond(rsp).unwrap();
     }
@@ -548,6 +528,44 @@ impl<R: Read, W: Write> DapServer<R, W> {
     }
 }
 
+/// Slice the stack as requested, sending at most `max_frames` frames.
+///
+/// `total_frames` is the depth of the whole stack so that clients can tell
+/// whether more frames are available and request them with `start_frame`.
+/// Requests starting beyond the depth of the stack get an empty page.
+fn stack_trace_page(
+    stack: &[FrameInfo],
+    fallback_sources: &HashMap<String, i32>,
+    start_frame: Option<i64>,
+    levels: Option<i64>,
+    max_frames: usize,
+) -> StackTraceResponse {
+    let n = stack.len();
+
+    let start = start_frame
+        .and_then(|start| usize::try_from(start).ok())
+        .unwrap_or(0);
+    let start = std::cmp::min(start, n);
+
+    // Zero or missing `levels` means all frames
+    let levels = match levels.and_then(|levels| usize::try_from(levels).ok()) {
+        Some(levels) if levels > 0 => std::cmp::min(levels, max_frames),
+        _ => max_frames,
+    };
+    let end = std::cmp::min(start.saturating_add(levels), n);
+
+    // Only convert the frames we send
+    let stack_frames = stack[start..end]
+        .iter()
+        .map(|frame| into_dap_frame(frame, fallback_sources))
+        .collect();
+
+    StackTraceResponse {
+        stack_frames,
+        total_frames: n.try_into().ok(),
+    }
+}
+
 fn into_dap_frame(frame: &FrameInfo, fallback_sources: &HashMap<String, i32>) -> StackFrame {
     let id = frame.id;
     let source_name = frame.source_name.clone();
@@ -597,3 +615,69 @@ fn into_dap_frame(frame: &FrameInfo, fallback_sources: &HashMap<String, i32>) ->
         presentation_hint: None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::dap::dap_r_main::FrameInfo;
+    use crate::dap::dap_r_main::FrameSource;
+    use crate::dap::dap_server::stack_trace_page;
+
+    // The frames of a 100-deep recursion `f(100)`, innermost first, along
+    // with the top level call
+    fn recursion_stack() -> Vec<FrameInfo> {
+        (0..101)
+            .map(|id| FrameInfo {
+                id,
+                source_name: String::from("f()"),
+                frame_name: format!("f(n - 1) [{id}]"),
+                source: FrameSource::File(String::from("/path/to/recursion.R")),
+                environment: None,
+                start_line: 2,
+                start_column: 1,
+                end_line: 2,
+                end_column: 10,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_stack_trace_page_is_capped() {
+        let stack = recursion_stack();
+        let sources = HashMap::new();
+
+        // The first page is capped, and the total tells there are more frames
+        let page = stack_trace_page(&stack, &sources, None, None, 20);
+        assert_eq!(page.stack_frames.len(), 20);
+        assert_eq!(page.stack_frames[0].id, 0);
+        assert_eq!(page.total_frames, Some(101));
+
+        // Requested levels are capped too
+        let page = stack_trace_page(&stack, &sources, Some(0), Some(50), 20);
+        assert_eq!(page.stack_frames.len(), 20);
+
+        // The next pages are requested with `start_frame`
+        let page = stack_trace_page(&stack, &sources, Some(20), Some(20), 20);
+        assert_eq!(page.stack_frames.len(), 20);
+        assert_eq!(page.stack_frames[0].id, 20);
+
+        let page = stack_trace_page(&stack, &sources, Some(100), Some(20), 20);
+        assert_eq!(page.stack_frames.len(), 1);
+        assert_eq!(page.stack_frames[0].id, 100);
+    }
+
+    #[test]
+    fn test_stack_trace_page_beyond_stack_depth() {
+        let stack = recursion_stack();
+        let sources = HashMap::new();
+
+        let page = stack_trace_page(&stack, &sources, Some(200), Some(20), 20);
+        assert!(page.stack_frames.is_empty());
+        assert_eq!(page.total_frames, Some(101));
+
+        let page = stack_trace_page(&[], &sources, None, None, 20);
+        assert!(page.stack_frames.is_empty());
+        assert_eq!(page.total_frames, Some(0));
+    }
+}
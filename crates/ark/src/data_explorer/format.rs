@@ -14,8 +14,10 @@ use harp::object::r_dbl_is_nan;
 use harp::object::r_length;
 use harp::object::RObject;
 use harp::r_null;
+use harp::raii::RLocalOption;
 use harp::utils::r_classes;
 use harp::utils::r_format_vec;
+use harp::utils::r_inherits;
 use harp::utils::r_is_null;
 use harp::utils::r_typeof;
 use harp::vector::CharacterVector;
@@ -49,6 +51,8 @@ pub fn format_string(x: SEXP, format_options: &FormatOptions) -> Vec<String> {
 }
 
 fn format(x: SEXP, format_options: &FormatOptions) -> Vec<FormattedValue> {
+    let format_options = &with_session_decimal_sep(format_options);
+
     let mut formatted = format_values(x, format_options).unwrap_or(unknown_format(x));
 
     // Truncate the values if they are too long
@@ -61,6 +65,18 @@ fn format(x: SEXP, format_options: &FormatOptions) -> Vec<FormattedValue> {
     formatted
 }
 
+/// Uses the session's `OutDec` option when the frontend doesn't specify a
+/// decimal separator. The frontend may specify its own so that a data viewer
+/// displays numbers in the locale of the user, independently of the locale of
+/// the computations.
+fn with_session_decimal_sep(format_options: &FormatOptions) -> FormatOptions {
+    let mut format_options = format_options.clone();
+    if format_options.decimal_sep.is_none() {
+        format_options.decimal_sep = harp::get_option("OutDec").try_into().ok();
+    }
+    format_options
+}
+
 // Truncating strings in Rust is more complicated that one would imagine.
 // If you index using eg s[..6] that would take 6 bytes, which is not necessarily 6 characters.
 // We need to iterate over the characters and truncate the string at the right character.
@@ -85,6 +101,17 @@ fn unknown_format(x: SEXP) -> Vec<FormattedValue> {
 // Format a column of data for display in the data explorer.
 fn format_values(x: SEXP, format_options: &FormatOptions) -> anyhow::Result<Vec<FormattedValue>> {
     if let Some(_) = r_classes(x) {
+        // `format()` methods use `OutDec` for the decimal separator
+        let decimal_sep = format_options.decimal_sep.as_deref().map(RObject::from);
+        let _out_dec = decimal_sep
+            .as_ref()
+            .map(|sep| RLocalOption::new("OutDec", sep.sexp));
+
+        if let Some(date_format) = &format_options.date_format {
+            if r_inherits(x, "Date") || r_inherits(x, "POSIXt") {
+                return Ok(format_date(x, date_format));
+            }
+        }
         return Ok(format_object(x));
     }
 
@@ -99,7 +126,10 @@ fn format_values(x: SEXP, format_options: &FormatOptions) -> anyhow::Result<Vec<
         )),
         STRSXP => Ok(format_chr(unsafe { CharacterVector::new_unchecked(x) })),
         LGLSXP => Ok(format_lgl(unsafe { LogicalVector::new_unchecked(x) })),
-        CPLXSXP => Ok(format_cpl(unsafe { ComplexVector::new_unchecked(x) })),
+        CPLXSXP => Ok(format_cpl(
+            unsafe { ComplexVector::new_unchecked(x) },
+            format_options,
+        )),
         VECSXP => Ok(format_list(x)),
         _ => Err(anyhow::anyhow!("Unsupported column type")),
     }
@@ -107,8 +137,21 @@ fn format_values(x: SEXP, format_options: &FormatOptions) -> anyhow::Result<Vec<
 
 fn format_object(x: SEXP) -> Vec<FormattedValue> {
     // We call r_format_vec() to dispatch the format method
-    let formatted: Vec<Option<String>> = match r_format_vec(x) {
-        Ok(fmt) => match RObject::from(fmt).try_into() {
+    let formatted = r_format_vec(x).map(RObject::from);
+    format_object_with(x, formatted)
+}
+
+fn format_date(x: SEXP, date_format: &str) -> Vec<FormattedValue> {
+    let formatted = RFunction::new("base", "format")
+        .add(x)
+        .param("format", date_format)
+        .call();
+    format_object_with(x, formatted)
+}
+
+fn format_object_with(x: SEXP, formatted: harp::Result<RObject>) -> Vec<FormattedValue> {
+    let formatted: Vec<Option<String>> = match formatted {
+        Ok(fmt) => match fmt.try_into() {
             Ok(x) => x,
             Err(_) => return unknown_format(x),
         },
@@ -219,10 +262,12 @@ fn format_list_elt(x: SEXP) -> String {
     format!("<{} [{}]>", class_str, dim_str)
 }
 
-fn format_cpl(x: ComplexVector) -> Vec<FormattedValue> {
+fn format_cpl(x: ComplexVector, options: &FormatOptions) -> Vec<FormattedValue> {
     x.iter()
         .map(|x| match x {
-            Some(v) => FormattedValue::Value(format!("{}+{}i", v.r, v.i)),
+            Some(v) => {
+                FormattedValue::Value(apply_decimal_sep(format!("{}+{}i", v.r, v.i), options))
+            },
             None => FormattedValue::NA,
         })
         .collect()
@@ -294,6 +339,10 @@ fn format_dbl_value(x: f64, options: &FormatOptions) -> FormattedValue {
     // notation
     let lower_threshold = f64::powf(10.0, -(options.small_num_digits as f64));
 
+    if let Some(digits) = options.significant_digits {
+        return format_dbl_value_significant(x, digits, upper_threshold, lower_threshold, options);
+    }
+
     let large_num_digits = options.large_num_digits as usize;
     let small_num_digits = options.small_num_digits as usize;
 
@@ -303,41 +352,93 @@ fn format_dbl_value(x: f64, options: &FormatOptions) -> FormattedValue {
         // large numbers use scientific notation
         // rust makes 1e7 instead of 1e+7 which aligns baddly
         let v = format!("{:.large_num_digits$e}", x).replace("e", "e+");
-        pad_exponent(v)
+        pad_exponent(apply_decimal_sep(v, options))
     } else if abs_x >= 1.0 {
         // this is considered medium numbers and they use a fixed amount of
         // digits after the decimal point
         apply_thousands_sep(
-            format!("{:.large_num_digits$}", x),
+            apply_decimal_sep(format!("{:.large_num_digits$}", x), options),
             options.thousands_sep.clone(),
         )
     } else if abs_x >= lower_threshold {
         // small numbers but not that small are formatted with a different
         // amount of digits after the decimal point
         apply_thousands_sep(
-            format!("{:.small_num_digits$}", x),
+            apply_decimal_sep(format!("{:.small_num_digits$}", x), options),
             options.thousands_sep.clone(),
         )
     } else if abs_x == 0.0 {
         // zero is special cased to behave like a medium number.
-        format!("{:.large_num_digits$}", x)
+        apply_decimal_sep(format!("{:.large_num_digits$}", x), options)
     } else {
         // very small numbers use scientific notation
         let v = format!("{:.large_num_digits$e}", x);
-        pad_exponent(v)
+        pad_exponent(apply_decimal_sep(v, options))
     };
 
     FormattedValue::Value(formatted)
 }
 
+fn format_dbl_value_significant(
+    x: f64,
+    digits: i64,
+    upper_threshold: f64,
+    lower_threshold: f64,
+    options: &FormatOptions,
+) -> FormattedValue {
+    let digits = digits.max(1);
+    let sci_digits = (digits - 1) as usize;
+
+    let abs_x = x.abs();
+
+    if abs_x != 0.0 && (abs_x >= upper_threshold || abs_x < lower_threshold) {
+        // very large and very small numbers use scientific notation
+        let v = format!("{:.sci_digits$e}", x);
+        let v = if v.contains("e-") {
+            v
+        } else {
+            v.replace("e", "e+")
+        };
+        return FormattedValue::Value(pad_exponent(apply_decimal_sep(v, options)));
+    }
+
+    // Round to the significant digits first and take the exponent of the
+    // rounded value, so that e.g. 9.999 becomes 10.0 and not 10.00, and
+    // 1234.5 becomes 1230 and not 1235, with 3 digits
+    let sci = format!("{:.sci_digits$e}", x);
+    let rounded: f64 = sci.parse().unwrap_or(x);
+    let exponent = sci
+        .split_once('e')
+        .and_then(|(_, exponent)| exponent.parse::<i64>().ok())
+        .unwrap_or(0);
+
+    let decimals = (digits - 1 - exponent).max(0) as usize;
+
+    FormattedValue::Value(apply_thousands_sep(
+        apply_decimal_sep(format!("{:.decimals$}", rounded), options),
+        options.thousands_sep.clone(),
+    ))
+}
+
+// Rust always formats numbers with a `.` decimal point
+fn apply_decimal_sep(x: String, options: &FormatOptions) -> String {
+    match options.decimal_sep.as_deref() {
+        Some(sep) if sep != "." => x.replace('.', sep),
+        _ => x,
+    }
+}
+
 fn apply_thousands_sep(x: String, sep: Option<String>) -> String {
     match sep {
         None => x,
         Some(sep) => {
             let mut formatted = String::new();
 
-            // Find the decimal point if any
-            let decimal_point = x.find('.').unwrap_or(x.len());
+            // Find the decimal separator if any, i.e. the end of the
+            // integral digits
+            let decimal_point = x
+                .find(|c: char| !c.is_ascii_digit() && c != '-')
+                .unwrap_or(x.len());
 
             // Walk backwards on the string to add the thousands separator
             let mut count: usize = 0;
@@ -448,7 +549,10 @@ mod tests {
             small_num_digits: 4,
             max_integral_digits: 7,
             thousands_sep: Some(",".to_string()),
+            decimal_sep: None,
             max_value_length: 100,
+            significant_digits: None,
+            date_format: None,
         }
     }
 
@@ -537,7 +641,10 @@ mod tests {
                 small_num_digits: 4,
                 max_integral_digits: 7,
                 thousands_sep: None,
+                decimal_sep: None,
                 max_value_length: 100,
+                significant_digits: None,
+                date_format: None,
             };
             let expected = vec![
                 "0.00",
@@ -564,7 +671,10 @@ mod tests {
                 small_num_digits: 4,
                 max_integral_digits: 7,
                 thousands_sep: Some("_".to_string()),
+                decimal_sep: None,
                 max_value_length: 100,
+                significant_digits: None,
+                date_format: None,
             };
 
             let expected = vec![
@@ -589,6 +699,74 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_real_formatting_significant_digits() {
+        r_task(|| {
+            let options = FormatOptions {
+                significant_digits: Some(3),
+                ..default_options()
+            };
+            let data = harp::parse_eval_global(
+                "c(3.14159, 9.999, 1234.5678, 0, -0.012345, 0.000012345, 123456789012)",
+            )
+            .unwrap();
+            let formatted = format_column(data.sexp, &options);
+            let expected = vec![
+                "3.14", "10.0", "1,230", "0.00", "-0.0123", "1.23e-05", "1.23e+11",
+            ];
+            assert_eq!(
+                formatted,
+                expected
+                    .into_iter()
+                    .map(|v| ColumnValue::FormattedValue(v.to_string()))
+                    .collect::<Vec<_>>()
+            );
+        })
+    }
+
+    #[test]
+    fn test_real_formatting_decimal_sep() {
+        r_task(|| {
+            let data = harp::parse_eval_global("c(1234.5678, 0.5, 0, 123456789012)").unwrap();
+            let values = |options: &FormatOptions| {
+                format_column(data.sexp, options)
+                    .into_iter()
+                    .map(|v| match v {
+                        ColumnValue::FormattedValue(v) => v,
+                        _ => panic!("Unexpected special value"),
+                    })
+                    .collect::<Vec<_>>()
+            };
+
+            // From the session's `OutDec` option
+            {
+                let _out_dec = RLocalOption::new("OutDec", RObject::from(",").sexp);
+                let options = FormatOptions {
+                    thousands_sep: Some(".".to_string()),
+                    ..default_options()
+                };
+                assert_eq!(values(&options), vec![
+                    "1.234,57", "0,5000", "0,00", "1,23e+11"
+                ]);
+            }
+
+            // Specified by the frontend, independently of `OutDec`
+            let options = FormatOptions {
+                decimal_sep: Some(",".to_string()),
+                thousands_sep: None,
+                ..default_options()
+            };
+            assert_eq!(values(&options), vec![
+                "1234,57", "0,5000", "0,00", "1,23e+11"
+            ]);
+
+            // Default
+            assert_eq!(values(&default_options()), vec![
+                "1,234.57", "0.5000", "0.00", "1.23e+11"
+            ]);
+        })
+    }
+
     #[test]
     fn test_float_special_values() {
         r_task(|| {
@@ -736,6 +914,24 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_date_formatting_with_format() {
+        r_task(|| {
+            let options = FormatOptions {
+                date_format: Some("%d/%m/%Y".to_string()),
+                ..default_options()
+            };
+            let data =
+                harp::parse_eval_global(r#"as.Date(c("2012-01-01", NA, "2017-05-27"))"#).unwrap();
+            let formatted = format_column(data.sexp, &options);
+            assert_eq!(formatted, vec![
+                ColumnValue::FormattedValue("01/01/2012".to_string()),
+                FormattedValue::NA.into(),
+                ColumnValue::FormattedValue("27/05/2017".to_string())
+            ]);
+        })
+    }
+
     #[test]
     fn test_truncation() {
         r_task(|| {
@@ -88,6 +88,7 @@ use crate::interface::RMain;
 use crate::lsp::events::EVENTS;
 use crate::modules::ARK_ENVS;
 use crate::r_task;
+use crate::r_task::r_task_preserving_seed;
 use crate::thread::RThreadSafe;
 use crate::variables::variable::WorkspaceVariableDisplayType;
 
@@ -468,7 +469,7 @@ impl RDataExplorer {
             DataExplorerBackendRequest::GetDataValues(GetDataValuesParams {
                 columns,
                 format_options,
-            }) => r_task(|| self.r_get_data_values(columns, format_options)),
+            }) => r_task_preserving_seed(|| self.r_get_data_values(columns, format_options)),
 
             DataExplorerBackendRequest::SetSortColumns(SetSortColumnsParams {
                 sort_keys: keys,
@@ -986,7 +987,10 @@ impl RDataExplorer {
 
             // The column will be always at index 0 because we already selected a single column above.
             let column = tbl_get_column(tbl.sexp, 0, self.shape.kind)?;
-            let formatted = format::format_column(column.sexp, &format_options);
+
+            // Columns may have their own formatting options
+            let options = selection.format_options.as_ref().unwrap_or(&format_options);
+            let formatted = format::format_column(column.sexp, options);
             column_data.push(formatted.clone());
         }
 
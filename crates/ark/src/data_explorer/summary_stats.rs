@@ -190,7 +190,10 @@ mod tests {
             small_num_digits: 4,
             max_integral_digits: 7,
             thousands_sep: Some(",".to_string()),
+            decimal_sep: None,
             max_value_length: 100,
+            significant_digits: None,
+            date_format: None,
         }
     }
 
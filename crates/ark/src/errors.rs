@@ -44,6 +44,13 @@ unsafe extern "C" fn ps_record_error(evalue: SEXP, traceback: SEXP) -> anyhow::R
     Ok(R_NilValue)
 }
 
+#[harp::register]
+unsafe extern "C" fn ps_record_startup_message(message: SEXP) -> anyhow::Result<SEXP> {
+    let message: String = RObject::view(message).try_into()?;
+    RMain::get_mut().record_startup_message(&message);
+    Ok(R_NilValue)
+}
+
 #[harp::register]
 unsafe extern "C" fn ps_format_traceback(calls: SEXP) -> anyhow::Result<SEXP> {
     Ok(r_format_traceback(calls.into())?.sexp)
@@ -7,8 +7,13 @@ use std::sync::OnceLock;
 
 use amalthea::fixtures::dummy_frontend::DummyConnection;
 use amalthea::fixtures::dummy_frontend::DummyFrontend;
+use amalthea::socket::shell::ExecuteQueue;
+use amalthea::wire::jupyter_message::Message;
+use amalthea::wire::status::ExecutionState;
 
+use crate::ansi::AnsiOutput;
 use crate::interface::SessionMode;
+use crate::startup::SkipStartupFiles;
 
 // There can be only one frontend per process. Needs to be in a mutex because
 // the frontend wraps zmq sockets which are unsafe to send across threads.
@@ -18,6 +23,8 @@ use crate::interface::SessionMode;
 // initialization in the future.
 static FRONTEND: OnceLock<Arc<Mutex<DummyFrontend>>> = OnceLock::new();
 
+const DUMMY_KERNEL_STACK_SIZE: usize = 16 * 1024 * 1024;
+
 /// Wrapper around `DummyFrontend` that checks sockets are empty on drop
 pub struct DummyArkFrontend {
     guard: MutexGuard<'static, DummyFrontend>,
@@ -28,6 +35,7 @@ struct DummyArkFrontendOptions {
     site_r_profile: bool,
     user_r_profile: bool,
     r_environ: bool,
+    skip_startup_files: SkipStartupFiles,
     session_mode: SessionMode,
 }
 
@@ -41,11 +49,23 @@ pub struct DummyArkFrontendNotebook {
     inner: DummyArkFrontend,
 }
 
+/// Wrapper around `DummyArkFrontend` with the UI comm open, as in Positron.
+/// The comm messages sent on IOPub, e.g. busy events, are skipped.
+pub struct DummyArkFrontendUi {
+    inner: DummyArkFrontend,
+}
+
 /// Wrapper around `DummyArkFrontend` that allows an `.Rprofile` to run
 pub struct DummyArkFrontendRprofile {
     inner: DummyArkFrontend,
 }
 
+/// Wrapper around `DummyArkFrontend` that allows the `.Rprofile` and
+/// `.Renviron` files to run, but starts in safe mode which skips the user ones
+pub struct DummyArkFrontendSafeMode {
+    inner: DummyArkFrontend,
+}
+
 impl DummyArkFrontend {
     pub fn lock() -> Self {
         Self {
@@ -93,20 +113,32 @@ impl DummyArkFrontend {
         if !options.r_environ {
             r_args.push(String::from("--no-environ"));
         }
+        options.skip_startup_files.apply(&mut r_args);
 
         // Start the kernel and REPL in a background thread, does not return and is never joined.
         // Must run `start_kernel()` in a background thread because it blocks until it receives
         // a `HandshakeReply`, which we send from `from_connection()` below.
-        stdext::spawn!("dummy_kernel", move || {
-            crate::start::start_kernel(
-                connection_file,
-                Some(registration_file),
-                r_args,
-                None,
-                options.session_mode,
-                false,
-            );
-        });
+        //
+        // R's stack checks are disabled in tests, so give the R thread a stack
+        // as large as a typical main thread. Runaway recursion then hits R's
+        // `expressions` limit and fails with an R error instead of overflowing
+        // the default 2 MiB stack of spawned threads.
+        std::thread::Builder::new()
+            .name(String::from("dummy_kernel"))
+            .stack_size(DUMMY_KERNEL_STACK_SIZE)
+            .spawn(move || {
+                crate::start::start_kernel(
+                    connection_file,
+                    Some(registration_file),
+                    r_args,
+                    None,
+                    options.session_mode,
+                    false,
+                    AnsiOutput::default(),
+                    ExecuteQueue::default(),
+                );
+            })
+            .unwrap();
 
         DummyFrontend::from_connection(connection)
     }
@@ -170,6 +202,65 @@ impl DerefMut for DummyArkFrontendNotebook {
     }
 }
 
+impl DummyArkFrontendUi {
+    /// Lock a frontend that opened the UI comm.
+    ///
+    /// NOTE: Only one `DummyArkFrontend` variant should call `lock()` within
+    /// a given process.
+    pub fn lock() -> Self {
+        FRONTEND.get_or_init(|| {
+            let options = DummyArkFrontendOptions::default();
+            let mut frontend = DummyArkFrontend::init(options);
+            Self::open_ui_comm(&mut frontend);
+            Arc::new(Mutex::new(frontend))
+        });
+
+        Self {
+            inner: DummyArkFrontend::lock(),
+        }
+    }
+
+    fn open_ui_comm(frontend: &mut DummyFrontend) {
+        let comm_id = frontend.send_comm_open("positron.ui", serde_json::json!({}));
+
+        // The kernel is busy while it handles the request. Once the channel
+        // is established, R sends the current prompt state. These can arrive
+        // in any order.
+        let mut idle = false;
+        let mut established = false;
+
+        while !idle || !established {
+            match frontend.recv_iopub() {
+                Message::Status(data) => {
+                    idle = data.content.execution_state == ExecutionState::Idle;
+                },
+                Message::CommMsg(data) => {
+                    assert_eq!(data.content.comm_id, comm_id);
+                    established = established || data.content.data["method"] == "prompt_state";
+                },
+                msg => panic!("Unexpected message while opening the UI comm: {msg:?}"),
+            }
+        }
+
+        frontend.skip_iopub_comm_msgs();
+    }
+}
+
+// Allow method calls to be forwarded to inner type
+impl Deref for DummyArkFrontendUi {
+    type Target = DummyFrontend;
+
+    fn deref(&self) -> &Self::Target {
+        Deref::deref(&self.inner)
+    }
+}
+
+impl DerefMut for DummyArkFrontendUi {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        DerefMut::deref_mut(&mut self.inner)
+    }
+}
+
 impl DummyArkFrontendRprofile {
     /// Lock a frontend that supports `.Rprofile`s.
     ///
@@ -216,6 +307,47 @@ impl DerefMut for DummyArkFrontendRprofile {
     }
 }
 
+impl DummyArkFrontendSafeMode {
+    /// Lock a frontend started in safe mode.
+    ///
+    /// NOTE: Like `DummyArkFrontendRprofile`, this variant can only be
+    /// called exactly once per process.
+    pub fn lock() -> Self {
+        Self::init();
+
+        Self {
+            inner: DummyArkFrontend::lock(),
+        }
+    }
+
+    fn init() {
+        let mut options = DummyArkFrontendOptions::default();
+        options.user_r_profile = true;
+        options.r_environ = true;
+        options.skip_startup_files = SkipStartupFiles::safe_mode();
+        let status = FRONTEND.set(Arc::new(Mutex::new(DummyArkFrontend::init(options))));
+
+        if status.is_err() {
+            panic!("You can only call `DummyArkFrontendSafeMode::lock()` once per process.");
+        }
+    }
+}
+
+// Allow method calls to be forwarded to inner type
+impl Deref for DummyArkFrontendSafeMode {
+    type Target = DummyFrontend;
+
+    fn deref(&self) -> &Self::Target {
+        Deref::deref(&self.inner)
+    }
+}
+
+impl DerefMut for DummyArkFrontendSafeMode {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        DerefMut::deref_mut(&mut self.inner)
+    }
+}
+
 impl Default for DummyArkFrontendOptions {
     fn default() -> Self {
         Self {
@@ -223,6 +355,7 @@ impl Default for DummyArkFrontendOptions {
             site_r_profile: false,
             user_r_profile: false,
             r_environ: false,
+            skip_startup_files: SkipStartupFiles::default(),
             session_mode: SessionMode::Console,
         }
     }
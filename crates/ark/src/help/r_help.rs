@@ -5,26 +5,41 @@
 //
 //
 
+use std::cell::Cell;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::help_comm::HelpBackendReply;
 use amalthea::comm::help_comm::HelpBackendRequest;
 use amalthea::comm::help_comm::HelpFrontendEvent;
 use amalthea::comm::help_comm::ShowHelpKind;
 use amalthea::comm::help_comm::ShowHelpParams;
+use amalthea::comm::help_comm::ShowHelpTopicAssetLinks;
+use amalthea::comm::help_comm::ShowHelpTopicContentFormat;
 use amalthea::socket::comm::CommSocket;
 use anyhow::anyhow;
+use base64::engine::general_purpose;
+use base64::Engine;
 use crossbeam::channel::Receiver;
 use crossbeam::channel::Sender;
 use crossbeam::select;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
+use harp::object::r_null_or_try_into;
+use harp::utils::r_is_null;
 use log::info;
 use log::trace;
 use log::warn;
+use regex::Captures;
+use regex::Regex;
 use stdext::spawn;
 
 use crate::help::message::HelpEvent;
 use crate::help::message::ShowHelpUrlParams;
+use crate::help_proxy;
+use crate::interface::RMain;
 use crate::r_task;
 
 /**
@@ -33,7 +48,7 @@ use crate::r_task;
  */
 pub struct RHelp {
     comm: CommSocket,
-    r_port: u16,
+    r_port: Cell<u16>,
     proxy_port: u16,
     help_event_rx: Receiver<HelpEvent>,
 }
@@ -60,7 +75,7 @@ impl RHelp {
         spawn!("ark-help", move || {
             let help = Self {
                 comm,
-                r_port,
+                r_port: Cell::new(r_port),
                 proxy_port,
                 help_event_rx,
             };
@@ -162,11 +177,21 @@ impl RHelp {
             HelpBackendRequest::ShowHelpTopic(topic) => {
                 // Look up the help topic and attempt to show it; this returns a
                 // boolean indicating whether the topic was found.
-                match self.show_help_topic(topic.topic.clone()) {
+                let format = topic
+                    .content_format
+                    .unwrap_or(ShowHelpTopicContentFormat::Url);
+                let asset_links = topic
+                    .asset_links
+                    .unwrap_or(ShowHelpTopicAssetLinks::Relative);
+                match self.show_help_topic(topic.topic.clone(), format, asset_links) {
                     Ok(found) => Ok(HelpBackendReply::ShowHelpTopicReply(found)),
                     Err(err) => Err(err),
                 }
             },
+            HelpBackendRequest::RestartHelpServer => {
+                let port = self.restart_help_server()?;
+                Ok(HelpBackendReply::RestartHelpServerReply(port as i64))
+            },
         }
     }
 
@@ -183,16 +208,19 @@ impl RHelp {
     /// `is_help_url()`, so if we get an unexpected prefix, that's an error.
     fn handle_show_help_url(&self, params: ShowHelpUrlParams) -> anyhow::Result<()> {
         let url = params.url;
+        let r_port = self.r_port.get();
 
-        if !Self::is_help_url(url.as_str(), self.r_port) {
-            let prefix = Self::help_url_prefix(self.r_port);
+        // URLs pointing to the help server from before a restart fail here.
+        // Requesting them again from R yields a URL with the new port.
+        if !Self::is_help_url(url.as_str(), r_port) {
+            let prefix = Self::help_url_prefix(r_port);
             return Err(anyhow!(
                 "Help URL '{url}' doesn't have expected prefix '{prefix}'."
             ));
         }
 
         // Re-direct the help event to our help proxy server.
-        let r_prefix = Self::help_url_prefix(self.r_port);
+        let r_prefix = Self::help_url_prefix(r_port);
         let proxy_prefix = Self::help_url_prefix(self.proxy_port);
 
         let proxy_url = url.replace(r_prefix.as_str(), proxy_prefix.as_str());
@@ -214,7 +242,85 @@ impl RHelp {
     }
 
     #[tracing::instrument(level = "trace", skip(self))]
-    fn show_help_topic(&self, topic: String) -> anyhow::Result<bool> {
+    fn show_help_topic(
+        &self,
+        topic: String,
+        format: ShowHelpTopicContentFormat,
+        asset_links: ShowHelpTopicAssetLinks,
+    ) -> anyhow::Result<bool> {
+        let kind = match format {
+            ShowHelpTopicContentFormat::Url => return self.show_help_topic_url(topic),
+            ShowHelpTopicContentFormat::Html => ShowHelpKind::Html,
+            ShowHelpTopicContentFormat::Markdown => ShowHelpKind::Markdown,
+            ShowHelpTopicContentFormat::Text => ShowHelpKind::Text,
+        };
+
+        let contents: Option<String> = r_task(|| {
+            let contents = RFunction::from(".ps.help.getHelpContents")
+                .add(topic.clone())
+                .add(format.to_string())
+                .call()?;
+            r_null_or_try_into(contents)
+        })?;
+
+        // Topics without an Rd page, e.g. only documented in a vignette, are
+        // left to the help server
+        let Some(content) = contents else {
+            log::trace!("No Rd contents for topic '{topic}', falling back to a help URL");
+            return self.show_help_topic_url(topic);
+        };
+
+        let content = match kind {
+            ShowHelpKind::Html => self.resolve_asset_links(&topic, content, asset_links)?,
+            _ => content,
+        };
+
+        let msg = HelpFrontendEvent::ShowHelp(ShowHelpParams {
+            content,
+            kind,
+            focus: true,
+        });
+        let json = serde_json::to_value(msg)?;
+        self.comm.outgoing_tx.send(CommMsg::Data(json))?;
+
+        Ok(true)
+    }
+
+    /// Rewrites the relative asset links of HTML help so that they work
+    /// outside of the help server, e.g. in a sandboxed webview
+    fn resolve_asset_links(
+        &self,
+        topic: &str,
+        html: String,
+        asset_links: ShowHelpTopicAssetLinks,
+    ) -> anyhow::Result<String> {
+        if asset_links == ShowHelpTopicAssetLinks::Relative {
+            return Ok(html);
+        }
+
+        let dirs = r_task(|| -> anyhow::Result<Option<HelpAssetDirs>> {
+            let dirs = RFunction::from(".ps.help.getHelpAssetDirs")
+                .add(topic)
+                .call()?;
+            if r_is_null(dirs.sexp) {
+                return Ok(None);
+            }
+            Ok(Some(HelpAssetDirs {
+                package: String::try_from(dirs.vector_elt(0)?)?,
+                html: PathBuf::from(String::try_from(dirs.vector_elt(1)?)?),
+                figures: PathBuf::from(String::try_from(dirs.vector_elt(2)?)?),
+            }))
+        })?;
+
+        let Some(dirs) = dirs else {
+            return Ok(html);
+        };
+
+        let server = Self::help_url_prefix(self.proxy_port);
+        Ok(resolve_help_asset_links(&html, &dirs, asset_links, &server))
+    }
+
+    fn show_help_topic_url(&self, topic: String) -> anyhow::Result<bool> {
         let found = r_task(|| unsafe {
             RFunction::from(".ps.help.showHelpTopic")
                 .add(topic)
@@ -224,6 +330,28 @@ impl RHelp {
         Ok(found)
     }
 
+    /// Restarts the R help server and points the help proxy and the R thread
+    /// to its new port, which R may pick anew.
+    fn restart_help_server(&self) -> anyhow::Result<u16> {
+        let port = r_task(|| -> anyhow::Result<u16> {
+            let port = Self::r_restart_help_server()?;
+
+            // So that help URLs browsed from R are recognised with the new port
+            if RMain::is_initialized() {
+                RMain::with_mut(|main| main.set_help_port(port));
+            }
+
+            Ok(port)
+        })?;
+
+        log::info!("Restarted R help server on port {port}");
+
+        help_proxy::set_target_port(port);
+        self.r_port.set(port);
+
+        Ok(port)
+    }
+
     pub fn r_start_or_reconnect_to_help_server() -> harp::Result<u16> {
         // Start the R help server.
         // If it is already started, it just returns the preexisting port number.
@@ -231,4 +359,168 @@ impl RHelp {
             .call()
             .and_then(|x| x.try_into())
     }
+
+    pub fn r_restart_help_server() -> harp::Result<u16> {
+        RFunction::from(".ps.help.restartHelpServer")
+            .call()
+            .and_then(|x| x.try_into())
+    }
+}
+
+/// Where the relative asset links of a help page resolve to on disk
+struct HelpAssetDirs {
+    package: String,
+    html: PathBuf,
+    figures: PathBuf,
+}
+
+/// The link of an asset referenced by `Rd2HTML()` output: figures, the
+/// stylesheet, and scripts
+static RE_HELP_ASSET_LINK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(<(?:img|link|script)\b[^>]*?\b(?:src|href)=")([^"]*)(")"#).unwrap()
+});
+
+/// Prefix of the links of `\figure{}` images, relative to the `html`
+/// directory of the package
+const HELP_FIGURES_PREFIX: &str = "../help/figures/";
+
+/// Rewrites the relative asset links of HTML help, either as absolute links
+/// to the help `server` or as inlined data URLs. Links that are already
+/// absolute or that can't be resolved are left as is.
+fn resolve_help_asset_links(
+    html: &str,
+    dirs: &HelpAssetDirs,
+    asset_links: ShowHelpTopicAssetLinks,
+    server: &str,
+) -> String {
+    let replacement = |caps: &Captures| -> String {
+        let link = &caps[2];
+
+        let resolved = if link.is_empty() ||
+            link.starts_with('#') ||
+            link.starts_with('/') ||
+            link.contains(':')
+        {
+            None
+        } else {
+            match asset_links {
+                ShowHelpTopicAssetLinks::Relative => None,
+                ShowHelpTopicAssetLinks::Absolute => absolute_asset_link(link, dirs, server),
+                ShowHelpTopicAssetLinks::Inline => inline_asset_link(link, dirs),
+            }
+        };
+
+        let link = resolved.unwrap_or_else(|| link.to_string());
+        format!("{}{link}{}", &caps[1], &caps[3])
+    };
+
+    RE_HELP_ASSET_LINK
+        .replace_all(html, replacement)
+        .into_owned()
+}
+
+fn absolute_asset_link(link: &str, dirs: &HelpAssetDirs, server: &str) -> Option<String> {
+    let base = url::Url::parse(server)
+        .ok()?
+        .join(&format!("library/{}/html/", dirs.package))
+        .ok()?;
+    Some(base.join(link).ok()?.to_string())
+}
+
+fn inline_asset_link(link: &str, dirs: &HelpAssetDirs) -> Option<String> {
+    let path = match link.strip_prefix(HELP_FIGURES_PREFIX) {
+        Some(figure) => dirs.figures.join(figure),
+        None => dirs.html.join(link),
+    };
+
+    let contents = match std::fs::read(&path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            log::warn!("Can't inline help asset {path:?}: {err}");
+            return None;
+        },
+    };
+
+    let mime = asset_mime_type(&path);
+    let data = general_purpose::STANDARD.encode(contents);
+    Some(format!("data:{mime};base64,{data}"))
+}
+
+fn asset_mime_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amalthea::comm::help_comm::ShowHelpTopicAssetLinks;
+
+    use crate::help::r_help::resolve_help_asset_links;
+    use crate::help::r_help::HelpAssetDirs;
+    use crate::r_task;
+
+    #[test]
+    fn test_help_asset_links_figure() {
+        let dir = tempfile::tempdir().unwrap();
+        let html_dir = dir.path().join("html");
+        let figures_dir = dir.path().join("help").join("figures");
+        std::fs::create_dir_all(&html_dir).unwrap();
+        std::fs::create_dir_all(&figures_dir).unwrap();
+        std::fs::write(html_dir.join("R.css"), "body {}").unwrap();
+        std::fs::write(figures_dir.join("fig.png"), [0x89, b'P', b'N', b'G']).unwrap();
+
+        // The HTML rendered by R for a help page with a figure
+        let html = r_task(|| {
+            let html = harp::parse_eval_global(
+                r#"local({
+                    rd <- "\\name{fig}\\alias{fig}\\title{Fig}\\description{\\figure{fig.png}{A figure}}"
+                    rd <- tools::parse_Rd(textConnection(rd))
+                    out <- tempfile(fileext = ".html")
+                    on.exit(unlink(out))
+                    tools::Rd2HTML(rd, out = out, package = "pkg")
+                    paste(readLines(out), collapse = "\n")
+                })"#,
+            )
+            .unwrap();
+            String::try_from(html).unwrap()
+        });
+        assert!(html.contains(r#"src="../help/figures/fig.png""#));
+
+        let dirs = HelpAssetDirs {
+            package: String::from("pkg"),
+            html: html_dir,
+            figures: figures_dir,
+        };
+        let server = "http://127.0.0.1:1234/";
+
+        let inlined =
+            resolve_help_asset_links(&html, &dirs, ShowHelpTopicAssetLinks::Inline, server);
+        assert!(inlined.contains(r#"src="data:image/png;base64,iVBORw==""#));
+        assert!(inlined.contains(r#"href="data:text/css;base64,Ym9keSB7fQ==""#));
+        assert!(!inlined.contains("../help/figures"));
+
+        let absolute =
+            resolve_help_asset_links(&html, &dirs, ShowHelpTopicAssetLinks::Absolute, server);
+        assert!(
+            absolute.contains(r#"src="http://127.0.0.1:1234/library/pkg/help/figures/fig.png""#)
+        );
+        assert!(absolute.contains(r#"href="http://127.0.0.1:1234/library/pkg/html/R.css""#));
+
+        let relative =
+            resolve_help_asset_links(&html, &dirs, ShowHelpTopicAssetLinks::Relative, server);
+        assert_eq!(relative, html);
+    }
 }
@@ -6,6 +6,8 @@
 //
 
 use std::net::TcpListener;
+use std::sync::atomic::AtomicU16;
+use std::sync::atomic::Ordering;
 
 use actix_web::get;
 use actix_web::http::header::ContentType;
@@ -40,12 +42,17 @@ struct PreviewRdParams {
     file: String,
 }
 
+// The port of the R help server that requests are proxied to. Shared by all
+// proxies since there is a single R help server, and updated when it restarts.
+static TARGET_PORT: AtomicU16 = AtomicU16::new(0);
+
 // Starts the help proxy.
 pub fn start(target_port: u16) -> anyhow::Result<u16> {
     let source_port = HelpProxy::get_os_assigned_port()?;
+    set_target_port(target_port);
 
     spawn!("ark-help-proxy", move || {
-        match task(source_port, target_port) {
+        match task(source_port) {
             Ok(value) => log::info!("Help proxy server exited with value: {:?}", value),
             Err(error) => log::error!("Help proxy server exited unexpectedly: {}", error),
         }
@@ -54,49 +61,38 @@ pub fn start(target_port: u16) -> anyhow::Result<u16> {
     Ok(source_port)
 }
 
+// Points the help proxy to a restarted R help server.
+pub fn set_target_port(target_port: u16) {
+    TARGET_PORT.store(target_port, Ordering::Relaxed);
+}
+
 // The help proxy main entry point.
 #[tokio::main]
-async fn task(source_port: u16, target_port: u16) -> anyhow::Result<()> {
+async fn task(source_port: u16) -> anyhow::Result<()> {
     // Create the help proxy.
-    let help_proxy = HelpProxy::new(source_port, target_port)?;
+    let help_proxy = HelpProxy::new(source_port)?;
 
     // Run the help proxy.
     Ok(help_proxy.run().await?)
 }
 
-// AppState struct.
-#[derive(Clone)]
-struct AppState {
-    target_port: u16,
-}
-
 // HelpProxy struct.
 struct HelpProxy {
     source_port: u16,
-    target_port: u16,
 }
 
 // HelpProxy implementation.
 impl HelpProxy {
     // Creates a new HelpProxy.
-    fn new(source_port: u16, target_port: u16) -> anyhow::Result<Self> {
-        Ok(HelpProxy {
-            source_port,
-            target_port,
-        })
+    fn new(source_port: u16) -> anyhow::Result<Self> {
+        Ok(HelpProxy { source_port })
     }
 
     // Runs the HelpProxy.
     async fn run(&self) -> anyhow::Result<()> {
-        // Create the app state.
-        let app_state = web::Data::new(AppState {
-            target_port: self.target_port,
-        });
-
         // Create the server.
         let server = HttpServer::new(move || {
             App::new()
-                .app_data(app_state.clone())
                 .service(preview_rd)
                 .service(preview_img)
                 .default_service(web::to(proxy_request))
@@ -113,8 +109,11 @@ impl HelpProxy {
 }
 
 // Proxies a request.
-async fn proxy_request(req: HttpRequest, app_state: web::Data<AppState>) -> HttpResponse {
-    let target_port = app_state.target_port;
+async fn proxy_request(req: HttpRequest) -> HttpResponse {
+    // Read on each request so requests made after a restart of the R help
+    // server reach the new port. Requests in flight during the restart fail
+    // with a bad gateway and can be retried.
+    let target_port = TARGET_PORT.load(Ordering::Relaxed);
 
     let target_path_and_query = req
         .uri()
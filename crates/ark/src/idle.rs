@@ -0,0 +1,82 @@
+//
+// idle.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use harp::object::r_null_or_try_into;
+
+/// What to do once the session has been idle for longer than the
+/// `ark.idle_timeout` option, in seconds. Configured with the
+/// `ark.idle_action` option.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum IdleAction {
+    /// Run the garbage collector to release memory. This is the default.
+    Gc,
+    /// Let the user know in the console
+    Warn,
+    /// Shut down the kernel
+    Shutdown,
+}
+
+impl FromStr for IdleAction {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "gc" => Ok(Self::Gc),
+            "warn" => Ok(Self::Warn),
+            "shutdown" => Ok(Self::Shutdown),
+            _ => Err(anyhow::anyhow!(
+                "Expected one of \"gc\", \"warn\", or \"shutdown\", got \"{value}\""
+            )),
+        }
+    }
+}
+
+/// The idle timeout and action, or `None` when the timeout is unset or isn't
+/// a positive number of seconds
+pub(crate) fn idle_config() -> Option<(Duration, IdleAction)> {
+    let timeout: Option<f64> = r_null_or_try_into(harp::get_option("ark.idle_timeout"))
+        .ok()
+        .flatten();
+    let timeout = timeout.filter(|timeout| timeout.is_finite() && *timeout > 0.0)?;
+
+    let action: Option<String> = r_null_or_try_into(harp::get_option("ark.idle_action"))
+        .ok()
+        .flatten();
+    let action = match action {
+        Some(action) => match IdleAction::from_str(&action) {
+            Ok(action) => action,
+            Err(err) => {
+                log::warn!("Ignoring invalid `ark.idle_action`: {err}");
+                IdleAction::Gc
+            },
+        },
+        None => IdleAction::Gc,
+    };
+
+    Some((Duration::from_secs_f64(timeout), action))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::idle::IdleAction;
+
+    #[test]
+    fn test_idle_action_from_str() {
+        assert_eq!(IdleAction::from_str("gc").unwrap(), IdleAction::Gc);
+        assert_eq!(IdleAction::from_str("warn").unwrap(), IdleAction::Warn);
+        assert_eq!(
+            IdleAction::from_str("shutdown").unwrap(),
+            IdleAction::Shutdown
+        );
+        assert!(IdleAction::from_str("restart").is_err());
+    }
+}
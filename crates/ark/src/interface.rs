@@ -15,6 +15,8 @@ use std::ffi::*;
 use std::os::raw::c_uchar;
 use std::path::PathBuf;
 use std::result::Result::Ok;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::task::Poll;
@@ -30,10 +32,13 @@ use amalthea::comm::ui_comm::UiFrontendRequest;
 use amalthea::socket::iopub::IOPubMessage;
 use amalthea::socket::iopub::Wait;
 use amalthea::socket::stdin::StdInRequest;
+use amalthea::wire::display_data::DisplayData;
 use amalthea::wire::exception::Exception;
 use amalthea::wire::execute_error::ExecuteError;
 use amalthea::wire::execute_input::ExecuteInput;
+use amalthea::wire::execute_reply::AssignmentTargets;
 use amalthea::wire::execute_reply::ExecuteReply;
+use amalthea::wire::execute_reply::ExecutionResources;
 use amalthea::wire::execute_request::ExecuteRequest;
 use amalthea::wire::execute_result::ExecuteResult;
 use amalthea::wire::input_reply::InputReply;
@@ -70,6 +75,7 @@ use harp::r_symbol;
 use harp::routines::r_register_routines;
 use harp::session::r_traceback;
 use harp::utils::r_is_data_frame;
+use harp::utils::r_is_null;
 use harp::utils::r_typeof;
 use harp::R_MAIN_THREAD_ID;
 use libr::R_BaseNamespace;
@@ -87,12 +93,19 @@ use stdext::result::ResultOrLog;
 use stdext::*;
 use uuid::Uuid;
 
+use crate::ansi::strip_ansi;
+use crate::ansi::AnsiHtmlBuffer;
+use crate::ansi::AnsiMode;
+use crate::ansi::AnsiOutput;
+use crate::assignment_targets::assignment_targets;
 use crate::dap::dap::DapBackendEvent;
 use crate::dap::dap_r_main::RMainDap;
 use crate::dap::Dap;
 use crate::errors;
 use crate::help::message::HelpEvent;
 use crate::help::r_help::RHelp;
+use crate::idle;
+use crate::idle::IdleAction;
 use crate::lsp::events::EVENTS;
 use crate::lsp::main_loop::Event;
 use crate::lsp::main_loop::KernelNotification;
@@ -109,15 +122,20 @@ use crate::request::debug_request_command;
 use crate::request::KernelRequest;
 use crate::request::RRequest;
 use crate::signals::initialize_signal_handlers;
+use crate::signals::interrupt_notifications;
 use crate::signals::interrupts_pending;
 use crate::signals::set_interrupts_pending;
 use crate::srcref::ns_populate_srcref;
 use crate::srcref::resource_loaded_namespaces;
 use crate::startup;
+use crate::startup::StartupHang;
 use crate::strings::lines;
 use crate::sys::console::console_to_utf8;
 use crate::ui::UiCommMessage;
 use crate::ui::UiCommSender;
+use crate::variables::methods::populate_methods_from_loaded_namespaces;
+use crate::variables::methods::populate_variable_methods_table;
+use crate::variables::methods::unregister_package_methods;
 
 static RE_DEBUG_PROMPT: Lazy<Regex> = Lazy::new(|| Regex::new(r"Browse\[\d+\]").unwrap());
 
@@ -156,14 +174,37 @@ static mut R_MAIN: Option<RMain> = None;
 /// Banner output accumulated during startup
 static mut R_BANNER: String = String::new();
 
+/// Set by the Control thread once a shutdown has been requested. If R is busy
+/// evaluating a request at that point, the computation is interrupted from the
+/// next polled event so that R gets back to `read_console()`, where the
+/// shutdown request is waiting.
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+pub fn request_shutdown() {
+    SHUTDOWN_REQUESTED.store(true, Ordering::Relaxed);
+}
+
 pub struct RMain {
-    kernel_init_tx: Bus<KernelInfo>,
+    /// Sends the kernel info to the startup watcher once R reaches its first
+    /// prompt. Kept alive for the lifetime of the session.
+    kernel_info_tx: Sender<KernelInfo>,
+
+    /// Kernel info computed at the end of initialization, pending until the
+    /// first prompt
+    kernel_info: Option<KernelInfo>,
 
     kernel_request_rx: Receiver<KernelRequest>,
 
     /// Whether we are running in Console, Notebook, or Background mode.
     pub session_mode: SessionMode,
 
+    /// How ANSI escape sequences of stdout and stderr are forwarded
+    ansi_output: AnsiOutput,
+
+    /// Styled output of stdout and stderr in HTML mode, not yet sent
+    stdout_html: AnsiHtmlBuffer,
+    stderr_html: AnsiHtmlBuffer,
+
     /// Channel used to send along messages relayed on the open comms.
     comm_manager_tx: Sender<CommManagerEvent>,
 
@@ -198,10 +239,42 @@ pub struct RMain {
     tasks_idle_rx: Receiver<RTask>,
     pending_futures: HashMap<Uuid, (BoxFuture<'static, ()>, RTaskStartInfo)>,
 
+    /// When we last handled an execute request or an input reply. Used to take
+    /// the `ark.idle_action` once the session has been idle for
+    /// `ark.idle_timeout` seconds. Background tasks, e.g. from the LSP or the
+    /// Variables pane polling, don't count as activity.
+    last_activity: std::time::Instant,
+
+    /// Whether the idle action was taken since the last activity, so that
+    /// it is taken once per idle period
+    idle_action_taken: bool,
+
+    /// Set when user code called `quit()` under the `"shutdown"` quit policy.
+    /// The active request is completed and R then exits at the next prompt.
+    quit_requested: bool,
+
+    /// Notified when the frontend requests an interrupt, so that we wake up
+    /// while waiting for input in `read_console()`
+    interrupt_rx: Receiver<()>,
+
     /// Channel to communicate requests and events to the frontend
     /// by forwarding them through the UI comm. Optional, and really Positron specific.
     ui_comm_tx: Option<UiCommSender>,
 
+    /// Events produced during startup, before the UI comm is connected, e.g.
+    /// the outcome of attaching the startup packages. Sent once the UI comm
+    /// channel is established.
+    pending_ui_events: Vec<UiFrontendEvent>,
+
+    /// Messages of `packageStartupMessage()` emitted by the current input,
+    /// e.g. attach banners. They are sent together once the input has been
+    /// evaluated, tagged so frontends can display them more quietly.
+    startup_messages: String,
+
+    /// The package and the code of the console input to evaluate in a
+    /// package context, retrieved by `.ps.eval_in_package()`
+    package_input: Option<(String, String)>,
+
     /// Represents whether an error occurred during R code execution.
     pub error_occurred: bool,
     pub error_message: String, // `evalue` in the Jupyter protocol
@@ -217,6 +290,10 @@ pub struct RMain {
 
     dap: RMainDap,
 
+    /// Environment of the frame we are paused in at a `browser()` prompt.
+    /// `None` when at top level. Used to scope console completions.
+    debug_env: Option<RObject>,
+
     pub positron_ns: Option<RObject>,
 
     pending_lines: Vec<String>,
@@ -229,8 +306,88 @@ struct ActiveReadConsoleRequest {
     request: ExecuteRequest,
     originator: Originator,
     reply_tx: Sender<amalthea::Result<ExecuteReply>>,
+    usage: ResourceUsage,
+}
+
+/// Tracks the resources used by the active request, reported in its
+/// `execute_reply`
+struct ResourceUsage {
+    started: std::time::Instant,
+
+    /// Total time spent waiting for the user to answer input requests
+    input_wait: Duration,
+
+    /// Set while an input request is pending
+    input_requested_at: Option<std::time::Instant>,
+
+    /// Memory used by R when the request started, in bytes. Only measured
+    /// when the `ark.execution.track_allocations` option is set since it
+    /// requires running the garbage collector.
+    memory_before: Option<f64>,
 }
 
+impl ResourceUsage {
+    fn start() -> Self {
+        let memory_before = Self::track_allocations().then(r_memory_used).flatten();
+
+        Self {
+            started: std::time::Instant::now(),
+            input_wait: Duration::ZERO,
+            input_requested_at: None,
+            memory_before,
+        }
+    }
+
+    fn start_input_wait(&mut self) {
+        self.input_requested_at = Some(std::time::Instant::now());
+    }
+
+    fn end_input_wait(&mut self) {
+        if let Some(requested_at) = self.input_requested_at.take() {
+            self.input_wait += requested_at.elapsed();
+        }
+    }
+
+    fn finish(mut self) -> ExecutionResources {
+        self.end_input_wait();
+        let elapsed = self.started.elapsed().saturating_sub(self.input_wait);
+
+        let allocation_delta_bytes = self
+            .memory_before
+            .and_then(|before| r_memory_used().map(|after| after - before));
+
+        ExecutionResources {
+            elapsed_ms: elapsed.as_millis() as u64,
+            allocation_delta_bytes,
+        }
+    }
+
+    fn track_allocations() -> bool {
+        harp::get_option("ark.execution.track_allocations")
+            .try_into()
+            .unwrap_or(false)
+    }
+}
+
+/// The memory used by R in bytes, as reported by `gc()`
+fn r_memory_used() -> Option<f64> {
+    let used = RFunction::from(".ps.memoryUsed")
+        .call()
+        .and_then(f64::try_from);
+
+    match used {
+        Ok(used) => Some(used),
+        Err(err) => {
+            log::error!("Can't measure memory used by R: {err:?}");
+            None
+        },
+    }
+}
+
+/// Broadcast to the Shell and LSP threads once R has reached its first
+/// prompt, or once the startup timeout has elapsed
+pub type KernelInit = Result<KernelInfo, StartupHang>;
+
 /// Represents kernel metadata (available after the kernel has fully started)
 #[derive(Debug, Clone)]
 pub struct KernelInfo {
@@ -269,6 +426,10 @@ pub struct PromptInfo {
     /// Whether this is a prompt from a fresh REPL iteration (browser or
     /// top level) or a prompt from some user code, e.g. via `readline()`
     input_request: bool,
+
+    /// The choices of a `menu()` prompt, forwarded to the frontend along
+    /// with the input request. `None` for other prompts.
+    menu_choices: Option<Vec<String>>,
 }
 
 pub enum ConsoleInput {
@@ -295,10 +456,11 @@ impl RMain {
         stdin_request_tx: Sender<StdInRequest>,
         stdin_reply_rx: Receiver<amalthea::Result<InputReply>>,
         iopub_tx: Sender<IOPubMessage>,
-        kernel_init_tx: Bus<KernelInfo>,
+        kernel_init_tx: Bus<KernelInit>,
         kernel_request_rx: Receiver<KernelRequest>,
         dap: Arc<Mutex<Dap>>,
         session_mode: SessionMode,
+        ansi_output: AnsiOutput,
     ) {
         // Set the main thread ID.
         // Must happen before doing anything that checks `RMain::on_main_thread()`,
@@ -314,6 +476,14 @@ impl RMain {
         let (tasks_interrupt_tx, tasks_interrupt_rx) = unbounded::<RTask>();
         let (tasks_idle_tx, tasks_idle_rx) = unbounded::<RTask>();
 
+        // Start watching for a hung startup before R is set up, so that a
+        // hang anywhere up to the first prompt is reported to the launcher
+        let (kernel_info_tx, kernel_info_rx) = bounded::<KernelInfo>(1);
+        let startup_timeout = startup::startup_timeout();
+        spawn!("ark-startup", move || {
+            startup::watch_startup(kernel_init_tx, kernel_info_rx, startup_timeout)
+        });
+
         unsafe {
             R_MAIN = Some(RMain::new(
                 tasks_interrupt_rx,
@@ -323,10 +493,11 @@ impl RMain {
                 stdin_request_tx,
                 stdin_reply_rx,
                 iopub_tx,
-                kernel_init_tx,
+                kernel_info_tx,
                 kernel_request_rx,
                 dap,
                 session_mode,
+                ansi_output,
             ));
         };
         let r_main = unsafe { R_MAIN.as_mut().unwrap() };
@@ -421,12 +592,18 @@ impl RMain {
                 }
             }
 
+            // Same for the ark methods defined in loaded namespaces
+            if let Err(err) = populate_methods_from_loaded_namespaces() {
+                log::error!("Can't populate ark methods for loaded packages: {err:?}");
+            }
+
             // Set up the global error handler (after support function initialization)
             errors::initialize();
 
             // Now that R has started (emitting any startup messages), and now that we have set
-            // up all hooks and handlers, officially finish the R initialization process to
-            // unblock the kernel-info request and also allow the LSP to start.
+            // up all hooks and handlers, officially finish the R initialization process. The
+            // kernel-info request is unblocked and the LSP started once we reach the first
+            // prompt, after the R profiles have run.
             log::info!(
                 "R has started and ark handlers have been registered, completing initialization."
             );
@@ -442,6 +619,14 @@ impl RMain {
             startup::source_user_r_profile();
         }
 
+        // Attach the packages configured with the `ark.startup_packages`
+        // option, e.g. from the R profiles
+        startup::attach_startup_packages();
+
+        // Evaluate setup chunks registered with `.ps.setup.register()`, e.g.
+        // from the R profiles, in dependency order
+        startup::run_setup_chunks();
+
         // Start the REPL. Does not return!
         crate::sys::interface::run_r();
     }
@@ -467,8 +652,10 @@ impl RMain {
             continuation_prompt: Some(continuation_prompt),
         };
 
-        log::info!("Sending kernel info: {version}");
-        self.kernel_init_tx.broadcast(kernel_info);
+        // The kernel info is sent once we reach the first prompt, so that
+        // a startup hang while running R profiles can still be reported
+        log::info!("Kernel info ready: {version}");
+        self.kernel_info = Some(kernel_info);
 
         // Thread-safe initialisation flag for R
         R_INIT.set(()).expect("`R_INIT` can only be set once");
@@ -482,10 +669,11 @@ impl RMain {
         stdin_request_tx: Sender<StdInRequest>,
         stdin_reply_rx: Receiver<amalthea::Result<InputReply>>,
         iopub_tx: Sender<IOPubMessage>,
-        kernel_init_tx: Bus<KernelInfo>,
+        kernel_info_tx: Sender<KernelInfo>,
         kernel_request_rx: Receiver<KernelRequest>,
         dap: Arc<Mutex<Dap>>,
         session_mode: SessionMode,
+        ansi_output: AnsiOutput,
     ) -> Self {
         Self {
             r_request_rx,
@@ -493,12 +681,16 @@ impl RMain {
             stdin_request_tx,
             stdin_reply_rx,
             iopub_tx,
-            kernel_init_tx,
+            kernel_info_tx,
+            kernel_info: None,
             kernel_request_rx,
             active_request: None,
             execution_count: 0,
             autoprint_output: String::new(),
             ui_comm_tx: None,
+            pending_ui_events: Vec::new(),
+            startup_messages: String::new(),
+            package_input: None,
             error_occurred: false,
             error_message: String::new(),
             error_traceback: Vec::new(),
@@ -506,10 +698,18 @@ impl RMain {
             help_port: None,
             lsp_events_tx: None,
             dap: RMainDap::new(dap),
+            debug_env: None,
             tasks_interrupt_rx,
             tasks_idle_rx,
             pending_futures: HashMap::new(),
+            last_activity: std::time::Instant::now(),
+            idle_action_taken: false,
+            quit_requested: false,
+            interrupt_rx: interrupt_notifications(),
             session_mode,
+            ansi_output,
+            stdout_html: AnsiHtmlBuffer::default(),
+            stderr_html: AnsiHtmlBuffer::default(),
             positron_ns: None,
             pending_lines: Vec::new(),
         }
@@ -517,8 +717,8 @@ impl RMain {
 
     /// Wait for complete R initialization
     ///
-    /// Wait for R being ready to evaluate R code. Resolves as the same time as
-    /// the `Bus<KernelInfo>` init channel does.
+    /// Wait for R being ready to evaluate R code. Resolves before the
+    /// `Bus<KernelInit>` init channel does, which waits for the first prompt.
     ///
     /// Thread-safe.
     pub fn wait_initialized() {
@@ -617,8 +817,20 @@ impl RMain {
             }
         }
 
+        // In a package context, the code is evaluated by an R helper in a child
+        // of the package namespace. The helper retrieves the code as a string
+        // rather than having it spliced into the source of the call, which
+        // would require escaping it.
+        let code = match &req.package_context {
+            Some(package) => {
+                self.package_input = Some((package.clone(), req.code.clone()));
+                String::from(".ps.eval_in_package()")
+            },
+            None => req.code.clone(),
+        };
+
         // Return the code to the R console to be evaluated and the corresponding exec count
-        (ConsoleInput::Input(req.code.clone()), self.execution_count)
+        (ConsoleInput::Input(code), self.execution_count)
     }
 
     /// Invoked by R to read console input from the user.
@@ -641,6 +853,22 @@ impl RMain {
         let info = Self::prompt_info(prompt);
         log::trace!("R prompt: {}", info.input_prompt);
 
+        // All output of the previous input has been written
+        self.flush_ansi_html(Stream::Stdout);
+        self.flush_ansi_html(Stream::Stderr);
+        self.flush_startup_messages();
+
+        // We've reached the first top-level prompt, startup is complete. This
+        // unblocks the kernel-info request and allows the LSP to start.
+        if !info.browser && !info.incomplete && !info.input_request {
+            if let Some(kernel_info) = self.kernel_info.take() {
+                log::info!("Sending kernel info: {}", kernel_info.version);
+                if let Err(err) = self.kernel_info_tx.send(kernel_info) {
+                    log::error!("Can't send kernel info: {err:?}");
+                }
+            }
+        }
+
         // Upon entering read-console, finalize any debug call text that we were capturing.
         // At this point, the user can either advance the debugger, causing us to capture
         // a new expression, or execute arbitrary code, where we will reuse a finalized
@@ -687,15 +915,36 @@ impl RMain {
             return console_result;
         };
 
-        // In the future we'll also send browser information, see
-        // https://github.com/posit-dev/positron/issues/3001. Currently this is
-        // a push model where we send the console inputs at each round. In the
-        // future, a pull model would be better, this way the LSP can manage a
-        // cache of inputs and we don't need to retraverse the environments as
-        // often. We'd still push a `DidChangeConsoleInputs` notification from
-        // here, but only containing high-level information such as `search()`
-        // contents and `ls(rho)`.
-        if !info.browser && !info.incomplete && !info.input_request {
+        // The request that called `quit()` has been replied to, exit now
+        if self.quit_requested && !info.incomplete && !info.input_request {
+            log::info!("Shutting down R after a call to `quit()`");
+            return ConsoleResult::Disconnected;
+        }
+
+        // Keep track of the frame we're paused in so that console inputs and
+        // completions are scoped to its bindings rather than to the global
+        // environment, see https://github.com/posit-dev/positron/issues/3001.
+        if !info.incomplete && !info.input_request {
+            self.debug_env = if info.browser {
+                match Self::browser_env() {
+                    Ok(env) => Some(env),
+                    Err(err) => {
+                        log::error!("ReadConsole: Can't get browser environment: {err:?}");
+                        None
+                    },
+                }
+            } else {
+                None
+            };
+        }
+
+        // Currently this is a push model where we send the console inputs at
+        // each round. In the future, a pull model would be better, this way the
+        // LSP can manage a cache of inputs and we don't need to retraverse the
+        // environments as often. We'd still push a `DidChangeConsoleInputs`
+        // notification from here, but only containing high-level information
+        // such as `search()` contents and `ls(rho)`.
+        if !info.incomplete && !info.input_request {
             self.refresh_lsp();
         }
 
@@ -763,9 +1012,24 @@ impl RMain {
 
                 // We've got a reply for readline
                 recv(self.stdin_reply_rx) -> reply => {
-                    return self.handle_input_reply(reply.unwrap(), buf, buflen);
+                    let Ok(reply) = reply else {
+                        // The StdIn channel is disconnected. This is an EOF,
+                        // not an interrupt.
+                        return ConsoleResult::Disconnected;
+                    };
+                    if let Some(req) = &mut self.active_request {
+                        req.usage.end_input_wait();
+                    }
+                    self.reset_idle_timer();
+                    return self.handle_input_reply(reply, buf, buflen);
                 }
 
+                // An interrupt was requested. Start the next loop tick right
+                // away so that, if we're waiting on user input, the check at
+                // the top of the loop unwinds the `readline()` call instead
+                // of waiting until input arrives.
+                recv(self.interrupt_rx) -> _ => {}
+
                 // We've got a kernel request
                 recv(self.kernel_request_rx) -> req => {
                     self.handle_kernel_request(req.unwrap(), &info);
@@ -787,11 +1051,66 @@ impl RMain {
                 // available data?
                 default(Duration::from_millis(200)) => {
                     unsafe { Self::process_events() };
+
+                    if !info.browser && !info.input_request {
+                        if let Some(result) = self.check_idle_timeout() {
+                            return result;
+                        }
+                    }
                 }
             }
         }
     }
 
+    fn reset_idle_timer(&mut self) {
+        self.last_activity = std::time::Instant::now();
+        self.idle_action_taken = false;
+    }
+
+    /// Takes the configured idle action once the session has been idle for
+    /// longer than the idle timeout. Background tasks that are still in
+    /// progress keep the session busy. Returns a console result when the
+    /// action is to shut down.
+    fn check_idle_timeout(&mut self) -> Option<ConsoleResult> {
+        if self.idle_action_taken || !self.pending_futures.is_empty() {
+            return None;
+        }
+
+        let (timeout, action) = idle::idle_config()?;
+
+        let idle = self.last_activity.elapsed();
+        if idle < timeout {
+            return None;
+        }
+        self.idle_action_taken = true;
+
+        log::info!(
+            "Session has been idle for {} seconds, taking idle action: {action:?}",
+            idle.as_secs()
+        );
+
+        match action {
+            IdleAction::Gc => {
+                if let Err(err) = RFunction::new("base", "gc").call() {
+                    log::error!("Can't run idle garbage collection: {err:?}");
+                }
+                None
+            },
+            IdleAction::Warn => {
+                let message = IOPubMessage::Stream(StreamOutput {
+                    name: Stream::Stderr,
+                    text: format!(
+                        "The R session has been idle for {} seconds.\n",
+                        idle.as_secs()
+                    ),
+                });
+                self.iopub_tx.send(message).unwrap();
+                None
+            },
+            IdleAction::Shutdown => Some(ConsoleResult::Disconnected),
+        }
+    }
+
     // We prefer to panic if there is an error while trying to determine the
     // prompt type because any confusion here is prone to put the frontend in a
     // bad state (e.g. causing freezes)
@@ -814,14 +1133,18 @@ impl RMain {
         // this means some user code is requesting input, e.g. via `readline()`
         let user_request = !browser && n_frame > 0;
 
-        // The request is incomplete if we see the continue prompt, except if
-        // we're in a user request, e.g. `readline("+ ")`. To guard against
-        // this, we check that we are at top-level (call stack is empty or we
-        // have a debug prompt).
+        // `menu()` asks for the number of one of its choices. Unlike the
+        // prompt type, failing to find the choices is harmless: the frontend
+        // then shows a regular text prompt.
+        let menu_choices = if user_request {
+            Self::menu_choices()
+        } else {
+            None
+        };
+
         let continuation_prompt: String = harp::get_option("continue").try_into().unwrap();
-        let matches_continuation = prompt == continuation_prompt;
         let top_level = n_frame == 0 || browser;
-        let incomplete = matches_continuation && top_level;
+        let incomplete = Self::is_incomplete_prompt(&prompt, &continuation_prompt, top_level);
 
         return PromptInfo {
             input_prompt: prompt,
@@ -829,9 +1152,39 @@ impl RMain {
             browser,
             incomplete,
             input_request: user_request,
+            menu_choices,
         };
     }
 
+    /// The choices of the `menu()` call waiting for a selection, if any
+    fn menu_choices() -> Option<Vec<String>> {
+        let choices = RFunction::from(".ps.menuChoices")
+            .call()
+            .and_then(r_null_or_try_into);
+
+        match choices {
+            Ok(choices) => choices,
+            Err(err) => {
+                log::error!("Can't look up the choices of `menu()`: {err:?}");
+                None
+            },
+        }
+    }
+
+    /// Whether R is waiting for the rest of an incomplete expression
+    ///
+    /// The request is incomplete if we see the continue prompt, except if
+    /// we're in a user request, e.g. `readline("+ ")`. To guard against this,
+    /// `top_level` must be set when the call stack is empty or we have a
+    /// debug prompt.
+    ///
+    /// Incomplete inputs are normally rejected before being sent to R (see
+    /// `check_console_input()`), so this mostly guards against R being left
+    /// at a continuation prompt where it would consume the next request.
+    fn is_incomplete_prompt(prompt: &str, continuation_prompt: &str, top_level: bool) -> bool {
+        top_level && prompt == continuation_prompt
+    }
+
     /// Returns:
     /// - `None` if we should fall through to the event loop to wait for more user input
     /// - `Some(ConsoleResult)` if we should immediately exit `read_console()`
@@ -859,11 +1212,18 @@ impl RMain {
         // put us into an `input_request` state. We must respond to that request before processing
         // the rest of the pending lines.
         if info.input_request {
-            if let Some(req) = &self.active_request {
+            if let Some(req) = &mut self.active_request {
                 // Send request to frontend. We'll wait for an `input_reply`
                 // from the frontend in the event loop in `read_console()`.
-                // The active request remains active.
-                self.request_input(req.originator.clone(), info.input_prompt.to_string());
+                // The active request remains active. Time spent waiting on
+                // the user doesn't count towards the request's resources.
+                req.usage.start_input_wait();
+                let originator = req.originator.clone();
+                self.request_input(
+                    originator,
+                    info.input_prompt.to_string(),
+                    info.menu_choices.clone(),
+                );
                 return None;
             } else {
                 // Invalid input request, propagate error to R
@@ -878,6 +1238,24 @@ impl RMain {
             unreachable!("Incomplete input in `ReadConsole` handler");
         }
 
+        // `quit()` unwound the evaluation to shut down, drop the rest of the
+        // request so that it is completed right away
+        if self.quit_requested && !info.incomplete {
+            self.pending_lines.clear();
+        }
+
+        // Plots drawn by the expression that just completed are emitted before
+        // the next one is evaluated, so that they are in order with its output
+        if !info.incomplete && !self.pending_lines.is_empty() {
+            unsafe {
+                graphics_device::on_did_execute_expression(
+                    self.comm_manager_tx.clone(),
+                    self.iopub_tx.clone(),
+                    self.is_dynamic_plots(),
+                )
+            };
+        }
+
         // Next check if we have any pending lines. If we do, we are in the middle of
         // evaluating a multi line selection, so immediately write the next line into R's buffer.
         // The active request remains active.
@@ -908,7 +1286,7 @@ impl RMain {
                 graphics_device::on_did_execute_request(
                     self.comm_manager_tx.clone(),
                     self.iopub_tx.clone(),
-                    self.is_ui_comm_connected() && self.session_mode == SessionMode::Console,
+                    self.is_dynamic_plots(),
                 )
             };
 
@@ -934,6 +1312,8 @@ impl RMain {
 
         let input = match req {
             RRequest::ExecuteCode(exec_req, originator, reply_tx) => {
+                self.reset_idle_timer();
+
                 // Extract input from request
                 let (input, exec_count) = { self.init_execute_request(&exec_req) };
 
@@ -943,12 +1323,19 @@ impl RMain {
                     request: exec_req,
                     originator,
                     reply_tx,
+                    usage: ResourceUsage::start(),
                 });
 
                 input
             },
 
-            RRequest::Shutdown(_) => ConsoleInput::EOF,
+            // Exit in both cases. On restart, the frontend starts a fresh
+            // process once this one is gone. R runs the `.Last()` hooks and
+            // finalizers registered with `onexit = TRUE` on the way out.
+            RRequest::Shutdown(restart) => {
+                log::info!("Shutting down R (restart: {restart})");
+                ConsoleInput::EOF
+            },
 
             RRequest::DebugCommand(cmd) => {
                 // Just ignore command in case we left the debugging state already
@@ -989,7 +1376,7 @@ impl RMain {
 
                 // Store input in R's buffer and return sentinel indicating some
                 // new input is ready
-                match Self::on_console_input(buf, buflen, code) {
+                match self.on_pending_console_input(buf, buflen, code) {
                     Ok(()) => Some(ConsoleResult::NewInput),
                     Err(err) => Some(ConsoleResult::Error(err)),
                 }
@@ -1194,6 +1581,19 @@ impl RMain {
 
             ui_comm_tx.send_refresh(input_prompt, continuation_prompt);
         });
+
+        for event in std::mem::take(&mut self.pending_ui_events) {
+            self.with_ui_comm_tx(|ui_comm_tx| ui_comm_tx.send_event(event));
+        }
+    }
+
+    /// Send `event` to the frontend, or hold on to it until the UI comm is
+    /// connected
+    pub(crate) fn send_or_defer_ui_event(&mut self, event: UiFrontendEvent) {
+        match self.get_ui_comm_tx() {
+            Some(ui_comm_tx) => ui_comm_tx.send_event(event),
+            None => self.pending_ui_events.push(event),
+        }
     }
 
     pub fn get_ui_comm_tx(&self) -> Option<&UiCommSender> {
@@ -1236,6 +1636,12 @@ impl RMain {
         self.get_ui_comm_tx().is_some()
     }
 
+    /// Whether plots are shown in Positron's Plots pane, as opposed to being
+    /// emitted as `display_data` on IOPub
+    pub(crate) fn is_dynamic_plots(&self) -> bool {
+        self.is_ui_comm_connected() && self.session_mode == SessionMode::Console
+    }
+
     fn handle_pending_line(&mut self, buf: *mut c_uchar, buflen: c_int) -> Option<ConsoleResult> {
         if self.error_occurred {
             // If an error has occurred, we've already sent a complete expression that resulted in
@@ -1250,7 +1656,7 @@ impl RMain {
             return None;
         };
 
-        match Self::on_console_input(buf, buflen, input) {
+        match self.on_pending_console_input(buf, buflen, input) {
             Ok(()) => Some(ConsoleResult::NewInput),
             Err(err) => Some(ConsoleResult::Error(err)),
         }
@@ -1308,12 +1714,64 @@ impl RMain {
     /// In the case of receiving too much input within a SINGLE line, we
     /// propagate up an informative `amalthea::Error::InvalidConsoleInput`
     /// error, which is turned into an R error and thrown in a POD context.
-    /// This is a fairly pathological case that we never expect to occur.
+    /// Lines of execute requests are split with `on_pending_console_input()`
+    /// instead, so this only happens with replies to `readline()` or `menu()`
+    /// which R reads in one go.
     fn on_console_input(
         buf: *mut c_uchar,
         buflen: c_int,
         mut input: String,
     ) -> amalthea::Result<()> {
+        if input.len() > Self::console_buffer_capacity(buflen) {
+            log::error!("Console input too large for buffer, throwing R error.");
+            return Err(Self::buffer_overflow_error());
+        }
+
+        // Push `\n`
+        input.push('\n');
+
+        Self::copy_console_input(buf, input);
+        Ok(())
+    }
+
+    /// Copy a line of an execute request into R's internal input buffer,
+    /// splitting it across `read_console()` calls when it is too long
+    ///
+    /// The head of the line is written without a trailing newline and the
+    /// tail is pushed back onto the pending lines. R's parser considers the
+    /// unterminated head incomplete and calls `read_console()` back with a
+    /// continuation prompt, so the expression is only evaluated, and the
+    /// request only completed, once the whole line has been passed on.
+    fn on_pending_console_input(
+        &mut self,
+        buf: *mut c_uchar,
+        buflen: c_int,
+        mut input: String,
+    ) -> amalthea::Result<()> {
+        let capacity = Self::console_buffer_capacity(buflen);
+        if input.len() <= capacity {
+            return Self::on_console_input(buf, buflen, input);
+        }
+
+        // Don't split a UTF-8 sequence
+        let mut split = capacity;
+        while !input.is_char_boundary(split) {
+            split -= 1;
+        }
+        if split == 0 {
+            return Err(Self::buffer_overflow_error());
+        }
+
+        let tail = input.split_off(split);
+        self.pending_lines.push(tail);
+
+        Self::copy_console_input(buf, input);
+        Ok(())
+    }
+
+    /// The number of bytes of input that fit in R's buffer, leaving room for
+    /// the final `\n` and `\0` terminator
+    fn console_buffer_capacity(buflen: c_int) -> usize {
         let buflen = buflen as usize;
 
         if buflen < 2 {
@@ -1321,28 +1779,19 @@ impl RMain {
             panic!("Console input `buflen` must be >=2.");
         }
 
-        // Leave room for final `\n` and `\0` terminator
-        let buflen = buflen - 2;
-
-        if input.len() > buflen {
-            log::error!("Console input too large for buffer, throwing R error.");
-            return Err(Self::buffer_overflow_error());
-        }
-
-        // Push `\n`
-        input.push('\n');
+        buflen - 2
+    }
 
+    fn copy_console_input(buf: *mut c_uchar, input: String) {
         // Push `\0` (automatically, as it converts to a C string)
         let input = CString::new(input).unwrap();
 
         unsafe {
             libc::strcpy(buf as *mut c_char, input.as_ptr());
         }
-
-        Ok(())
     }
 
-    // Hitting this means a SINGLE line from the user was longer than the buffer size (>4000 characters)
+    // Hitting this means a SINGLE line of a `readline()` reply was longer than the buffer size (>4000 characters)
     fn buffer_overflow_error() -> amalthea::Error {
         Error::InvalidConsoleInput(String::from(
             "Can't pass console input on to R, a single line exceeds R's internal console buffer size."
@@ -1354,6 +1803,11 @@ impl RMain {
     fn reply_execute_request(&mut self, req: ActiveReadConsoleRequest, prompt_info: &PromptInfo) {
         let prompt = &prompt_info.input_prompt;
 
+        // We're back at a prompt so the output of the request is complete.
+        // Flush any trailing partial line that IOPub is holding back waiting
+        // for a newline.
+        self.iopub_tx.send(IOPubMessage::Flush).unwrap();
+
         let (reply, result) = if prompt_info.incomplete {
             log::trace!("Got prompt {} signaling incomplete request", prompt);
             (new_incomplete_reply(&req.request, req.exec_count), None)
@@ -1363,9 +1817,15 @@ impl RMain {
             log::trace!("Got R prompt '{}', completing execution", prompt);
 
             self.make_execute_reply_error(req.exec_count)
-                .unwrap_or_else(|| self.make_execute_reply(req.exec_count))
+                .unwrap_or_else(|| self.make_execute_reply(req.exec_count, &req.request))
         };
 
+        let resources = req.usage.finish();
+        let reply = reply.map(|reply| ExecuteReply {
+            resources: Some(resources),
+            ..reply
+        });
+
         if let Some(result) = result {
             self.iopub_tx.send(result).unwrap();
         }
@@ -1440,7 +1900,14 @@ impl RMain {
     fn make_execute_reply(
         &mut self,
         exec_count: u32,
+        request: &ExecuteRequest,
     ) -> (amalthea::Result<ExecuteReply>, Option<IOPubMessage>) {
+        // Only compute the representations the frontend can render
+        let supports = |mimetype: &str| match &request.supported_mimetypes {
+            Some(mimetypes) => mimetypes.iter().any(|x| x == mimetype),
+            None => true,
+        };
+
         // TODO: Implement rich printing of certain outputs.
         // Will we need something similar to the RStudio model,
         // where we implement custom print() methods? Or can
@@ -1462,21 +1929,32 @@ impl RMain {
             data.insert("text/plain".to_string(), json!(autoprint));
         }
 
+        let value = unsafe { Rf_findVarInFrame(R_GlobalEnv, r_symbol!(".Last.value")) };
+
         // Include HTML representation of data.frame
-        unsafe {
-            let value = Rf_findVarInFrame(R_GlobalEnv, r_symbol!(".Last.value"));
-            if r_is_data_frame(value) {
-                match to_html(value) {
-                    Ok(html) => data.insert("text/html".to_string(), json!(html)),
-                    Err(err) => {
-                        log::error!("{:?}", err);
-                        None
-                    },
-                };
+        if supports("text/html") && r_is_data_frame(value) {
+            match to_html(value) {
+                Ok(html) => data.insert("text/html".to_string(), json!(html)),
+                Err(err) => {
+                    log::error!("{:?}", err);
+                    None
+                },
+            };
+        } else if autoprint.len() != 0 && (supports("text/html") || supports("text/markdown")) {
+            // Otherwise bridge to the rich output of `knitr::knit_print()`
+            // methods of auto-printed objects
+            match knit_print_output(value) {
+                Ok(Some((mimetype, content))) if supports(&mimetype) => {
+                    data.insert(mimetype, json!(content));
+                },
+                Ok(_) => {},
+                Err(err) => log::error!("Can't get `knit_print()` output: {err:?}"),
             }
         }
 
-        let reply = new_execute_reply(exec_count);
+        // Let the frontend know which variables to refresh
+        let targets = assignment_targets(&request.code);
+        let reply = new_execute_reply(exec_count, targets);
 
         let result = (data.len() > 0).then(|| {
             IOPubMessage::ExecuteResult(ExecuteResult {
@@ -1509,8 +1987,9 @@ impl RMain {
     }
 
     /// Request input from frontend in case code like `readline()` is
-    /// waiting for input
-    fn request_input(&self, originator: Originator, prompt: String) {
+    /// waiting for input. The prompt is forwarded as is. `choices` are the
+    /// options of a `menu()` prompt.
+    fn request_input(&self, originator: Originator, prompt: String, choices: Option<Vec<String>>) {
         // TODO: We really should not have to wait on IOPub to be cleared, but
         // if an IOPub `'stream'` message arrives on the frontend while an input
         // request is being handled, it currently breaks the Console. We should
@@ -1533,6 +2012,7 @@ impl RMain {
                 request: InputRequest {
                     prompt,
                     password: false,
+                    choices,
                 },
             })),
             Err(err) => panic!("Could not send input request: {}", err)
@@ -1540,6 +2020,12 @@ impl RMain {
     }
 
     /// Invoked by R to write output to the console.
+    /// Active sinks don't need any handling here. R writes sunk output to
+    /// the sink connection and only calls us for output that isn't sunk,
+    /// including for `sink(split = TRUE)`. The same goes for messages sunk
+    /// with `sink(type = "message")`, which R writes to the sink connection
+    /// instead of stderr. Removing the sink with `sink()` routes output back
+    /// to us and so to IOPub.
     fn write_console(buf: *const c_char, _buflen: i32, otype: i32) {
         let content = match console_to_utf8(buf) {
             Ok(content) => content,
@@ -1558,6 +2044,10 @@ impl RMain {
         // match based fallback
         r_main.dap.handle_stdout(&content);
 
+        // R writes `message()`, warnings, and errors with an `otype` of 1.
+        // IOPub coalesces consecutive writes on the same stream and flushes
+        // the active stream before switching to the other one, so that
+        // stdout and stderr output keep their relative order.
         let stream = if otype == 0 {
             Stream::Stdout
         } else {
@@ -1572,7 +2062,21 @@ impl RMain {
             }
         }
 
-        if stream == Stream::Stdout && is_auto_printing() {
+        // Autoprinted output ends up in the `execute_result`, which carries
+        // its own HTML representation of data frames, so it is only stripped
+        let auto_printing = stream == Stream::Stdout && is_auto_printing();
+
+        let ansi_mode = match stream {
+            Stream::Stdout => r_main.ansi_output.stdout,
+            Stream::Stderr => r_main.ansi_output.stderr,
+        };
+        let content = match ansi_mode {
+            AnsiMode::Passthrough => content,
+            AnsiMode::Html if !auto_printing => content,
+            AnsiMode::Strip | AnsiMode::Html => strip_ansi(&content),
+        };
+
+        if auto_printing {
             // If we are at top-level, we're handling visible output auto-printed by
             // the R REPL. We accumulate this output (it typically comes in multiple
             // parts) so we can emit it later on as part of the execution reply
@@ -1614,6 +2118,24 @@ impl RMain {
             // IOPub.
         }
 
+        // Output of the other stream comes after the styled output buffered
+        // so far
+        let other = match stream {
+            Stream::Stdout => Stream::Stderr,
+            Stream::Stderr => Stream::Stdout,
+        };
+        r_main.flush_ansi_html(other);
+
+        // Styled output can't be streamed as HTML, it is buffered and sent as
+        // a display once flushed
+        if ansi_mode == AnsiMode::Html {
+            let buffer = r_main.ansi_html_buffer(stream);
+            if !buffer.is_plain(&content) {
+                buffer.push(&content);
+                return;
+            }
+        }
+
         // Stream output via the IOPub channel.
         let message = IOPubMessage::Stream(StreamOutput {
             name: stream,
@@ -1622,6 +2144,60 @@ impl RMain {
         r_main.iopub_tx.send(message).unwrap();
     }
 
+    fn ansi_html_buffer(&mut self, stream: Stream) -> &mut AnsiHtmlBuffer {
+        match stream {
+            Stream::Stdout => &mut self.stdout_html,
+            Stream::Stderr => &mut self.stderr_html,
+        }
+    }
+
+    /// Sends the styled output buffered for `stream` as a display. IOPub
+    /// flushes the stream before forwarding it so the output stays in order.
+    fn flush_ansi_html(&mut self, stream: Stream) {
+        let Some(output) = self.ansi_html_buffer(stream).flush() else {
+            return;
+        };
+
+        let message = IOPubMessage::DisplayData(DisplayData {
+            data: json!({
+                "text/html": output.html,
+                "text/plain": output.plain,
+            }),
+            metadata: json!({}),
+            transient: json!({}),
+        });
+        self.iopub_tx.send(message).unwrap();
+    }
+
+    pub fn record_startup_message(&mut self, message: &str) {
+        // Like other output, dropped for silent requests
+        if let Some(ref req) = self.active_request {
+            if req.request.silent {
+                return;
+            }
+        }
+        self.startup_messages.push_str(message);
+    }
+
+    /// Sends the package startup messages of the last input as a display
+    /// whose metadata marks them as such
+    fn flush_startup_messages(&mut self) {
+        if self.startup_messages.is_empty() {
+            return;
+        }
+
+        let message = IOPubMessage::DisplayData(DisplayData {
+            data: json!({
+                "text/plain": std::mem::take(&mut self.startup_messages),
+            }),
+            metadata: json!({
+                "package_startup_message": true,
+            }),
+            transient: json!({}),
+        });
+        self.iopub_tx.send(message).unwrap();
+    }
+
     /// Invoked by R to change busy state
     fn busy(&mut self, which: i32) {
         // Ensure signal handlers are initialized.
@@ -1667,6 +2243,13 @@ impl RMain {
             return;
         }
 
+        // Interrupt the computation if a shutdown was requested while busy
+        if self.active_request.is_some() && SHUTDOWN_REQUESTED.swap(false, Ordering::Relaxed) {
+            log::info!("Interrupting computation to shut down");
+            crate::signals::set_interrupts_pending(true);
+            crate::signals::notify_interrupt();
+        }
+
         // Coalesce up to three concurrent tasks in case the R event loop is
         // slowed down
         for _ in 0..3 {
@@ -1708,6 +2291,10 @@ impl RMain {
         self.help_port = Some(help_port);
     }
 
+    pub(crate) fn set_help_port(&mut self, help_port: u16) {
+        self.help_port = Some(help_port);
+    }
+
     pub(crate) fn send_help_event(&self, event: HelpEvent) -> anyhow::Result<()> {
         let Some(ref tx) = self.help_event_tx else {
             return Err(anyhow!("No help channel available to handle help event. Is the help comm open? Event {event:?}."));
@@ -1746,8 +2333,24 @@ impl RMain {
         self.refresh_lsp();
     }
 
+    /// Environment of the frame we are currently paused in, if any
+    pub fn debug_env(&self) -> Option<&RObject> {
+        self.debug_env.as_ref()
+    }
+
+    /// The innermost frame on the stack is the one being browsed
+    fn browser_env() -> anyhow::Result<RObject> {
+        let n_frame = harp::session::r_n_frame()?;
+        Ok(harp::session::r_sys_frame(n_frame)?)
+    }
+
     pub fn refresh_lsp(&self) {
-        match console_inputs() {
+        let env = match &self.debug_env {
+            Some(env) => env.sexp,
+            None => R_ENVS.global,
+        };
+
+        match console_inputs(env) {
             Ok(inputs) => {
                 self.send_lsp_notification(KernelNotification::DidChangeConsoleInputs(inputs));
             },
@@ -1826,11 +2429,16 @@ fn new_incomplete_reply(req: &ExecuteRequest, exec_count: u32) -> amalthea::Resu
 static RE_STACK_OVERFLOW: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"C stack usage [ 0-9]+ is too close to the limit\n").unwrap());
 
-fn new_execute_reply(exec_count: u32) -> amalthea::Result<ExecuteReply> {
+fn new_execute_reply(
+    exec_count: u32,
+    assignment_targets: AssignmentTargets,
+) -> amalthea::Result<ExecuteReply> {
     Ok(ExecuteReply {
         status: Status::Ok,
         execution_count: exec_count,
         user_expressions: json!({}),
+        assignment_targets: Some(assignment_targets),
+        resources: None,
     })
 }
 
@@ -1849,11 +2457,22 @@ fn to_html(frame: SEXP) -> Result<String> {
     }
 }
 
+/// The mimetype and content of the output of the `knitr::knit_print()`
+/// method of `value`, if any
+fn knit_print_output(value: SEXP) -> Result<Option<(String, String)>> {
+    let output = RFunction::from(".ps.format.knitPrint").add(value).call()?;
+    if r_is_null(output.sexp) {
+        return Ok(None);
+    }
+
+    let mimetype: String = output.vector_elt(0)?.try_into()?;
+    let content: String = output.vector_elt(1)?.try_into()?;
+    Ok(Some((mimetype, content)))
+}
+
 // Inputs generated by `ReadConsole` for the LSP
-pub(crate) fn console_inputs() -> anyhow::Result<ConsoleInputs> {
-    // TODO: Should send the debug environment if debugging:
-    // https://github.com/posit-dev/positron/issues/3001
-    let env = Environment::new(R_ENVS.global.into());
+pub(crate) fn console_inputs(env: SEXP) -> anyhow::Result<ConsoleInputs> {
+    let env = Environment::new(env.into());
     let scopes = env.ancestors().map(|e| e.names()).collect();
 
     // Get the set of installed packages
@@ -1962,6 +2581,12 @@ unsafe extern "C" fn ps_onload_hook(pkg: SEXP, _path: SEXP) -> anyhow::Result<SE
     // Need to reset parent as this might run in the context of another thread's R task
     let _span = tracing::trace_span!(parent: None, "onload_hook", pkg = pkg).entered();
 
+    // Register the ark methods of this package only, the namespaces loaded
+    // before startup have been populated once in the background
+    if let Err(err) = populate_variable_methods_table(&pkg) {
+        log::error!("Can't populate ark methods for `{pkg}`: {err:?}");
+    }
+
     // Populate fake source refs if needed
     if do_resource_namespaces() {
         r_task::spawn_idle(|| async move {
@@ -1974,6 +2599,49 @@ unsafe extern "C" fn ps_onload_hook(pkg: SEXP, _path: SEXP) -> anyhow::Result<SE
     Ok(RObject::null().sexp)
 }
 
+// Counterpart of `ps_onload_hook()`, called for every package unloaded from
+// the session
+#[harp::register]
+unsafe extern "C" fn ps_onunload_hook(pkg: SEXP) -> anyhow::Result<SEXP> {
+    let pkg: String = RObject::view(pkg).try_into()?;
+
+    let _span = tracing::trace_span!(parent: None, "onunload_hook", pkg = pkg).entered();
+
+    if let Err(err) = unregister_package_methods(&pkg) {
+        log::error!("Can't unregister ark methods for `{pkg}`: {err:?}");
+    }
+
+    Ok(RObject::null().sexp)
+}
+
+#[harp::register]
+unsafe extern "C" fn ps_session_mode() -> anyhow::Result<SEXP> {
+    let mode = match RMain::get().session_mode {
+        SessionMode::Console => "console",
+        SessionMode::Notebook => "notebook",
+        SessionMode::Background => "background",
+    };
+    Ok(RObject::from(mode).sexp)
+}
+
+// Called by `.ps.eval_in_package()` to get the package and the code of the
+// console input
+#[harp::register]
+unsafe extern "C" fn ps_take_package_input() -> anyhow::Result<SEXP> {
+    let Some((package, code)) = RMain::get_mut().package_input.take() else {
+        return Err(anyhow::anyhow!("No console input to evaluate in a package"));
+    };
+    Ok(RObject::from(vec![package, code]).sexp)
+}
+
+// Called by the `quit()` override under the `"shutdown"` policy, before it
+// unwinds to top level
+#[harp::register]
+unsafe extern "C" fn ps_request_quit() -> anyhow::Result<SEXP> {
+    RMain::get_mut().quit_requested = true;
+    Ok(RObject::null().sexp)
+}
+
 fn do_resource_namespaces() -> bool {
     // Don't slow down integration tests with srcref generation
     if stdext::IS_TESTING {
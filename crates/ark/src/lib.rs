@@ -6,7 +6,10 @@
 //
 
 pub mod analysis;
+pub mod ansi;
+pub mod assignment_targets;
 pub mod browser;
+pub mod build_output;
 pub mod connections;
 pub mod control;
 pub mod coordinates;
@@ -16,6 +19,7 @@ pub mod errors;
 pub mod fixtures;
 pub mod help;
 pub mod help_proxy;
+pub mod idle;
 pub mod interface;
 pub mod json;
 pub mod logger;
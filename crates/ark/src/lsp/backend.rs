@@ -42,6 +42,9 @@ use crate::lsp::main_loop::TokioUnboundedSender;
 use crate::lsp::statement_range;
 use crate::lsp::statement_range::StatementRangeParams;
 use crate::lsp::statement_range::StatementRangeResponse;
+use crate::lsp::todo_markers;
+use crate::lsp::todo_markers::TodoMarkersParams;
+use crate::lsp::todo_markers::TodoMarkersResponse;
 use crate::r_task;
 
 // Based on https://stackoverflow.com/a/69324393/1725177
@@ -90,12 +93,21 @@ pub(crate) enum LspRequest {
     GotoDefinition(GotoDefinitionParams),
     GotoImplementation(GotoImplementationParams),
     SelectionRange(SelectionRangeParams),
+    DocumentLink(DocumentLinkParams),
+    PrepareTypeHierarchy(TypeHierarchyPrepareParams),
+    TypeHierarchySupertypes(TypeHierarchySupertypesParams),
+    TypeHierarchySubtypes(TypeHierarchySubtypesParams),
     References(ReferenceParams),
+    PrepareRename(TextDocumentPositionParams),
+    Rename(RenameParams),
     StatementRange(StatementRangeParams),
     HelpTopic(HelpTopicParams),
     OnTypeFormatting(DocumentOnTypeFormattingParams),
+    Formatting(DocumentFormattingParams),
+    RangeFormatting(DocumentRangeFormattingParams),
     VirtualDocument(VirtualDocumentParams),
     InputBoundaries(InputBoundariesParams),
+    TodoMarkers(TodoMarkersParams),
 }
 
 #[derive(Debug)]
@@ -112,12 +124,21 @@ pub(crate) enum LspResponse {
     GotoDefinition(Option<GotoDefinitionResponse>),
     GotoImplementation(Option<GotoImplementationResponse>),
     SelectionRange(Option<Vec<SelectionRange>>),
+    DocumentLink(Option<Vec<DocumentLink>>),
+    PrepareTypeHierarchy(Option<Vec<TypeHierarchyItem>>),
+    TypeHierarchySupertypes(Option<Vec<TypeHierarchyItem>>),
+    TypeHierarchySubtypes(Option<Vec<TypeHierarchyItem>>),
     References(Option<Vec<Location>>),
+    PrepareRename(Option<PrepareRenameResponse>),
+    Rename(Option<WorkspaceEdit>),
     StatementRange(Option<StatementRangeResponse>),
     HelpTopic(Option<HelpTopicResponse>),
     OnTypeFormatting(Option<Vec<TextEdit>>),
+    Formatting(Option<Vec<TextEdit>>),
+    RangeFormatting(Option<Vec<TextEdit>>),
     VirtualDocument(VirtualDocumentResponse),
     InputBoundaries(InputBoundariesResponse),
+    TodoMarkers(TodoMarkersResponse),
 }
 
 #[derive(Debug)]
@@ -288,6 +309,45 @@ impl LanguageServer for Backend {
         )
     }
 
+    async fn document_link(&self, params: DocumentLinkParams) -> Result<Option<Vec<DocumentLink>>> {
+        cast_response!(
+            self.request(LspRequest::DocumentLink(params)).await,
+            LspResponse::DocumentLink
+        )
+    }
+
+    async fn prepare_type_hierarchy(
+        &self,
+        params: TypeHierarchyPrepareParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        cast_response!(
+            self.request(LspRequest::PrepareTypeHierarchy(params)).await,
+            LspResponse::PrepareTypeHierarchy
+        )
+    }
+
+    async fn supertypes(
+        &self,
+        params: TypeHierarchySupertypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        cast_response!(
+            self.request(LspRequest::TypeHierarchySupertypes(params))
+                .await,
+            LspResponse::TypeHierarchySupertypes
+        )
+    }
+
+    async fn subtypes(
+        &self,
+        params: TypeHierarchySubtypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        cast_response!(
+            self.request(LspRequest::TypeHierarchySubtypes(params))
+                .await,
+            LspResponse::TypeHierarchySubtypes
+        )
+    }
+
     async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
         cast_response!(
             self.request(LspRequest::References(params)).await,
@@ -295,6 +355,23 @@ impl LanguageServer for Backend {
         )
     }
 
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        cast_response!(
+            self.request(LspRequest::PrepareRename(params)).await,
+            LspResponse::PrepareRename
+        )
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        cast_response!(
+            self.request(LspRequest::Rename(params)).await,
+            LspResponse::Rename
+        )
+    }
+
     async fn on_type_formatting(
         &self,
         params: DocumentOnTypeFormattingParams,
@@ -304,6 +381,23 @@ impl LanguageServer for Backend {
             LspResponse::OnTypeFormatting
         )
     }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        cast_response!(
+            self.request(LspRequest::Formatting(params)).await,
+            LspResponse::Formatting
+        )
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        cast_response!(
+            self.request(LspRequest::RangeFormatting(params)).await,
+            LspResponse::RangeFormatting
+        )
+    }
 }
 
 // Custom methods for the backend.
@@ -362,6 +456,16 @@ impl Backend {
         )
     }
 
+    async fn todo_markers(
+        &self,
+        params: TodoMarkersParams,
+    ) -> tower_lsp::jsonrpc::Result<TodoMarkersResponse> {
+        cast_response!(
+            self.request(LspRequest::TodoMarkers(params)).await,
+            LspResponse::TodoMarkers
+        )
+    }
+
     async fn notification(&self, params: Option<Value>) {
         log::info!("Received Positron notification: {:?}", params);
     }
@@ -420,6 +524,10 @@ pub fn start_lsp(runtime: Arc<Runtime>, address: String, conn_init_tx: Sender<bo
                 input_boundaries::POSITRON_INPUT_BOUNDARIES_REQUEST,
                 Backend::input_boundaries,
             )
+            .custom_method(
+                todo_markers::POSITRON_TODO_MARKERS_REQUEST,
+                Backend::todo_markers,
+            )
             .custom_method("positron/notification", Backend::notification)
             .finish();
 
@@ -6,10 +6,26 @@
 //
 
 mod completion_item;
+mod package_index;
 mod provide;
 mod resolve;
 mod sources;
 mod types;
 
 pub(crate) use provide::provide_completions;
+pub(crate) use provide::Completions;
+pub(crate) use provide::SLOW_SOURCES_BUDGET;
 pub(crate) use resolve::resolve_completion;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompletionsConfig {
+    /// Whether to offer the exports of loaded packages that aren't attached,
+    /// inserting a `library()` call when one of them is accepted.
+    pub auto_import: bool,
+}
+
+impl Default for CompletionsConfig {
+    fn default() -> Self {
+        Self { auto_import: false }
+    }
+}
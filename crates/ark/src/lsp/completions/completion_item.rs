@@ -251,7 +251,7 @@ pub(super) unsafe fn completion_item_from_object(
     })?;
 
     item.detail = Some("(Object)".to_string());
-    item.kind = Some(CompletionItemKind::STRUCT);
+    item.kind = Some(CompletionItemKind::VARIABLE);
 
     if !is_symbol_valid(name) {
         item.insert_text = Some(sym_quote(name));
@@ -453,6 +453,22 @@ pub(super) fn completion_item_from_parameter(
     Ok(item)
 }
 
+/// A case label of a `switch()` call, inserted with its `=` so that the
+/// branch can be filled in or left empty to fall through.
+pub(super) fn completion_item_from_switch_case(case: &str) -> Result<CompletionItem> {
+    let case = sym_quote_invalid(case);
+    let label = case.clone() + " = ";
+
+    let mut item = completion_item(label.as_str(), CompletionData::Unknown)?;
+
+    item.kind = Some(CompletionItemKind::FIELD);
+    item.insert_text = Some(label);
+    item.filter_text = Some(case.clone());
+    item.sort_text = Some(case);
+
+    Ok(item)
+}
+
 fn completion_item_from_dot_dot_dot(
     callee: &str,
     context: &DocumentContext,
@@ -0,0 +1,142 @@
+//
+// package_index.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::object::RObject;
+use harp::utils::r_is_null;
+
+/// The completion data of a package: its exports, the formals of its
+/// exported functions, and the titles of its help topics.
+#[derive(Debug)]
+pub(super) struct PackageIndex {
+    pub(super) exports: Vec<String>,
+    pub(super) formals: HashMap<String, Vec<String>>,
+    pub(super) titles: HashMap<String, String>,
+}
+
+struct PackageIndexEntry {
+    key: String,
+    index: Arc<PackageIndex>,
+}
+
+/// Package indices by package name. An index is rebuilt when the key of its
+/// package changes, i.e. when another version of the package is loaded, or
+/// when a development package is loaded again with `load_all()`.
+static PACKAGE_INDICES: LazyLock<Mutex<HashMap<String, PackageIndexEntry>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The completion index of a loaded package, built on first use and cached
+/// until the package is reloaded. `None` if the package isn't loaded.
+///
+/// Must be called on the R thread.
+pub(super) fn package_index(package: &str) -> Result<Option<Arc<PackageIndex>>> {
+    let key = RFunction::from(".ps.completions.packageIndexKey")
+        .add(package)
+        .call()?;
+    if r_is_null(key.sexp) {
+        return Ok(None);
+    }
+    let key = String::try_from(key)?;
+
+    let mut indices = PACKAGE_INDICES.lock().unwrap();
+
+    if let Some(entry) = indices.get(package) {
+        if entry.key == key {
+            return Ok(Some(entry.index.clone()));
+        }
+    }
+
+    log::trace!("Building completion index of package '{package}' ('{key}')");
+    let index = Arc::new(build_package_index(package)?);
+
+    indices.insert(package.to_string(), PackageIndexEntry {
+        key,
+        index: index.clone(),
+    });
+
+    Ok(Some(index))
+}
+
+fn build_package_index(package: &str) -> Result<PackageIndex> {
+    let index = RFunction::from(".ps.completions.packageIndex")
+        .add(package)
+        .call()?;
+
+    let exports = Vec::<String>::try_from(&index.vector_elt(0)?)?;
+
+    let formals = named_elements(index.vector_elt(1)?, |value| {
+        Ok(Vec::<String>::try_from(&value)?)
+    })?;
+
+    let titles = index.vector_elt(2)?;
+    let titles = std::iter::zip(
+        titles.names().unwrap_or_default(),
+        Vec::<String>::try_from(&titles)?,
+    )
+    .filter_map(|(name, title)| Some((name?, title)))
+    .collect();
+
+    Ok(PackageIndex {
+        exports,
+        formals,
+        titles,
+    })
+}
+
+fn named_elements<T>(
+    list: RObject,
+    convert: impl Fn(RObject) -> Result<T>,
+) -> Result<HashMap<String, T>> {
+    let mut elements = HashMap::new();
+
+    for (i, name) in list.names().unwrap_or_default().into_iter().enumerate() {
+        let Some(name) = name else {
+            continue;
+        };
+        elements.insert(name, convert(list.vector_elt(i as isize)?)?);
+    }
+
+    Ok(elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::lsp::completions::package_index::package_index;
+    use crate::r_task;
+
+    #[test]
+    fn test_package_index() {
+        r_task(|| {
+            let index = package_index("stats").unwrap().unwrap();
+
+            assert!(index.exports.iter().any(|export| export == "median"));
+            assert_eq!(index.formals["median"], vec!["x", "na.rm", "..."]);
+            assert_eq!(index.titles["median"], "Median Value");
+
+            // Not loaded
+            assert!(package_index("notapackage").unwrap().is_none());
+        })
+    }
+
+    #[test]
+    fn test_package_index_is_reused() {
+        r_task(|| {
+            let first = package_index("utils").unwrap().unwrap();
+            let second = package_index("utils").unwrap().unwrap();
+            assert!(Arc::ptr_eq(&first, &second));
+        })
+    }
+}
@@ -5,6 +5,9 @@
 //
 //
 
+use std::time::Duration;
+use std::time::Instant;
+
 use anyhow::Result;
 use tower_lsp::lsp_types::CompletionItem;
 
@@ -13,20 +16,41 @@ use crate::lsp::completions::sources::completions_from_unique_sources;
 use crate::lsp::document_context::DocumentContext;
 use crate::lsp::state::WorldState;
 
+/// How long the first pass of a completion request may take before the
+/// remaining slow sources are skipped
+pub(crate) const SLOW_SOURCES_BUDGET: Duration = Duration::from_millis(250);
+
+pub(crate) struct Completions {
+    pub(crate) items: Vec<CompletionItem>,
+
+    /// Whether slow sources were skipped because the deadline passed. The
+    /// client should request completions again to get the full list.
+    pub(crate) is_incomplete: bool,
+}
+
 // Entry point for completions.
 // Must be within an `r_task()`.
+//
+// All sources are consulted until `deadline` passes. After that, the sources
+// that need to evaluate objects or scan packages are skipped so that a first
+// list can be returned quickly, and the list is marked incomplete. Without a
+// deadline all sources are consulted.
 pub(crate) fn provide_completions(
     context: &DocumentContext,
     state: &WorldState,
-) -> Result<Vec<CompletionItem>> {
+    deadline: Option<Instant>,
+) -> Result<Completions> {
     log::info!("provide_completions()");
 
     if let Some(completions) = completions_from_unique_sources(context)? {
-        return Ok(completions);
+        return Ok(Completions {
+            items: completions,
+            is_incomplete: false,
+        });
     };
 
     // At this point we aren't in a "unique" completion case, so just return a
     // set of reasonable completions based on loaded packages, the open
     // document, the current workspace, and any call related arguments
-    completions_from_composite_sources(context, state)
+    completions_from_composite_sources(context, state, deadline)
 }
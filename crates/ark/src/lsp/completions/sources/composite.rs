@@ -5,6 +5,7 @@
 //
 //
 
+mod auto_import;
 mod call;
 mod document;
 mod keyword;
@@ -15,8 +16,10 @@ mod subset;
 mod workspace;
 
 use std::collections::HashSet;
+use std::time::Instant;
 
 use anyhow::Result;
+use auto_import::completions_from_auto_import;
 use call::completions_from_call;
 use document::completions_from_document;
 use keyword::completions_from_keywords;
@@ -26,23 +29,36 @@ use search_path::completions_from_search_path;
 use snippets::completions_from_snippets;
 use stdext::*;
 use subset::completions_from_subset;
+use subset::find_subset_node;
 use tower_lsp::lsp_types::CompletionItem;
 use tower_lsp::lsp_types::CompletionItemKind;
 use tree_sitter::Node;
 use workspace::completions_from_workspace;
 
+use crate::lsp::completions::Completions;
 use crate::lsp::document_context::DocumentContext;
 use crate::lsp::state::WorldState;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
+/// Composite completions. Pipe and subset completions evaluate objects, and
+/// search path and auto-import completions scan the exports of packages.
+/// Auto-import is limited to the namespaces that are already loaded, the
+/// exports of packages that are only installed aren't indexed.
+///
+/// These slow sources are skipped once `deadline` has passed, and the
+/// completions are then marked incomplete if any of them would have applied.
 pub fn completions_from_composite_sources(
     context: &DocumentContext,
     state: &WorldState,
-) -> Result<Vec<CompletionItem>> {
+    deadline: Option<Instant>,
+) -> Result<Completions> {
     log::info!("completions_from_composite_sources()");
 
     let mut completions: Vec<CompletionItem> = vec![];
+    let mut is_incomplete = false;
+
+    let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
 
     let root = find_pipe_root(context)?;
 
@@ -52,13 +68,21 @@ pub fn completions_from_composite_sources(
     }
 
     // Try pipe completions
-    if let Some(mut additional_completions) = completions_from_pipe(root.clone())? {
-        completions.append(&mut additional_completions);
+    if !expired() {
+        if let Some(mut additional_completions) = completions_from_pipe(root.clone())? {
+            completions.append(&mut additional_completions);
+        }
+    } else if root.as_ref().is_some_and(|root| root.object.is_some()) {
+        is_incomplete = true;
     }
 
     // Try subset completions (`[` or `[[`)
-    if let Some(mut additional_completions) = completions_from_subset(context)? {
-        completions.append(&mut additional_completions);
+    if !expired() {
+        if let Some(mut additional_completions) = completions_from_subset(context)? {
+            completions.append(&mut additional_completions);
+        }
+    } else if find_subset_node(context).is_some() {
+        is_incomplete = true;
     }
 
     // Call, pipe, and subset completions should show up no matter what when
@@ -69,7 +93,16 @@ pub fn completions_from_composite_sources(
     if is_identifier_like(context.node) {
         completions.append(&mut completions_from_keywords());
         completions.append(&mut completions_from_snippets());
-        completions.append(&mut completions_from_search_path(context)?);
+        if !expired() {
+            completions.append(&mut completions_from_search_path(context)?);
+        } else {
+            is_incomplete = true;
+        }
+        if !expired() {
+            completions.append(&mut completions_from_auto_import(context, state)?);
+        } else if state.config.completions.auto_import {
+            is_incomplete = true;
+        }
 
         if let Some(mut additional_completions) = completions_from_document(context)? {
             completions.append(&mut additional_completions);
@@ -122,7 +155,10 @@ pub fn completions_from_composite_sources(
         }
     }
 
-    Ok(completions)
+    Ok(Completions {
+        items: completions,
+        is_incomplete,
+    })
 }
 
 fn is_identifier_like(x: Node) -> bool {
@@ -148,11 +184,16 @@ fn is_identifier_like(x: Node) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+
     use tree_sitter::Point;
 
+    use crate::lsp::completions::sources::composite::completions_from_composite_sources;
     use crate::lsp::completions::sources::composite::is_identifier_like;
     use crate::lsp::document_context::DocumentContext;
     use crate::lsp::documents::Document;
+    use crate::lsp::state::WorldState;
     use crate::r_task;
     use crate::treesitter::NodeType;
     use crate::treesitter::NodeTypeExt;
@@ -175,4 +216,55 @@ mod tests {
             }
         })
     }
+
+    #[test]
+    fn test_completions_first_request_has_slow_sources() {
+        r_task(|| {
+            let state = WorldState::default();
+
+            let document = Document::new("mea", None);
+            let point = Point { row: 0, column: 3 };
+            let context = DocumentContext::new(&document, point, None);
+
+            // A first request that doesn't run out of time has the search
+            // path completions and is complete
+            let deadline = Instant::now() + Duration::from_secs(60);
+            let first =
+                completions_from_composite_sources(&context, &state, Some(deadline)).unwrap();
+            assert!(!first.is_incomplete);
+            assert!(first.items.iter().any(|item| item.label == "mean"));
+        })
+    }
+
+    #[test]
+    fn test_completions_slow_sources_are_deferred() {
+        r_task(|| {
+            let state = WorldState::default();
+
+            // Search path completions apply to identifiers
+            let document = Document::new("my_local <- 1\nmea", None);
+            let point = Point { row: 1, column: 3 };
+            let context = DocumentContext::new(&document, point, None);
+
+            // Past the deadline, the slow sources are skipped
+            let deadline = Instant::now();
+            let fast =
+                completions_from_composite_sources(&context, &state, Some(deadline)).unwrap();
+            assert!(fast.is_incomplete);
+            assert!(fast.items.iter().any(|item| item.label == "my_local"));
+            assert!(!fast.items.iter().any(|item| item.label == "mean"));
+
+            // The full list is complete and has the fast items exactly once
+            let full = completions_from_composite_sources(&context, &state, None).unwrap();
+            assert!(!full.is_incomplete);
+            assert!(full.items.iter().any(|item| item.label == "mean"));
+            assert_eq!(
+                full.items
+                    .iter()
+                    .filter(|item| item.label == "my_local")
+                    .count(),
+                1
+            );
+        })
+    }
 }
@@ -0,0 +1,219 @@
+//
+// auto_import.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use ropey::Rope;
+use tower_lsp::lsp_types::CompletionItem;
+use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::TextEdit;
+use tree_sitter::Node;
+use tree_sitter::Point;
+
+use crate::lsp::completions::completion_item::completion_item_from_namespace;
+use crate::lsp::document_context::DocumentContext;
+use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::node_text;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Completions for the exported functions of packages that are loaded but
+/// not attached. Accepting one of them inserts a `library()` call after the
+/// `library()` calls at the top of the document. When several packages
+/// export the same name, including attached ones, the completions are
+/// qualified with `pkg::` instead since attaching would be a guess, or would
+/// mask an object in use.
+///
+/// Only loaded namespaces are considered, loading every installed package to
+/// find its exports would be too slow.
+pub(super) fn completions_from_auto_import(
+    context: &DocumentContext,
+    state: &WorldState,
+) -> Result<Vec<CompletionItem>> {
+    log::info!("completions_from_auto_import()");
+
+    let mut completions = vec![];
+
+    if !state.config.completions.auto_import {
+        return Ok(completions);
+    }
+
+    let node = context.node;
+    if !node.is_identifier() {
+        return Ok(completions);
+    }
+
+    let token = context.document.contents.node_slice(&node)?.to_string();
+    if token.is_empty() {
+        return Ok(completions);
+    }
+
+    let candidates = RFunction::from(".ps.completions.autoImportCandidates")
+        .add(token.as_str())
+        .call()?;
+    let names = unsafe { candidates.vector_elt(0)?.to::<Vec<String>>()? };
+    let packages = unsafe { candidates.vector_elt(1)?.to::<Vec<String>>()? };
+    let masked = Vec::<bool>::try_from(&candidates.vector_elt(2)?)?;
+
+    let mut candidates: BTreeMap<String, (Vec<String>, bool)> = BTreeMap::new();
+    for ((name, package), masked) in std::iter::zip(std::iter::zip(names, packages), masked) {
+        let candidate = candidates.entry(name).or_default();
+        candidate.0.push(package);
+        candidate.1 = masked;
+    }
+
+    let contents = &context.document.contents;
+    let root = context.document.ast.root_node();
+
+    for (name, (packages, masked)) in candidates.iter() {
+        let ambiguous = *masked || packages.len() > 1;
+
+        for package in packages.iter() {
+            let namespace = RFunction::new("base", "asNamespace")
+                .add(package.as_str())
+                .call()?;
+
+            let mut item =
+                match unsafe { completion_item_from_namespace(name, namespace.sexp, package) } {
+                    Ok(item) => item,
+                    Err(err) => {
+                        log::error!("{err:?}");
+                        continue;
+                    },
+                };
+
+            if ambiguous {
+                let insert_text = item.insert_text.take().unwrap_or_else(|| name.clone());
+                item.label = format!("{package}::{name}");
+                item.filter_text = Some(name.clone());
+                item.insert_text = Some(format!("{package}::{insert_text}"));
+            } else if let Some(edit) = library_insertion_edit(&root, contents, package) {
+                item.additional_text_edits = Some(vec![edit]);
+            }
+
+            completions.push(item);
+        }
+    }
+
+    Ok(completions)
+}
+
+/// The edit inserting `library(<package>)` after the `library()` and
+/// `require()` calls at the top of the document, or at the very top when
+/// there are none. `None` when the document already attaches the package.
+fn library_insertion_edit(root: &Node, contents: &Rope, package: &str) -> Option<TextEdit> {
+    let mut cursor = root.walk();
+    let children: Vec<Node> = root.named_children(&mut cursor).collect();
+
+    if children
+        .iter()
+        .any(|child| attached_package(child, contents).as_deref() == Some(package))
+    {
+        return None;
+    }
+
+    // The last of the leading `library()` calls, comments notwithstanding
+    let last = children
+        .iter()
+        .take_while(|child| child.is_comment() || attached_package(child, contents).is_some())
+        .filter(|child| !child.is_comment())
+        .last();
+
+    let (point, new_text) = match last {
+        Some(last) => (last.end_position(), format!("\nlibrary({package})")),
+        None => (Point::new(0, 0), format!("library({package})\n")),
+    };
+    let position = convert_point_to_position(contents, point);
+
+    Some(TextEdit {
+        range: Range::new(position, position),
+        new_text,
+    })
+}
+
+/// The package attached by a `library()` or `require()` call
+fn attached_package(node: &Node, contents: &Rope) -> Option<String> {
+    if !node.is_call() {
+        return None;
+    }
+
+    let function = node_text(&node.child_by_field_name("function")?, contents)?;
+    if !matches!(function.as_str(), "library" | "require") {
+        return None;
+    }
+
+    let arguments = node.child_by_field_name("arguments")?;
+    let mut cursor = arguments.walk();
+    let argument = arguments
+        .named_children(&mut cursor)
+        .find(|child| child.node_type() == NodeType::Argument)?;
+    let value = argument.child_by_field_name("value")?;
+
+    let package = node_text(&value, contents)?;
+    Some(package.trim_matches(|c| c == '"' || c == '\'').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+    use tree_sitter::Point;
+
+    use crate::lsp::completions::sources::composite::auto_import::completions_from_auto_import;
+    use crate::lsp::completions::sources::composite::auto_import::library_insertion_edit;
+    use crate::lsp::document_context::DocumentContext;
+    use crate::lsp::documents::Document;
+    use crate::lsp::state::WorldState;
+    use crate::r_task;
+
+    #[test]
+    fn test_completions_auto_import_library_edit() {
+        r_task(|| {
+            // A loaded but unattached namespace with a unique export
+            harp::parse_eval_global("loadNamespace('tools')").unwrap();
+
+            let mut state = WorldState::default();
+
+            let document = Document::new("file_path_sans_", None);
+            let point = Point { row: 0, column: 15 };
+            let context = DocumentContext::new(&document, point, None);
+
+            // Opt-in
+            let completions = completions_from_auto_import(&context, &state).unwrap();
+            assert!(completions.is_empty());
+
+            state.config.completions.auto_import = true;
+            let completions = completions_from_auto_import(&context, &state).unwrap();
+
+            let item = completions
+                .iter()
+                .find(|item| item.label == "file_path_sans_ext")
+                .unwrap();
+            let edits = item.additional_text_edits.as_ref().unwrap();
+            assert_eq!(edits.len(), 1);
+            assert_eq!(edits[0].new_text, "library(tools)\n");
+            assert_eq!(edits[0].range.start, Position::new(0, 0));
+        })
+    }
+
+    #[test]
+    fn test_library_insertion_edit() {
+        let document = Document::new("# header\nlibrary(stats)\nrequire('utils')\n\nx <- 1", None);
+        let root = document.ast.root_node();
+
+        let edit = library_insertion_edit(&root, &document.contents, "tools").unwrap();
+        assert_eq!(edit.new_text, "\nlibrary(tools)");
+        assert_eq!(edit.range.start, Position::new(2, 16));
+
+        // Already attached by the document
+        assert!(library_insertion_edit(&root, &document.contents, "utils").is_none());
+    }
+}
@@ -12,16 +12,22 @@ use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::object::RObject;
 use harp::utils::r_is_function;
+use harp::utils::r_is_null;
+use harp::utils::r_typeof;
+use libr::STRSXP;
 use tower_lsp::lsp_types::CompletionItem;
 use tree_sitter::Node;
 
 use super::pipe::PipeRoot;
 use crate::lsp::completions::completion_item::completion_item_from_parameter;
+use crate::lsp::completions::completion_item::completion_item_from_switch_case;
+use crate::lsp::completions::package_index::package_index;
 use crate::lsp::completions::sources::utils::call_node_position_type;
 use crate::lsp::completions::sources::utils::set_sort_text_by_first_appearance;
 use crate::lsp::completions::sources::utils::CallNodePositionType;
 use crate::lsp::document_context::DocumentContext;
 use crate::lsp::indexer;
+use crate::lsp::traits::point::PointExt;
 use crate::lsp::traits::rope::RopeExt;
 use crate::treesitter::NodeTypeExt;
 
@@ -87,6 +93,12 @@ pub(super) fn completions_from_call(
 
     let callee = context.document.contents.node_slice(&callee)?.to_string();
 
+    if matches!(callee.as_str(), "switch" | "base::switch") {
+        if let Some(completions) = completions_from_switch_cases(context, &node)? {
+            return Ok(Some(completions));
+        }
+    }
+
     // - Prefer `root` as the first argument if it exists
     // - Then fall back to looking it up, if possible
     // - Otherwise use `NULL` to signal that we can't figure it out
@@ -104,6 +116,80 @@ pub(super) fn completions_from_call(
     completions_from_arguments(context, &callee, object)
 }
 
+/// At most this many values of a `switch()` scrutinee are offered as case
+/// labels. Longer vectors are unlikely to be a vocabulary of cases.
+const MAX_SWITCH_CASES: isize = 100;
+
+/// Case labels for `switch(x, )` when `x` evaluates to a small character
+/// vector. The labels already used, including empty fallthrough branches
+/// like `a = ,`, are left out.
+fn completions_from_switch_cases(
+    context: &DocumentContext,
+    node: &Node,
+) -> Result<Option<Vec<CompletionItem>>> {
+    let Some(arguments) = node.child_by_field_name("arguments") else {
+        return Ok(None);
+    };
+
+    let mut cursor = arguments.walk();
+    let arguments: Vec<Node> = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .collect();
+
+    // Nothing to offer while the scrutinee itself is being typed
+    let Some(scrutinee) = arguments.first() else {
+        return Ok(None);
+    };
+    if context.point.is_before_or_equal(scrutinee.end_position()) {
+        return Ok(None);
+    }
+
+    let Some(object) = get_first_argument(context, node)? else {
+        return Ok(None);
+    };
+    if r_typeof(object.sexp) != STRSXP || object.length() > MAX_SWITCH_CASES {
+        return Ok(None);
+    }
+    let Ok(cases) = Vec::<String>::try_from(&object) else {
+        return Ok(None);
+    };
+
+    log::info!("completions_from_switch_cases()");
+
+    let mut used = vec![];
+    for argument in arguments.iter().skip(1) {
+        // The label under the cursor is still being typed
+        if argument.start_position().is_before_or_equal(context.point) &&
+            context.point.is_before_or_equal(argument.end_position())
+        {
+            continue;
+        }
+        if let Some(name) = argument.child_by_field_name("name") {
+            let name = context.document.contents.node_slice(&name)?.to_string();
+            used.push(
+                name.trim_matches(|c| c == '`' || c == '"' || c == '\'')
+                    .to_string(),
+            );
+        }
+    }
+
+    let mut completions = vec![];
+    for case in cases.iter() {
+        if case.is_empty() || used.contains(case) {
+            continue;
+        }
+        match completion_item_from_switch_case(case) {
+            Ok(item) => completions.push(item),
+            Err(err) => log::error!("{err:?}"),
+        }
+    }
+
+    // Cases are offered in the order of the scrutinee's values
+    set_sort_text_by_first_appearance(&mut completions);
+
+    Ok(Some(completions))
+}
+
 fn get_first_argument(context: &DocumentContext, node: &Node) -> Result<Option<RObject>> {
     // Get the first argument, if any (object used for dispatch).
     // TODO: We should have some way of matching calls, so we can
@@ -170,6 +256,12 @@ fn completions_from_arguments(
 ) -> Result<Option<Vec<CompletionItem>>> {
     log::info!("completions_from_arguments({callable:?})");
 
+    // Qualified calls without an object to dispatch on use the cached index
+    // of the package
+    if let Some(completions) = completions_from_package_arguments(context, callable, &object)? {
+        return Ok(Some(completions));
+    }
+
     // Try looking up session function first, as the "current state of the world"
     // will provide the most accurate completions
     if let Some(completions) = completions_from_session_arguments(context, callable, object)? {
@@ -183,6 +275,45 @@ fn completions_from_arguments(
     Ok(None)
 }
 
+fn completions_from_package_arguments(
+    context: &DocumentContext,
+    callable: &str,
+    object: &RObject,
+) -> Result<Option<Vec<CompletionItem>>> {
+    if !r_is_null(object.sexp) {
+        return Ok(None);
+    }
+
+    // `pkg::fn`, but not `pkg:::fn` which may not be exported
+    let Some((package, name)) = callable.split_once("::") else {
+        return Ok(None);
+    };
+    if name.starts_with(':') {
+        return Ok(None);
+    }
+
+    let Some(index) = package_index(package)? else {
+        return Ok(None);
+    };
+    let Some(formals) = index.formals.get(name) else {
+        return Ok(None);
+    };
+
+    log::info!("completions_from_package_arguments({callable:?})");
+
+    let mut completions = vec![];
+    for formal in formals.iter() {
+        match completion_item_from_parameter(formal, callable, context) {
+            Ok(item) => completions.push(item),
+            Err(err) => log::error!("{err:?}"),
+        }
+    }
+
+    set_sort_text_by_first_appearance(&mut completions);
+
+    Ok(Some(completions))
+}
+
 fn completions_from_session_arguments(
     context: &DocumentContext,
     callable: &str,
@@ -283,9 +414,12 @@ fn completions_from_workspace_arguments(
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use harp::eval::RParseEvalOptions;
     use tree_sitter::Point;
 
+    use crate::lsp::completions::package_index::package_index;
     use crate::lsp::completions::sources::composite::call::completions_from_call;
     use crate::lsp::document_context::DocumentContext;
     use crate::lsp::documents::Document;
@@ -319,6 +453,61 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_completions_from_package_index() {
+        r_task(|| {
+            let point = Point { row: 0, column: 14 };
+            let document = Document::new("stats::median()", None);
+            let context = DocumentContext::new(&document, point, None);
+
+            let index = package_index("stats").unwrap().unwrap();
+
+            // The index is built once and reused across requests
+            for _ in 0..2 {
+                let completions = completions_from_call(&context, None).unwrap().unwrap();
+                let labels: Vec<&str> =
+                    completions.iter().map(|item| item.label.as_str()).collect();
+                assert_eq!(labels, vec!["x = ", "na.rm = ", "..."]);
+                assert!(Arc::ptr_eq(
+                    &index,
+                    &package_index("stats").unwrap().unwrap()
+                ));
+            }
+        })
+    }
+
+    #[test]
+    fn test_completions_switch_cases() {
+        r_task(|| {
+            harp::parse_eval_global("type <- c('a', 'b')").unwrap();
+
+            let labels = |code: &str, column: usize| -> Vec<String> {
+                let point = Point { row: 0, column };
+                let document = Document::new(code, None);
+                let context = DocumentContext::new(&document, point, None);
+                completions_from_call(&context, None)
+                    .unwrap()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|item| item.label)
+                    .collect()
+            };
+
+            assert_eq!(labels("switch(type, )", 13), vec!["a = ", "b = "]);
+
+            // Empty fallthrough branches count as used
+            assert_eq!(labels("switch(type, a = , )", 19), vec!["b = "]);
+
+            // The label being typed doesn't
+            assert_eq!(labels("switch(type, a)", 14), vec!["a = ", "b = "]);
+
+            // Not while typing the scrutinee
+            assert!(!labels("switch(type)", 11).contains(&String::from("a = ")));
+
+            harp::parse_eval_global("rm(type)").unwrap();
+        })
+    }
+
     #[test]
     fn test_session_arguments() {
         // Can't find the function
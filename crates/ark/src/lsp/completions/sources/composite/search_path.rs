@@ -6,6 +6,7 @@
 //
 
 use anyhow::Result;
+use harp::environment::R_ENVS;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::utils::r_env_is_pkg_env;
@@ -14,11 +15,13 @@ use harp::vector::CharacterVector;
 use harp::vector::Vector;
 use harp::RObject;
 use libr::R_EmptyEnv;
-use libr::R_GlobalEnv;
 use libr::R_lsInternal;
 use libr::ENCLOS;
+use libr::SEXP;
 use tower_lsp::lsp_types::CompletionItem;
+use tower_lsp::lsp_types::CompletionItemLabelDetails;
 
+use crate::interface::RMain;
 use crate::lsp::completions::completion_item::completion_item_from_package;
 use crate::lsp::completions::completion_item::completion_item_from_symbol;
 use crate::lsp::completions::sources::utils::filter_out_dot_prefixes;
@@ -31,6 +34,20 @@ pub(super) fn completions_from_search_path(
 ) -> Result<Vec<CompletionItem>> {
     log::info!("completions_from_search_path()");
 
+    // When paused in a `browser()`, start from the browsed frame so that its
+    // local bindings are offered before the global ones
+    let debug_env = RMain::is_initialized()
+        .then(|| RMain::get().debug_env())
+        .flatten();
+    let envir = debug_env.map(|env| env.sexp).unwrap_or(R_ENVS.global);
+
+    completions_from_environments(context, envir)
+}
+
+fn completions_from_environments(
+    context: &DocumentContext,
+    envir: SEXP,
+) -> Result<Vec<CompletionItem>> {
     let mut completions = vec![];
 
     const R_CONTROL_FLOW_KEYWORDS: &[&str] = &[
@@ -38,8 +55,8 @@ pub(super) fn completions_from_search_path(
     ];
 
     unsafe {
-        // Iterate through environments starting from the global environment.
-        let mut envir = R_GlobalEnv;
+        // Iterate through environments starting from `envir`.
+        let mut envir = envir;
 
         while envir != R_EmptyEnv {
             let is_pkg_env = r_env_is_pkg_env(envir);
@@ -55,6 +72,9 @@ pub(super) fn completions_from_search_path(
 
             let name = name.as_deref();
 
+            // The package shown along with the completions of its environment
+            let package = name.map(|name| name.trim_start_matches("package:").to_string());
+
             // If this is a package environment, we will need to force promises to give meaningful completions,
             // particularly with functions because we add a `CompletionItem::command()` that adds trailing `()` onto
             // the completion and triggers parameter completions.
@@ -90,7 +110,15 @@ pub(super) fn completions_from_search_path(
                 };
 
                 match item {
-                    Ok(item) => completions.push(item),
+                    Ok(mut item) => {
+                        if let Some(package) = &package {
+                            item.label_details = Some(CompletionItemLabelDetails {
+                                detail: None,
+                                description: Some(package.clone()),
+                            });
+                        }
+                        completions.push(item)
+                    },
                     Err(error) => log::error!("{:?}", error),
                 };
             }
@@ -120,3 +148,68 @@ pub(super) fn completions_from_search_path(
 
     Ok(completions)
 }
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::R_ENVS;
+    use tower_lsp::lsp_types::CompletionItemKind;
+    use tree_sitter::Point;
+
+    use crate::lsp::completions::sources::composite::search_path::completions_from_environments;
+    use crate::lsp::document_context::DocumentContext;
+    use crate::lsp::documents::Document;
+    use crate::r_task;
+
+    #[test]
+    fn test_completions_from_frame_environment() {
+        r_task(|| {
+            // Simulates the frame of a function paused in `browser()`
+            let env = harp::parse_eval_global("local({ tmp <- 1; environment() })").unwrap();
+
+            let point = Point { row: 0, column: 2 };
+            let document = Document::new("tm", None);
+            let context = DocumentContext::new(&document, point, None);
+
+            let completions = completions_from_environments(&context, env.sexp).unwrap();
+            assert!(completions.iter().any(|item| item.label == "tmp"));
+
+            // Frame locals are not visible from the global environment
+            let completions = completions_from_environments(&context, R_ENVS.global).unwrap();
+            assert!(!completions.iter().any(|item| item.label == "tmp"));
+        })
+    }
+
+    #[test]
+    fn test_completions_kind_and_package() {
+        r_task(|| {
+            harp::parse_eval_global("ark_completion_obj <- 1").unwrap();
+
+            let point = Point { row: 0, column: 2 };
+            let document = Document::new("me", None);
+            let context = DocumentContext::new(&document, point, None);
+            let completions = completions_from_environments(&context, R_ENVS.global).unwrap();
+
+            // Functions of attached packages show their package
+            let median = completions
+                .iter()
+                .find(|item| item.label == "median")
+                .unwrap();
+            assert_eq!(median.kind, Some(CompletionItemKind::FUNCTION));
+            let description = median
+                .label_details
+                .as_ref()
+                .and_then(|details| details.description.as_deref());
+            assert_eq!(description, Some("stats"));
+
+            // Global objects are variables without a package
+            let obj = completions
+                .iter()
+                .find(|item| item.label == "ark_completion_obj")
+                .unwrap();
+            assert_eq!(obj.kind, Some(CompletionItemKind::VARIABLE));
+            assert!(obj.label_details.is_none());
+
+            harp::parse_eval_global("rm(ark_completion_obj)").unwrap();
+        })
+    }
+}
@@ -7,6 +7,7 @@
 
 use anyhow::Result;
 use tower_lsp::lsp_types::CompletionItem;
+use tree_sitter::Node;
 
 use crate::lsp::completions::sources::common::subset::is_within_subset_delimiters;
 use crate::lsp::completions::sources::utils::completions_from_evaluated_object_names;
@@ -26,49 +27,50 @@ pub(super) fn completions_from_subset(
 
     const ENQUOTE: bool = true;
 
+    let Some(node) = find_subset_node(context) else {
+        // Didn't detect anything worth completing in this context,
+        // let other sources add their own candidates instead
+        return Ok(None);
+    };
+
+    let Some(child) = node.child(0) else {
+        // There is almost definitely a child here. If there isn't,
+        // we "tried" to do subset completions but found nothing.
+        return Ok(Some(vec![]));
+    };
+
+    let text = context.document.contents.node_slice(&child)?.to_string();
+
+    completions_from_evaluated_object_names(&text, ENQUOTE)
+}
+
+/// Finds the `x[` or `x[[` node the user is completing within, without
+/// evaluating anything
+pub(super) fn find_subset_node<'a>(context: &DocumentContext<'a>) -> Option<Node<'a>> {
     let mut node = context.node;
-    let mut needs_completions = false;
 
     loop {
         let node_type = node.node_type();
 
         if matches!(node_type, NodeType::Subset | NodeType::Subset2) {
-            needs_completions = true;
             break;
         }
 
         // If we reach a brace list, bail.
         if node.is_braced_expression() {
-            break;
+            return None;
         }
 
         // Update the node.
-        node = match node.parent() {
-            Some(node) => node,
-            None => break,
-        };
+        node = node.parent()?;
     }
 
-    if !needs_completions {
-        // Didn't detect anything worth completing in this context,
-        // let other sources add their own candidates instead
-        return Ok(None);
-    };
-
     // Only provide subset completions if you are actually within `x[<here>]` or `x[[<here>]]`
     if !is_within_subset_delimiters(&context.point, &node) {
-        return Ok(None);
+        return None;
     }
 
-    let Some(child) = node.child(0) else {
-        // There is almost definitely a child here. If there isn't,
-        // we "tried" to do subset completions but found nothing.
-        return Ok(Some(vec![]));
-    };
-
-    let text = context.document.contents.node_slice(&child)?.to_string();
-
-    completions_from_evaluated_object_names(&text, ENQUOTE)
+    Some(node)
 }
 
 #[cfg(test)]
@@ -444,6 +444,45 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_completion_custom_match_arg() {
+        r_task(|| {
+            harp::parse_eval_global(
+                "ark_test_match_arg <- function(type = c('linear', 'quadratic'), method) {
+                    type <- match.arg(type)
+                    method <- match.arg(method, c('fast', 'exact'))
+                }",
+            )
+            .unwrap();
+
+            let labels = |text: &str| {
+                let (text, point) = point_from_cursor(text);
+                let document = Document::new(text.as_str(), None);
+                let context = DocumentContext::new(&document, point, None);
+                completions_from_custom_source(&context)
+                    .unwrap()
+                    .unwrap()
+                    .into_iter()
+                    .map(|item| (item.label, item.insert_text.unwrap()))
+                    .collect::<Vec<_>>()
+            };
+
+            // Choices from the default of the formal
+            assert_eq!(labels("ark_test_match_arg(type = @)"), vec![
+                (String::from("linear"), String::from("\"linear\"")),
+                (String::from("quadratic"), String::from("\"quadratic\"")),
+            ]);
+
+            // Explicit choices of the `match.arg()` call
+            assert_eq!(labels("ark_test_match_arg(method = @)"), vec![
+                (String::from("fast"), String::from("\"fast\"")),
+                (String::from("exact"), String::from("\"exact\"")),
+            ]);
+
+            harp::parse_eval_global("rm(ark_test_match_arg)").unwrap();
+        })
+    }
+
     #[test]
     fn test_completion_custom_options() {
         r_task(|| {
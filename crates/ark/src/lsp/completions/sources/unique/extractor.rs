@@ -18,6 +18,7 @@ use tower_lsp::lsp_types::CompletionItem;
 use tree_sitter::Node;
 
 use crate::lsp::completions::completion_item::completion_item_from_data_variable;
+use crate::lsp::completions::sources::utils::is_active_binding_access;
 use crate::lsp::completions::sources::utils::set_sort_text_by_first_appearance;
 use crate::lsp::document_context::DocumentContext;
 use crate::lsp::traits::rope::RopeExt;
@@ -109,6 +110,10 @@ fn completions_from_extractor_object(text: &str, fun: &str) -> Result<Vec<Comple
 
     let mut completions = vec![];
 
+    if is_active_binding_access(text) {
+        return Ok(completions);
+    }
+
     unsafe {
         let env_utils = RFunction::new("base", "asNamespace").add("utils").call()?;
         let sym = r_symbol!(fun);
@@ -240,6 +245,37 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_dollar_completions_on_active_binding() {
+        r_task(|| {
+            let options = RParseEvalOptions {
+                forbid_function_calls: false,
+                ..Default::default()
+            };
+
+            harp::parse_eval("calls <- 0", options.clone()).unwrap();
+            harp::parse_eval(
+                "makeActiveBinding('foo', function() { calls <<- calls + 1; list(a = 1) }, globalenv())",
+                options.clone(),
+            )
+            .unwrap();
+
+            let (text, point) = point_from_cursor("foo$@");
+            let document = Document::new(text.as_str(), None);
+            let context = DocumentContext::new(&document, point, None);
+
+            // The binding isn't evaluated, but the `$` completions are unique
+            let completions = completions_from_dollar(&context).unwrap().unwrap();
+            assert_eq!(completions.len(), 0);
+
+            let calls = harp::parse_eval("calls", options.clone()).unwrap();
+            assert_eq!(f64::try_from(calls).unwrap(), 0.0);
+
+            // Clean up
+            harp::parse_eval("remove(foo, calls)", options.clone()).unwrap();
+        })
+    }
+
     #[test]
     fn test_dollar_completions_on_complex_lhs() {
         r_task(|| {
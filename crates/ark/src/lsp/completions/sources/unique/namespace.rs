@@ -16,11 +16,13 @@ use libr::Rboolean_TRUE;
 use libr::Rf_findVarInFrame;
 use libr::SEXP;
 use tower_lsp::lsp_types::CompletionItem;
+use tower_lsp::lsp_types::CompletionItemLabelDetails;
 use tree_sitter::Node;
 use tree_sitter::Point;
 
 use crate::lsp::completions::completion_item::completion_item_from_lazydata;
 use crate::lsp::completions::completion_item::completion_item_from_namespace;
+use crate::lsp::completions::package_index::package_index;
 use crate::lsp::completions::sources::utils::set_sort_text_by_words_first;
 use crate::lsp::document_context::DocumentContext;
 use crate::lsp::traits::rope::RopeExt;
@@ -71,20 +73,33 @@ pub fn completions_from_namespace(
         return Ok(None);
     };
 
-    let symbols = if package == "base" {
-        list_namespace_symbols(*namespace)
-    } else if exports_only {
-        list_namespace_exports(*namespace)
-    } else {
-        list_namespace_symbols(*namespace)
+    // Exports are listed from the cached index of the package. A package
+    // that can't be indexed falls back to the exports of its namespace.
+    let index = package_index(package).unwrap_or_else(|err| {
+        log::error!("Can't index package '{package}': {err:?}");
+        None
+    });
+
+    let strings = match &index {
+        Some(index) if exports_only && package != "base" => index.exports.clone(),
+        None if exports_only && package != "base" => unsafe {
+            list_namespace_exports(*namespace).to::<Vec<String>>()?
+        },
+        _ => unsafe { list_namespace_symbols(*namespace).to::<Vec<String>>()? },
     };
 
-    let strings = unsafe { symbols.to::<Vec<String>>()? };
-
     for string in strings.iter() {
         let item = unsafe { completion_item_from_namespace(string, *namespace, package) };
         match item {
-            Ok(item) => completions.push(item),
+            Ok(mut item) => {
+                if let Some(title) = index.as_ref().and_then(|index| index.titles.get(string)) {
+                    item.label_details = Some(CompletionItemLabelDetails {
+                        detail: None,
+                        description: Some(title.clone()),
+                    });
+                }
+                completions.push(item)
+            },
             Err(error) => log::error!("{:?}", error),
         }
     }
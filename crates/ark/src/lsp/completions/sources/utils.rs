@@ -6,11 +6,14 @@
 //
 
 use anyhow::Result;
+use harp::environment::R_ENVS;
 use harp::error::Error;
 use harp::eval::RParseEvalOptions;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::object::RObject;
+use harp::r_symbol;
+use harp::utils::r_env_binding_is_active;
 use regex::Regex;
 use tower_lsp::lsp_types::CompletionItem;
 use tree_sitter::Node;
@@ -173,6 +176,10 @@ pub(super) fn completions_from_evaluated_object_names(
 ) -> Result<Option<Vec<CompletionItem>>> {
     log::info!("completions_from_evaluated_object_names({name:?})");
 
+    if is_active_binding_access(name) {
+        return Ok(None);
+    }
+
     let options = RParseEvalOptions {
         forbid_function_calls: true,
         ..Default::default()
@@ -211,6 +218,28 @@ pub(super) fn completions_from_evaluated_object_names(
     Ok(Some(completions))
 }
 
+/// Whether evaluating `text`, e.g. `x` or `x$y`, would call the function of
+/// an active binding `x` of the global environment. Completions don't
+/// evaluate these objects since the function may have side effects or be
+/// slow, like a binding that reads a file or queries a database.
+pub(super) fn is_active_binding_access(text: &str) -> bool {
+    let root = text
+        .split(|c: char| matches!(c, '$' | '@' | '[') || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .trim_matches('`');
+
+    if root.is_empty() {
+        return false;
+    }
+
+    let symbol = unsafe { r_symbol!(root) };
+
+    // Objects that don't exist in the global environment are not active
+    // bindings there
+    matches!(r_env_binding_is_active(R_ENVS.global, symbol), Ok(true))
+}
+
 pub(super) fn completions_from_object_names(
     object: RObject,
     name: &str,
@@ -490,4 +519,28 @@ mod tests {
             parse_eval_global("remove(x)").unwrap();
         })
     }
+
+    #[test]
+    fn test_completions_from_evaluated_object_names_active_binding() {
+        r_task(|| {
+            parse_eval_global("calls <- 0").unwrap();
+            parse_eval_global(
+                "makeActiveBinding('x', function() { calls <<- calls + 1; data.frame(a = 1) }, globalenv())",
+            )
+            .unwrap();
+
+            // Evaluating the binding could have side effects
+            assert!(completions_from_evaluated_object_names("x", false)
+                .unwrap()
+                .is_none());
+            assert!(completions_from_evaluated_object_names("x$a", false)
+                .unwrap()
+                .is_none());
+
+            let calls = parse_eval_global("calls").unwrap();
+            assert_eq!(f64::try_from(calls).unwrap(), 0.0);
+
+            parse_eval_global("remove(x, calls)").unwrap();
+        })
+    }
 }
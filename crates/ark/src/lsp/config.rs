@@ -1,14 +1,20 @@
+use std::collections::HashMap;
+
 use serde::Deserialize;
 use serde::Serialize;
 use struct_field_names_as_array::FieldNamesAsArray;
 
 use crate::lsp;
+use crate::lsp::completions::CompletionsConfig;
 use crate::lsp::diagnostics::DiagnosticsConfig;
+use crate::lsp::diagnostics_lintr::parse_lint_severity;
+use crate::lsp::diagnostics_lintr::LintrConfig;
 
 /// Configuration of the LSP
 #[derive(Clone, Debug)]
 pub(crate) struct LspConfig {
     pub(crate) diagnostics: DiagnosticsConfig,
+    pub(crate) completions: CompletionsConfig,
 }
 
 /// Configuration of a document.
@@ -51,6 +57,16 @@ pub(crate) struct VscDocumentConfig {
 pub(crate) struct VscDiagnosticsConfig {
     // DEV NOTE: Update `section_from_key()` method after adding a field
     pub enable: bool,
+    // Frontends that don't declare the settings send `null`
+    pub lintr_enable: Option<bool>,
+    pub lintr_severities: Option<HashMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize, FieldNamesAsArray, Clone, Debug)]
+pub(crate) struct VscCompletionsConfig {
+    // DEV NOTE: Update `section_from_key()` method after adding a field
+    // Frontends that don't declare the setting send `null`
+    pub auto_import: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -64,6 +80,7 @@ impl Default for LspConfig {
     fn default() -> Self {
         Self {
             diagnostics: Default::default(),
+            completions: Default::default(),
         }
     }
 }
@@ -121,6 +138,8 @@ impl VscDiagnosticsConfig {
     pub(crate) fn section_from_key(key: &str) -> &str {
         match key {
             "enable" => "positron.r.diagnostics.enable",
+            "lintr_enable" => "positron.r.diagnostics.lintr.enable",
+            "lintr_severities" => "positron.r.diagnostics.lintr.severities",
             _ => "unknown", // To be caught via downstream errors
         }
     }
@@ -128,8 +147,43 @@ impl VscDiagnosticsConfig {
 
 impl From<VscDiagnosticsConfig> for DiagnosticsConfig {
     fn from(value: VscDiagnosticsConfig) -> Self {
+        // Severities are configured by name, e.g. `"object_name_linter": "hint"`
+        let severities = value
+            .lintr_severities
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(linter, severity)| match parse_lint_severity(&severity) {
+                Some(severity) => Some((linter, severity)),
+                None => {
+                    lsp::log_warn!("Unknown severity '{severity}' for lints of '{linter}'");
+                    None
+                },
+            })
+            .collect();
+
         Self {
             enable: value.enable,
+            lintr: LintrConfig {
+                enable: value.lintr_enable.unwrap_or(false),
+                severities,
+            },
+        }
+    }
+}
+
+impl VscCompletionsConfig {
+    pub(crate) fn section_from_key(key: &str) -> &str {
+        match key {
+            "auto_import" => "positron.r.completions.autoImport",
+            _ => "unknown", // To be caught via downstream errors
+        }
+    }
+}
+
+impl From<VscCompletionsConfig> for CompletionsConfig {
+    fn from(value: VscCompletionsConfig) -> Self {
+        Self {
+            auto_import: value.auto_import.unwrap_or(false),
         }
     }
 }
@@ -5,23 +5,47 @@
 //
 //
 
+use std::path::Path;
+
 use anyhow::Result;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
 use tower_lsp::lsp_types::GotoDefinitionParams;
 use tower_lsp::lsp_types::GotoDefinitionResponse;
 use tower_lsp::lsp_types::LocationLink;
+use tower_lsp::lsp_types::Position;
 use tower_lsp::lsp_types::Range;
 use tower_lsp::lsp_types::Url;
+use tree_sitter::Node;
 
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::convert_point_to_position;
 use crate::lsp::encoding::convert_position_to_point;
 use crate::lsp::indexer;
+use crate::lsp::indexer::IndexEntryData;
+use crate::lsp::state::WorldState;
 use crate::lsp::traits::node::NodeExt;
 use crate::lsp::traits::rope::RopeExt;
+use crate::r_task;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
+/// An assignment or a parameter defining a symbol in a document
+struct LocalDefinition<'tree> {
+    /// The whole assignment, or the parameter
+    node: Node<'tree>,
+
+    /// The name being defined
+    name: Node<'tree>,
+
+    /// The assigned value
+    value: Option<Node<'tree>>,
+}
+
 pub unsafe fn goto_definition<'a>(
     document: &'a Document,
+    state: &WorldState,
     params: GotoDefinitionParams,
 ) -> Result<Option<GotoDefinitionResponse>> {
     // get reference to AST
@@ -42,32 +66,21 @@ pub unsafe fn goto_definition<'a>(
     let end = convert_point_to_position(contents, node.end_position());
     let range = Range { start, end };
 
-    // search for a reference in the document index
+    let uri = params.text_document_position_params.text_document.uri;
+
     if node.is_identifier() {
         let symbol = document.contents.node_slice(&node)?.to_string();
-        if let Some((path, entry)) = indexer::find(symbol.as_str()) {
-            let link = LocationLink {
-                origin_selection_range: None,
-                target_uri: Url::from_file_path(path).unwrap(),
-                target_range: entry.range,
-                target_selection_range: entry.range,
-            };
-            let response = GotoDefinitionResponse::Link(vec![link]);
-            return Ok(Some(response));
+        let links = definition_links(document, state, &uri, node, &symbol)?;
+        if !links.is_empty() {
+            return Ok(Some(GotoDefinitionResponse::Link(links)));
         }
     }
 
-    // TODO: We should see if we can find the referenced item in:
-    //
-    // 1. The document's current AST,
-    // 2. The public functions from other documents in the project,
-    // 3. A definition in the R session (which we could open in a virtual document)
-    //
-    // If we can't find a definition, then we can return the referenced item itself,
+    // If we can't find a definition, then we return the referenced item itself,
     // which will tell Positron to instead try to look for references for that symbol.
     let link = LocationLink {
         origin_selection_range: Some(range),
-        target_uri: params.text_document_position_params.text_document.uri,
+        target_uri: uri,
         target_range: range,
         target_selection_range: range,
     };
@@ -75,3 +88,491 @@ pub unsafe fn goto_definition<'a>(
     let response = GotoDefinitionResponse::Link(vec![link]);
     Ok(Some(response))
 }
+
+/// All plausible definitions of `symbol`, closest scope first:
+///
+/// 1. Assignments and parameters of the functions enclosing `node`,
+///    innermost first, then top-level assignments of the document.
+/// 2. Top-level assignments of the other open documents, including unsaved
+///    ones, then functions of the other files of the workspace.
+/// 3. Functions on the search path that have source references, such as
+///    package exports. R is only consulted as a last resort, when the
+///    document and the workspace don't define the symbol.
+///
+/// When jumping from an S3 generic, the methods defined in the document and
+/// in the workspace come last, e.g. `print.foo` and `print.bar` for `print`.
+fn definition_links(
+    document: &Document,
+    state: &WorldState,
+    uri: &Url,
+    node: Node,
+    symbol: &str,
+) -> Result<Vec<LocationLink>> {
+    let contents = &document.contents;
+    let mut links: Vec<LocationLink> = vec![];
+
+    let push = |links: &mut Vec<LocationLink>, link: LocationLink| {
+        let duplicate = links.iter().any(|other| {
+            other.target_uri == link.target_uri &&
+                other.target_selection_range == link.target_selection_range
+        });
+        if !duplicate {
+            links.push(link);
+        }
+    };
+
+    let local_link = |definition: &LocalDefinition| definition_link(document, uri, definition);
+
+    let locals = local_definitions(document, node, symbol);
+    for definition in locals.iter() {
+        push(&mut links, local_link(definition));
+    }
+
+    // The open documents are searched as they are in the editor. The index
+    // of their files may be out of date, so it's only used for the others.
+    let mut open: Vec<(&Url, &Document)> = state
+        .documents
+        .iter()
+        .filter(|(other, _)| *other != uri)
+        .collect();
+    open.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+
+    for (other_uri, other) in open.iter() {
+        for definition in top_level_definitions(other, |name| name == symbol) {
+            push(&mut links, definition_link(other, other_uri, &definition));
+        }
+    }
+
+    let open_paths: Vec<_> = open
+        .iter()
+        .filter_map(|(other_uri, _)| other_uri.to_file_path().ok())
+        .collect();
+
+    let path = uri.to_file_path().ok();
+    let is_other_file = |other: &Path| path.as_deref() != Some(other);
+
+    for (other, entry) in indexer::find_all(symbol) {
+        let other_path = Path::new(&other);
+        if !is_other_file(other_path) || open_paths.iter().any(|open| open == other_path) {
+            continue;
+        }
+        let Ok(target_uri) = Url::from_file_path(&other) else {
+            continue;
+        };
+        push(&mut links, LocationLink {
+            origin_selection_range: None,
+            target_uri,
+            target_range: entry.range,
+            target_selection_range: entry.range,
+        });
+    }
+
+    let search_path = if links.is_empty() {
+        r_task(|| r_search_path_definitions(symbol))?
+    } else {
+        SearchPathDefinitions::default()
+    };
+    for link in search_path.links {
+        push(&mut links, link);
+    }
+
+    // A generic defined in the document dispatches with `UseMethod()`
+    let local_generic = locals.iter().any(|definition| {
+        definition.value.is_some_and(|value| {
+            value.is_function_definition() &&
+                contents
+                    .node_slice(&value)
+                    .is_ok_and(|text| text.to_string().contains("UseMethod("))
+        })
+    });
+
+    if local_generic || search_path.generic {
+        let prefix = format!("{symbol}.");
+
+        for definition in top_level_definitions(document, |name| name.starts_with(&prefix)) {
+            if definition
+                .value
+                .is_some_and(|value| value.is_function_definition())
+            {
+                push(&mut links, local_link(&definition));
+            }
+        }
+
+        let mut methods = vec![];
+        indexer::map(|other, name, entry| {
+            if name.starts_with(&prefix) &&
+                matches!(entry.data, IndexEntryData::Function { .. }) &&
+                is_other_file(other)
+            {
+                methods.push((other.to_path_buf(), entry.range));
+            }
+        });
+        methods.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (other, range) in methods {
+            let Ok(target_uri) = Url::from_file_path(&other) else {
+                continue;
+            };
+            push(&mut links, LocationLink {
+                origin_selection_range: None,
+                target_uri,
+                target_range: range,
+                target_selection_range: range,
+            });
+        }
+    }
+
+    Ok(links)
+}
+
+fn definition_link(document: &Document, uri: &Url, definition: &LocalDefinition) -> LocationLink {
+    LocationLink {
+        origin_selection_range: None,
+        target_uri: uri.clone(),
+        target_range: node_range(document, &definition.node),
+        target_selection_range: node_range(document, &definition.name),
+    }
+}
+
+/// The definitions of `symbol` visible from `node`, innermost scope first
+fn local_definitions<'tree>(
+    document: &Document,
+    node: Node<'tree>,
+    symbol: &str,
+) -> Vec<LocalDefinition<'tree>> {
+    let mut definitions = vec![];
+    let matches = |name: &str| name == symbol;
+
+    let mut scope = Some(node);
+    while let Some(node) = scope {
+        if node.is_function_definition() {
+            definitions.append(&mut parameter_definitions(document, node, matches));
+            if let Some(body) = node.child_by_field_name("body") {
+                definitions.append(&mut scope_definitions(document, body, matches));
+            }
+        } else if node.is_program() {
+            definitions.append(&mut scope_definitions(document, node, matches));
+        }
+        scope = node.parent();
+    }
+
+    definitions
+}
+
+/// The top-level assignments of the document whose names match
+fn top_level_definitions<'tree>(
+    document: &'tree Document,
+    matches: impl Fn(&str) -> bool,
+) -> Vec<LocalDefinition<'tree>> {
+    scope_definitions(document, document.ast.root_node(), matches)
+}
+
+/// The assignments of a scope, excluding those of nested functions, in
+/// document order
+fn scope_definitions<'tree>(
+    document: &Document,
+    scope: Node<'tree>,
+    matches: impl Fn(&str) -> bool,
+) -> Vec<LocalDefinition<'tree>> {
+    let mut definitions = vec![];
+    let mut stack = vec![scope];
+
+    while let Some(node) = stack.pop() {
+        let (name, value) = match node.node_type() {
+            NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment) |
+            NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+            NodeType::BinaryOperator(BinaryOperatorType::LeftSuperAssignment) => (
+                node.child_by_field_name("lhs"),
+                node.child_by_field_name("rhs"),
+            ),
+            NodeType::BinaryOperator(BinaryOperatorType::RightAssignment) |
+            NodeType::BinaryOperator(BinaryOperatorType::RightSuperAssignment) => (
+                node.child_by_field_name("rhs"),
+                node.child_by_field_name("lhs"),
+            ),
+
+            // Functions create a new scope
+            NodeType::FunctionDefinition if node != scope => continue,
+
+            _ => (None, None),
+        };
+
+        if let Some(name) = name {
+            if name.is_identifier_or_string() && matches(&symbol_text(document, &name)) {
+                definitions.push(LocalDefinition { node, name, value });
+            }
+        }
+
+        // Keep going in case of nested assignments. Children are pushed in
+        // reverse so they are visited in document order.
+        let mut cursor = node.walk();
+        let children: Vec<Node<'tree>> = node.children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+
+    definitions
+}
+
+fn parameter_definitions<'tree>(
+    document: &Document,
+    function: Node<'tree>,
+    matches: impl Fn(&str) -> bool,
+) -> Vec<LocalDefinition<'tree>> {
+    let mut definitions = vec![];
+
+    let Some(parameters) = function.child_by_field_name("parameters") else {
+        return definitions;
+    };
+
+    let mut cursor = parameters.walk();
+    for node in parameters.children(&mut cursor) {
+        if node.node_type() != NodeType::Parameter {
+            continue;
+        }
+        let Some(name) = node.child_by_field_name("name") else {
+            continue;
+        };
+        if name.is_identifier() && matches(&symbol_text(document, &name)) {
+            definitions.push(LocalDefinition {
+                node,
+                name,
+                value: node.child_by_field_name("default"),
+            });
+        }
+    }
+
+    definitions
+}
+
+/// The text of an identifier or string, without quotes or backticks
+pub(crate) fn symbol_text(document: &Document, node: &Node) -> String {
+    let text = document
+        .contents
+        .node_slice(node)
+        .map(|text| text.to_string())
+        .unwrap_or_default();
+
+    let quotes: &[char] = &['"', '\'', '`'];
+    text.trim_matches(quotes).to_string()
+}
+
+pub(crate) fn node_range(document: &Document, node: &Node) -> Range {
+    Range {
+        start: convert_point_to_position(&document.contents, node.start_position()),
+        end: convert_point_to_position(&document.contents, node.end_position()),
+    }
+}
+
+#[derive(Default)]
+struct SearchPathDefinitions {
+    links: Vec<LocationLink>,
+    generic: bool,
+}
+
+fn r_search_path_definitions(symbol: &str) -> Result<SearchPathDefinitions> {
+    let definitions = RFunction::from(".ps.lsp.searchPathDefinitions")
+        .add(symbol)
+        .call()?;
+
+    let file = Vec::<String>::try_from(&definitions.vector_elt(0)?)?;
+    let line = Vec::<i32>::try_from(&definitions.vector_elt(1)?)?;
+    let column = Vec::<i32>::try_from(&definitions.vector_elt(2)?)?;
+    let end_line = Vec::<i32>::try_from(&definitions.vector_elt(3)?)?;
+    let end_column = Vec::<i32>::try_from(&definitions.vector_elt(4)?)?;
+    let generic = bool::try_from(definitions.vector_elt(5)?)?;
+
+    let mut links = vec![];
+
+    for i in 0..file.len() {
+        // Virtual documents of namespaces have `ark:` URIs
+        let target_uri = if file[i].starts_with("ark:") {
+            Url::parse(&file[i]).ok()
+        } else {
+            Url::from_file_path(&file[i]).ok()
+        };
+        let Some(target_uri) = target_uri else {
+            continue;
+        };
+
+        // Source references count from 1 and the end column is inclusive
+        let range = Range {
+            start: Position::new((line[i] - 1) as u32, (column[i] - 1) as u32),
+            end: Position::new((end_line[i] - 1) as u32, end_column[i] as u32),
+        };
+
+        links.push(LocationLink {
+            origin_selection_range: None,
+            target_uri,
+            target_range: range,
+            target_selection_range: range,
+        });
+    }
+
+    Ok(SearchPathDefinitions { links, generic })
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::GotoDefinitionParams;
+    use tower_lsp::lsp_types::GotoDefinitionResponse;
+    use tower_lsp::lsp_types::LocationLink;
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Range;
+    use tower_lsp::lsp_types::TextDocumentIdentifier;
+    use tower_lsp::lsp_types::TextDocumentPositionParams;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::definitions::goto_definition;
+    use crate::lsp::documents::Document;
+    use crate::lsp::state::WorldState;
+    use crate::r_task;
+
+    fn definitions(text: &str, position: Position) -> (Url, Vec<LocationLink>) {
+        definitions_with_state(text, position, &WorldState::default())
+    }
+
+    fn definitions_with_state(
+        text: &str,
+        position: Position,
+        state: &WorldState,
+    ) -> (Url, Vec<LocationLink>) {
+        let uri = Url::parse("file:///test_definitions.R").unwrap();
+        let document = Document::new(text, None);
+
+        let params = GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+
+        let response = unsafe { goto_definition(&document, state, params) }.unwrap();
+        match response {
+            Some(GotoDefinitionResponse::Link(links)) => (uri, links),
+            response => panic!("Unexpected response: {response:?}"),
+        }
+    }
+
+    #[test]
+    fn test_definitions_local_and_package() {
+        // Simulate an attached package whose function has source references
+        r_task(|| {
+            harp::parse_eval_global(
+                r#"local({
+                    code <- "ark_test_defn <- function() 1\n"
+                    srcfile <- srcfilecopy("/tmp/arktestpkg/R/defn.R", code)
+                    env <- new.env()
+                    eval(parse(text = code, srcfile = srcfile, keep.source = TRUE), env)
+                    attach(env, name = "package:arktestpkg")
+                })"#,
+            )
+            .unwrap();
+        });
+
+        let text =
+            "ark_test_defn <- function() 2\nf <- function(ark_test_defn) {\n  ark_test_defn\n}\n";
+        let (uri, links) = definitions(text, Position::new(2, 2));
+
+        let targets: Vec<_> = links
+            .iter()
+            .map(|link| {
+                (
+                    link.target_uri.to_string(),
+                    link.target_selection_range.start,
+                )
+            })
+            .collect();
+
+        // The parameter comes first, then the top-level assignment. The
+        // package export is shadowed.
+        assert_eq!(targets, vec![
+            (uri.to_string(), Position::new(1, 14)),
+            (uri.to_string(), Position::new(0, 0)),
+        ]);
+
+        // The package is only searched when the symbol isn't defined locally
+        let (_, links) = definitions("ark_test_defn()\n", Position::new(0, 0));
+        let targets: Vec<_> = links
+            .iter()
+            .map(|link| {
+                (
+                    link.target_uri.to_string(),
+                    link.target_selection_range.start,
+                )
+            })
+            .collect();
+        assert_eq!(targets, vec![(
+            Url::from_file_path("/tmp/arktestpkg/R/defn.R")
+                .unwrap()
+                .to_string(),
+            Position::new(0, 17)
+        )]);
+
+        r_task(|| {
+            harp::parse_eval_global("detach('package:arktestpkg')").unwrap();
+        });
+    }
+
+    #[test]
+    fn test_definitions_generic_methods() {
+        let text = "print.foo <- function(x, ...) 1\nprint.bar <- function(x, ...) 2\nprint(x)\n";
+        let (uri, links) = definitions(text, Position::new(2, 0));
+
+        // The methods defined in the document are offered after `print()`
+        // itself, which may have sources in a virtual namespace document
+        let methods: Vec<_> = links
+            .iter()
+            .filter(|link| link.target_uri == uri)
+            .map(|link| link.target_selection_range.start)
+            .collect();
+        assert_eq!(methods, vec![Position::new(0, 0), Position::new(1, 0)]);
+    }
+
+    #[test]
+    fn test_definitions_open_documents() {
+        let mut state = WorldState::default();
+
+        // An unsaved document, which isn't in the workspace index
+        let untitled = Url::parse("untitled:Untitled-1").unwrap();
+        state.documents.insert(
+            untitled.clone(),
+            Document::new(
+                "x <- 1
+ark_test_helper <- function(x) x
+",
+                None,
+            ),
+        );
+
+        // Assignments nested in functions aren't definitions for other files
+        let other = Url::parse("file:///test_definitions_other.R").unwrap();
+        state.documents.insert(
+            other.clone(),
+            Document::new(
+                "f <- function() {
+  ark_test_helper <- 1
+}
+",
+                None,
+            ),
+        );
+
+        let (_, links) = definitions_with_state(
+            "ark_test_helper(1)
+",
+            Position::new(0, 0),
+            &state,
+        );
+
+        let targets: Vec<_> = links
+            .iter()
+            .map(|link| (link.target_uri.clone(), link.target_selection_range))
+            .collect();
+        assert_eq!(targets, vec![(
+            untitled,
+            Range::new(Position::new(1, 0), Position::new(1, 15))
+        )]);
+    }
+}
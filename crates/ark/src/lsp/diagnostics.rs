@@ -20,6 +20,9 @@ use tree_sitter::Node;
 use tree_sitter::Range;
 
 use crate::lsp::declarations::top_level_declare;
+use crate::lsp::diagnostics_lintr::LintrConfig;
+use crate::lsp::diagnostics_syntax::merge_parse_error_diagnostic;
+use crate::lsp::diagnostics_syntax::parse_error_diagnostic;
 use crate::lsp::diagnostics_syntax::syntax_diagnostics;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
@@ -35,6 +38,7 @@ use crate::treesitter::UnaryOperatorType;
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DiagnosticsConfig {
     pub enable: bool,
+    pub lintr: LintrConfig,
 }
 
 #[derive(Clone)]
@@ -64,7 +68,10 @@ pub struct DiagnosticContext<'a> {
 
 impl Default for DiagnosticsConfig {
     fn default() -> Self {
-        Self { enable: true }
+        Self {
+            enable: true,
+            lintr: Default::default(),
+        }
     }
 }
 
@@ -155,6 +162,13 @@ pub(crate) fn generate_diagnostics(doc: Document, state: WorldState) -> Vec<Diag
         Err(err) => log::error!("Error while generating syntax diagnostics: {err:?}"),
     }
 
+    // Pinpoint the first syntax error with R's parser
+    match parse_error_diagnostic(root, &context) {
+        Ok(Some(parse_error)) => merge_parse_error_diagnostic(&mut diagnostics, parse_error),
+        Ok(None) => {},
+        Err(err) => log::error!("Error while generating parse error diagnostic: {err:?}"),
+    }
+
     // Collect semantic related diagnostics
     match semantic_diagnostics(root, &mut context) {
         Ok(mut semantic_diagnostics) => diagnostics.append(&mut semantic_diagnostics),
@@ -1026,6 +1040,7 @@ fn check_symbol_in_scope(
 
 #[cfg(test)]
 mod tests {
+    use harp::environment::R_ENVS;
     use harp::eval::RParseEvalOptions;
     use once_cell::sync::Lazy;
     use tower_lsp::lsp_types::Position;
@@ -1040,7 +1055,7 @@ mod tests {
     static DEFAULT_STATE: Lazy<WorldState> = Lazy::new(|| current_state());
 
     fn current_state() -> WorldState {
-        let inputs = console_inputs().unwrap();
+        let inputs = console_inputs(R_ENVS.global).unwrap();
 
         WorldState {
             console_scopes: inputs.console_scopes,
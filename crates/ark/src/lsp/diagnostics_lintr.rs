@@ -0,0 +1,569 @@
+//
+// diagnostics_lintr.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::anyhow;
+use ropey::Rope;
+use rust_embed::RustEmbed;
+use tower_lsp::lsp_types::Diagnostic;
+use tower_lsp::lsp_types::DiagnosticSeverity;
+use tower_lsp::lsp_types::NumberOrString;
+use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::Url;
+use tree_sitter::Point;
+
+use crate::lsp;
+use crate::lsp::declarations::top_level_declare;
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::state::WorldState;
+
+#[derive(RustEmbed)]
+#[folder = "resources/lintr/"]
+struct Asset;
+
+/// How long a document must be left unchanged before it's linted
+const LINTR_DEBOUNCE: Duration = Duration::from_millis(750);
+
+/// How long lintr may take before its process is killed
+const LINTR_TIMEOUT: Duration = Duration::from_secs(30);
+
+const LINTER_FILE: &str = ".lintr";
+
+/// The lints of the open documents
+static LINTR_STATE: LazyLock<Mutex<HashMap<Url, DocumentLints>>> =
+    LazyLock::new(|| Default::default());
+
+#[derive(Default)]
+struct DocumentLints {
+    /// Incremented each time lintr is scheduled for the document, so that
+    /// runs superseded by a later change are skipped
+    generation: u64,
+
+    /// The version of the document that was last linted, and its lints
+    linted: Option<(Option<i32>, Vec<Lint>)>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LintrConfig {
+    /// Whether to lint documents with lintr. Off by default since lintr is
+    /// slow and may not be installed.
+    pub enable: bool,
+
+    /// Severities by linter name, e.g. `object_name_linter`, or by lint type,
+    /// i.e. `style`, `warning`, or `error`. Linter names take precedence.
+    pub severities: HashMap<String, DiagnosticSeverity>,
+}
+
+/// A lint reported by lintr
+#[derive(Clone, Debug)]
+pub(crate) struct Lint {
+    /// The 1-based line of the lint
+    pub(crate) line: usize,
+
+    /// The 1-based and inclusive range of characters of the lint
+    pub(crate) column: usize,
+    pub(crate) end_column: usize,
+
+    /// The lint type: `style`, `warning`, or `error` for the linters of
+    /// lintr. Custom linters may use other types.
+    pub(crate) kind: String,
+
+    pub(crate) message: String,
+    pub(crate) linter: String,
+}
+
+/// Parses the name of a severity as used in the configuration
+pub(crate) fn parse_lint_severity(name: &str) -> Option<DiagnosticSeverity> {
+    match name {
+        "error" => Some(DiagnosticSeverity::ERROR),
+        "warning" => Some(DiagnosticSeverity::WARNING),
+        "information" | "info" => Some(DiagnosticSeverity::INFORMATION),
+        "hint" => Some(DiagnosticSeverity::HINT),
+        _ => None,
+    }
+}
+
+/// The configured severity of the lint's linter, or else of the lint's type.
+/// Without configuration, style lints are informational and errors are
+/// errors. Everything else is a warning, including lints of unknown types.
+pub(crate) fn lint_severity(lint: &Lint, config: &LintrConfig) -> DiagnosticSeverity {
+    let configured = config
+        .severities
+        .get(&lint.linter)
+        .or_else(|| config.severities.get(&lint.kind));
+
+    if let Some(severity) = configured {
+        return *severity;
+    }
+
+    match lint.kind.as_str() {
+        "style" => DiagnosticSeverity::INFORMATION,
+        "error" => DiagnosticSeverity::ERROR,
+        _ => DiagnosticSeverity::WARNING,
+    }
+}
+
+/// The lints of `document`. lintr is slow, so documents are linted in a
+/// separate R process once they haven't changed for `LINTR_DEBOUNCE`. Until
+/// then, the lints of the previous version are returned, and the diagnostics
+/// of the document are refreshed once the new lints are in.
+pub(crate) fn lintr_diagnostics(
+    uri: &Url,
+    document: &Document,
+    state: &WorldState,
+) -> Vec<Diagnostic> {
+    let config = &state.config.diagnostics.lintr;
+    if !state.config.diagnostics.enable || !config.enable {
+        return Vec::new();
+    }
+    if !top_level_declare(&document.ast, &document.contents).diagnostics {
+        return Vec::new();
+    }
+
+    let mut documents = LINTR_STATE.lock().unwrap();
+    let lints = documents.entry(uri.clone()).or_default();
+
+    if !matches!(&lints.linted, Some((version, _)) if *version == document.version) {
+        lints.generation += 1;
+        spawn_lintr(
+            uri.clone(),
+            document.clone(),
+            state.clone(),
+            lints.generation,
+        );
+    }
+
+    let Some((_, lints)) = &lints.linted else {
+        return Vec::new();
+    };
+
+    lints
+        .iter()
+        .filter_map(|lint| lint_diagnostic(lint, config, &document.contents))
+        .collect()
+}
+
+/// Forget the lints of a closed document
+pub(crate) fn remove_lintr_diagnostics(uri: &Url) {
+    LINTR_STATE.lock().unwrap().remove(uri);
+}
+
+fn spawn_lintr(uri: Url, document: Document, state: WorldState, generation: u64) {
+    let is_current = move |uri: &Url| {
+        let documents = LINTR_STATE.lock().unwrap();
+        documents
+            .get(uri)
+            .is_some_and(|lints| lints.generation == generation)
+    };
+
+    lsp::spawn_blocking(move || {
+        std::thread::sleep(LINTR_DEBOUNCE);
+
+        // The document changed again in the meantime
+        if !is_current(&uri) {
+            return Ok(None);
+        }
+
+        let (directory, linter_file) = lintr_paths(&uri, &state.workspace.folders);
+        let text = document.contents.to_string();
+        let Some(lints) = run_lintr(&text, directory.as_deref(), linter_file.as_deref())? else {
+            // lintr isn't installed
+            return Ok(None);
+        };
+
+        {
+            let mut documents = LINTR_STATE.lock().unwrap();
+            let Some(entry) = documents.get_mut(&uri) else {
+                // The document was closed
+                return Ok(None);
+            };
+            if entry.generation != generation {
+                return Ok(None);
+            }
+            entry.linted = Some((document.version, lints));
+        }
+
+        lsp::spawn_diagnostics_refresh(uri, document, state);
+        Ok(None)
+    });
+}
+
+/// The folder lintr runs from, i.e. the workspace folder of the document or
+/// else its directory, and the `.lintr` file that applies to the document:
+/// the closest one between the directory of the document and its workspace
+/// folder
+fn lintr_paths(uri: &Url, folders: &[Url]) -> (Option<PathBuf>, Option<PathBuf>) {
+    let folders: Vec<PathBuf> = folders
+        .iter()
+        .filter_map(|folder| folder.to_file_path().ok())
+        .collect();
+
+    // Untitled documents use the settings of the first workspace folder
+    let Ok(path) = uri.to_file_path() else {
+        let Some(folder) = folders.into_iter().next() else {
+            return (None, None);
+        };
+        let linter_file = Some(folder.join(LINTER_FILE)).filter(|file| file.is_file());
+        return (Some(folder), linter_file);
+    };
+
+    let Some(directory) = path.parent() else {
+        return (None, None);
+    };
+    let folder = folders
+        .into_iter()
+        .find(|folder| directory.starts_with(folder));
+
+    let linter_file = directory
+        .ancestors()
+        .take_while(|dir| {
+            folder
+                .as_ref()
+                .map_or(true, |folder| dir.starts_with(folder))
+        })
+        .map(|dir| dir.join(LINTER_FILE))
+        .find(|file| file.is_file());
+
+    let directory = folder.unwrap_or_else(|| directory.to_path_buf());
+    (Some(directory), linter_file)
+}
+
+/// Lints `text` with `resources/lintr/lint.R` in a new R process. Returns
+/// `None` if lintr isn't installed, and an error with the output of the
+/// process on stderr if it fails.
+fn run_lintr(
+    text: &str,
+    directory: Option<&Path>,
+    linter_file: Option<&Path>,
+) -> anyhow::Result<Option<Vec<Lint>>> {
+    let script = Asset::get("lint.R").ok_or(anyhow!("Can't find the lintr script"))?;
+    let script = String::from_utf8_lossy(&script.data).to_string();
+
+    // The script and its argument are passed through the environment to
+    // avoid quoting issues with command lines
+    let mut command = Command::new(rscript_path());
+    command
+        .arg("-e")
+        .arg("eval(parse(text = Sys.getenv('ARK_LINTR_SCRIPT')))")
+        .env("ARK_LINTR_SCRIPT", script)
+        .env(
+            "ARK_LINTR_FILE",
+            linter_file.map(|file| file.as_os_str()).unwrap_or_default(),
+        )
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(directory) = directory {
+        command.current_dir(directory);
+    }
+
+    let mut child = command.spawn()?;
+
+    // Write from a thread so that a large document can't fill the pipe while
+    // we're waiting on the process
+    let mut stdin = child.stdin.take().ok_or(anyhow!("Can't write to lintr"))?;
+    let text = text.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(text.as_bytes()));
+
+    let mut stdout = child
+        .stdout
+        .take()
+        .ok_or(anyhow!("Can't read from lintr"))?;
+    let reader = std::thread::spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).map(|_| output)
+    });
+
+    let mut stderr = child
+        .stderr
+        .take()
+        .ok_or(anyhow!("Can't read from lintr"))?;
+    let error_reader = std::thread::spawn(move || {
+        let mut output = String::new();
+        stderr.read_to_string(&mut output).map(|_| output)
+    });
+
+    // Don't let a stuck linter hold on to a thread of the pool
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > LINTR_TIMEOUT {
+            child.kill()?;
+            child.wait()?;
+            return Err(anyhow!("lintr timed out after {LINTR_TIMEOUT:?}"));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+
+    writer
+        .join()
+        .map_err(|_| anyhow!("lintr writer panicked"))??;
+    let output = reader
+        .join()
+        .map_err(|_| anyhow!("lintr reader panicked"))??;
+    let errors = error_reader
+        .join()
+        .map_err(|_| anyhow!("lintr reader panicked"))??;
+
+    if !status.success() {
+        return Err(anyhow!("lintr failed with {status}: {}", errors.trim()));
+    }
+
+    if output.lines().any(|line| line == "ark-lintr-unavailable") {
+        return Ok(None);
+    }
+
+    let lints = output
+        .lines()
+        .filter_map(|line| line.strip_prefix("ark-lint\t"))
+        .filter_map(parse_lint)
+        .collect();
+
+    Ok(Some(lints))
+}
+
+fn parse_lint(line: &str) -> Option<Lint> {
+    let mut fields = line.splitn(6, '\t');
+
+    Some(Lint {
+        line: fields.next()?.parse().ok()?,
+        column: fields.next()?.parse().ok()?,
+        end_column: fields.next()?.parse().ok()?,
+        kind: fields.next()?.to_string(),
+        linter: fields.next()?.to_string(),
+        message: fields.next()?.to_string(),
+    })
+}
+
+/// `Rscript` of the R installation of the session, or else the one on the
+/// `PATH`
+fn rscript_path() -> PathBuf {
+    let name = if cfg!(windows) {
+        "Rscript.exe"
+    } else {
+        "Rscript"
+    };
+    match std::env::var("R_HOME") {
+        Ok(home) => Path::new(&home).join("bin").join(name),
+        Err(_) => PathBuf::from(name),
+    }
+}
+
+pub(crate) fn lint_diagnostic(
+    lint: &Lint,
+    config: &LintrConfig,
+    contents: &Rope,
+) -> Option<Diagnostic> {
+    if lint.line == 0 || lint.line > contents.len_lines() {
+        return None;
+    }
+    let row = lint.line - 1;
+
+    // Clamp the range to the line in case the document changed since
+    let line = contents.line(row);
+    let n_chars = line.len_chars();
+    let start = lint.column.saturating_sub(1).min(n_chars);
+    let end = lint.end_column.max(start + 1).min(n_chars);
+
+    let start = Point::new(row, line.char_to_byte(start));
+    let end = Point::new(row, line.char_to_byte(end));
+    let range = Range::new(
+        convert_point_to_position(contents, start),
+        convert_point_to_position(contents, end),
+    );
+
+    let mut diagnostic = Diagnostic::new_simple(range, lint.message.clone());
+    diagnostic.severity = Some(lint_severity(lint, config));
+    diagnostic.code = Some(NumberOrString::String(lint.linter.clone()));
+    diagnostic.source = Some(String::from("lintr"));
+
+    Some(diagnostic)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use ropey::Rope;
+    use tower_lsp::lsp_types::DiagnosticSeverity;
+    use tower_lsp::lsp_types::NumberOrString;
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::diagnostics_lintr::lint_diagnostic;
+    use crate::lsp::diagnostics_lintr::lint_severity;
+    use crate::lsp::diagnostics_lintr::lintr_paths;
+    use crate::lsp::diagnostics_lintr::parse_lint;
+    use crate::lsp::diagnostics_lintr::run_lintr;
+    use crate::lsp::diagnostics_lintr::Lint;
+    use crate::lsp::diagnostics_lintr::LintrConfig;
+
+    fn lint(kind: &str, linter: &str) -> Lint {
+        Lint {
+            line: 2,
+            column: 1,
+            end_column: 5,
+            kind: String::from(kind),
+            message: String::from("Oops"),
+            linter: String::from(linter),
+        }
+    }
+
+    #[test]
+    fn test_lint_severity_defaults() {
+        let config = LintrConfig::default();
+
+        assert_eq!(
+            lint_severity(&lint("style", "object_name_linter"), &config),
+            DiagnosticSeverity::INFORMATION
+        );
+        assert_eq!(
+            lint_severity(&lint("warning", "object_usage_linter"), &config),
+            DiagnosticSeverity::WARNING
+        );
+        assert_eq!(
+            lint_severity(&lint("error", "error"), &config),
+            DiagnosticSeverity::ERROR
+        );
+
+        // Custom linters with their own types
+        assert_eq!(
+            lint_severity(&lint("custom", "my_linter"), &config),
+            DiagnosticSeverity::WARNING
+        );
+    }
+
+    #[test]
+    fn test_lint_severity_configured() {
+        let config = LintrConfig {
+            enable: true,
+            severities: HashMap::from([
+                (
+                    String::from("object_name_linter"),
+                    DiagnosticSeverity::ERROR,
+                ),
+                (String::from("style"), DiagnosticSeverity::HINT),
+            ]),
+        };
+
+        // The linter takes precedence over its type
+        let contents = Rope::from("x <- 1\nmyVar <- 2\n");
+        let diagnostic =
+            lint_diagnostic(&lint("style", "object_name_linter"), &config, &contents).unwrap();
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            diagnostic.code,
+            Some(NumberOrString::String(String::from("object_name_linter")))
+        );
+        assert_eq!(diagnostic.source, Some(String::from("lintr")));
+        assert_eq!(diagnostic.range.start, Position::new(1, 0));
+        assert_eq!(diagnostic.range.end, Position::new(1, 5));
+
+        let diagnostic =
+            lint_diagnostic(&lint("style", "infix_spaces_linter"), &config, &contents).unwrap();
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::HINT));
+
+        // Lints outside of the document are dropped
+        let mut outside = lint("style", "infix_spaces_linter");
+        outside.line = 10;
+        assert!(lint_diagnostic(&outside, &config, &contents).is_none());
+    }
+
+    #[test]
+    fn test_lintr_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("R/nested")).unwrap();
+        std::fs::write(root.join(".lintr"), "").unwrap();
+
+        let folders = vec![Url::from_directory_path(root).unwrap()];
+        let uri = Url::from_file_path(root.join("R/nested/code.R")).unwrap();
+
+        // Runs from the workspace folder with its `.lintr`
+        let (directory, linter_file) = lintr_paths(&uri, &folders);
+        assert_eq!(directory.as_deref(), Some(root));
+        assert_eq!(linter_file, Some(root.join(".lintr")));
+
+        // The closest `.lintr` wins
+        std::fs::write(root.join("R/.lintr"), "").unwrap();
+        let (_, linter_file) = lintr_paths(&uri, &folders);
+        assert_eq!(linter_file, Some(root.join("R/.lintr")));
+
+        // Files outside the workspace run from their directory
+        let (directory, linter_file) = lintr_paths(&uri, &[]);
+        assert_eq!(directory, Some(root.join("R/nested")));
+        assert_eq!(linter_file, Some(root.join("R/.lintr")));
+    }
+
+    #[test]
+    fn test_parse_lint() {
+        let lint =
+            parse_lint("3\t5\t6\tstyle\tcommas_linter\tPut a space\tafter a comma.").unwrap();
+        assert_eq!((lint.line, lint.column, lint.end_column), (3, 5, 6));
+        assert_eq!(lint.kind, "style");
+        assert_eq!(lint.linter, "commas_linter");
+        assert_eq!(lint.message, "Put a space\tafter a comma.");
+
+        assert!(parse_lint("3\tstyle").is_none());
+    }
+
+    #[test]
+    fn test_lintr_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(
+            root.join(".lintr"),
+            "linters: linters_with_defaults(assignment_linter = NULL)\n",
+        )
+        .unwrap();
+
+        let folders = vec![Url::from_directory_path(root).unwrap()];
+        let uri = Url::from_file_path(root.join("code.R")).unwrap();
+        let (directory, linter_file) = lintr_paths(&uri, &folders);
+
+        let text = "x = c(1,2)\ny = c(3,4)\nz = c(5,6)\n";
+        let lints = run_lintr(text, directory.as_deref(), linter_file.as_deref()).unwrap();
+        let Some(lints) = lints else {
+            // lintr isn't installed
+            return;
+        };
+
+        // The `.lintr` of the workspace disables the assignment linter
+        assert!(lints.iter().all(|lint| lint.linter != "assignment_linter"));
+
+        // Every line has a lint, which requires the script to get past the
+        // first one
+        let lines: Vec<_> = lints.iter().map(|lint| lint.line).collect();
+        assert_eq!(lines, vec![1, 2, 3]);
+
+        let config = LintrConfig {
+            enable: true,
+            severities: HashMap::from([(String::from("commas_linter"), DiagnosticSeverity::HINT)]),
+        };
+        for (i, lint) in lints.iter().enumerate() {
+            assert_eq!(lint.linter, "commas_linter");
+
+            let diagnostic = lint_diagnostic(lint, &config, &Rope::from(text)).unwrap();
+            assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::HINT));
+            assert_eq!(diagnostic.range.start.line, i as u32);
+        }
+    }
+}
@@ -5,17 +5,33 @@
 //
 //
 
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crossbeam::channel::bounded;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use regex::Regex;
 use tower_lsp::lsp_types::Diagnostic;
 use tree_sitter::Node;
+use tree_sitter::Point;
 use tree_sitter::Range;
 
 use crate::lsp::diagnostics::DiagnosticContext;
 use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
 use crate::lsp::traits::rope::RopeExt;
+use crate::r_task;
 use crate::treesitter::node_has_error_or_missing;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
+/// Location and message of R's parse errors, e.g. `<text>:2:13: unexpected ')'`
+static RE_PARSE_ERROR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^<text>:(\d+):(\d+): (.*)").unwrap());
+
+/// How long diagnostics wait for R to be idle to get its parse error
+const PARSE_ERROR_TIMEOUT: Duration = Duration::from_millis(200);
+
 pub(crate) fn syntax_diagnostics(
     root: Node,
     context: &DiagnosticContext,
@@ -327,6 +343,133 @@ fn new_missing_close_diagnostic(
     new_syntax_diagnostic(message, range, context)
 }
 
+/// A diagnostic for the first syntax error found by R's parser, on the token
+/// it stopped at and with its message. R stops at the first error, so there
+/// may be more when code follows it.
+///
+/// Only consults R when tree-sitter found syntax errors in the first place,
+/// and only if R is idle. Otherwise the diagnostics of tree-sitter stand on
+/// their own.
+pub(crate) fn parse_error_diagnostic(
+    root: Node,
+    context: &DiagnosticContext,
+) -> anyhow::Result<Option<Diagnostic>> {
+    if !node_has_error_or_missing(&root) {
+        return Ok(None);
+    }
+
+    let text = context.contents.to_string();
+    let Some((line, column, message)) = r_idle_parse_error(text)? else {
+        return Ok(None);
+    };
+
+    // R reports 1-based lines and columns in characters, the column being
+    // the last character of the offending token. A column of 0 is used at
+    // the end of input, which tree-sitter already reports precisely as
+    // missing tokens.
+    if line == 0 || column == 0 || line > context.contents.len_lines() {
+        return Ok(None);
+    }
+    let row = line - 1;
+
+    let line_text = context.contents.line(row);
+    if column > line_text.len_chars() {
+        return Ok(None);
+    }
+    let point = Point::new(row, line_text.char_to_byte(column - 1));
+
+    // Target the offending token
+    let range = match root.descendant_for_point_range(point, point) {
+        Some(node) if node.child_count() == 0 => node.range(),
+        _ => {
+            let start_byte = context.contents.line_to_byte(row) + point.column;
+            Range {
+                start_byte,
+                start_point: point,
+                end_byte: start_byte + 1,
+                end_point: Point::new(row, point.column + 1),
+            }
+        },
+    };
+
+    let rest = context
+        .contents
+        .byte_slice(range.end_byte..)
+        .chars()
+        .any(|c| !c.is_whitespace());
+
+    let message = if rest {
+        format!("{message}. R reports the first syntax error only, there may be more.")
+    } else {
+        message
+    };
+
+    Ok(Some(new_syntax_diagnostic(message, range, context)))
+}
+
+/// R's parse error of `text`, computed in an idle task so that diagnostics
+/// don't wait on the R thread while user code runs. `None` if R doesn't get
+/// to it within `PARSE_ERROR_TIMEOUT`.
+fn r_idle_parse_error(text: String) -> anyhow::Result<Option<(usize, usize, String)>> {
+    let (tx, rx) = bounded(1);
+
+    r_task::spawn_idle(|| async move {
+        // Nobody is listening anymore if we timed out
+        let _ = tx.send(r_parse_error(&text));
+    });
+
+    match rx.recv_timeout(PARSE_ERROR_TIMEOUT) {
+        Ok(result) => result,
+        Err(_) => {
+            log::trace!("R isn't idle, skipping the parse error diagnostic");
+            Ok(None)
+        },
+    }
+}
+
+fn r_parse_error(text: &str) -> anyhow::Result<Option<(usize, usize, String)>> {
+    let result = RFunction::new("base", "parse")
+        .param("text", text)
+        .param("keep.source", false)
+        .call();
+
+    let message = match result {
+        Ok(_) => return Ok(None),
+        Err(harp::Error::TryCatchError { message, .. }) => message,
+        Err(err) => return Err(err.into()),
+    };
+
+    let Some(captures) = RE_PARSE_ERROR.captures(&message) else {
+        log::trace!("Can't find the location of parse error: {message}");
+        return Ok(None);
+    };
+
+    let line = captures[1].parse()?;
+    let column = captures[2].parse()?;
+    let message = captures[3].to_string();
+
+    Ok(Some((line, column, message)))
+}
+
+/// Adds the diagnostic of R's parse error to the tree-sitter syntax
+/// diagnostics. Tree-sitter diagnostics that span the offending token are
+/// less precise and are replaced. Those that already target the exact token
+/// are kept instead, as they tend to be more informative.
+pub(crate) fn merge_parse_error_diagnostic(
+    diagnostics: &mut Vec<Diagnostic>,
+    parse_error: Diagnostic,
+) {
+    if diagnostics.iter().any(|d| d.range == parse_error.range) {
+        return;
+    }
+
+    let start = parse_error.range.start;
+    let end = parse_error.range.end;
+    diagnostics.retain(|d| !(d.range.start <= start && end <= d.range.end));
+
+    diagnostics.push(parse_error);
+}
+
 fn new_syntax_diagnostic(message: String, range: Range, context: &DiagnosticContext) -> Diagnostic {
     let range = convert_tree_sitter_range_to_lsp_range(context.contents, range);
     Diagnostic::new_simple(range, message)
@@ -338,6 +481,8 @@ mod tests {
     use tower_lsp::lsp_types::Position;
 
     use crate::lsp::diagnostics::DiagnosticContext;
+    use crate::lsp::diagnostics_syntax::merge_parse_error_diagnostic;
+    use crate::lsp::diagnostics_syntax::parse_error_diagnostic;
     use crate::lsp::diagnostics_syntax::syntax_diagnostics;
     use crate::lsp::documents::Document;
 
@@ -526,4 +671,41 @@ function(x {
         assert_eq!(diagnostic.range.start, Position::new(3, 0));
         assert_eq!(diagnostic.range.end, Position::new(3, 1));
     }
+
+    #[test]
+    fn test_parse_error_location() {
+        let text = "x <- 1\ny <- c(1, 2))\nz <- 3";
+        let document = Document::new(text, None);
+        let context = DiagnosticContext::new(&document.contents);
+        let root = document.ast.root_node();
+
+        let diagnostic = parse_error_diagnostic(root, &context).unwrap().unwrap();
+        assert_eq!(diagnostic.range.start, Position::new(1, 12));
+        assert_eq!(diagnostic.range.end, Position::new(1, 13));
+        assert_eq!(
+            diagnostic.message,
+            "unexpected ')'. R reports the first syntax error only, there may be more."
+        );
+
+        // Merged with the tree-sitter diagnostics
+        let mut diagnostics = syntax_diagnostics(root, &context).unwrap();
+        merge_parse_error_diagnostic(&mut diagnostics, diagnostic);
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.range.start == Position::new(1, 12)));
+
+        // Nothing after the error
+        let document = Document::new("y <- c(1, 2))", None);
+        let context = DiagnosticContext::new(&document.contents);
+        let diagnostic = parse_error_diagnostic(document.ast.root_node(), &context)
+            .unwrap()
+            .unwrap();
+        assert_eq!(diagnostic.message, "unexpected ')'");
+
+        // No syntax errors
+        let document = Document::new("y <- c(1, 2)", None);
+        let context = DiagnosticContext::new(&document.contents);
+        let diagnostic = parse_error_diagnostic(document.ast.root_node(), &context).unwrap();
+        assert!(diagnostic.is_none());
+    }
 }
@@ -0,0 +1,245 @@
+//
+// document_links.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use tower_lsp::lsp_types::DocumentLink;
+use tower_lsp::lsp_types::Url;
+use tree_sitter::Node;
+use tree_sitter::Point;
+
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+static RE_URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"https?://[^\s"'`<>()\[\]{}]+"#).unwrap());
+
+/// Extensions of files that are commonly referred to from R code. A string
+/// without a path separator is only considered a path when it ends with one of
+/// these, so that strings like `"data.frame"` aren't mistaken for files.
+const FILE_EXTENSIONS: &[&str] = &[
+    "R", "r", "Rmd", "rmd", "qmd", "Rd", "md", "txt", "csv", "tsv", "json", "yaml", "yml", "toml",
+    "xml", "html", "css", "js", "sql", "py", "c", "cpp", "h", "sh", "rds", "rda", "RData", "rdata",
+    "xls", "xlsx", "parquet", "feather", "sav", "dta", "sas7bdat", "png", "jpg", "jpeg", "svg",
+    "pdf", "tex", "bib", "log", "zip", "gz",
+];
+
+/// Characters that don't appear in the paths people write in R code but are
+/// common in regular expressions, format strings, and other non-path strings
+const NON_PATH_CHARACTERS: &[char] = &[
+    '*', '?', '<', '>', '|', '"', '\'', '{', '}', '[', ']', '$', '^', '%', ';', ',', '\n',
+];
+
+/// Find links in a document: string literals that look like file paths and
+/// URLs in comments or strings.
+///
+/// Relative paths are resolved against `root`, typically the R working
+/// directory. Links are offered whether or not the file exists, it is up to
+/// the client to handle missing targets.
+pub(crate) fn document_links(document: &Document, root: &Path) -> Vec<DocumentLink> {
+    let contents = &document.contents;
+    let mut links = vec![];
+
+    document.ast.walk().recurse(|node| match node.node_type() {
+        NodeType::Comment => {
+            if let Ok(comment) = contents.node_slice(&node) {
+                links.append(&mut comment_links(document, &node, &comment.to_string()));
+            }
+            false
+        },
+        NodeType::String => {
+            if let Some(link) = string_link(document, &node, root) {
+                links.push(link);
+            }
+            false
+        },
+        _ => true,
+    });
+
+    links
+}
+
+fn comment_links(document: &Document, node: &Node, comment: &str) -> Vec<DocumentLink> {
+    RE_URL
+        .find_iter(comment)
+        .filter_map(|m| {
+            let url = trim_url(m.as_str());
+            let target = Url::parse(url).ok()?;
+
+            // Comments span a single line
+            let start = node.start_position();
+            let range = tree_sitter::Range {
+                start_byte: node.start_byte() + m.start(),
+                end_byte: node.start_byte() + m.start() + url.len(),
+                start_point: Point::new(start.row, start.column + m.start()),
+                end_point: Point::new(start.row, start.column + m.start() + url.len()),
+            };
+
+            Some(DocumentLink {
+                range: convert_tree_sitter_range_to_lsp_range(&document.contents, range),
+                target: Some(target),
+                tooltip: None,
+                data: None,
+            })
+        })
+        .collect()
+}
+
+fn string_link(document: &Document, node: &Node, root: &Path) -> Option<DocumentLink> {
+    let mut cursor = node.walk();
+    let content = node
+        .named_children(&mut cursor)
+        .find(|child| child.node_type() == NodeType::StringContent)?;
+
+    // Skip strings with escapes, the text in the document isn't the path
+    if content.named_child_count() > 0 {
+        return None;
+    }
+
+    let text = document.contents.node_slice(&content).ok()?.to_string();
+
+    let target = if text.starts_with("http://") || text.starts_with("https://") {
+        let url = RE_URL.find(&text)?;
+        if url.as_str().len() != text.len() {
+            return None;
+        }
+        Url::parse(&text).ok()?
+    } else if is_path_like(&text) {
+        Url::from_file_path(resolve_path(&text, root)?).ok()?
+    } else {
+        return None;
+    };
+
+    Some(DocumentLink {
+        range: convert_tree_sitter_range_to_lsp_range(&document.contents, content.range()),
+        target: Some(target),
+        tooltip: None,
+        data: None,
+    })
+}
+
+fn is_path_like(text: &str) -> bool {
+    if text.is_empty() || text.trim() != text || text.contains(NON_PATH_CHARACTERS) {
+        return false;
+    }
+
+    // URLs with other schemes, e.g. `s3://`, aren't local files
+    if text.contains("://") {
+        return false;
+    }
+
+    if text.contains('/') || text.contains('\\') {
+        // At least one path component that isn't just separators or dots
+        return text.chars().any(|c| c.is_alphanumeric());
+    }
+
+    match text.rsplit_once('.') {
+        Some((stem, extension)) => !stem.is_empty() && FILE_EXTENSIONS.contains(&extension),
+        None => false,
+    }
+}
+
+fn resolve_path(text: &str, root: &Path) -> Option<PathBuf> {
+    if let Some(rest) = text.strip_prefix("~/") {
+        return Some(home::home_dir()?.join(rest));
+    }
+
+    let path = Path::new(text);
+    if path.is_absolute() {
+        return Some(path.to_path_buf());
+    }
+
+    Some(root.join(path))
+}
+
+/// Trailing punctuation is most likely part of the sentence, not of the URL
+fn trim_url(url: &str) -> &str {
+    url.trim_end_matches(['.', ',', ';', ':', '!', '?'])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Range;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::document_links::document_links;
+    use crate::lsp::document_links::is_path_like;
+    use crate::lsp::documents::Document;
+
+    #[test]
+    fn test_document_links() {
+        let code = r#"source("helper.R")
+# See https://example.com/docs.
+x <- "not a path"
+inherits(x, "data.frame")
+read.csv("data/missing.csv")
+"#;
+        let document = Document::new(code, None);
+        let root = if cfg!(windows) {
+            Path::new("C:\\project")
+        } else {
+            Path::new("/project")
+        };
+
+        let links = document_links(&document, root);
+        assert_eq!(links.len(), 3);
+
+        assert_eq!(
+            links[0].range,
+            Range::new(Position::new(0, 8), Position::new(0, 16))
+        );
+        assert_eq!(
+            links[0].target,
+            Some(Url::from_file_path(root.join("helper.R")).unwrap())
+        );
+
+        assert_eq!(
+            links[1].range,
+            Range::new(Position::new(1, 6), Position::new(1, 30))
+        );
+        assert_eq!(
+            links[1].target,
+            Some(Url::parse("https://example.com/docs").unwrap())
+        );
+
+        // Paths that don't exist are still linked
+        assert_eq!(
+            links[2].range,
+            Range::new(Position::new(4, 10), Position::new(4, 26))
+        );
+        assert_eq!(
+            links[2].target,
+            Some(Url::from_file_path(root.join("data/missing.csv")).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_is_path_like() {
+        assert!(is_path_like("helper.R"));
+        assert!(is_path_like("../data/raw"));
+        assert!(is_path_like("~/notes.md"));
+
+        assert!(!is_path_like("data.frame"));
+        assert!(!is_path_like("is.na"));
+        assert!(!is_path_like("not a path"));
+        assert!(!is_path_like("^[a-z]+\\.R$"));
+        assert!(!is_path_like("%s/%s.csv"));
+        assert!(!is_path_like("s3://bucket/file.csv"));
+        assert!(!is_path_like("/"));
+        assert!(!is_path_like(".R"));
+    }
+}
@@ -84,22 +84,26 @@ impl Document {
         }
     }
 
-    pub fn on_did_change(&mut self, parser: &mut Parser, params: &DidChangeTextDocumentParams) {
+    pub fn on_did_change(
+        &mut self,
+        parser: &mut Parser,
+        params: &DidChangeTextDocumentParams,
+    ) -> Result<()> {
         let new_version = params.text_document.version;
 
         // Check for out-of-order change notifications
         if let Some(old_version) = self.version {
             // According to the spec, versions might not be consecutive but they must be monotonically
-            // increasing. If that's not the case this is a hard nope as we
-            // can't maintain our state integrity. Currently panicking but in
-            // principle we should shut down the LSP in an orderly fashion.
+            // increasing. The ranges of a stale notification refer to contents
+            // we no longer have, so we reject it rather than corrupt the
+            // document.
             if new_version < old_version {
-                panic!(
-                    "out-of-sync change notification: currently at {old_version}, got {new_version}"
-                );
+                bail!("out-of-sync change notification: currently at {old_version}, got {new_version}");
             }
         }
 
+        // Changes are applied in order, each range refers to the contents
+        // resulting from the previous change
         for event in &params.content_changes {
             if let Err(err) = self.update(parser, event) {
                 panic!("Failed to update document: {err:?}");
@@ -108,6 +112,8 @@ impl Document {
 
         // Set new version
         self.version = Some(new_version);
+
+        Ok(())
     }
 
     fn update(
@@ -115,10 +121,11 @@ impl Document {
         parser: &mut Parser,
         change: &TextDocumentContentChangeEvent,
     ) -> Result<()> {
-        // Extract edit range. Nothing to do if there wasn't an edit.
-        let range = match change.range {
-            Some(r) => r,
-            None => return Ok(()),
+        // A change without a range replaces the whole document
+        let Some(range) = change.range else {
+            self.contents = Rope::from(change.text.as_str());
+            self.ast = parser.parse(change.text.as_str(), None).unwrap();
+            return Ok(());
         };
 
         // Update the AST. We do this before updating the underlying document
@@ -203,8 +210,57 @@ impl Document {
 
 #[cfg(test)]
 mod tests {
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Range;
+    use tower_lsp::lsp_types::VersionedTextDocumentIdentifier;
+    use url::Url;
+
     use super::*;
 
+    fn test_parser() -> Parser {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_r::LANGUAGE.into())
+            .unwrap();
+        parser
+    }
+
+    fn change(
+        range: Option<((u32, u32), (u32, u32))>,
+        text: &str,
+    ) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: range.map(|(start, end)| Range {
+                start: Position::new(start.0, start.1),
+                end: Position::new(end.0, end.1),
+            }),
+            range_length: None,
+            text: String::from(text),
+        }
+    }
+
+    fn change_params(
+        version: i32,
+        content_changes: Vec<TextDocumentContentChangeEvent>,
+    ) -> DidChangeTextDocumentParams {
+        DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier {
+                uri: Url::parse("file:///test.R").unwrap(),
+                version,
+            },
+            content_changes,
+        }
+    }
+
+    /// The incrementally updated tree must match a fresh parse
+    fn assert_tree_in_sync(doc: &Document) {
+        let fresh = Document::new(&doc.contents.to_string(), None);
+        assert_eq!(
+            doc.ast.root_node().to_sexp(),
+            fresh.ast.root_node().to_sexp()
+        );
+    }
+
     #[test]
     fn test_point_computation() {
         // empty strings shouldn't do anything
@@ -232,4 +288,74 @@ mod tests {
         let root = document.ast.root_node();
         assert_eq!(root.start_position(), Point::new(0, 0));
     }
+
+    #[test]
+    fn test_did_change_multibyte() {
+        let mut parser = test_parser();
+        let mut doc = Document::new_with_parser("\"é\" <- 1\nfoo(😀, bar)\n", &mut parser, Some(1));
+
+        // Positions are in UTF-16 code units, the emoji takes two of them
+        let params = change_params(2, vec![change(Some(((1, 8), (1, 11))), "baz")]);
+        doc.on_did_change(&mut parser, &params).unwrap();
+        assert_eq!(doc.contents.to_string(), "\"é\" <- 1\nfoo(😀, baz)\n");
+
+        let params = change_params(3, vec![change(Some(((0, 1), (0, 2))), "ü")]);
+        doc.on_did_change(&mut parser, &params).unwrap();
+        assert_eq!(doc.contents.to_string(), "\"ü\" <- 1\nfoo(😀, baz)\n");
+
+        assert_eq!(doc.version, Some(3));
+        assert_tree_in_sync(&doc);
+    }
+
+    #[test]
+    fn test_did_change_across_lines() {
+        let mut parser = test_parser();
+        let mut doc = Document::new_with_parser("a <- 1\nb <- 2\nc <- 3\n", &mut parser, Some(1));
+
+        let params = change_params(2, vec![change(Some(((0, 5), (2, 1))), "10\nd")]);
+        doc.on_did_change(&mut parser, &params).unwrap();
+        assert_eq!(doc.contents.to_string(), "a <- 10\nd <- 3\n");
+        assert_tree_in_sync(&doc);
+
+        // Each change applies to the result of the previous one
+        let params = change_params(3, vec![
+            change(Some(((1, 6), (1, 6))), "\ne <- function() {\n"),
+            change(Some(((3, 0), (3, 0))), "}"),
+        ]);
+        doc.on_did_change(&mut parser, &params).unwrap();
+        assert_eq!(
+            doc.contents.to_string(),
+            "a <- 10\nd <- 3\ne <- function() {\n}\n"
+        );
+        assert_tree_in_sync(&doc);
+    }
+
+    #[test]
+    fn test_did_change_full_contents() {
+        let mut parser = test_parser();
+        let mut doc = Document::new_with_parser("a <- 1\n", &mut parser, Some(1));
+
+        let params = change_params(2, vec![change(None, "f(x)\n")]);
+        doc.on_did_change(&mut parser, &params).unwrap();
+        assert_eq!(doc.contents.to_string(), "f(x)\n");
+        assert_tree_in_sync(&doc);
+    }
+
+    #[test]
+    fn test_did_change_out_of_order() {
+        let mut parser = test_parser();
+        let mut doc = Document::new_with_parser("a <- 1\n", &mut parser, Some(5));
+
+        // A stale notification is rejected and leaves the document untouched
+        let params = change_params(4, vec![change(Some(((0, 5), (0, 6))), "2")]);
+        assert!(doc.on_did_change(&mut parser, &params).is_err());
+        assert_eq!(doc.contents.to_string(), "a <- 1\n");
+        assert_eq!(doc.version, Some(5));
+
+        // Versions don't need to be consecutive
+        let params = change_params(8, vec![change(Some(((0, 5), (0, 6))), "2")]);
+        doc.on_did_change(&mut parser, &params).unwrap();
+        assert_eq!(doc.contents.to_string(), "a <- 2\n");
+        assert_eq!(doc.version, Some(8));
+    }
 }
@@ -0,0 +1,356 @@
+//
+// formatting.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::utils::r_is_null;
+use ropey::Rope;
+use tree_sitter::Node;
+use tree_sitter::Point;
+use tree_sitter::Range;
+
+use crate::lsp;
+use crate::lsp::config::IndentationConfig;
+use crate::lsp::documents::Document;
+use crate::lsp::indent::new_line_indent;
+use crate::lsp::offset::ArkPoint;
+use crate::lsp::offset::ArkRange;
+use crate::lsp::offset::ArkTextEdit;
+use crate::r_task;
+use crate::treesitter::NodeTypeExt;
+
+/// Formats the whole document with styler
+///
+/// The indentation follows the configuration of the document, which is
+/// updated from the formatting options of the request, and the line endings
+/// follow the document.
+///
+/// Returns `None` if styler isn't installed or if the document doesn't
+/// parse, in which case the document is left alone.
+pub(crate) fn format_document(doc: &Document) -> anyhow::Result<Option<Vec<ArkTextEdit>>> {
+    let text = doc.contents.to_string();
+
+    let Some(formatted) = style_text(&text, &doc.config.indent)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(document_edits(&doc.contents, formatted)))
+}
+
+/// The edits replacing `contents` with `formatted`: none if they are
+/// identical, otherwise a single edit spanning the whole document. The
+/// final newline and the line endings of `contents` are preserved.
+fn document_edits(contents: &Rope, mut formatted: String) -> Vec<ArkTextEdit> {
+    let text = contents.to_string();
+
+    let trailing_newline = text.ends_with('\n');
+    while formatted.ends_with('\n') {
+        formatted.pop();
+    }
+    if trailing_newline {
+        formatted.push('\n');
+    }
+    let formatted = formatted.replace('\n', line_ending(&text));
+
+    if formatted == text {
+        return Vec::new();
+    }
+
+    let last_line = contents.len_lines() - 1;
+    let end = ArkPoint {
+        row: last_line,
+        column: contents.line(last_line).len_bytes(),
+    };
+
+    vec![ArkTextEdit {
+        range: ArkRange {
+            start: ArkPoint { row: 0, column: 0 },
+            end,
+        },
+        new_text: formatted,
+    }]
+}
+
+/// Formats the selection between `start` and `end` with styler
+///
+/// The selection is snapped to the complete expressions it overlaps in the
+/// innermost block or at top level. These are formatted on their own, then
+/// indented like their first line so that a selection inside a block isn't
+/// dedented to column zero.
+pub(crate) fn format_range(
+    doc: &Document,
+    start: Point,
+    end: Point,
+) -> anyhow::Result<Option<Vec<ArkTextEdit>>> {
+    let Some(range) = expressions_range(doc.ast.root_node(), start, end) else {
+        return Ok(None);
+    };
+
+    let text = doc
+        .contents
+        .byte_slice(range.start_byte..range.end_byte)
+        .to_string();
+
+    let Some(formatted) = style_text(&text, &doc.config.indent)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(range_edits(&doc.contents, range, formatted)))
+}
+
+/// The range spanning the expressions of the innermost block, or of the
+/// program, that overlap the selection
+fn expressions_range(root: Node, start: Point, end: Point) -> Option<Range> {
+    let mut node = root.descendant_for_point_range(start, end)?;
+    while !node.is_program() && !node.is_braced_expression() {
+        node = node.parent()?;
+    }
+
+    let overlaps = |child: &Node| {
+        if start == end {
+            child.start_position() <= start && start <= child.end_position()
+        } else {
+            child.start_position() < end && start < child.end_position()
+        }
+    };
+
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node
+        .named_children(&mut cursor)
+        .filter(|child| overlaps(child))
+        .collect();
+
+    let first = children.first()?;
+    let last = children.last()?;
+
+    Some(Range {
+        start_byte: first.start_byte(),
+        end_byte: last.end_byte(),
+        start_point: first.start_position(),
+        end_point: last.end_position(),
+    })
+}
+
+/// The edits replacing `range` with `formatted`, indented like the first
+/// line of `range`, with the line endings of `contents`
+fn range_edits(contents: &Rope, range: Range, formatted: String) -> Vec<ArkTextEdit> {
+    let indent: String = contents
+        .line(range.start_point.row)
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect();
+    let line_ending = line_ending(&contents.to_string());
+
+    let mut new_text = String::new();
+    for (i, line) in formatted.trim_end_matches('\n').split('\n').enumerate() {
+        if i > 0 {
+            new_text.push_str(line_ending);
+            if !line.is_empty() {
+                new_text.push_str(&indent);
+            }
+        }
+        new_text.push_str(line);
+    }
+
+    let old_text = contents
+        .byte_slice(range.start_byte..range.end_byte)
+        .to_string();
+    if new_text == old_text {
+        return Vec::new();
+    }
+
+    vec![ArkTextEdit {
+        range: ArkRange {
+            start: range.start_point,
+            end: range.end_point,
+        },
+        new_text,
+    }]
+}
+
+/// The line ending of `text`, CRLF if any line ends with one
+fn line_ending(text: &str) -> &'static str {
+    if text.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+/// Styles `text` with styler, with LF line endings. The indentation that
+/// styler writes with spaces is converted to tabs if `indent` uses them.
+fn style_text(text: &str, indent: &IndentationConfig) -> anyhow::Result<Option<String>> {
+    let text = text.replace("\r\n", "\n");
+
+    let Some(styled) = r_task(|| r_style_text(&text, indent.indent_size))? else {
+        return Ok(None);
+    };
+
+    Ok(Some(reindent(&styled, indent)))
+}
+
+/// Rewrites the leading spaces of the lines of `text` with `indent`
+fn reindent(text: &str, indent: &IndentationConfig) -> String {
+    let lines: Vec<String> = text
+        .split('\n')
+        .map(|line| {
+            let code = line.trim_start_matches(' ');
+            new_line_indent(indent, line.len() - code.len()) + code
+        })
+        .collect();
+
+    lines.join("\n")
+}
+
+fn r_style_text(text: &str, indent_size: usize) -> anyhow::Result<Option<String>> {
+    let styled = RFunction::from(".ps.formatting.styleText")
+        .add(text)
+        .param("indent_by", indent_size as i32)
+        .call()?;
+
+    if r_is_null(styled.sexp) {
+        lsp::log_warn!("Can't format because styler is not installed");
+        return Ok(None);
+    }
+
+    let styled = Vec::<Option<String>>::try_from(styled)?;
+    match styled.into_iter().next().flatten() {
+        Some(styled) => Ok(Some(styled)),
+        None => {
+            log::trace!("Can't format code that doesn't parse");
+            Ok(None)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+    use tree_sitter::Point;
+
+    use crate::lsp::config::IndentStyle;
+    use crate::lsp::config::IndentationConfig;
+    use crate::lsp::documents::Document;
+    use crate::lsp::formatting::document_edits;
+    use crate::lsp::formatting::expressions_range;
+    use crate::lsp::formatting::format_document;
+    use crate::lsp::formatting::format_range;
+    use crate::lsp::formatting::range_edits;
+    use crate::lsp::formatting::reindent;
+    use crate::lsp::offset::apply_text_edits;
+
+    fn apply(text: &str, formatted: &str) -> String {
+        let mut text = String::from(text);
+        let edits = document_edits(&Rope::from(text.as_str()), String::from(formatted));
+        apply_text_edits(edits, &mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_document_edits() {
+        assert_eq!(apply("x<-1\ny<-2\n", "x <- 1\ny <- 2"), "x <- 1\ny <- 2\n");
+        assert_eq!(apply("x<-1", "x <- 1\n"), "x <- 1");
+        assert_eq!(apply("é<-'ü'\n", "é <- \"ü\"\n"), "é <- \"ü\"\n");
+
+        // Nothing to do for formatted documents
+        let contents = Rope::from("x <- 1\n");
+        assert!(document_edits(&contents, String::from("x <- 1")).is_empty());
+
+        // CRLF line endings are preserved
+        assert_eq!(
+            apply("x<-1\r\ny<-2\r\n", "x <- 1\ny <- 2\n"),
+            "x <- 1\r\ny <- 2\r\n"
+        );
+        let contents = Rope::from("x <- 1\r\n");
+        assert!(document_edits(&contents, String::from("x <- 1\n")).is_empty());
+    }
+
+    #[test]
+    fn test_reindent() {
+        let text = "f <- function() {\n    if (x) {\n        y\n    }\n}";
+
+        let spaces = IndentationConfig {
+            indent_style: IndentStyle::Space,
+            indent_size: 4,
+            tab_width: 4,
+        };
+        assert_eq!(reindent(text, &spaces), text);
+
+        let tabs = IndentationConfig {
+            indent_style: IndentStyle::Tab,
+            ..spaces
+        };
+        assert_eq!(
+            reindent(text, &tabs),
+            "f <- function() {\n\tif (x) {\n\t\ty\n\t}\n}"
+        );
+    }
+
+    #[test]
+    fn test_format_document_parse_error() {
+        // Documents that don't parse are left alone, whether or not styler
+        // is installed
+        let doc = Document::new("foo(\n", None);
+        assert!(format_document(&doc).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_format_range_in_if_block() {
+        let text = "if (TRUE) {\n    x<-1\n    y<-list(a=1,\nb=2)\n}\n";
+        let doc = Document::new(text, None);
+
+        // Snaps to the expressions of the block that overlap the selection
+        let range =
+            expressions_range(doc.ast.root_node(), Point::new(1, 6), Point::new(2, 6)).unwrap();
+        assert_eq!(range.start_point, Point::new(1, 4));
+        assert_eq!(range.end_point, Point::new(3, 4));
+
+        // The formatted expressions keep the indentation of the block
+        let formatted = "x <- 1\ny <- list(\n  a = 1,\n\n  b = 2\n)\n";
+        let edits = range_edits(&doc.contents, range, String::from(formatted));
+
+        let mut text = String::from(text);
+        apply_text_edits(edits, &mut text).unwrap();
+        assert_eq!(
+            text,
+            "if (TRUE) {\n    x <- 1\n    y <- list(\n      a = 1,\n\n      b = 2\n    )\n}\n"
+        );
+
+        // With the line endings of the document
+        let text = "if (TRUE) {\r\n    x<-1\r\n    y<-2\r\n}\r\n";
+        let doc = Document::new(text, None);
+        let range =
+            expressions_range(doc.ast.root_node(), Point::new(1, 4), Point::new(2, 8)).unwrap();
+        let edits = range_edits(&doc.contents, range, String::from("x <- 1\ny <- 2\n"));
+
+        let mut text = String::from(text);
+        apply_text_edits(edits, &mut text).unwrap();
+        assert_eq!(text, "if (TRUE) {\r\n    x <- 1\r\n    y <- 2\r\n}\r\n");
+    }
+
+    #[test]
+    fn test_format_range_snaps_to_expressions() {
+        let doc = Document::new("x <- 1\ny <- foo(2)\n\nz <- 3\n", None);
+        let root = doc.ast.root_node();
+
+        // A cursor inside an expression selects the whole expression
+        let range = expressions_range(root, Point::new(1, 9), Point::new(1, 9)).unwrap();
+        assert_eq!(range.start_point, Point::new(1, 0));
+        assert_eq!(range.end_point, Point::new(1, 11));
+
+        // Selecting whole lines doesn't include the next expression
+        let range = expressions_range(root, Point::new(0, 0), Point::new(2, 0)).unwrap();
+        assert_eq!(range.start_point, Point::new(0, 0));
+        assert_eq!(range.end_point, Point::new(1, 11));
+
+        // Nothing to format in an empty line
+        assert!(expressions_range(root, Point::new(2, 0), Point::new(2, 0)).is_none());
+        assert!(format_range(&doc, Point::new(2, 0), Point::new(2, 0))
+            .unwrap()
+            .is_none());
+    }
+}
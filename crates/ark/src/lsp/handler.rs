@@ -15,16 +15,16 @@ use stdext::spawn;
 use tokio::runtime::Runtime;
 
 use super::backend;
-use crate::interface::KernelInfo;
+use crate::interface::KernelInit;
 
 pub struct Lsp {
     runtime: Arc<Runtime>,
-    kernel_init_rx: BusReader<KernelInfo>,
+    kernel_init_rx: BusReader<KernelInit>,
     kernel_initialized: bool,
 }
 
 impl Lsp {
-    pub fn new(kernel_init_rx: BusReader<KernelInfo>) -> Self {
+    pub fn new(kernel_init_rx: BusReader<KernelInit>) -> Self {
         Self {
             runtime: Arc::new(tokio::runtime::Runtime::new().unwrap()),
             kernel_init_rx,
@@ -45,9 +45,10 @@ impl ServerHandler for Lsp {
         // is ready; on subsequent starts (reconnects), the kernel will already
         // be initialized.
         if !self.kernel_initialized {
-            let status = self.kernel_init_rx.recv();
-            if let Err(error) = status {
-                log::error!("Error waiting for kernel to initialize: {}", error);
+            match self.kernel_init_rx.recv() {
+                Ok(Ok(_)) => {},
+                Ok(Err(hang)) => log::error!("Kernel failed to start: {hang}"),
+                Err(error) => log::error!("Error waiting for kernel to initialize: {}", error),
             }
             self.kernel_initialized = true;
         }
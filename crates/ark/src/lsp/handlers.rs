@@ -5,6 +5,8 @@
 //
 //
 
+use std::time::Instant;
+
 use anyhow::anyhow;
 use dashmap::DashMap;
 use once_cell::sync::Lazy;
@@ -12,9 +14,15 @@ use serde_json::Value;
 use stdext::unwrap;
 use struct_field_names_as_array::FieldNamesAsArray;
 use tower_lsp::lsp_types::CompletionItem;
+use tower_lsp::lsp_types::CompletionList;
 use tower_lsp::lsp_types::CompletionParams;
 use tower_lsp::lsp_types::CompletionResponse;
+use tower_lsp::lsp_types::CompletionTriggerKind;
+use tower_lsp::lsp_types::DocumentFormattingParams;
+use tower_lsp::lsp_types::DocumentLink;
+use tower_lsp::lsp_types::DocumentLinkParams;
 use tower_lsp::lsp_types::DocumentOnTypeFormattingParams;
+use tower_lsp::lsp_types::DocumentRangeFormattingParams;
 use tower_lsp::lsp_types::DocumentSymbolParams;
 use tower_lsp::lsp_types::DocumentSymbolResponse;
 use tower_lsp::lsp_types::GotoDefinitionParams;
@@ -24,14 +32,21 @@ use tower_lsp::lsp_types::HoverContents;
 use tower_lsp::lsp_types::HoverParams;
 use tower_lsp::lsp_types::Location;
 use tower_lsp::lsp_types::MessageType;
+use tower_lsp::lsp_types::PrepareRenameResponse;
 use tower_lsp::lsp_types::ReferenceParams;
 use tower_lsp::lsp_types::Registration;
+use tower_lsp::lsp_types::RenameParams;
 use tower_lsp::lsp_types::SelectionRange;
 use tower_lsp::lsp_types::SelectionRangeParams;
 use tower_lsp::lsp_types::SignatureHelp;
 use tower_lsp::lsp_types::SignatureHelpParams;
 use tower_lsp::lsp_types::SymbolInformation;
+use tower_lsp::lsp_types::TextDocumentPositionParams;
 use tower_lsp::lsp_types::TextEdit;
+use tower_lsp::lsp_types::TypeHierarchyItem;
+use tower_lsp::lsp_types::TypeHierarchyPrepareParams;
+use tower_lsp::lsp_types::TypeHierarchySubtypesParams;
+use tower_lsp::lsp_types::TypeHierarchySupertypesParams;
 use tower_lsp::lsp_types::WorkspaceEdit;
 use tower_lsp::lsp_types::WorkspaceSymbolParams;
 use tower_lsp::Client;
@@ -42,21 +57,27 @@ use crate::analysis::input_boundaries::input_boundaries;
 use crate::lsp;
 use crate::lsp::completions::provide_completions;
 use crate::lsp::completions::resolve_completion;
+use crate::lsp::completions::SLOW_SOURCES_BUDGET;
 use crate::lsp::config::VscDiagnosticsConfig;
 use crate::lsp::config::VscDocumentConfig;
 use crate::lsp::definitions::goto_definition;
 use crate::lsp::document_context::DocumentContext;
+use crate::lsp::document_links::document_links;
 use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::formatting::format_document;
+use crate::lsp::formatting::format_range;
 use crate::lsp::help_topic::help_topic;
 use crate::lsp::help_topic::HelpTopicParams;
 use crate::lsp::help_topic::HelpTopicResponse;
 use crate::lsp::hover::r_hover;
 use crate::lsp::indent::indent_edit;
+use crate::lsp::indexer;
 use crate::lsp::input_boundaries::InputBoundariesParams;
 use crate::lsp::input_boundaries::InputBoundariesResponse;
 use crate::lsp::main_loop::LspState;
 use crate::lsp::offset::IntoLspOffset;
 use crate::lsp::references::find_references;
+use crate::lsp::rename;
 use crate::lsp::selection_range::convert_selection_range_from_tree_sitter_to_lsp;
 use crate::lsp::selection_range::selection_range;
 use crate::lsp::signature_help::r_signature_help;
@@ -65,7 +86,11 @@ use crate::lsp::statement_range::statement_range;
 use crate::lsp::statement_range::StatementRangeParams;
 use crate::lsp::statement_range::StatementRangeResponse;
 use crate::lsp::symbols;
+use crate::lsp::todo_markers::todo_markers;
+use crate::lsp::todo_markers::TodoMarkersResponse;
+use crate::lsp::type_hierarchy;
 use crate::r_task;
+use crate::r_task::r_task_preserving_seed;
 
 pub static ARK_VDOC_REQUEST: &'static str = "ark/internal/virtualDocument";
 
@@ -109,6 +134,30 @@ pub(crate) async fn handle_initialized(
         regs.append(&mut config_diagnostics_regs);
     }
 
+    if lsp_state.needs_registration.did_change_watched_files {
+        // Keeps the workspace index up to date with files that are changed
+        // on disk without being opened in the editor
+        regs.push(Registration {
+            id: uuid::Uuid::new_v4().to_string(),
+            method: String::from("workspace/didChangeWatchedFiles"),
+            register_options: Some(serde_json::json!({
+                "watchers": [{ "globPattern": "**/*.{r,R}" }]
+            })),
+        });
+    }
+
+    if lsp_state.needs_registration.type_hierarchy {
+        // Type hierarchies aren't part of the server capabilities we can
+        // declare at initialization
+        regs.push(Registration {
+            id: uuid::Uuid::new_v4().to_string(),
+            method: String::from("textDocument/prepareTypeHierarchy"),
+            register_options: Some(serde_json::json!({
+                "documentSelector": [{ "language": "r" }]
+            })),
+        });
+    }
+
     client
         .register_capability(regs)
         .instrument(span.exit())
@@ -175,16 +224,31 @@ pub(crate) fn handle_completion(
     let position = params.text_document_position.position;
     let point = convert_position_to_point(&document.contents, position);
 
+    // The first request consults all sources unless it runs out of time, in
+    // which case the remaining slow sources are skipped and the list is
+    // marked incomplete. The client then requests the full list again as
+    // the user keeps typing.
+    let is_rerequest = params.context.as_ref().is_some_and(|ctxt| {
+        ctxt.trigger_kind == CompletionTriggerKind::TRIGGER_FOR_INCOMPLETE_COMPLETIONS
+    });
+    let deadline = (!is_rerequest).then(|| Instant::now() + SLOW_SOURCES_BUDGET);
+
     let trigger = params.context.and_then(|ctxt| ctxt.trigger_character);
 
     // Build the document context.
     let context = DocumentContext::new(&document, point, trigger);
     lsp::log_info!("Completion context: {:#?}", context);
 
-    let completions = r_task(|| provide_completions(&context, state))?;
+    let completions = r_task_preserving_seed(|| provide_completions(&context, state, deadline))?;
 
-    if !completions.is_empty() {
-        Ok(Some(CompletionResponse::Array(completions)))
+    if completions.is_incomplete {
+        // Send an incomplete list even if empty so the client asks again
+        Ok(Some(CompletionResponse::List(CompletionList {
+            is_incomplete: true,
+            items: completions.items,
+        })))
+    } else if !completions.items.is_empty() {
+        Ok(Some(CompletionResponse::Array(completions.items)))
     } else {
         Ok(None)
     }
@@ -194,7 +258,7 @@ pub(crate) fn handle_completion(
 pub(crate) fn handle_completion_resolve(
     mut item: CompletionItem,
 ) -> anyhow::Result<CompletionItem> {
-    r_task(|| resolve_completion(&mut item))?;
+    r_task_preserving_seed(|| resolve_completion(&mut item))?;
     Ok(item)
 }
 
@@ -213,7 +277,7 @@ pub(crate) fn handle_hover(
     let context = DocumentContext::new(&document, point, None);
 
     // request hover information
-    let result = r_task(|| r_hover(&context));
+    let result = r_task_preserving_seed(|| r_hover(&context));
 
     // unwrap errors
     let result = unwrap!(result, Err(err) => {
@@ -247,7 +311,7 @@ pub(crate) fn handle_signature_help(
     let context = DocumentContext::new(&document, point, None);
 
     // request signature help
-    let result = r_task(|| r_signature_help(&context));
+    let result = r_task_preserving_seed(|| r_signature_help(&context));
 
     // unwrap errors
     let result = unwrap!(result, Err(err) => {
@@ -273,7 +337,7 @@ pub(crate) fn handle_goto_definition(
     let document = state.get_document(uri)?;
 
     // build goto definition context
-    let result = unwrap!(unsafe { goto_definition(&document, params) }, Err(err) => {
+    let result = unwrap!(unsafe { goto_definition(&document, state, params) }, Err(err) => {
         lsp::log_error!("{err:?}");
         return Ok(None);
     });
@@ -312,6 +376,61 @@ pub(crate) fn handle_selection_range(
     Ok(Some(selections))
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_document_link(
+    params: DocumentLinkParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<DocumentLink>>> {
+    let uri = params.text_document.uri;
+    let document = state.get_document(&uri)?;
+
+    // R's working directory is the process working directory
+    let root = std::env::current_dir()?;
+
+    Ok(Some(document_links(&document, &root)))
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_prepare_type_hierarchy(
+    params: TypeHierarchyPrepareParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<TypeHierarchyItem>>> {
+    let uri = params.text_document_position_params.text_document.uri;
+    let position = params.text_document_position_params.position;
+    let document = state.get_document(&uri)?;
+
+    let definitions = indexer::classes();
+    Ok(r_task_preserving_seed(|| {
+        type_hierarchy::prepare_type_hierarchy(
+            document,
+            &uri,
+            position,
+            &definitions,
+            type_hierarchy::r_loaded_class,
+        )
+    }))
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_type_hierarchy_supertypes(
+    params: TypeHierarchySupertypesParams,
+) -> anyhow::Result<Option<Vec<TypeHierarchyItem>>> {
+    let definitions = indexer::classes();
+    Ok(Some(r_task_preserving_seed(|| {
+        type_hierarchy::supertypes(&params.item, &definitions, type_hierarchy::r_loaded_class)
+    })))
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_type_hierarchy_subtypes(
+    params: TypeHierarchySubtypesParams,
+) -> anyhow::Result<Option<Vec<TypeHierarchyItem>>> {
+    let definitions = indexer::classes();
+    Ok(Some(r_task_preserving_seed(|| {
+        type_hierarchy::subtypes(&params.item, &definitions, type_hierarchy::r_loaded_class)
+    })))
+}
+
 #[tracing::instrument(level = "info", skip_all)]
 pub(crate) fn handle_references(
     params: ReferenceParams,
@@ -331,6 +450,22 @@ pub(crate) fn handle_references(
     }
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_prepare_rename(
+    params: TextDocumentPositionParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<PrepareRenameResponse>> {
+    rename::prepare_rename(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_rename(
+    params: RenameParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<WorkspaceEdit>> {
+    rename::rename(params, state)
+}
+
 #[tracing::instrument(level = "info", skip_all)]
 pub(crate) fn handle_statement_range(
     params: StatementRangeParams,
@@ -384,6 +519,31 @@ pub(crate) fn handle_indent(
     })
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_formatting(
+    params: DocumentFormattingParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<TextEdit>>> {
+    let doc = state.get_document(&params.text_document.uri)?;
+    let edits = format_document(doc)?;
+
+    Ok(edits.map(|edits| edits.into_lsp_offset(&doc.contents)))
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_range_formatting(
+    params: DocumentRangeFormattingParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<TextEdit>>> {
+    let doc = state.get_document(&params.text_document.uri)?;
+
+    let start = convert_position_to_point(&doc.contents, params.range.start);
+    let end = convert_position_to_point(&doc.contents, params.range.end);
+    let edits = format_range(doc, start, end)?;
+
+    Ok(edits.map(|edits| edits.into_lsp_offset(&doc.contents)))
+}
+
 // TODO: Should be in WorldState and updated via message passing
 pub static mut ARK_VDOCS: Lazy<DashMap<String, String>> = Lazy::new(|| DashMap::new());
 
@@ -403,3 +563,9 @@ pub(crate) fn handle_input_boundaries(
     let boundaries = r_task(|| input_boundaries(&params.text))?;
     Ok(InputBoundariesResponse { boundaries })
 }
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_todo_markers(state: &WorldState) -> anyhow::Result<TodoMarkersResponse> {
+    let markers = todo_markers(state)?;
+    Ok(TodoMarkersResponse { markers })
+}
@@ -272,6 +272,31 @@ impl RHtmlHelp {
         Ok(result)
     }
 
+    /// A short summary of the help page: its title and the first paragraph
+    /// of its description.
+    pub fn summary(&self) -> String {
+        let mut markdown = String::new();
+
+        if let Some(title) = self.title() {
+            push!(markdown, md_bold(&title), md_newline());
+        }
+
+        let mut description = None;
+        for_each_section(&self.html, |header, elements| {
+            if description.is_none() && elt_text(header) == "Description" {
+                description = elements
+                    .first()
+                    .map(|elt| MarkdownConverter::new(**elt).convert());
+            }
+        });
+
+        if let Some(description) = description {
+            markdown.push_str(description.trim());
+        }
+
+        markdown.trim_end().to_string()
+    }
+
     pub fn markdown(&self) -> anyhow::Result<String> {
         let mut markdown = String::new();
 
@@ -5,6 +5,9 @@
 //
 //
 
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::utils::r_is_null;
 use serde::Deserialize;
 use serde::Serialize;
 use tower_lsp::lsp_types::Position;
@@ -15,8 +18,10 @@ use tree_sitter::Tree;
 
 use crate::lsp;
 use crate::lsp::documents::Document;
+use crate::lsp::hover::global_binding_value;
 use crate::lsp::traits::node::NodeExt;
 use crate::lsp::traits::rope::RopeExt;
+use crate::r_task;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
@@ -52,8 +57,19 @@ pub(crate) fn help_topic(
     // Get the text of the node
     let text = document.contents.node_slice(&node)?.to_string();
 
+    // Calls to S3 generics use the help of the dispatched method when it has
+    // one, e.g. `summary.lm` for `summary(fit)`
+    let topic = match r_task(|| method_help_topic(&node, document)) {
+        Ok(Some(method)) => method,
+        Ok(None) => text,
+        Err(err) => {
+            lsp::log_warn!("help_topic(): Can't resolve the dispatched method: {err:?}");
+            text
+        },
+    };
+
     // Form the response
-    let response = HelpTopicResponse { topic: text };
+    let response = HelpTopicResponse { topic };
 
     lsp::log_info!(
         "help_topic(): Using help topic '{}' at position {}",
@@ -64,6 +80,70 @@ pub(crate) fn help_topic(
     Ok(Some(response))
 }
 
+/// The topic of the help page of the S3 method that a call to a generic
+/// dispatches to, when `node` is the function of the call and its first
+/// argument is an object bound in the global environment. `None` when the
+/// method can't be determined or has no help page, in which case the help of
+/// the generic applies.
+///
+/// Must be called on the R thread.
+pub(crate) fn method_help_topic(
+    node: &Node,
+    document: &Document,
+) -> anyhow::Result<Option<String>> {
+    if !node.is_identifier() {
+        return Ok(None);
+    }
+
+    let Some(call) = node.parent() else {
+        return Ok(None);
+    };
+    if !call.is_call() || call.child_by_field_name("function") != Some(*node) {
+        return Ok(None);
+    }
+
+    // The first argument, which must be passed by position
+    let Some(arguments) = call.child_by_field_name("arguments") else {
+        return Ok(None);
+    };
+    let mut cursor = arguments.walk();
+    let Some(argument) = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .next()
+    else {
+        return Ok(None);
+    };
+    if argument.child_by_field_name("name").is_some() {
+        return Ok(None);
+    }
+
+    // Only simple bindings, the class of other expressions isn't known
+    // without evaluating them
+    let Some(value) = argument.child_by_field_name("value") else {
+        return Ok(None);
+    };
+    if !value.is_identifier() {
+        return Ok(None);
+    }
+
+    let name = document.contents.node_slice(&value)?.to_string();
+    let Some(value) = global_binding_value(&name)? else {
+        return Ok(None);
+    };
+
+    let generic = document.contents.node_slice(node)?.to_string();
+    let topic = RFunction::from(".ps.help.methodTopic")
+        .param("generic", generic)
+        .param("x", value)
+        .call()?;
+
+    if r_is_null(topic.sexp) {
+        return Ok(None);
+    }
+
+    Ok(Some(String::try_from(topic)?))
+}
+
 fn locate_help_node(tree: &Tree, point: Point) -> Option<Node> {
     let root = tree.root_node();
 
@@ -101,7 +181,10 @@ mod tests {
     use tree_sitter::Parser;
 
     use crate::fixtures::point_from_cursor;
+    use crate::lsp::documents::Document;
+    use crate::lsp::help_topic::help_topic;
     use crate::lsp::help_topic::locate_help_node;
+    use crate::r_task;
 
     #[test]
     fn test_locate_help_node() {
@@ -139,4 +222,25 @@ mod tests {
         let text = node.utf8_text(text.as_bytes()).unwrap();
         assert_eq!(text, "dplyr:::across");
     }
+
+    #[test]
+    fn test_help_topic_method() {
+        r_task(|| {
+            harp::parse_eval_global("help_topic_df <- data.frame(a = 1)").unwrap();
+        });
+
+        let topic = |code: &str| {
+            let (text, point) = point_from_cursor(code);
+            let document = Document::new(text.as_str(), None);
+            help_topic(point, &document).unwrap().unwrap().topic
+        };
+
+        assert_eq!(topic("pr@int(help_topic_df)"), "print.data.frame");
+        assert_eq!(topic("pr@int(x = help_topic_df)"), "print");
+        assert_eq!(topic("pr@int(help_topic_unbound)"), "print");
+
+        r_task(|| {
+            harp::parse_eval_global("rm(help_topic_df)").unwrap();
+        });
+    }
 }
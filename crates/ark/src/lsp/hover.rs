@@ -6,6 +6,30 @@
 //
 
 use anyhow::*;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::r_symbol;
+use harp::utils::r_env_binding_is_active;
+use harp::utils::r_inherits;
+use harp::utils::r_is_matrix;
+use harp::utils::r_is_null;
+use harp::utils::r_is_object;
+use harp::utils::r_is_unbound;
+use harp::utils::r_promise_is_forced;
+use harp::utils::r_promise_value;
+use harp::utils::r_typeof;
+use libr::R_GlobalEnv;
+use libr::Rf_findVarInFrame;
+use libr::Rf_xlength;
+use libr::CPLXSXP;
+use libr::INTSXP;
+use libr::LGLSXP;
+use libr::PROMSXP;
+use libr::RAWSXP;
+use libr::REALSXP;
+use libr::SEXP;
+use libr::STRSXP;
+use libr::VECSXP;
 use stdext::unwrap;
 use stdext::unwrap::IntoResult;
 use tower_lsp::lsp_types::MarkupContent;
@@ -14,8 +38,15 @@ use tree_sitter::Node;
 
 use crate::lsp::document_context::DocumentContext;
 use crate::lsp::help::RHtmlHelp;
+use crate::lsp::help_topic::method_help_topic;
+use crate::lsp::references::binding_scope;
 use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
+use crate::variables::variable::WorkspaceVariableDisplayValue;
+
+/// Vectors longer than this are previewed by their type and length only
+const MAX_PREVIEW_LENGTH: isize = 10;
 
 enum HoverContext {
     Topic { topic: String },
@@ -75,28 +106,282 @@ pub(crate) fn r_hover(context: &DocumentContext) -> anyhow::Result<Option<Markup
         return Ok(None);
     }
 
+    if let Some(preview) = value_preview(*node, context)? {
+        return Ok(Some(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("```r\n{preview}\n```"),
+        }));
+    }
+
     let ctx = hover_context(*node, context)?;
     let ctx = unwrap!(ctx, None => {
         return Ok(None);
     });
 
+    let (topic, package) = match ctx {
+        HoverContext::QualifiedTopic { package, topic } => (topic, Some(package)),
+        HoverContext::Topic { topic } => (topic, None),
+    };
+
+    // Calls to S3 generics show the help of the dispatched method when it has
+    // one, e.g. `summary.lm` for `summary(fit)`
+    let method = match package {
+        Some(_) => None,
+        None => method_help_topic(node, &context.document)?,
+    };
+
     // Currently, `hover_context()` restricts to only showing hover docs for functions,
     // so we also use `RHtmlHelp::from_function()` here
-    let help = match ctx {
-        HoverContext::QualifiedTopic { package, topic } => {
-            RHtmlHelp::from_function(topic.as_str(), Some(package.as_str()))?
-        },
-
-        HoverContext::Topic { topic } => RHtmlHelp::from_function(topic.as_str(), None)?,
+    let help = match method {
+        Some(method) => RHtmlHelp::from_function(method.as_str(), None)?,
+        None => None,
+    };
+    let help = match help {
+        Some(help) => Some(help),
+        None => RHtmlHelp::from_function(topic.as_str(), package.as_deref())?,
     };
+    let signature = function_signature(topic.as_str(), package.as_deref())?;
+
+    // Show the signature followed by a summary of the help page. Functions
+    // without help, e.g. the ones defined in the session, only show their
+    // signature.
+    let mut sections = vec![];
+    if let Some(signature) = signature {
+        sections.push(format!("```r\n{signature}\n```"));
+    }
+    if let Some(help) = help {
+        sections.push(help.summary());
+    }
 
-    let help = unwrap!(help, None => {
+    if sections.is_empty() {
         return Ok(None);
-    });
+    }
 
-    let markdown = help.markdown()?;
     Ok(Some(MarkupContent {
         kind: MarkupKind::Markdown,
-        value: markdown,
+        value: sections.join("\n\n"),
     }))
 }
+
+/// The signature of a function as shown by `args()`, e.g. `median(x, na.rm =
+/// FALSE, ...)`. `None` when the symbol isn't bound to a function, or when
+/// the package isn't loaded.
+fn function_signature(name: &str, package: Option<&str>) -> Result<Option<String>> {
+    let signature = RFunction::from(".ps.help.functionSignature")
+        .param("name", name)
+        .param("package", package.map(String::from))
+        .call()?;
+
+    if r_is_null(signature.sexp) {
+        return Ok(None);
+    }
+
+    Ok(Some(String::try_from(signature)?))
+}
+
+/// A compact preview of the value bound to a bare identifier in the global
+/// environment, e.g. `x: numeric(1) = 3.14` or `df: data.frame [100 x 5]`.
+///
+/// Only inspects existing bindings, no code is evaluated: active bindings
+/// and unforced promises are skipped. Only objects that are cheap to
+/// summarise are previewed, unclassed vectors and data frames. Long vectors
+/// are previewed by their type and length only.
+fn value_preview(node: Node, context: &DocumentContext) -> Result<Option<String>> {
+    if !node.is_identifier() {
+        return Ok(None);
+    }
+
+    // Function names and `pkg::` or `x$` accessors are not global bindings
+    if let Some(parent) = node.parent() {
+        if parent.is_call() || parent.is_namespace_operator() {
+            return Ok(None);
+        }
+        if matches!(parent.node_type(), NodeType::ExtractOperator(_)) &&
+            parent.child_by_field_name("rhs") == Some(node)
+        {
+            return Ok(None);
+        }
+    }
+
+    let name = context.document.contents.node_slice(&node)?.to_string();
+
+    // Parameters and locals of an enclosing function shadow the global
+    if !binding_scope(context.document, node, &name).is_program() {
+        return Ok(None);
+    }
+
+    let Some(value) = global_binding_value(&name)? else {
+        return Ok(None);
+    };
+
+    Ok(object_preview(value).map(|preview| format!("{name}: {preview}")))
+}
+
+/// The value bound to `name` in the global environment, without evaluating
+/// anything. `None` for unbound symbols, active bindings, and unforced
+/// promises.
+pub(crate) fn global_binding_value(name: &str) -> Result<Option<SEXP>> {
+    unsafe {
+        let env = R_GlobalEnv;
+        let symbol = r_symbol!(name);
+
+        if r_env_binding_is_active(env, symbol)? {
+            return Ok(None);
+        }
+
+        let value = Rf_findVarInFrame(env, symbol);
+        if r_is_unbound(value) {
+            return Ok(None);
+        }
+
+        if r_typeof(value) == PROMSXP {
+            if !r_promise_is_forced(value) {
+                return Ok(None);
+            }
+            return Ok(Some(r_promise_value(value)));
+        }
+
+        Ok(Some(value))
+    }
+}
+
+fn object_preview(value: SEXP) -> Option<String> {
+    if r_typeof(value) == VECSXP && r_inherits(value, "data.frame") {
+        let dim = unsafe { harp::df_dim(value) }.ok()?;
+        return Some(format!("data.frame [{} x {}]", dim.num_rows, dim.num_cols));
+    }
+
+    if r_is_object(value) || r_is_matrix(value) {
+        return None;
+    }
+
+    let vec_type = match r_typeof(value) {
+        LGLSXP => "logical",
+        INTSXP => "integer",
+        REALSXP => "numeric",
+        CPLXSXP => "complex",
+        STRSXP => "character",
+        RAWSXP => "raw",
+        _ => return None,
+    };
+
+    let length = unsafe { Rf_xlength(value) };
+    if length == 0 || length > MAX_PREVIEW_LENGTH {
+        return Some(format!("{vec_type}({length})"));
+    }
+
+    let value = WorkspaceVariableDisplayValue::from(value);
+    let ellipsis = if value.is_truncated { " ..." } else { "" };
+
+    Some(format!(
+        "{vec_type}({length}) = {}{ellipsis}",
+        value.display_value
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use crate::lsp::document_context::DocumentContext;
+    use crate::lsp::documents::Document;
+    use crate::lsp::hover::r_hover;
+    use crate::r_task;
+
+    fn hover_value(code: &str, point: Point) -> Option<String> {
+        let document = Document::new(code, None);
+        let context = DocumentContext::new(&document, point, None);
+        r_hover(&context).unwrap().map(|content| content.value)
+    }
+
+    #[test]
+    fn test_hover_value_preview() {
+        r_task(|| {
+            harp::parse_eval_global("hover_x <- 3.14").unwrap();
+            harp::parse_eval_global(
+                "hover_df <- data.frame(a = 1:100, b = 1, c = 1, d = 1, e = 1)",
+            )
+            .unwrap();
+            harp::parse_eval_global("hover_long <- 1:1000").unwrap();
+            harp::parse_eval_global("makeActiveBinding('hover_active', function() 1, globalenv())")
+                .unwrap();
+
+            assert_eq!(
+                hover_value("hover_x + 1", Point::new(0, 2)).unwrap(),
+                "```r\nhover_x: numeric(1) = 3.14\n```"
+            );
+            assert_eq!(
+                hover_value("nrow(hover_df)", Point::new(0, 7)).unwrap(),
+                "```r\nhover_df: data.frame [100 x 5]\n```"
+            );
+
+            // Large objects are previewed by their type only
+            assert_eq!(
+                hover_value("hover_long", Point::new(0, 2)).unwrap(),
+                "```r\nhover_long: integer(1000)\n```"
+            );
+
+            // Active bindings and unbound symbols are skipped
+            assert!(hover_value("hover_active", Point::new(0, 2)).is_none());
+            assert!(hover_value("hover_unbound", Point::new(0, 2)).is_none());
+
+            // Parameters and locals that shadow a global are skipped
+            let text = "f <- function(hover_x) hover_x";
+            assert!(hover_value(text, Point::new(0, 15)).is_none());
+            assert!(hover_value(text, Point::new(0, 25)).is_none());
+            let text = "g <- function() {\n  hover_x <- 1\n  hover_x\n}";
+            assert!(hover_value(text, Point::new(2, 3)).is_none());
+
+            harp::parse_eval_global(
+                "rm(hover_x, hover_df, hover_long, hover_active, envir = globalenv())",
+            )
+            .unwrap();
+        })
+    }
+
+    #[test]
+    fn test_hover_function_help() {
+        r_task(|| {
+            let hover = hover_value("lm(y ~ x)", Point::new(0, 1)).unwrap();
+            assert!(hover.starts_with("```r\nlm(formula, data, subset,"));
+            assert!(hover.contains("**Fitting Linear Models**"));
+            assert!(!hover.contains("### Arguments"));
+
+            // Qualified names
+            let hover = hover_value("stats::median(x)", Point::new(0, 9)).unwrap();
+            assert!(hover.starts_with("```r\nmedian(x, na.rm = FALSE, ...)\n```"));
+            assert!(hover.contains("**Median Value**"));
+
+            // Without help, only the signature is shown
+            harp::parse_eval_global("hover_fn <- function(a, b = 2) NULL").unwrap();
+            assert_eq!(
+                hover_value("hover_fn(1)", Point::new(0, 2)).unwrap(),
+                "```r\nhover_fn(a, b = 2)\n```"
+            );
+            harp::parse_eval_global("rm(hover_fn, envir = globalenv())").unwrap();
+
+            assert!(hover_value("hover_unbound(1)", Point::new(0, 2)).is_none());
+        })
+    }
+
+    #[test]
+    fn test_hover_method_help() {
+        r_task(|| {
+            // `print.data.frame()` has its own help page
+            harp::parse_eval_global("hover_df <- data.frame(a = 1)").unwrap();
+            let hover = hover_value("print(hover_df)", Point::new(0, 2)).unwrap();
+            assert!(hover.starts_with("```r\nprint(x, ...)\n```"));
+            assert!(hover.contains("**Printing Data Frames**"));
+
+            // Falls back to the help of the generic when the method isn't
+            // documented
+            harp::parse_eval_global("hover_foo <- structure(list(), class = 'foo')").unwrap();
+            harp::parse_eval_global("print.foo <- function(x, ...) invisible(x)").unwrap();
+            let hover = hover_value("print(hover_foo)", Point::new(0, 2)).unwrap();
+            assert!(hover.contains("**Print Values**"));
+
+            harp::parse_eval_global("rm(hover_df, hover_foo, print.foo, envir = globalenv())")
+                .unwrap();
+        })
+    }
+}
@@ -18,6 +18,7 @@ use ropey::Rope;
 use stdext::unwrap;
 use stdext::unwrap::IntoResult;
 use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::Url;
 use tree_sitter::Node;
 use walkdir::DirEntry;
 use walkdir::WalkDir;
@@ -26,6 +27,8 @@ use crate::lsp;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::convert_point_to_position;
 use crate::lsp::traits::rope::RopeExt;
+use crate::lsp::type_hierarchy::document_class_definitions;
+use crate::lsp::type_hierarchy::ClassDefinition;
 use crate::treesitter::BinaryOperatorType;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
@@ -53,28 +56,56 @@ type DocumentPath = String;
 type DocumentSymbol = String;
 type DocumentSymbolIndex = HashMap<DocumentSymbol, IndexEntry>;
 type WorkspaceIndex = Arc<Mutex<HashMap<DocumentPath, DocumentSymbolIndex>>>;
+type ClassIndex = Arc<Mutex<HashMap<DocumentPath, Vec<ClassDefinition>>>>;
 
 static WORKSPACE_INDEX: LazyLock<WorkspaceIndex> = LazyLock::new(|| Default::default());
+static CLASS_INDEX: LazyLock<ClassIndex> = LazyLock::new(|| Default::default());
 pub static RE_COMMENT_SECTION: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^\s*(#+)\s*(.*?)\s*[#=-]{4,}\s*$").unwrap());
 
+/// Maximum number of threads indexing files concurrently. Indexing is mostly
+/// parsing so it's CPU bound, but we leave some room for the rest of the
+/// session.
+const MAX_INDEXING_THREADS: usize = 4;
+
 #[tracing::instrument(level = "info", skip_all)]
 pub fn start(folders: Vec<String>) {
     let now = std::time::Instant::now();
     lsp::log_info!("Initial indexing started");
 
-    for folder in folders {
-        let walker = WalkDir::new(folder);
-        for entry in walker.into_iter().filter_entry(|e| filter_entry(e)) {
-            if let Ok(entry) = entry {
-                if entry.file_type().is_file() {
+    let (files_tx, files_rx) = crossbeam::channel::unbounded::<DirEntry>();
+
+    let n_threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .clamp(1, MAX_INDEXING_THREADS);
+
+    std::thread::scope(|scope| {
+        for _ in 0..n_threads {
+            let files_rx = files_rx.clone();
+            scope.spawn(move || {
+                for entry in files_rx.iter() {
                     if let Err(err) = index_file(entry.path()) {
                         lsp::log_error!("Can't index file {:?}: {err:?}", entry.path());
                     }
                 }
+            });
+        }
+
+        for folder in folders {
+            let walker = WalkDir::new(folder);
+            for entry in walker.into_iter().filter_entry(|e| filter_entry(e)) {
+                if let Ok(entry) = entry {
+                    if entry.file_type().is_file() {
+                        files_tx.send(entry).unwrap();
+                    }
+                }
             }
         }
-    }
+
+        // Let the workers finish once the queue is drained
+        drop(files_tx);
+    });
 
     lsp::log_info!(
         "Initial indexing finished after {}ms",
@@ -94,6 +125,33 @@ pub fn find(symbol: &str) -> Option<(String, IndexEntry)> {
     None
 }
 
+/// All the entries of `symbol`, one per file at most, sorted by path
+pub fn find_all(symbol: &str) -> Vec<(String, IndexEntry)> {
+    let index = WORKSPACE_INDEX.lock().unwrap();
+
+    let mut entries: Vec<(String, IndexEntry)> = index
+        .iter()
+        .filter_map(|(path, index)| Some((path.clone(), index.get(symbol)?.clone())))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    entries
+}
+
+/// All the formal classes defined with `setClass()` or `setRefClass()`,
+/// sorted by path
+pub(crate) fn classes() -> Vec<ClassDefinition> {
+    let index = CLASS_INDEX.lock().unwrap();
+
+    let mut paths: Vec<&DocumentPath> = index.keys().collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .flat_map(|path| index[path].iter().cloned())
+        .collect()
+}
+
 pub fn map(mut callback: impl FnMut(&Path, &String, &IndexEntry)) {
     let index = WORKSPACE_INDEX.lock().unwrap();
 
@@ -112,6 +170,25 @@ pub fn update(document: &Document, path: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Re-index a file from disk, e.g. after it was changed by another program.
+/// Files in ignored folders are skipped.
+#[tracing::instrument(level = "trace", skip_all, fields(path = ?path))]
+pub fn update_file(path: &Path) -> anyhow::Result<()> {
+    if is_ignored(path) {
+        return Ok(());
+    }
+    clear(path)?;
+    index_file(path)
+}
+
+/// Remove a deleted file from the index
+pub fn remove(path: &Path) -> anyhow::Result<()> {
+    let path = str_from_path(path)?;
+    WORKSPACE_INDEX.lock().unwrap().remove(path);
+    CLASS_INDEX.lock().unwrap().remove(path);
+    Ok(())
+}
+
 fn insert(path: &Path, entry: IndexEntry) -> anyhow::Result<()> {
     let mut index = WORKSPACE_INDEX.lock().unwrap();
     let path = str_from_path(path)?;
@@ -131,6 +208,8 @@ fn clear(path: &Path) -> anyhow::Result<()> {
         index.clear();
     });
 
+    CLASS_INDEX.lock().unwrap().remove(path);
+
     Ok(())
 }
 
@@ -145,24 +224,35 @@ fn str_from_path(path: &Path) -> anyhow::Result<&str> {
 // TODO: What about front-end ignores?
 // TODO: What about other kinds of ignores (e.g. revdepcheck)?
 pub fn filter_entry(entry: &DirEntry) -> bool {
-    let name = entry.file_name();
+    !is_ignored_name(entry.path())
+}
+
+fn is_ignored_name(path: &Path) -> bool {
+    let Some(name) = path.file_name() else {
+        return false;
+    };
 
     // skip common ignores
     for ignore in [".git", ".Rproj.user", "node_modules", "revdep"] {
         if name == ignore {
-            return false;
+            return true;
         }
     }
 
     // skip project 'renv' folder
     if name == "renv" {
-        let companion = entry.path().join("activate.R");
+        let companion = path.join("activate.R");
         if companion.exists() {
-            return false;
+            return true;
         }
     }
 
-    true
+    false
+}
+
+/// Whether the file lives in a folder that `filter_entry()` would skip
+fn is_ignored(path: &Path) -> bool {
+    path.ancestors().skip(1).any(is_ignored_name)
 }
 
 fn index_file(path: &Path) -> anyhow::Result<()> {
@@ -198,6 +288,30 @@ fn index_document(document: &Document, path: &Path) {
             lsp::log_error!("Can't index document: {err:?}");
         }
     }
+
+    if let Err(err) = index_classes(document, path) {
+        lsp::log_error!("Can't index classes: {err:?}");
+    }
+}
+
+/// Class definitions can be nested in calls, e.g. in `local()` or in the
+/// `.onLoad()` hook of a package, so the whole tree is searched
+fn index_classes(document: &Document, path: &Path) -> anyhow::Result<()> {
+    let uri = Url::from_file_path(path)
+        .map_err(|_| anyhow!("Couldn't convert path {} to URI", path.display()))?;
+
+    let definitions = document_class_definitions(document, &uri);
+    if definitions.is_empty() {
+        return Ok(());
+    }
+
+    let path = str_from_path(path)?;
+    CLASS_INDEX
+        .lock()
+        .unwrap()
+        .insert(path.to_string(), definitions);
+
+    Ok(())
 }
 
 fn index_node(path: &Path, contents: &Rope, node: &Node) -> anyhow::Result<Option<IndexEntry>> {
@@ -292,3 +406,119 @@ fn index_comment(_path: &Path, contents: &Rope, node: &Node) -> anyhow::Result<O
         data: IndexEntryData::Section { level, title },
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::WorkspaceSymbolParams;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::indexer;
+    use crate::lsp::symbols;
+
+    fn write(dir: &Path, path: &str, contents: &str) -> std::path::PathBuf {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_indexer_indexes_unopened_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        let file = write(
+            root,
+            "R/indexer_fixture.R",
+            "indexer_fixture_fn <- function(x, y) x\n",
+        );
+        write(
+            root,
+            "R/nested/other.r",
+            "indexer_fixture_nested <- function() NULL\n",
+        );
+        write(
+            root,
+            "node_modules/ignored.R",
+            "indexer_fixture_ignored <- function() NULL\n",
+        );
+        write(
+            root,
+            "R/notes.txt",
+            "indexer_fixture_txt <- function() NULL\n",
+        );
+
+        indexer::start(vec![root.to_str().unwrap().to_string()]);
+
+        let (path, entry) = indexer::find("indexer_fixture_fn").unwrap();
+        assert_eq!(Path::new(&path), file);
+        let indexer::IndexEntryData::Function { arguments, .. } = entry.data else {
+            panic!("Expected a function entry");
+        };
+        assert_eq!(arguments, vec!["x", "y"]);
+
+        // Searchable as workspace symbols without being opened
+        let params = WorkspaceSymbolParams {
+            query: String::from("indexer_fixture"),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let names: Vec<String> = symbols::symbols(&params)
+            .unwrap()
+            .into_iter()
+            .map(|info| info.name)
+            .collect();
+        assert!(names.contains(&String::from("indexer_fixture_fn")));
+        assert!(names.contains(&String::from("indexer_fixture_nested")));
+        assert!(!names.contains(&String::from("indexer_fixture_ignored")));
+
+        assert!(indexer::find("indexer_fixture_nested").is_some());
+        assert!(indexer::find("indexer_fixture_ignored").is_none());
+        assert!(indexer::find("indexer_fixture_txt").is_none());
+
+        // Changes on disk
+        std::fs::write(&file, "indexer_fixture_renamed <- function() NULL\n").unwrap();
+        indexer::update_file(&file).unwrap();
+        assert!(indexer::find("indexer_fixture_fn").is_none());
+        assert!(indexer::find("indexer_fixture_renamed").is_some());
+
+        let ignored = root.join("node_modules/ignored.R");
+        indexer::update_file(&ignored).unwrap();
+        assert!(indexer::find("indexer_fixture_ignored").is_none());
+
+        indexer::remove(&file).unwrap();
+        assert!(indexer::find("indexer_fixture_renamed").is_none());
+    }
+
+    #[test]
+    fn test_indexer_indexes_classes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        let has_class = |name: &str| indexer::classes().iter().any(|def| def.name == name);
+
+        let file = write(
+            root,
+            "R/classes.R",
+            "local({\n  setClass(\"IndexerB\", contains = \"IndexerA\")\n})\n",
+        );
+        indexer::start(vec![root.to_str().unwrap().to_string()]);
+
+        let definition = indexer::classes()
+            .into_iter()
+            .find(|def| def.name == "IndexerB")
+            .unwrap();
+        assert_eq!(definition.contains, vec!["IndexerA"]);
+
+        // Open documents update the index as they change
+        let document = Document::new("setRefClass(\"IndexerC\")\n", None);
+        indexer::update(&document, &file).unwrap();
+        assert!(!has_class("IndexerB"));
+        assert!(has_class("IndexerC"));
+
+        indexer::remove(&file).unwrap();
+        assert!(!has_class("IndexerC"));
+    }
+}
@@ -25,6 +25,7 @@ use crate::lsp::backend::LspNotification;
 use crate::lsp::backend::LspRequest;
 use crate::lsp::backend::LspResponse;
 use crate::lsp::diagnostics;
+use crate::lsp::diagnostics_lintr::lintr_diagnostics;
 use crate::lsp::documents::Document;
 use crate::lsp::handlers;
 use crate::lsp::state::WorldState;
@@ -117,6 +118,8 @@ pub(crate) struct LspState {
 #[derive(Debug, Default)]
 pub(crate) struct ClientCaps {
     pub(crate) did_change_configuration: bool,
+    pub(crate) did_change_watched_files: bool,
+    pub(crate) type_hierarchy: bool,
 }
 
 /// State for the auxiliary loop
@@ -227,8 +230,8 @@ impl GlobalState {
                         LspNotification::DidChangeConfiguration(params) => {
                             state_handlers::did_change_configuration(params, &self.client, &mut self.world).await?;
                         },
-                        LspNotification::DidChangeWatchedFiles(_params) => {
-                            // TODO: Re-index the changed files.
+                        LspNotification::DidChangeWatchedFiles(params) => {
+                            state_handlers::did_change_watched_files(params, &self.world)?;
                         },
                         LspNotification::DidOpenTextDocument(params) => {
                             state_handlers::did_open(params, &mut self.lsp_state, &mut self.world)?;
@@ -262,8 +265,13 @@ impl GlobalState {
                         LspRequest::DocumentSymbol(params) => {
                             respond(tx, handlers::handle_document_symbol(params, &self.world), LspResponse::DocumentSymbol)?;
                         },
-                        LspRequest::ExecuteCommand(_params) => {
-                            respond(tx, handlers::handle_execute_command(&self.client).await, LspResponse::ExecuteCommand)?;
+                        LspRequest::ExecuteCommand(params) => {
+                            if params.command == state_handlers::REPARSE_ALL_COMMAND {
+                                respond(tx, state_handlers::reparse_all(&mut self.lsp_state, &mut self.world).map(|()| None), LspResponse::ExecuteCommand)?;
+                                lsp::spawn_diagnostics_refresh_all(self.world.clone());
+                            } else {
+                                respond(tx, handlers::handle_execute_command(&self.client).await, LspResponse::ExecuteCommand)?;
+                            }
                         },
                         LspRequest::Completion(params) => {
                             respond(tx, handlers::handle_completion(params, &self.world), LspResponse::Completion)?;
@@ -287,9 +295,27 @@ impl GlobalState {
                         LspRequest::SelectionRange(params) => {
                             respond(tx, handlers::handle_selection_range(params, &self.world), LspResponse::SelectionRange)?;
                         },
+                        LspRequest::DocumentLink(params) => {
+                            respond(tx, handlers::handle_document_link(params, &self.world), LspResponse::DocumentLink)?;
+                        },
+                        LspRequest::PrepareTypeHierarchy(params) => {
+                            respond(tx, handlers::handle_prepare_type_hierarchy(params, &self.world), LspResponse::PrepareTypeHierarchy)?;
+                        },
+                        LspRequest::TypeHierarchySupertypes(params) => {
+                            respond(tx, handlers::handle_type_hierarchy_supertypes(params), LspResponse::TypeHierarchySupertypes)?;
+                        },
+                        LspRequest::TypeHierarchySubtypes(params) => {
+                            respond(tx, handlers::handle_type_hierarchy_subtypes(params), LspResponse::TypeHierarchySubtypes)?;
+                        },
                         LspRequest::References(params) => {
                             respond(tx, handlers::handle_references(params, &self.world), LspResponse::References)?;
                         },
+                        LspRequest::PrepareRename(params) => {
+                            respond(tx, handlers::handle_prepare_rename(params, &self.world), LspResponse::PrepareRename)?;
+                        },
+                        LspRequest::Rename(params) => {
+                            respond(tx, handlers::handle_rename(params, &self.world), LspResponse::Rename)?;
+                        },
                         LspRequest::StatementRange(params) => {
                             respond(tx, handlers::handle_statement_range(params, &self.world), LspResponse::StatementRange)?;
                         },
@@ -300,12 +326,23 @@ impl GlobalState {
                             state_handlers::did_change_formatting_options(&params.text_document_position.text_document.uri, &params.options, &mut self.world);
                             respond(tx, handlers::handle_indent(params, &self.world), LspResponse::OnTypeFormatting)?;
                         },
+                        LspRequest::Formatting(params) => {
+                            state_handlers::did_change_formatting_options(&params.text_document.uri, &params.options, &mut self.world);
+                            respond(tx, handlers::handle_formatting(params, &self.world), LspResponse::Formatting)?;
+                        },
+                        LspRequest::RangeFormatting(params) => {
+                            state_handlers::did_change_formatting_options(&params.text_document.uri, &params.options, &mut self.world);
+                            respond(tx, handlers::handle_range_formatting(params, &self.world), LspResponse::RangeFormatting)?;
+                        },
                         LspRequest::VirtualDocument(params) => {
                             respond(tx, handlers::handle_virtual_document(params), LspResponse::VirtualDocument)?;
                         },
                         LspRequest::InputBoundaries(params) => {
                             respond(tx, handlers::handle_input_boundaries(params), LspResponse::InputBoundaries)?;
                         },
+                        LspRequest::TodoMarkers(_params) => {
+                            respond(tx, handlers::handle_todo_markers(&self.world), LspResponse::TodoMarkers)?;
+                        },
                     };
                 },
             },
@@ -531,7 +568,11 @@ pub(crate) fn spawn_diagnostics_refresh(uri: Url, document: Document, state: Wor
         let _s = tracing::info_span!("diagnostics_refresh", uri = %uri).entered();
 
         let version = document.version;
-        let diagnostics = diagnostics::generate_diagnostics(document, state);
+        let mut diagnostics = diagnostics::generate_diagnostics(document.clone(), state.clone());
+
+        // Lints are computed in the background and come in with a later
+        // refresh
+        diagnostics.append(&mut lintr_diagnostics(&uri, &document, &state));
 
         Ok(Some(AuxiliaryEvent::PublishDiagnostics(
             uri,
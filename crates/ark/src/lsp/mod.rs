@@ -12,11 +12,14 @@ mod config;
 mod declarations;
 pub mod definitions;
 pub mod diagnostics;
+pub mod diagnostics_lintr;
 pub mod diagnostics_syntax;
 pub mod document_context;
+pub mod document_links;
 pub mod documents;
 pub mod encoding;
 pub mod events;
+pub mod formatting;
 pub mod handler;
 pub mod handlers;
 pub mod help;
@@ -29,13 +32,17 @@ pub mod main_loop;
 pub mod markdown;
 pub mod offset;
 pub mod references;
+pub mod rename;
 pub mod selection_range;
+pub mod semantic_tokens;
 pub mod signature_help;
 pub mod state;
 pub mod state_handlers;
 pub mod statement_range;
 pub mod symbols;
+pub mod todo_markers;
 pub mod traits;
+pub mod type_hierarchy;
 pub mod util;
 
 // These send LSP messages in a non-async and non-blocking way.
@@ -8,7 +8,6 @@
 use std::path::Path;
 
 use anyhow::anyhow;
-use ropey::Rope;
 use stdext::unwrap::IntoResult;
 use stdext::*;
 use tower_lsp::lsp_types::Location;
@@ -21,28 +20,29 @@ use tree_sitter::Point;
 use walkdir::WalkDir;
 
 use crate::lsp;
+use crate::lsp::definitions::symbol_text;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::convert_point_to_position;
 use crate::lsp::encoding::convert_position_to_point;
 use crate::lsp::indexer::filter_entry;
 use crate::lsp::state::with_document;
 use crate::lsp::state::WorldState;
-use crate::lsp::traits::cursor::TreeCursorExt;
-use crate::lsp::traits::rope::RopeExt;
+use crate::lsp::traits::node::NodeExt;
 use crate::lsp::traits::url::UrlExt;
+use crate::treesitter::BinaryOperatorType;
 use crate::treesitter::ExtractOperatorType;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
 #[derive(Debug, PartialEq)]
-enum ReferenceKind {
+pub(crate) enum ReferenceKind {
     SymbolName, // a regular R symbol
     DollarName, // a dollar name, following '$'
     AtName,     // a slot name, following '@'
 }
 
 // Assuming `x` is an `identifier`, is it the RHS of a `$` or `@`?
-fn node_reference_kind(x: &Node) -> ReferenceKind {
+pub(crate) fn node_reference_kind(x: &Node) -> ReferenceKind {
     let Some(parent) = x.parent() else {
         // No `parent`, must be a regular symbol
         return ReferenceKind::SymbolName;
@@ -70,14 +70,14 @@ fn node_reference_kind(x: &Node) -> ReferenceKind {
     }
 }
 
-struct Context {
-    kind: ReferenceKind,
-    symbol: String,
+pub(crate) struct Context {
+    pub(crate) kind: ReferenceKind,
+    pub(crate) symbol: String,
 }
 
-fn add_reference(node: &Node, contents: &Rope, path: &Path, locations: &mut Vec<Location>) {
-    let start = convert_point_to_position(contents, node.start_position());
-    let end = convert_point_to_position(contents, node.end_position());
+fn add_reference(node: &Node, document: &Document, path: &Path, locations: &mut Vec<Location>) {
+    let start = convert_point_to_position(&document.contents, node.start_position());
+    let end = convert_point_to_position(&document.contents, node.end_position());
 
     let location = Location::new(
         Url::from_file_path(path).expect("valid path"),
@@ -86,116 +86,325 @@ fn add_reference(node: &Node, contents: &Rope, path: &Path, locations: &mut Vec<
     locations.push(location);
 }
 
-fn found_match(node: &Node, contents: &Rope, context: &Context) -> bool {
+fn found_match(node: &Node, document: &Document, scope: &Node, context: &Context) -> bool {
     if !node.is_identifier() {
         return false;
     }
 
-    let symbol = contents.node_slice(node).unwrap().to_string();
+    let symbol = symbol_text(document, node);
     if symbol != context.symbol {
         return false;
     }
 
-    context.kind == node_reference_kind(node)
+    if context.kind != node_reference_kind(node) {
+        return false;
+    }
+
+    // The names of arguments, like `x` in `f(x = 1)`, aren't references
+    if is_argument_name(node) {
+        return false;
+    }
+
+    // `$` and `@` names aren't scoped
+    if context.kind != ReferenceKind::SymbolName {
+        return true;
+    }
+
+    binding_scope(document, *node, &symbol) == *scope
 }
 
-fn build_context(uri: &Url, position: Position, state: &WorldState) -> anyhow::Result<Context> {
-    // Unwrap the URL.
-    let path = uri.file_path()?;
+/// The scope binding `symbol` where `node` is: the innermost function that
+/// defines it as a parameter or with a local assignment, or the program for
+/// top-level and global symbols. The target of a super assignment is bound
+/// in a scope enclosing its function.
+pub(crate) fn binding_scope<'tree>(
+    document: &Document,
+    node: Node<'tree>,
+    symbol: &str,
+) -> Node<'tree> {
+    let mut start = node;
+
+    if is_super_assignment_target(&node) {
+        if let Some(parent) = node
+            .ancestors()
+            .find(|node| node.is_function_definition())
+            .and_then(|function| function.parent())
+        {
+            start = parent;
+        }
+    }
 
-    // Figure out the identifier we're looking for.
-    let context = with_document(path.as_path(), state, |document| {
-        let ast = &document.ast;
-        let contents = &document.contents;
-        let point = convert_position_to_point(contents, position);
+    let mut scope = start;
+    for node in start.ancestors() {
+        if node.is_function_definition() && function_binds(document, node, symbol) {
+            return node;
+        }
+        scope = node;
+    }
 
-        let mut node = ast
-            .root_node()
-            .descendant_for_point_range(point, point)
-            .into_result()?;
+    // The root of the tree
+    scope
+}
+
+/// Whether `symbol` is bound in `scope`, a function or the program
+pub(crate) fn scope_binds(document: &Document, scope: Node, symbol: &str) -> bool {
+    if scope.is_function_definition() {
+        function_binds(document, scope, symbol)
+    } else {
+        assigns(document, scope, symbol)
+    }
+}
+
+/// Whether `symbol` is a parameter of `function` or is assigned in its body
+fn function_binds(document: &Document, function: Node, symbol: &str) -> bool {
+    if let Some(parameters) = function.child_by_field_name("parameters") {
+        let mut cursor = parameters.walk();
+        for node in parameters.children(&mut cursor) {
+            if node.node_type() != NodeType::Parameter {
+                continue;
+            }
+            let Some(name) = node.child_by_field_name("name") else {
+                continue;
+            };
+            if name.is_identifier() && symbol_text(document, &name) == symbol {
+                return true;
+            }
+        }
+    }
+
+    let Some(body) = function.child_by_field_name("body") else {
+        return false;
+    };
+
+    assigns(document, body, symbol)
+}
 
-        // Check and see if we got an identifier. If we didn't, we might need to use
-        // some heuristics to look around. Unfortunately, it seems like if you double-click
-        // to select an identifier, and then use Right Click -> Find All References, the
-        // position received by the LSP maps to the _end_ of the selected range, which
-        // is technically not part of the associated identifier's range. In addition, we
-        // can't just subtract 1 from the position column since that would then fail to
-        // resolve the correct identifier when the cursor is located at the start of the
-        // identifier.
-        if !node.is_identifier() && point.column > 0 {
-            let point = Point::new(point.row, point.column - 1);
-            node = ast
-                .root_node()
-                .descendant_for_point_range(point, point)
-                .into_result()?;
+/// Whether `symbol` is assigned in `node`, outside of nested functions, or is
+/// the variable of a `for` loop. Super assignments don't count as they don't
+/// bind in the scope of `node`.
+fn assigns(document: &Document, node: Node, symbol: &str) -> bool {
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        // Functions create a new scope
+        if node.is_function_definition() {
+            continue;
         }
 
-        // double check that we found an identifier
-        if !node.is_identifier() {
-            return Err(anyhow!(
-                "couldn't find an identifier associated with point {point:?}",
-            ));
+        let target = match node.node_type() {
+            NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment) |
+            NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) => {
+                node.child_by_field_name("lhs")
+            },
+            NodeType::BinaryOperator(BinaryOperatorType::RightAssignment) => {
+                node.child_by_field_name("rhs")
+            },
+            NodeType::ForStatement => node.child_by_field_name("variable"),
+            _ => None,
+        };
+
+        if let Some(target) = target {
+            if target.is_identifier_or_string() && symbol_text(document, &target) == symbol {
+                return true;
+            }
         }
 
-        let kind = node_reference_kind(&node);
+        let mut cursor = node.walk();
+        stack.extend(node.children(&mut cursor));
+    }
 
-        // return identifier text contents
-        let symbol = document.contents.node_slice(&node)?.to_string();
+    false
+}
 
-        Ok(Context { kind, symbol })
-    });
+/// If `node` is the target of an assignment, the operator and the field of
+/// the side it is on
+fn assignment_target_side(node: &Node) -> Option<(BinaryOperatorType, &'static str)> {
+    let parent = node.parent()?;
+    let NodeType::BinaryOperator(operator) = parent.node_type() else {
+        return None;
+    };
 
-    return context;
+    let side = match operator {
+        BinaryOperatorType::EqualsAssignment |
+        BinaryOperatorType::LeftAssignment |
+        BinaryOperatorType::LeftSuperAssignment => "lhs",
+        BinaryOperatorType::RightAssignment | BinaryOperatorType::RightSuperAssignment => "rhs",
+        _ => return None,
+    };
+
+    if parent.child_by_field_name(side) != Some(*node) {
+        return None;
+    }
+
+    Some((operator, side))
 }
 
-fn find_references_in_folder(
-    context: &Context,
-    path: &Path,
-    locations: &mut Vec<Location>,
+fn is_super_assignment_target(node: &Node) -> bool {
+    matches!(
+        assignment_target_side(node),
+        Some((
+            BinaryOperatorType::LeftSuperAssignment | BinaryOperatorType::RightSuperAssignment,
+            _
+        ))
+    )
+}
+
+/// Whether `node` is the name of an argument in a call, like `x` in `f(x = 1)`
+pub(crate) fn is_argument_name(node: &Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    parent.node_type() == NodeType::Argument && parent.child_by_field_name("name") == Some(*node)
+}
+
+/// Whether `node` is the name of a parameter, the target of an assignment, or
+/// the variable of a `for` loop
+fn is_declaration(node: &Node) -> bool {
+    if assignment_target_side(node).is_some() {
+        return true;
+    }
+
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    match parent.node_type() {
+        NodeType::Parameter => parent.child_by_field_name("name") == Some(*node),
+        NodeType::ForStatement => parent.child_by_field_name("variable") == Some(*node),
+        _ => false,
+    }
+}
+
+pub(crate) fn build_context<'tree>(
+    document: &'tree Document,
+    position: Position,
+) -> anyhow::Result<(Context, Node<'tree>)> {
+    let ast = &document.ast;
+    let contents = &document.contents;
+    let point = convert_position_to_point(contents, position);
+
+    let mut node = ast
+        .root_node()
+        .descendant_for_point_range(point, point)
+        .into_result()?;
+
+    // Check and see if we got an identifier. If we didn't, we might need to use
+    // some heuristics to look around. Unfortunately, it seems like if you double-click
+    // to select an identifier, and then use Right Click -> Find All References, the
+    // position received by the LSP maps to the _end_ of the selected range, which
+    // is technically not part of the associated identifier's range. In addition, we
+    // can't just subtract 1 from the position column since that would then fail to
+    // resolve the correct identifier when the cursor is located at the start of the
+    // identifier.
+    if !node.is_identifier() && point.column > 0 {
+        let point = Point::new(point.row, point.column - 1);
+        node = ast
+            .root_node()
+            .descendant_for_point_range(point, point)
+            .into_result()?;
+    }
+
+    // double check that we found an identifier
+    if !node.is_identifier() {
+        return Err(anyhow!(
+            "couldn't find an identifier associated with point {point:?}",
+        ));
+    }
+
+    let kind = node_reference_kind(&node);
+
+    // return identifier text contents
+    let symbol = symbol_text(document, &node);
+
+    let context = Context { kind, symbol };
+
+    Ok((context, node))
+}
+
+/// Calls `callback` with the R files of the workspace folders, except `skip`
+pub(crate) fn for_each_workspace_document(
     state: &WorldState,
+    skip: &Path,
+    mut callback: impl FnMut(&Path, &Document),
 ) {
-    let walker = WalkDir::new(path);
-    for entry in walker.into_iter().filter_entry(|entry| filter_entry(entry)) {
-        let entry = unwrap!(entry, Err(_) => { continue; });
-        let path = entry.path();
-        let ext = unwrap!(path.extension(), None => { continue; });
-        if ext != "r" && ext != "R" {
+    for folder in state.workspace.folders.iter() {
+        let Ok(folder) = folder.to_file_path() else {
             continue;
-        }
-
-        lsp::log_info!("found R file {}", path.display());
-        let result = with_document(path, state, |document| {
-            find_references_in_document(context, path, document, locations);
-            return Ok(());
-        });
+        };
+        lsp::log_info!("searching references in folder {}", folder.display());
+
+        let walker = WalkDir::new(folder);
+        for entry in walker.into_iter().filter_entry(|entry| filter_entry(entry)) {
+            let entry = unwrap!(entry, Err(_) => { continue; });
+            let path = entry.path();
+            let ext = unwrap!(path.extension(), None => { continue; });
+            if ext != "r" && ext != "R" {
+                continue;
+            }
 
-        match result {
-            Ok(result) => result,
-            Err(_error) => {
-                lsp::log_warn!("error retrieving document for path {}", path.display());
+            // Already searched
+            if path == skip {
                 continue;
-            },
+            }
+
+            lsp::log_info!("found R file {}", path.display());
+            let result = with_document(path, state, |document| {
+                callback(path, document);
+                return Ok(());
+            });
+
+            match result {
+                Ok(result) => result,
+                Err(_error) => {
+                    lsp::log_warn!("error retrieving document for path {}", path.display());
+                    continue;
+                },
+            }
         }
     }
 }
 
+/// The identifiers of `document` that refer to the binding of the context in
+/// `scope`, in document order
+pub(crate) fn reference_nodes<'tree>(
+    context: &Context,
+    document: &'tree Document,
+    scope: &Node,
+) -> Vec<Node<'tree>> {
+    let mut nodes = vec![];
+    let mut stack = vec![document.ast.root_node()];
+
+    while let Some(node) = stack.pop() {
+        if found_match(&node, document, scope, context) {
+            nodes.push(node);
+        }
+
+        // Children are pushed in reverse so they are visited in document order
+        let mut cursor = node.walk();
+        let children: Vec<Node<'tree>> = node.children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+
+    nodes
+}
+
+/// Adds the references of `document` to `locations`. While `skip_declaration`
+/// is set, the next declaration is skipped and the flag is reset, so that
+/// only the first binding is left out when the declaration isn't requested.
+/// Later assignments are references to the same binding.
 fn find_references_in_document(
     context: &Context,
     path: &Path,
     document: &Document,
+    scope: &Node,
+    skip_declaration: &mut bool,
     locations: &mut Vec<Location>,
 ) {
-    let ast = &document.ast;
-    let contents = &document.contents;
-
-    let mut cursor = ast.walk();
-    cursor.recurse(|node| {
-        if found_match(&node, contents, &context) {
-            add_reference(&node, contents, path, locations);
+    for node in reference_nodes(context, document, scope) {
+        if *skip_declaration && is_declaration(&node) {
+            *skip_declaration = false;
+            continue;
         }
-
-        return true;
-    });
+        add_reference(&node, document, path, locations);
+    }
 }
 
 pub(crate) fn find_references(
@@ -208,19 +417,195 @@ pub(crate) fn find_references(
     // Extract relevant parameters.
     let uri = params.text_document_position.text_document.uri;
     let position = params.text_document_position.position;
+    let mut skip_declaration = !params.context.include_declaration;
+
+    let path = uri.file_path()?;
 
-    // Figure out what we're looking for.
-    let context = unwrap!(build_context(&uri, position, state), Err(err) => {
+    // Figure out what we're looking for, and search the document first.
+    // Symbols bound in one of its functions can't be referred to elsewhere.
+    let result = with_document(path.as_path(), state, |document| {
+        let (context, node) = build_context(document, position)?;
+
+        let scope = match context.kind {
+            ReferenceKind::SymbolName => binding_scope(document, node, &context.symbol),
+            _ => document.ast.root_node(),
+        };
+        find_references_in_document(
+            &context,
+            &path,
+            document,
+            &scope,
+            &mut skip_declaration,
+            &mut locations,
+        );
+
+        Ok((context, scope.is_program()))
+    });
+
+    let (context, top_level) = unwrap!(result, Err(err) => {
         return Err(anyhow!("Failed to find build context at position {position:?}: {err:?}"));
     });
 
-    // Now, start searching through workspace folders for references to that identifier.
-    for folder in state.workspace.folders.iter() {
-        if let Ok(path) = folder.to_file_path() {
-            lsp::log_info!("searching references in folder {}", path.display());
-            find_references_in_folder(&context, &path, &mut locations, state);
-        }
+    if !top_level {
+        return Ok(locations);
     }
 
+    // Now, start searching through workspace folders for references to that identifier.
+    for_each_workspace_document(state, &path, |path, document| {
+        let scope = document.ast.root_node();
+        find_references_in_document(
+            &context,
+            path,
+            document,
+            &scope,
+            &mut skip_declaration,
+            &mut locations,
+        );
+    });
+
     return Ok(locations);
 }
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::ReferenceContext;
+    use tower_lsp::lsp_types::ReferenceParams;
+    use tower_lsp::lsp_types::TextDocumentIdentifier;
+    use tower_lsp::lsp_types::TextDocumentPositionParams;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::references::find_references;
+    use crate::lsp::state::WorldState;
+
+    /// The start positions of the references to the symbol at `position`
+    fn references(text: &str, position: Position, include_declaration: bool) -> Vec<Position> {
+        let uri = Url::parse("file:///test_references.R").unwrap();
+
+        let mut state = WorldState::default();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(text, None));
+
+        let params = ReferenceParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: ReferenceContext {
+                include_declaration,
+            },
+        };
+
+        find_references(params, &state)
+            .unwrap()
+            .into_iter()
+            .map(|location| location.range.start)
+            .collect()
+    }
+
+    #[test]
+    fn test_references_local() {
+        let text = "x <- 1
+f <- function(x) {
+  x + 1
+}
+g <- function() {
+  x <- 2
+  h <- function(x) x
+  x
+}
+x
+";
+
+        // The parameter of `f`
+        assert_eq!(references(text, Position::new(2, 2), true), vec![
+            Position::new(1, 14),
+            Position::new(2, 2),
+        ]);
+
+        // The local variable of `g` is shadowed by the parameter of `h`
+        assert_eq!(references(text, Position::new(7, 2), true), vec![
+            Position::new(5, 2),
+            Position::new(7, 2),
+        ]);
+
+        // The top-level variable isn't the parameter of `f` or the local
+        // variable of `g`
+        assert_eq!(references(text, Position::new(9, 0), true), vec![
+            Position::new(0, 0),
+            Position::new(9, 0),
+        ]);
+    }
+
+    #[test]
+    fn test_references_include_declaration() {
+        let text = "f <- function(x) {
+  y <- x
+  y <- y + 1
+  y
+}
+";
+        // Only the first binding is left out, the reassignment is a
+        // reference
+        assert_eq!(references(text, Position::new(3, 2), false), vec![
+            Position::new(2, 2),
+            Position::new(2, 7),
+            Position::new(3, 2),
+        ]);
+        assert_eq!(references(text, Position::new(1, 7), false), vec![
+            Position::new(1, 7)
+        ]);
+        assert_eq!(references(text, Position::new(1, 7), true), vec![
+            Position::new(0, 14),
+            Position::new(1, 7),
+        ]);
+    }
+
+    #[test]
+    fn test_references_for_variable() {
+        let text = "i <- 0
+f <- function(xs) {
+  for (i in xs) print(i)
+  i
+}
+";
+        // The loop variable is bound in `f`, it isn't the top-level `i`
+        assert_eq!(references(text, Position::new(3, 2), true), vec![
+            Position::new(2, 7),
+            Position::new(2, 22),
+            Position::new(3, 2),
+        ]);
+        assert_eq!(references(text, Position::new(3, 2), false), vec![
+            Position::new(2, 22),
+            Position::new(3, 2),
+        ]);
+        assert_eq!(references(text, Position::new(0, 0), true), vec![
+            Position::new(0, 0)
+        ]);
+    }
+
+    #[test]
+    fn test_references_super_assignment() {
+        let text = "f <- function() {
+  count <- 0
+  g <- function() count <<- count + 1
+  count
+}
+count
+";
+        // The super assignment of `g` refers to the variable of `f`
+        assert_eq!(references(text, Position::new(3, 2), true), vec![
+            Position::new(1, 2),
+            Position::new(2, 18),
+            Position::new(2, 28),
+            Position::new(3, 2),
+        ]);
+        assert_eq!(references(text, Position::new(5, 0), true), vec![
+            Position::new(5, 0)
+        ]);
+    }
+}
@@ -0,0 +1,375 @@
+//
+// rename.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use harp::utils::is_symbol_valid;
+use tower_lsp::lsp_types::PrepareRenameResponse;
+use tower_lsp::lsp_types::RenameParams;
+use tower_lsp::lsp_types::TextDocumentPositionParams;
+use tower_lsp::lsp_types::TextEdit;
+use tower_lsp::lsp_types::Url;
+use tower_lsp::lsp_types::WorkspaceEdit;
+use tree_sitter::Node;
+
+use crate::lsp::definitions::node_range;
+use crate::lsp::definitions::symbol_text;
+use crate::lsp::documents::Document;
+use crate::lsp::references::binding_scope;
+use crate::lsp::references::build_context;
+use crate::lsp::references::for_each_workspace_document;
+use crate::lsp::references::is_argument_name;
+use crate::lsp::references::node_reference_kind;
+use crate::lsp::references::reference_nodes;
+use crate::lsp::references::scope_binds;
+use crate::lsp::references::Context;
+use crate::lsp::references::ReferenceKind;
+use crate::lsp::state::with_document;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::node::NodeExt;
+use crate::lsp::traits::url::UrlExt;
+use crate::treesitter::NodeTypeExt;
+
+/// Words that can only be used as names with backticks
+const RESERVED_WORDS: &[&str] = &[
+    "if",
+    "else",
+    "repeat",
+    "while",
+    "function",
+    "for",
+    "in",
+    "next",
+    "break",
+    "TRUE",
+    "FALSE",
+    "NULL",
+    "Inf",
+    "NaN",
+    "NA",
+    "NA_integer_",
+    "NA_real_",
+    "NA_character_",
+    "NA_complex_",
+];
+
+pub(crate) fn prepare_rename(
+    params: TextDocumentPositionParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<PrepareRenameResponse>> {
+    let path = params.text_document.uri.file_path()?;
+
+    with_document(path.as_path(), state, |document| {
+        let (context, node) = build_context(document, params.position)?;
+        check_renameable(&context, &node)?;
+
+        Ok(Some(PrepareRenameResponse::RangeWithPlaceholder {
+            range: node_range(document, &node),
+            placeholder: context.symbol,
+        }))
+    })
+}
+
+/// Renames the symbol at the cursor and the references to its binding. The
+/// rename is refused when the new name would be captured by another binding,
+/// or would capture references to another binding.
+pub(crate) fn rename(
+    params: RenameParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<WorkspaceEdit>> {
+    let uri = params.text_document_position.text_document.uri;
+    let position = params.text_document_position.position;
+    let (new_symbol, new_text) = rename_text(&params.new_name)?;
+
+    let path = uri.file_path()?;
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+
+    let result = with_document(path.as_path(), state, |document| {
+        let (context, node) = build_context(document, position)?;
+        check_renameable(&context, &node)?;
+
+        let scope = binding_scope(document, node, &context.symbol);
+        let edits = rename_in_document(&context, document, &scope, &new_symbol, &new_text)?;
+        changes.insert(uri.clone(), edits);
+
+        Ok((context, scope.is_program()))
+    });
+    let (context, top_level) = result?;
+
+    if context.symbol == new_symbol {
+        return Ok(None);
+    }
+
+    // Top-level symbols may be referred to from the other files of the
+    // workspace
+    if top_level {
+        let mut error = None;
+
+        for_each_workspace_document(state, &path, |path, document| {
+            if error.is_some() {
+                return;
+            }
+
+            let scope = document.ast.root_node();
+            match rename_in_document(&context, document, &scope, &new_symbol, &new_text) {
+                Ok(edits) if edits.is_empty() => (),
+                Ok(edits) => {
+                    if let Ok(uri) = Url::from_file_path(path) {
+                        changes.insert(uri, edits);
+                    }
+                },
+                Err(err) => error = Some(err),
+            }
+        });
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+    }
+
+    Ok(Some(WorkspaceEdit::new(changes)))
+}
+
+fn check_renameable(context: &Context, node: &Node) -> anyhow::Result<()> {
+    if context.kind != ReferenceKind::SymbolName {
+        return Err(anyhow!("Can't rename `$` and `@` names."));
+    }
+    if is_argument_name(node) {
+        return Err(anyhow!("Can't rename the name of an argument."));
+    }
+    Ok(())
+}
+
+/// The symbol of the new name, without backticks, and the text it's written
+/// with, which has backticks if the symbol isn't syntactic
+fn rename_text(new_name: &str) -> anyhow::Result<(String, String)> {
+    let symbol = new_name
+        .strip_prefix('`')
+        .and_then(|name| name.strip_suffix('`'))
+        .unwrap_or(new_name);
+
+    if symbol.is_empty() || symbol.contains(['`', '\n', '\r']) {
+        return Err(anyhow!("`{new_name}` is not a valid R name."));
+    }
+
+    // `...` and `..1` have special meanings, even with backticks
+    let is_dots = symbol == "..." ||
+        symbol
+            .strip_prefix("..")
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+    if is_dots {
+        return Err(anyhow!("Can't rename to `{symbol}`."));
+    }
+
+    // A leading dot can't be followed by a digit, `.1` is a number
+    let is_number = symbol
+        .strip_prefix('.')
+        .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()));
+
+    let text = if RESERVED_WORDS.contains(&symbol) || is_number || !is_symbol_valid(symbol) {
+        format!("`{symbol}`")
+    } else {
+        symbol.to_string()
+    };
+
+    Ok((symbol.to_string(), text))
+}
+
+/// The edits renaming the references of `document` to the binding in `scope`.
+/// Fails if renaming changes the meaning of the code.
+fn rename_in_document(
+    context: &Context,
+    document: &Document,
+    scope: &Node,
+    new_symbol: &str,
+    new_text: &str,
+) -> anyhow::Result<Vec<TextEdit>> {
+    let nodes = reference_nodes(context, document, scope);
+    if nodes.is_empty() || context.symbol == new_symbol {
+        return Ok(vec![]);
+    }
+
+    let line = |node: &Node| node.start_position().row + 1;
+
+    // Two bindings would be merged
+    if scope_binds(document, *scope, new_symbol) {
+        return Err(anyhow!(
+            "Can't rename `{}` to `{new_symbol}`, which is already defined in the same scope.",
+            context.symbol
+        ));
+    }
+
+    // A renamed reference would be captured by a binding of the new name in
+    // a nested function
+    for node in nodes.iter() {
+        let target = binding_scope(document, *node, new_symbol);
+        if is_nested_in(&target, scope) {
+            return Err(anyhow!(
+                "Can't rename `{}` to `{new_symbol}`, the reference at line {} would refer to a nested definition.",
+                context.symbol,
+                line(node)
+            ));
+        }
+    }
+
+    // An existing reference to the new name would be captured by the renamed
+    // binding
+    let mut stack = vec![*scope];
+    while let Some(node) = stack.pop() {
+        if node.is_identifier() &&
+            node_reference_kind(&node) == ReferenceKind::SymbolName &&
+            !is_argument_name(&node) &&
+            symbol_text(document, &node) == new_symbol &&
+            !is_nested_in(&binding_scope(document, node, new_symbol), scope)
+        {
+            return Err(anyhow!(
+                "Can't rename `{}` to `{new_symbol}`, which is already used at line {}.",
+                context.symbol,
+                line(&node)
+            ));
+        }
+
+        let mut cursor = node.walk();
+        stack.extend(node.children(&mut cursor));
+    }
+
+    let edits = nodes
+        .iter()
+        .map(|node| TextEdit::new(node_range(document, node), new_text.to_string()))
+        .collect();
+
+    Ok(edits)
+}
+
+/// Whether `node` is a strict descendant of `scope`
+fn is_nested_in(node: &Node, scope: &Node) -> bool {
+    node != scope && node.ancestors().any(|ancestor| ancestor == *scope)
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::RenameParams;
+    use tower_lsp::lsp_types::TextDocumentIdentifier;
+    use tower_lsp::lsp_types::TextDocumentPositionParams;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::rename::rename;
+    use crate::lsp::rename::rename_text;
+    use crate::lsp::state::WorldState;
+
+    /// The document after renaming the symbol at `position` to `new_name`
+    fn renamed(text: &str, position: Position, new_name: &str) -> anyhow::Result<String> {
+        let uri = Url::parse("file:///test_rename.R").unwrap();
+
+        let mut state = WorldState::default();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(text, None));
+
+        let params = RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position,
+            },
+            new_name: new_name.to_string(),
+            work_done_progress_params: Default::default(),
+        };
+
+        let Some(edit) = rename(params, &state)? else {
+            return Ok(text.to_string());
+        };
+
+        // Apply the edits from the end so the earlier ranges stay valid
+        let mut edits = edit.changes.unwrap().remove(&uri).unwrap_or_default();
+        edits.sort_by_key(|edit| edit.range.start);
+
+        let mut lines: Vec<String> = text.lines().map(String::from).collect();
+        for edit in edits.iter().rev() {
+            assert_eq!(edit.range.start.line, edit.range.end.line);
+            let line = &mut lines[edit.range.start.line as usize];
+            let start = edit.range.start.character as usize;
+            let end = edit.range.end.character as usize;
+            line.replace_range(start..end, &edit.new_text);
+        }
+
+        Ok(lines.join("\n") + "\n")
+    }
+
+    #[test]
+    fn test_rename_local() {
+        let text = "x <- 1
+f <- function(x) {
+  g(x = x)
+}
+";
+        // The argument name of `g()` and the top-level `x` are left alone
+        assert_eq!(
+            renamed(text, Position::new(2, 8), "value").unwrap(),
+            "x <- 1
+f <- function(value) {
+  g(x = value)
+}
+"
+        );
+
+        // Names that aren't syntactic get backticks
+        assert_eq!(
+            renamed(text, Position::new(0, 0), "my value").unwrap(),
+            "`my value` <- 1
+f <- function(x) {
+  g(x = x)
+}
+"
+        );
+
+        // Argument names can't be renamed
+        assert!(renamed(text, Position::new(2, 4), "value").is_err());
+    }
+
+    #[test]
+    fn test_rename_collisions() {
+        let text = "f <- function(x) {
+  y <- 1
+  h <- function(z) z + x
+  x + y
+}
+";
+        // Already defined in the scope
+        assert!(renamed(text, Position::new(3, 2), "y").is_err());
+
+        // The reference in `h()` would refer to its parameter
+        assert!(renamed(text, Position::new(3, 2), "z").is_err());
+
+        // Fine to shadow a parameter of a nested function that doesn't refer
+        // to the renamed binding
+        assert!(renamed(text, Position::new(1, 2), "z").is_ok());
+
+        // The global `print` would refer to the renamed binding
+        let text = "f <- function(x) {
+  print(x)
+}
+";
+        assert!(renamed(text, Position::new(1, 8), "print").is_err());
+    }
+
+    #[test]
+    fn test_rename_text() {
+        assert_eq!(rename_text("foo.bar").unwrap().1, "foo.bar");
+        assert_eq!(rename_text("if").unwrap().1, "`if`");
+        assert_eq!(rename_text(".1x").unwrap().1, "`.1x`");
+        assert_eq!(
+            rename_text("`a b`").unwrap(),
+            (String::from("a b"), String::from("`a b`"))
+        );
+        assert!(rename_text("").is_err());
+        assert!(rename_text("a`b").is_err());
+        assert!(rename_text("..1").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//
+// semantic_tokens.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use harp::RObject;
+use libr::SEXP;
+use serde::Serialize;
+use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::SemanticTokenType;
+use tree_sitter::Node;
+
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Anonymous nodes that are R keywords. Other keywords such as `NULL` or
+/// `break` have their own node types, see `NodeTypeExt::is_keyword()`.
+const KEYWORDS: &[&str] = &[
+    "function", "\\", "if", "else", "for", "in", "while", "repeat",
+];
+
+#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokenSpan {
+    pub range: Range,
+    /// One of the standard LSP semantic token types, e.g. `keyword`.
+    pub token_type: String,
+}
+
+/// Classify the tokens of a document, in document order.
+///
+/// Parse errors are tolerated: tokens are still classified from whatever tree
+/// tree-sitter recovered, so callers should check `has_error()` on the AST
+/// before relying on the result being complete.
+pub(crate) fn semantic_tokens(document: &Document) -> Vec<SemanticTokenSpan> {
+    let mut tokens = vec![];
+
+    document.ast.walk().recurse(|node| {
+        let Some(token_type) = token_type(&node) else {
+            return true;
+        };
+
+        tokens.push(SemanticTokenSpan {
+            range: convert_tree_sitter_range_to_lsp_range(&document.contents, node.range()),
+            token_type: token_type.as_str().to_string(),
+        });
+
+        // Tokens are leaves, apart from strings whose contents we don't
+        // classify separately
+        false
+    });
+
+    tokens
+}
+
+fn token_type(node: &Node) -> Option<SemanticTokenType> {
+    if node.is_keyword() {
+        return Some(SemanticTokenType::KEYWORD);
+    }
+
+    match node.node_type() {
+        NodeType::Comment => Some(SemanticTokenType::COMMENT),
+        NodeType::String => Some(SemanticTokenType::STRING),
+        NodeType::Integer | NodeType::Float | NodeType::Complex => Some(SemanticTokenType::NUMBER),
+        NodeType::Identifier | NodeType::Dots | NodeType::DotDotI => Some(identifier_type(node)),
+        NodeType::Anonymous(kind) if KEYWORDS.contains(&kind.as_str()) => {
+            Some(SemanticTokenType::KEYWORD)
+        },
+        NodeType::Anonymous(_) if is_field(node, "operator") => Some(SemanticTokenType::OPERATOR),
+        _ => None,
+    }
+}
+
+fn identifier_type(node: &Node) -> SemanticTokenType {
+    let Some(parent) = node.parent() else {
+        return SemanticTokenType::VARIABLE;
+    };
+
+    match parent.node_type() {
+        NodeType::Call if is_field(node, "function") => SemanticTokenType::FUNCTION,
+        NodeType::Parameter | NodeType::Argument if is_field(node, "name") => {
+            SemanticTokenType::PARAMETER
+        },
+        NodeType::ExtractOperator(_) if is_field(node, "rhs") => SemanticTokenType::PROPERTY,
+        NodeType::NamespaceOperator(_) if is_field(node, "lhs") => SemanticTokenType::NAMESPACE,
+        NodeType::NamespaceOperator(_) if is_field(&parent, "function") => {
+            SemanticTokenType::FUNCTION
+        },
+        _ => SemanticTokenType::VARIABLE,
+    }
+}
+
+fn is_field(node: &Node, field: &str) -> bool {
+    node.parent()
+        .and_then(|parent| parent.child_by_field_name(field))
+        .is_some_and(|child| child.id() == node.id())
+}
+
+/// Classify the tokens of some R code that isn't backed by a document, such
+/// as deparsed objects. Returns a list of `tokens` and a `valid` flag that is
+/// `FALSE` when the code doesn't parse as standalone R.
+#[harp::register]
+pub unsafe extern "C" fn ps_semantic_tokens(code: SEXP) -> anyhow::Result<SEXP> {
+    let code: String = RObject::view(code).try_into()?;
+    let document = Document::new(&code, None);
+
+    let out = serde_json::json!({
+        "valid": !document.ast.root_node().has_error(),
+        "tokens": semantic_tokens(&document),
+    });
+
+    Ok(RObject::try_from(out)?.sexp)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::documents::Document;
+    use crate::lsp::semantic_tokens::semantic_tokens;
+
+    fn classify(code: &str) -> Vec<(String, String)> {
+        let document = Document::new(code, None);
+
+        semantic_tokens(&document)
+            .into_iter()
+            .map(|token| {
+                let start = token.range.start.character as usize;
+                let end = token.range.end.character as usize;
+                (code[start..end].to_string(), token.token_type)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let tokens =
+            classify("f <- function(x, n = 1L) if (x) base::rep(x$a, times = n) else NULL");
+
+        let expected = vec![
+            ("f", "variable"),
+            ("<-", "operator"),
+            ("function", "keyword"),
+            ("x", "parameter"),
+            ("n", "parameter"),
+            ("1L", "number"),
+            ("if", "keyword"),
+            ("x", "variable"),
+            ("base", "namespace"),
+            ("::", "operator"),
+            ("rep", "function"),
+            ("x", "variable"),
+            ("$", "operator"),
+            ("a", "property"),
+            ("times", "parameter"),
+            ("n", "variable"),
+            ("else", "keyword"),
+            ("NULL", "keyword"),
+        ];
+        let expected: Vec<(String, String)> = expected
+            .into_iter()
+            .map(|(text, kind)| (text.to_string(), kind.to_string()))
+            .collect();
+
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_semantic_tokens_strings_and_comments() {
+        let tokens = classify("'a' # comment");
+        assert_eq!(tokens, vec![
+            (String::from("'a'"), String::from("string")),
+            (String::from("# comment"), String::from("comment")),
+        ]);
+    }
+}
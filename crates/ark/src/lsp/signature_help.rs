@@ -280,6 +280,13 @@ pub(crate) fn r_signature_help(context: &DocumentContext) -> anyhow::Result<Opti
                 continue;
             }
 
+            // Arguments after `...` can only be supplied by name, so any
+            // remaining unnamed arguments are collected by `...`.
+            if argument.name == "..." {
+                offset = Some(index as u32);
+                break;
+            }
+
             // Otherwise, check and see if we have any remaining commas.
             if num_unnamed_arguments > 0 {
                 num_unnamed_arguments -= 1;
@@ -533,6 +540,35 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_signature_help_active_parameter() {
+        crate::r_task(|| {
+            let active_parameter = |code: &str| {
+                let (text, point) = point_from_cursor(code);
+                let document = Document::new(&text, None);
+                let context = DocumentContext::new(&document, point, None);
+                r_signature_help(&context)
+                    .unwrap()
+                    .unwrap()
+                    .active_parameter
+            };
+
+            // `rnorm(n, mean = 0, sd = 1)`
+            assert_eq!(active_parameter("rnorm(@)"), Some(0));
+            assert_eq!(active_parameter("rnorm(10, @)"), Some(1));
+
+            // Named arguments are skipped when matching positions
+            assert_eq!(active_parameter("rnorm(mean = 1, @)"), Some(0));
+            assert_eq!(active_parameter("rnorm(n = 1, mean = 0, @)"), Some(2));
+            assert_eq!(active_parameter("rnorm(10, sd = @)"), Some(2));
+
+            // `paste(..., sep = " ", collapse = NULL, recycle0 = FALSE)`:
+            // unnamed arguments stay on `...`
+            assert_eq!(active_parameter("paste(1, 2, @)"), Some(0));
+            assert_eq!(active_parameter("paste(1, collapse = @)"), Some(2));
+        })
+    }
+
     #[test]
     fn test_no_signature_help_outside_parentheses() {
         crate::r_task(|| {
@@ -14,16 +14,20 @@ use tower_lsp::lsp_types::CompletionOptions;
 use tower_lsp::lsp_types::ConfigurationItem;
 use tower_lsp::lsp_types::DidChangeConfigurationParams;
 use tower_lsp::lsp_types::DidChangeTextDocumentParams;
+use tower_lsp::lsp_types::DidChangeWatchedFilesParams;
 use tower_lsp::lsp_types::DidCloseTextDocumentParams;
 use tower_lsp::lsp_types::DidOpenTextDocumentParams;
+use tower_lsp::lsp_types::DocumentLinkOptions;
 use tower_lsp::lsp_types::DocumentOnTypeFormattingOptions;
 use tower_lsp::lsp_types::ExecuteCommandOptions;
+use tower_lsp::lsp_types::FileChangeType;
 use tower_lsp::lsp_types::FormattingOptions;
 use tower_lsp::lsp_types::HoverProviderCapability;
 use tower_lsp::lsp_types::ImplementationProviderCapability;
 use tower_lsp::lsp_types::InitializeParams;
 use tower_lsp::lsp_types::InitializeResult;
 use tower_lsp::lsp_types::OneOf;
+use tower_lsp::lsp_types::RenameOptions;
 use tower_lsp::lsp_types::SelectionRangeProviderCapability;
 use tower_lsp::lsp_types::ServerCapabilities;
 use tower_lsp::lsp_types::ServerInfo;
@@ -38,11 +42,14 @@ use tree_sitter::Parser;
 use url::Url;
 
 use crate::lsp;
+use crate::lsp::completions::CompletionsConfig;
 use crate::lsp::config::indent_style_from_lsp;
 use crate::lsp::config::DocumentConfig;
+use crate::lsp::config::VscCompletionsConfig;
 use crate::lsp::config::VscDiagnosticsConfig;
 use crate::lsp::config::VscDocumentConfig;
 use crate::lsp::diagnostics::DiagnosticsConfig;
+use crate::lsp::diagnostics_lintr::remove_lintr_diagnostics;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::get_position_encoding_kind;
 use crate::lsp::indexer;
@@ -52,6 +59,12 @@ use crate::lsp::state::WorldState;
 
 // Handlers that mutate the world state
 
+/// Internal command that reparses all open documents from scratch with fresh
+/// parsers, e.g. to check that a tree drifted through incremental edits
+/// matches a full parse. The grammar is compiled into ark, so this doesn't
+/// pick up a rebuilt grammar, that still requires restarting the session.
+pub(crate) const REPARSE_ALL_COMMAND: &str = "ark.internal.reparseAll";
+
 /// Information sent from the kernel to the LSP after each top-level evaluation.
 #[derive(Debug)]
 pub struct ConsoleInputs {
@@ -83,6 +96,18 @@ pub(crate) fn initialize(
             lsp_state.needs_registration.did_change_configuration = true;
         }
     }
+    if let Some(workspace_caps) = params.capabilities.workspace.as_ref() {
+        if matches!(&workspace_caps.did_change_watched_files, Some(caps) if matches!(caps.dynamic_registration, Some(true)))
+        {
+            lsp_state.needs_registration.did_change_watched_files = true;
+        }
+    }
+    if let Some(doc_caps) = params.capabilities.text_document.as_ref() {
+        if matches!(&doc_caps.type_hierarchy, Some(caps) if matches!(caps.dynamic_registration, Some(true)))
+        {
+            lsp_state.needs_registration.type_hierarchy = true;
+        }
+    }
 
     // Initialize the workspace folders
     let mut folders: Vec<String> = Vec::new();
@@ -114,6 +139,10 @@ pub(crate) fn initialize(
                 TextDocumentSyncKind::INCREMENTAL,
             )),
             selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+            document_link_provider: Some(DocumentLinkOptions {
+                resolve_provider: Some(false),
+                work_done_progress_options: Default::default(),
+            }),
             hover_provider: Some(HoverProviderCapability::from(true)),
             completion_provider: Some(CompletionOptions {
                 resolve_provider: Some(true),
@@ -133,10 +162,14 @@ pub(crate) fn initialize(
             type_definition_provider: None,
             implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
             references_provider: Some(OneOf::Left(true)),
+            rename_provider: Some(OneOf::Right(RenameOptions {
+                prepare_provider: Some(true),
+                work_done_progress_options: Default::default(),
+            })),
             document_symbol_provider: Some(OneOf::Left(true)),
             workspace_symbol_provider: Some(OneOf::Left(true)),
             execute_command_provider: Some(ExecuteCommandOptions {
-                commands: vec![],
+                commands: vec![String::from(REPARSE_ALL_COMMAND)],
                 work_done_progress_options: Default::default(),
             }),
             workspace: Some(WorkspaceServerCapabilities {
@@ -146,6 +179,8 @@ pub(crate) fn initialize(
                 }),
                 file_operations: None,
             }),
+            document_formatting_provider: Some(OneOf::Left(true)),
+            document_range_formatting_provider: Some(OneOf::Left(true)),
             document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
                 first_trigger_character: String::from("\n"),
                 more_trigger_character: None,
@@ -165,11 +200,7 @@ pub(crate) fn did_open(
     let uri = params.text_document.uri;
     let version = params.text_document.version;
 
-    let mut parser = Parser::new();
-    parser
-        .set_language(&tree_sitter_r::LANGUAGE.into())
-        .unwrap();
-
+    let mut parser = new_parser();
     let document = Document::new_with_parser(contents, &mut parser, Some(version));
 
     lsp_state.parsers.insert(uri.clone(), parser);
@@ -198,7 +229,7 @@ pub(crate) fn did_change(
         .get_mut(uri)
         .ok_or(anyhow!("No parser for {uri}"))?;
 
-    doc.on_did_change(&mut parser, &params);
+    doc.on_did_change(&mut parser, &params)?;
 
     update_index(uri, doc);
     lsp::spawn_diagnostics_refresh(uri.clone(), doc.clone(), state.clone());
@@ -206,6 +237,34 @@ pub(crate) fn did_change(
     Ok(())
 }
 
+/// Recreates the parsers of all open documents and reparses them from their
+/// current contents without reusing the old trees, then rebuilds their index
+/// entries. This runs as an
+/// exclusive handler, so it can't interleave with the incremental reparse of
+/// a `did_change` notification. The caller is responsible for refreshing
+/// diagnostics.
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn reparse_all(lsp_state: &mut LspState, state: &mut WorldState) -> anyhow::Result<()> {
+    for (uri, doc) in state.documents.iter_mut() {
+        let mut parser = new_parser();
+
+        let contents = doc.contents.to_string();
+        let mut reparsed = Document::new_with_parser(&contents, &mut parser, doc.version);
+        reparsed.config = doc.config.clone();
+        *doc = reparsed;
+
+        lsp_state.parsers.insert(uri.clone(), parser);
+        update_index(uri, doc);
+    }
+
+    lsp::log_info!(
+        "reparse_all(): reparsed {} documents.",
+        state.documents.len()
+    );
+
+    Ok(())
+}
+
 #[tracing::instrument(level = "info", skip_all)]
 pub(crate) fn did_close(
     params: DidCloseTextDocumentParams,
@@ -216,6 +275,7 @@ pub(crate) fn did_close(
 
     // Publish empty set of diagnostics to clear them
     lsp::publish_diagnostics(uri.clone(), Vec::new(), None);
+    remove_lintr_diagnostics(&uri);
 
     state
         .documents
@@ -263,6 +323,12 @@ pub(crate) fn did_change_formatting_options(
     // indent size, as in the R core sources. So we just ignore the less
     // rich updates in this case.
     if doc.config.indent.indent_size != doc.config.indent.tab_width {
+        log::trace!(
+            "Ignoring formatting options (tab size {}) in favour of the configured indent size {} and tab width {}",
+            opts.tab_size,
+            doc.config.indent.indent_size,
+            doc.config.indent.tab_width
+        );
         return;
     }
 
@@ -293,6 +359,16 @@ async fn update_config(
         .collect();
     items.append(&mut diagnostics_items);
 
+    let completions_keys = VscCompletionsConfig::FIELD_NAMES_AS_ARRAY;
+    let mut completions_items: Vec<ConfigurationItem> = completions_keys
+        .iter()
+        .map(|key| ConfigurationItem {
+            scope_uri: None,
+            section: Some(VscCompletionsConfig::section_from_key(key).into()),
+        })
+        .collect();
+    items.append(&mut completions_items);
+
     // For document configs we collect all pairs of URIs and config keys of
     // interest in a flat vector
     let document_keys = VscDocumentConfig::FIELD_NAMES_AS_ARRAY;
@@ -313,7 +389,8 @@ async fn update_config(
     // by chunk
     let n_document_items = document_keys.len();
     let n_diagnostics_items = diagnostics_keys.len();
-    let n_items = n_diagnostics_items + (n_document_items * uris.len());
+    let n_completions_items = completions_keys.len();
+    let n_items = n_diagnostics_items + n_completions_items + (n_document_items * uris.len());
 
     if configs.len() != n_items {
         return Err(anyhow!(
@@ -348,6 +425,19 @@ async fn update_config(
         lsp::spawn_diagnostics_refresh_all(state.clone());
     }
 
+    // --- Completions
+    let keys = completions_keys.into_iter();
+    let items: Vec<Value> = configs.by_ref().take(n_completions_items).collect();
+
+    let mut map = serde_json::Map::new();
+    std::iter::zip(keys, items).for_each(|(key, item)| {
+        map.insert(key.into(), item);
+    });
+
+    let config: VscCompletionsConfig = serde_json::from_value(serde_json::Value::Object(map))?;
+    let config: CompletionsConfig = config.into();
+    state.config.completions = config;
+
     // --- Documents
     // For each document, deserialise the vector of JSON values into a typed config
     for uri in uris.into_iter() {
@@ -372,6 +462,42 @@ async fn update_config(
     Ok(())
 }
 
+/// Keep the workspace index in sync with R files created, changed, or deleted
+/// outside of the editor, e.g. by switching git branches. Open documents are
+/// skipped since the index tracks their buffer instead.
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn did_change_watched_files(
+    params: DidChangeWatchedFilesParams,
+    state: &WorldState,
+) -> anyhow::Result<()> {
+    let changes: Vec<_> = params
+        .changes
+        .into_iter()
+        .filter(|change| !state.documents.contains_key(&change.uri))
+        .filter_map(|change| Some((change.uri.to_file_path().ok()?, change.typ)))
+        .collect();
+
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    lsp::spawn_blocking(move || {
+        for (path, typ) in changes {
+            let result = if typ == FileChangeType::DELETED {
+                indexer::remove(&path)
+            } else {
+                indexer::update_file(&path)
+            };
+            if let Err(err) = result {
+                lsp::log_error!("Can't update index for {path:?}: {err:?}");
+            }
+        }
+        Ok(None)
+    });
+
+    Ok(())
+}
+
 #[tracing::instrument(level = "info", skip_all)]
 pub(crate) fn did_change_console_inputs(
     inputs: ConsoleInputs,
@@ -389,6 +515,14 @@ pub(crate) fn did_change_console_inputs(
     Ok(())
 }
 
+fn new_parser() -> Parser {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_r::LANGUAGE.into())
+        .unwrap();
+    parser
+}
+
 // FIXME: The initial indexer is currently racing against our state notification
 // handlers. The indexer is synchronised through a mutex but we might end up in
 // a weird state. Eventually the index should be moved to WorldState and created
@@ -401,3 +535,137 @@ fn update_index(uri: &url::Url, doc: &Document) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::DocumentSymbolParams;
+    use tower_lsp::lsp_types::FormattingOptions;
+    use tower_lsp::lsp_types::TextDocumentIdentifier;
+    use url::Url;
+
+    use crate::lsp::config::IndentStyle;
+    use crate::lsp::config::IndentationConfig;
+    use crate::lsp::documents::Document;
+    use crate::lsp::indent::indent_edit;
+    use crate::lsp::indexer;
+    use crate::lsp::main_loop::LspState;
+    use crate::lsp::offset::apply_text_edits;
+    use crate::lsp::state::WorldState;
+    use crate::lsp::state_handlers::did_change_formatting_options;
+    use crate::lsp::state_handlers::reparse_all;
+    use crate::lsp::symbols::document_symbols;
+
+    fn test_state(text: &str, indent: IndentationConfig) -> (WorldState, Url) {
+        let uri = Url::parse("file:///test.R").unwrap();
+
+        let mut doc = Document::new(text, None);
+        doc.config.indent = indent;
+
+        let mut state = WorldState::default();
+        state.documents.insert(uri.clone(), doc);
+
+        (state, uri)
+    }
+
+    fn reindent(state: &WorldState, uri: &Url, line: usize) -> String {
+        let doc = state.get_document(uri).unwrap();
+        let mut text = doc.contents.to_string();
+
+        let edits = indent_edit(doc, line).unwrap().unwrap();
+        apply_text_edits(edits, &mut text).unwrap();
+
+        text
+    }
+
+    #[test]
+    fn test_formatting_options_tab_size() {
+        let indent = IndentationConfig {
+            indent_style: IndentStyle::Space,
+            indent_size: 2,
+            tab_width: 2,
+        };
+        let (mut state, uri) = test_state("{\nfoo\n}", indent);
+
+        let opts = FormattingOptions {
+            tab_size: 4,
+            insert_spaces: true,
+            ..Default::default()
+        };
+        did_change_formatting_options(&uri, &opts, &mut state);
+
+        assert_eq!(reindent(&state, &uri, 1), "{\n    foo\n}");
+    }
+
+    #[test]
+    fn test_formatting_options_insert_tabs() {
+        let indent = IndentationConfig {
+            indent_style: IndentStyle::Space,
+            indent_size: 2,
+            tab_width: 2,
+        };
+        let (mut state, uri) = test_state("{\nfoo\n}", indent);
+
+        let opts = FormattingOptions {
+            tab_size: 4,
+            insert_spaces: false,
+            ..Default::default()
+        };
+        did_change_formatting_options(&uri, &opts, &mut state);
+
+        assert_eq!(reindent(&state, &uri, 1), "{\n\tfoo\n}");
+    }
+
+    #[test]
+    fn test_formatting_options_ignored_with_distinct_tab_width() {
+        // The configured indentation can't be expressed with formatting
+        // options, so it takes precedence
+        let indent = IndentationConfig {
+            indent_style: IndentStyle::Space,
+            indent_size: 4,
+            tab_width: 8,
+        };
+        let (mut state, uri) = test_state("{\nfoo\n}", indent);
+
+        let opts = FormattingOptions {
+            tab_size: 2,
+            insert_spaces: true,
+            ..Default::default()
+        };
+        did_change_formatting_options(&uri, &opts, &mut state);
+
+        assert_eq!(reindent(&state, &uri, 1), "{\n    foo\n}");
+    }
+
+    #[test]
+    fn test_reparse_all() {
+        let uri = Url::parse("file:///test_reparse_all.R").unwrap();
+
+        let mut doc = Document::new("reparsed_fn <- function(x) {\n  x\n}\n", Some(3));
+        doc.config.indent.indent_size = 4;
+
+        let mut state = WorldState::default();
+        state.documents.insert(uri.clone(), doc);
+        let mut lsp_state = LspState::default();
+
+        let params = DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let symbols = document_symbols(&state, &params).unwrap();
+        assert!(indexer::find("reparsed_fn").is_none());
+
+        reparse_all(&mut lsp_state, &mut state).unwrap();
+
+        // The document gets a fresh parser and keeps its version and config
+        assert!(lsp_state.parsers.contains_key(&uri));
+        let doc = state.get_document(&uri).unwrap();
+        assert_eq!(doc.version, Some(3));
+        assert_eq!(doc.config.indent.indent_size, 4);
+
+        // The symbols of the new tree match the old ones and the workspace
+        // index is rebuilt
+        assert_eq!(document_symbols(&state, &params).unwrap(), symbols);
+        assert!(indexer::find("reparsed_fn").is_some());
+    }
+}
@@ -0,0 +1,142 @@
+//
+// todo_markers.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Deserialize;
+use serde::Serialize;
+use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::Url;
+
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::NodeTypeExt;
+
+pub static POSITRON_TODO_MARKERS_REQUEST: &'static str = "positron/todoMarkers";
+
+static RE_TODO_MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^#+'?\s*(TODO|FIXME|NOTE)\b:?\s*(.*?)\s*$").unwrap());
+
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoMarkersParams {}
+
+#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+pub struct TodoMarkersResponse {
+    pub markers: Vec<TodoMarker>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+pub struct TodoMarker {
+    /// The document containing the comment.
+    pub uri: Url,
+    /// The marker, one of `TODO`, `FIXME`, or `NOTE`.
+    pub kind: String,
+    /// The text following the marker.
+    pub text: String,
+    /// The range of the whole comment.
+    pub range: Range,
+}
+
+/// Collect markers from all open documents, sorted by document and position
+pub(crate) fn todo_markers(state: &WorldState) -> anyhow::Result<Vec<TodoMarker>> {
+    let mut markers = vec![];
+
+    for (uri, document) in state.documents.iter() {
+        markers.append(&mut document_todo_markers(uri, document)?);
+    }
+
+    markers.sort_by(|x, y| (x.uri.as_str(), x.range.start).cmp(&(y.uri.as_str(), y.range.start)));
+
+    Ok(markers)
+}
+
+/// Only comment nodes are scanned, so markers inside strings are ignored
+fn document_todo_markers(uri: &Url, document: &Document) -> anyhow::Result<Vec<TodoMarker>> {
+    let contents = &document.contents;
+    let mut comments = vec![];
+
+    document.ast.walk().recurse(|node| {
+        if node.is_comment() {
+            comments.push(node);
+        }
+        true
+    });
+
+    let mut markers = vec![];
+
+    for node in comments {
+        let comment = contents.node_slice(&node)?.to_string();
+
+        let Some(caps) = RE_TODO_MARKER.captures(&comment) else {
+            continue;
+        };
+
+        markers.push(TodoMarker {
+            uri: uri.clone(),
+            kind: caps[1].to_string(),
+            text: caps[2].to_string(),
+            range: convert_tree_sitter_range_to_lsp_range(contents, node.range()),
+        });
+    }
+
+    Ok(markers)
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Range;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::state::WorldState;
+    use crate::lsp::todo_markers::todo_markers;
+
+    #[test]
+    fn test_todo_markers() {
+        let code = r#"
+# TODO: Vectorise this
+f <- function(x) {
+  x <- "TODO: not a comment"
+  # FIXME handle NAs
+  x # NOTE: inline
+}
+# Not a TODO marker
+"#;
+        let uri = Url::parse("file:///todo.R").unwrap();
+
+        let mut state = WorldState::default();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(code, None));
+
+        let markers = todo_markers(&state).unwrap();
+
+        let summary: Vec<(&str, &str)> = markers
+            .iter()
+            .map(|marker| (marker.kind.as_str(), marker.text.as_str()))
+            .collect();
+        assert_eq!(summary, vec![
+            ("TODO", "Vectorise this"),
+            ("FIXME", "handle NAs"),
+            ("NOTE", "inline"),
+        ]);
+
+        assert_eq!(markers[0].uri, uri);
+        assert_eq!(markers[0].range, Range {
+            start: Position::new(1, 0),
+            end: Position::new(1, 22),
+        });
+        assert_eq!(markers[1].range.start, Position::new(4, 2));
+        assert_eq!(markers[2].range.start, Position::new(5, 4));
+    }
+}
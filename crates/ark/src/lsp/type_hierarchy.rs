@@ -0,0 +1,535 @@
+//
+// type_hierarchy.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::utils::r_is_null;
+use ropey::Rope;
+use tower_lsp::lsp_types::Position;
+use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::SymbolKind;
+use tower_lsp::lsp_types::TypeHierarchyItem;
+use tower_lsp::lsp_types::Url;
+use tree_sitter::Node;
+
+use crate::lsp;
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::treesitter::node_text;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// A formal class defined with `setClass()` or `setRefClass()`
+#[derive(Debug, Clone)]
+pub(crate) struct ClassDefinition {
+    pub name: String,
+    /// The direct superclasses, from `contains` and unnamed `representation()`
+    /// slots
+    pub contains: Vec<String>,
+    /// Whether the class is virtual, i.e. `"VIRTUAL"` is part of its
+    /// representation
+    pub is_virtual: bool,
+    pub is_ref_class: bool,
+    pub uri: Url,
+    /// The range of the whole `setClass()` call
+    pub range: Range,
+    /// The range of the class name
+    pub selection_range: Range,
+}
+
+impl ClassDefinition {
+    fn item(&self) -> TypeHierarchyItem {
+        TypeHierarchyItem {
+            name: self.name.clone(),
+            kind: SymbolKind::CLASS,
+            tags: None,
+            detail: Some(class_detail(self.is_ref_class, self.is_virtual)),
+            uri: self.uri.clone(),
+            range: self.range,
+            selection_range: self.selection_range,
+            data: None,
+        }
+    }
+}
+
+/// A formal class known to the R session, e.g. one exported by a loaded
+/// package, as found by `.ps.lsp.classHierarchy()`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LoadedClass {
+    /// The direct superclasses, from `methods::extends()`
+    pub contains: Vec<String>,
+    /// The direct subclasses known to the session
+    pub subclasses: Vec<String>,
+    pub is_virtual: bool,
+    pub is_ref_class: bool,
+    pub package: String,
+}
+
+impl LoadedClass {
+    /// Loaded classes don't have a source location, so their item points to
+    /// `location`, the item or symbol the hierarchy was requested from
+    fn item(&self, name: &str, uri: &Url, location: Range) -> TypeHierarchyItem {
+        let detail = class_detail(self.is_ref_class, self.is_virtual);
+
+        TypeHierarchyItem {
+            name: name.to_string(),
+            kind: SymbolKind::CLASS,
+            tags: None,
+            detail: Some(format!("{detail} from {}", self.package)),
+            uri: uri.clone(),
+            range: location,
+            selection_range: location,
+            data: None,
+        }
+    }
+}
+
+fn class_detail(is_ref_class: bool, is_virtual: bool) -> String {
+    let mut detail = String::from(if is_ref_class {
+        "Reference class"
+    } else {
+        "S4 class"
+    });
+    if is_virtual {
+        detail.push_str(" (virtual)");
+    }
+    detail
+}
+
+/// Prepare the type hierarchy for the class named at `position`, either in a
+/// string like `setClass("B", contains = "A")` or as a symbol like
+/// `new(B)`.
+///
+/// The hierarchy is computed from `definitions`, the class definitions of the
+/// workspace index, and from the classes known to the session through
+/// `loaded`. S3 classes are plain strings without any formal hierarchy, so
+/// `"foo"` in `class(x) <- "foo"` doesn't prepare anything unless a formal
+/// class of the same name is defined.
+pub(crate) fn prepare_type_hierarchy(
+    document: &Document,
+    uri: &Url,
+    position: Position,
+    definitions: &[ClassDefinition],
+    loaded: impl Fn(&str) -> Option<LoadedClass>,
+) -> Option<Vec<TypeHierarchyItem>> {
+    let point = convert_position_to_point(&document.contents, position);
+    let node = document
+        .ast
+        .root_node()
+        .descendant_for_point_range(point, point)?;
+
+    let name = match node.node_type() {
+        NodeType::Identifier => node_text(&node, &document.contents)?,
+        NodeType::String => string_value(&node, &document.contents)?,
+        NodeType::StringContent => string_value(&node.parent()?, &document.contents)?,
+        _ => return None,
+    };
+
+    if let Some(definition) = definitions.iter().find(|def| def.name == name) {
+        return Some(vec![definition.item()]);
+    }
+
+    let class = loaded(&name)?;
+    let location = convert_tree_sitter_range_to_lsp_range(&document.contents, node.range());
+    Some(vec![class.item(&name, uri, location)])
+}
+
+/// The direct superclasses of `item`
+pub(crate) fn supertypes(
+    item: &TypeHierarchyItem,
+    definitions: &[ClassDefinition],
+    loaded: impl Fn(&str) -> Option<LoadedClass>,
+) -> Vec<TypeHierarchyItem> {
+    let contains = match definitions.iter().find(|def| def.name == item.name) {
+        Some(definition) => definition.contains.clone(),
+        None => loaded(&item.name)
+            .map(|class| class.contains)
+            .unwrap_or_default(),
+    };
+
+    contains
+        .iter()
+        .filter_map(|name| class_item(name, item, definitions, &loaded))
+        .collect()
+}
+
+/// The classes that directly extend `item`, in the workspace and then in the
+/// session
+pub(crate) fn subtypes(
+    item: &TypeHierarchyItem,
+    definitions: &[ClassDefinition],
+    loaded: impl Fn(&str) -> Option<LoadedClass>,
+) -> Vec<TypeHierarchyItem> {
+    let mut items: Vec<TypeHierarchyItem> = definitions
+        .iter()
+        .filter(|def| def.contains.contains(&item.name))
+        .map(|def| def.item())
+        .collect();
+
+    let subclasses = loaded(&item.name)
+        .map(|class| class.subclasses)
+        .unwrap_or_default();
+
+    for name in subclasses.iter() {
+        if items.iter().any(|item| &item.name == name) {
+            continue;
+        }
+        if let Some(subclass) = class_item(name, item, definitions, &loaded) {
+            items.push(subclass);
+        }
+    }
+
+    items
+}
+
+/// The item of the class `name`, preferably from its definition in the
+/// workspace. Loaded classes point to `origin`.
+fn class_item(
+    name: &str,
+    origin: &TypeHierarchyItem,
+    definitions: &[ClassDefinition],
+    loaded: &impl Fn(&str) -> Option<LoadedClass>,
+) -> Option<TypeHierarchyItem> {
+    if let Some(definition) = definitions.iter().find(|def| def.name == name) {
+        return Some(definition.item());
+    }
+    let class = loaded(name)?;
+    Some(class.item(name, &origin.uri, origin.selection_range))
+}
+
+/// The formal class `name` as known to the session. Must be called on the R
+/// thread.
+pub(crate) fn r_loaded_class(name: &str) -> Option<LoadedClass> {
+    let result = (|| -> anyhow::Result<Option<LoadedClass>> {
+        let class = RFunction::from(".ps.lsp.classHierarchy").add(name).call()?;
+        if r_is_null(class.sexp) {
+            return Ok(None);
+        }
+
+        Ok(Some(LoadedClass {
+            contains: Vec::<String>::try_from(&class.vector_elt(0)?)?,
+            subclasses: Vec::<String>::try_from(&class.vector_elt(1)?)?,
+            is_virtual: bool::try_from(class.vector_elt(2)?)?,
+            is_ref_class: bool::try_from(class.vector_elt(3)?)?,
+            package: String::try_from(class.vector_elt(4)?)?,
+        }))
+    })();
+
+    match result {
+        Ok(class) => class,
+        Err(err) => {
+            lsp::log_error!("Can't look up class `{name}`: {err:?}");
+            None
+        },
+    }
+}
+
+pub(crate) fn document_class_definitions(document: &Document, uri: &Url) -> Vec<ClassDefinition> {
+    let contents = &document.contents;
+    let mut definitions = vec![];
+
+    document.ast.walk().recurse(|node| {
+        if let Some(definition) = class_definition(&node, contents, uri) {
+            definitions.push(definition);
+        }
+        true
+    });
+
+    definitions
+}
+
+fn class_definition(node: &Node, contents: &Rope, uri: &Url) -> Option<ClassDefinition> {
+    if !node.is_call() {
+        return None;
+    }
+
+    let function = node_text(&node.child_by_field_name("function")?, contents)?;
+    let function = function.strip_prefix("methods::").unwrap_or(&function);
+    let is_ref_class = match function {
+        "setClass" => false,
+        "setRefClass" => true,
+        _ => return None,
+    };
+
+    let arguments = call_arguments(node, contents);
+
+    // The `Class` argument, named or else first unnamed
+    let class = arguments
+        .iter()
+        .find(|(name, _)| name.as_deref() == Some("Class"))
+        .or_else(|| arguments.iter().find(|(name, _)| name.is_none()))?;
+    let class = class.1;
+    if class.node_type() != NodeType::String {
+        return None;
+    }
+    let name = string_value(&class, contents)?;
+
+    let mut contains = vec![];
+
+    for (argument, value) in arguments.iter() {
+        if argument.as_deref() == Some("contains") {
+            contains.append(&mut string_values(value, contents));
+        }
+    }
+
+    // `representation` is the second argument of `setClass()`, `setRefClass()`
+    // takes `fields` there instead
+    let representation = arguments
+        .iter()
+        .find(|(name, _)| name.as_deref() == Some("representation"))
+        .or_else(|| {
+            if is_ref_class {
+                return None;
+            }
+            arguments
+                .iter()
+                .find(|(name, value)| name.is_none() && value.id() != class.id())
+        });
+
+    // `representation("VIRTUAL")` or `representation("A", x = "numeric")`
+    // where unnamed elements are superclasses
+    if let Some((_, value)) = representation {
+        if value.node_type() == NodeType::String {
+            contains.append(&mut string_values(value, contents));
+        } else if value.is_call() {
+            for (slot, value) in call_arguments(value, contents) {
+                if slot.is_none() {
+                    contains.append(&mut string_values(&value, contents));
+                }
+            }
+        }
+    }
+
+    let is_virtual = contains.iter().any(|class| class == "VIRTUAL");
+    contains.retain(|class| class != "VIRTUAL");
+
+    Some(ClassDefinition {
+        name,
+        contains,
+        is_virtual,
+        is_ref_class,
+        uri: uri.clone(),
+        range: convert_tree_sitter_range_to_lsp_range(contents, node.range()),
+        selection_range: convert_tree_sitter_range_to_lsp_range(contents, class.range()),
+    })
+}
+
+/// The arguments of a call as pairs of optional name and value
+fn call_arguments<'tree>(
+    node: &Node<'tree>,
+    contents: &Rope,
+) -> Vec<(Option<String>, Node<'tree>)> {
+    let Some(arguments) = node.child_by_field_name("arguments") else {
+        return vec![];
+    };
+
+    let mut cursor = arguments.walk();
+    arguments
+        .named_children(&mut cursor)
+        .filter(|child| child.node_type() == NodeType::Argument)
+        .filter_map(|argument| {
+            let value = argument.child_by_field_name("value")?;
+            let name = argument
+                .child_by_field_name("name")
+                .and_then(|name| node_text(&name, contents));
+            Some((name, value))
+        })
+        .collect()
+}
+
+/// A string literal, or a `c()` call of string literals
+fn string_values(node: &Node, contents: &Rope) -> Vec<String> {
+    match node.node_type() {
+        NodeType::String => string_value(node, contents).into_iter().collect(),
+        NodeType::Call => {
+            let is_c = node
+                .child_by_field_name("function")
+                .and_then(|function| node_text(&function, contents))
+                .is_some_and(|function| function == "c");
+            if !is_c {
+                return vec![];
+            }
+
+            call_arguments(node, contents)
+                .into_iter()
+                .filter_map(|(_, value)| {
+                    (value.node_type() == NodeType::String)
+                        .then(|| string_value(&value, contents))
+                        .flatten()
+                })
+                .collect()
+        },
+        _ => vec![],
+    }
+}
+
+fn string_value(node: &Node, contents: &Rope) -> Option<String> {
+    let mut cursor = node.walk();
+    let content = node
+        .named_children(&mut cursor)
+        .find(|child| child.node_type() == NodeType::StringContent)?;
+
+    if content.named_child_count() > 0 {
+        return None;
+    }
+
+    node_text(&content, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::TypeHierarchyItem;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::type_hierarchy::document_class_definitions;
+    use crate::lsp::type_hierarchy::prepare_type_hierarchy;
+    use crate::lsp::type_hierarchy::r_loaded_class;
+    use crate::lsp::type_hierarchy::subtypes;
+    use crate::lsp::type_hierarchy::supertypes;
+    use crate::lsp::type_hierarchy::LoadedClass;
+    use crate::r_task;
+
+    fn names(items: &[TypeHierarchyItem]) -> Vec<&str> {
+        items.iter().map(|item| item.name.as_str()).collect()
+    }
+
+    fn not_loaded(_name: &str) -> Option<LoadedClass> {
+        None
+    }
+
+    #[test]
+    fn test_type_hierarchy() {
+        let code = r#"setClass("A", representation("VIRTUAL"))
+setClass("B", contains = "A")
+Gen <- setRefClass("C", contains = c("B", "environment"))
+x <- structure(list(), class = "S3")
+"#;
+        let document = Document::new(code, None);
+        let uri = Url::parse("file:///classes.R").unwrap();
+        let definitions = document_class_definitions(&document, &uri);
+        assert_eq!(definitions.len(), 3);
+
+        // On the `"B"` string of `setClass("B", ...)`
+        let items = prepare_type_hierarchy(
+            &document,
+            &uri,
+            Position::new(1, 10),
+            &definitions,
+            not_loaded,
+        )
+        .unwrap();
+        assert_eq!(names(&items), vec!["B"]);
+        assert_eq!(items[0].detail.as_deref(), Some("S4 class"));
+
+        let b = &items[0];
+        assert_eq!(names(&supertypes(b, &definitions, not_loaded)), vec!["A"]);
+        assert_eq!(names(&subtypes(b, &definitions, not_loaded)), vec!["C"]);
+
+        let a = &supertypes(b, &definitions, not_loaded)[0];
+        assert_eq!(a.detail.as_deref(), Some("S4 class (virtual)"));
+        assert!(supertypes(a, &definitions, not_loaded).is_empty());
+        assert_eq!(names(&subtypes(a, &definitions, not_loaded)), vec!["B"]);
+
+        // Superclasses that aren't defined anywhere are skipped
+        let items = prepare_type_hierarchy(
+            &document,
+            &uri,
+            Position::new(2, 20),
+            &definitions,
+            not_loaded,
+        )
+        .unwrap();
+        assert_eq!(names(&items), vec!["C"]);
+        assert_eq!(items[0].detail.as_deref(), Some("Reference class"));
+        assert_eq!(
+            names(&supertypes(&items[0], &definitions, not_loaded)),
+            vec!["B"]
+        );
+    }
+
+    #[test]
+    fn test_type_hierarchy_s3_classes() {
+        let code = r#"x <- structure(list(), class = "S3")
+class(x) <- "data.frame"
+"#;
+        let document = Document::new(code, None);
+        let uri = Url::parse("file:///classes.R").unwrap();
+        let definitions = document_class_definitions(&document, &uri);
+        assert!(definitions.is_empty());
+
+        r_task(|| {
+            // `data.frame` is registered with `setOldClass()` but is still an
+            // S3 class
+            for position in [Position::new(0, 32), Position::new(1, 14)] {
+                let items =
+                    prepare_type_hierarchy(&document, &uri, position, &definitions, r_loaded_class);
+                assert!(items.is_none());
+            }
+        })
+    }
+
+    #[test]
+    fn test_type_hierarchy_loaded_classes() {
+        r_task(|| {
+            harp::parse_eval_global(
+                r#"{
+                    setClass("TypeHierarchyA", representation("VIRTUAL"))
+                    setClass("TypeHierarchyB", contains = "TypeHierarchyA")
+                }"#,
+            )
+            .unwrap();
+
+            // Workspace classes extending a class of the session
+            let code = r#"setClass("C", contains = "TypeHierarchyB")"#;
+            let document = Document::new(code, None);
+            let uri = Url::parse("file:///classes.R").unwrap();
+            let definitions = document_class_definitions(&document, &uri);
+
+            let c = &prepare_type_hierarchy(
+                &document,
+                &uri,
+                Position::new(0, 10),
+                &definitions,
+                r_loaded_class,
+            )
+            .unwrap()[0];
+
+            let supers = supertypes(c, &definitions, r_loaded_class);
+            assert_eq!(names(&supers), vec!["TypeHierarchyB"]);
+            assert_eq!(supers[0].uri, uri);
+            assert_eq!(
+                supers[0].detail.as_deref(),
+                Some("S4 class from .GlobalEnv")
+            );
+
+            let supers = supertypes(&supers[0], &definitions, r_loaded_class);
+            assert_eq!(names(&supers), vec!["TypeHierarchyA"]);
+            assert_eq!(
+                supers[0].detail.as_deref(),
+                Some("S4 class (virtual) from .GlobalEnv")
+            );
+
+            // The session knows about B, the workspace about C
+            let subs = subtypes(&supers[0], &definitions, r_loaded_class);
+            assert_eq!(names(&subs), vec!["TypeHierarchyB"]);
+            let subs = subtypes(&subs[0], &definitions, r_loaded_class);
+            assert_eq!(names(&subs), vec!["C"]);
+
+            harp::parse_eval_global(
+                r#"{
+                    removeClass("TypeHierarchyB")
+                    removeClass("TypeHierarchyA")
+                }"#,
+            )
+            .unwrap();
+        })
+    }
+}
@@ -12,10 +12,14 @@ use std::env;
 
 use amalthea::kernel;
 use amalthea::kernel_spec::KernelSpec;
+use amalthea::socket::shell::ExecuteQueue;
+use amalthea::socket::shell::ExecuteQueuePolicy;
+use ark::ansi::AnsiOutput;
 use ark::interface::SessionMode;
 use ark::logger;
 use ark::signals::initialize_signal_block;
 use ark::start::start_kernel;
+use ark::startup::SkipStartupFiles;
 use ark::traps::register_trap_handlers;
 use ark::version::detect_r;
 use crossbeam::channel::unbounded;
@@ -41,6 +45,22 @@ Available options:
 --startup-file FILE      An R file to run on session startup
 --session-mode MODE      The mode in which the session is running (console, notebook, background)
 --no-capture-streams     Do not capture stdout/stderr from R
+--ansi MODE              How ANSI escapes in R output are forwarded: passthrough
+                         (the default), strip, or html. Set per stream with
+                         e.g. stdout=html,stderr=strip
+--execute-queue-limit N  Bound the number of pending execute requests sent by
+                         automated clients, i.e. silent requests or requests
+                         not stored in the history (unbounded by default)
+--execute-queue-policy POLICY
+                         What happens to the execute requests beyond the
+                         limit: reject-new (the default) or drop-oldest. Both
+                         send an error reply in place of running the request
+--safe-mode              Skip the user's .Rprofile and .Renviron but not the
+                         site files, e.g. to bypass a broken profile
+--skip-startup-files LIST
+                         Skip the given R startup files, from a comma separated
+                         list of user-profile, user-environ, site-profile, and
+                         site-environ
 --version                Print the version of Ark
 --log FILE               Log to the given file (if not specified, stdout/stderr
                          will be used)
@@ -72,6 +92,9 @@ fn main() -> anyhow::Result<()> {
     let mut r_args: Vec<String> = Vec::new();
     let mut has_action = false;
     let mut capture_streams = true;
+    let mut ansi_output = AnsiOutput::default();
+    let mut execute_queue = ExecuteQueue::default();
+    let mut skip_startup_files = SkipStartupFiles::default();
 
     // Process remaining arguments. TODO: Need an argument that can passthrough args to R
     while let Some(arg) = argv.next() {
@@ -127,6 +150,46 @@ fn main() -> anyhow::Result<()> {
                 has_action = true;
             },
             "--no-capture-streams" => capture_streams = false,
+            "--ansi" => {
+                if let Some(spec) = argv.next() {
+                    ansi_output = AnsiOutput::parse(&spec)?;
+                } else {
+                    return Err(anyhow::anyhow!(
+                        "An ANSI mode must be specified when using the `--ansi` argument."
+                    ));
+                }
+            },
+            "--execute-queue-limit" => {
+                if let Some(limit) = argv.next() {
+                    let Ok(limit) = limit.parse::<usize>() else {
+                        return Err(anyhow::anyhow!("Can't parse execute queue limit '{limit}'"));
+                    };
+                    execute_queue.limit = Some(limit);
+                } else {
+                    return Err(anyhow::anyhow!(
+                        "A limit must be specified when using the `--execute-queue-limit` argument."
+                    ));
+                }
+            },
+            "--execute-queue-policy" => {
+                if let Some(policy) = argv.next() {
+                    execute_queue.policy = ExecuteQueuePolicy::parse(&policy)?;
+                } else {
+                    return Err(anyhow::anyhow!(
+                        "A policy must be specified when using the `--execute-queue-policy` argument."
+                    ));
+                }
+            },
+            "--safe-mode" => skip_startup_files.merge(SkipStartupFiles::safe_mode()),
+            "--skip-startup-files" => {
+                if let Some(list) = argv.next() {
+                    skip_startup_files.merge(SkipStartupFiles::parse(&list)?);
+                } else {
+                    return Err(anyhow::anyhow!(
+                        "A list of startup files must be specified when using the `--skip-startup-files` argument."
+                    ));
+                }
+            },
             "--log" => {
                 if let Some(file) = argv.next() {
                     log_file = Some(file);
@@ -242,6 +305,8 @@ fn main() -> anyhow::Result<()> {
         r_args.push(String::from("--interactive"));
     }
 
+    skip_startup_files.apply(&mut r_args);
+
     // This causes panics on background threads to propagate on the main
     // thread. If we don't propagate a background thread panic, the program
     // keeps running in an unstable state as all communications with this
@@ -310,6 +375,8 @@ fn main() -> anyhow::Result<()> {
         startup_file,
         session_mode,
         capture_streams,
+        ansi_output,
+        execute_queue,
     );
 
     // Just to please Rust
@@ -5,6 +5,8 @@
 //
 //
 
+use std::sync::Mutex;
+
 use anyhow::anyhow;
 use harp::environment::Environment;
 use harp::environment::R_ENVS;
@@ -13,6 +15,7 @@ use harp::exec::RFunctionExt;
 use harp::r_symbol;
 use harp::utils::r_poke_option;
 use harp::RObject;
+use libr::R_NilValue;
 use libr::Rf_ScalarLogical;
 use libr::SEXP;
 use once_cell::sync::Lazy;
@@ -28,6 +31,10 @@ struct PositronModuleAsset;
 #[folder = "src/modules/rstudio"]
 struct RStudioModuleAsset;
 
+// Held while modules are being sourced so that a reload can't start while
+// another one, or the development watcher, is halfway through the modules
+static IMPORT_LOCK: Mutex<()> = Mutex::new(());
+
 fn source_asset<T: RustEmbed>(file: &str, fun: &str, env: SEXP) -> anyhow::Result<()> {
     with_asset::<T, _>(file, |source| {
         let exprs = harp::parse_exprs_with_srcrefs(source)?;
@@ -101,52 +108,68 @@ pub fn initialize() -> anyhow::Result<RObject> {
     Environment::view(namespace.sexp).lock(false);
 
     // Load the positron and rstudio namespaces and their exported functions
-    for file in PositronModuleAsset::iter() {
-        source_asset::<PositronModuleAsset>(&file, "import_positron", namespace.sexp)?;
-    }
-    for file in RStudioModuleAsset::iter() {
-        source_asset::<RStudioModuleAsset>(&file, "import_rstudio", namespace.sexp)?;
-    }
+    import_modules(namespace.sexp)?;
 
     // Create a directory watcher that reloads module files as they are changed.
     #[cfg(debug_assertions)]
-    {
-        use std::path::Path;
-
-        use debug::*;
-
-        let source = std::env!("CARGO_MANIFEST_DIR");
-        let root = Path::new(&source).join("src").join("modules").to_path_buf();
-
-        if root.exists() {
-            // First reload all modules from source to reflect new changes that have
-            // not been built into the binary yet.
-            log::trace!("Loading R modules from sources via cargo manifest");
-            import_directory(
-                &root.join("positron"),
-                RModuleSource::Positron,
-                namespace.sexp,
-            )?;
-            import_directory(
-                &root.join("rstudio"),
-                debug::RModuleSource::RStudio,
-                namespace.sexp,
-            )?;
-
+    match debug::modules_root() {
+        Some(root) => {
             // Spawn the watcher thread when R is idle so we don't try to access
             // the R API while R is starting up
             r_task::spawn_idle(move || async {
                 log::info!("Watching R modules from sources via cargo manifest");
-                spawn_watcher_thread(root);
+                debug::spawn_watcher_thread(root);
             });
-        } else {
-            log::error!("Can't find ark R modules from sources");
-        }
+        },
+        None => log::error!("Can't find ark R modules from sources"),
     }
 
     return Ok(namespace);
 }
 
+/// Re-source all R modules into the positron namespace.
+///
+/// In development builds, modules are sourced from the cargo manifest so that
+/// changes that haven't been built into the binary yet are picked up. Fails if
+/// modules are already being sourced, e.g. when called reentrantly from R code
+/// evaluated during a reload.
+pub fn reload() -> anyhow::Result<()> {
+    let Ok(_guard) = IMPORT_LOCK.try_lock() else {
+        return Err(anyhow!("R modules are already being reloaded"));
+    };
+
+    log::info!("Reloading R modules");
+    import_modules(ARK_ENVS.positron_ns)
+}
+
+fn import_modules(namespace: SEXP) -> anyhow::Result<()> {
+    for file in PositronModuleAsset::iter() {
+        source_asset::<PositronModuleAsset>(&file, "import_positron", namespace)?;
+    }
+    for file in RStudioModuleAsset::iter() {
+        source_asset::<RStudioModuleAsset>(&file, "import_rstudio", namespace)?;
+    }
+
+    // Now reload all modules from source to reflect new changes that have
+    // not been built into the binary yet
+    #[cfg(debug_assertions)]
+    if let Some(root) = debug::modules_root() {
+        use debug::RModuleSource;
+
+        log::trace!("Loading R modules from sources via cargo manifest");
+        debug::import_directory(&root.join("positron"), RModuleSource::Positron, namespace)?;
+        debug::import_directory(&root.join("rstudio"), RModuleSource::RStudio, namespace)?;
+    }
+
+    Ok(())
+}
+
+#[harp::register]
+pub unsafe extern "C" fn ps_reload_modules() -> anyhow::Result<SEXP> {
+    reload()?;
+    Ok(R_NilValue)
+}
+
 #[cfg(debug_assertions)]
 mod debug {
     use std::collections::HashMap;
@@ -161,8 +184,15 @@ mod debug {
     use stdext::spawn;
 
     use crate::interface::RMain;
+    use crate::modules::IMPORT_LOCK;
     use crate::r_task;
 
+    pub fn modules_root() -> Option<PathBuf> {
+        let source = std::env!("CARGO_MANIFEST_DIR");
+        let root = Path::new(&source).join("src").join("modules");
+        root.exists().then_some(root)
+    }
+
     pub fn spawn_watcher_thread(root: PathBuf) {
         spawn!("ark-modules-watcher", {
             move || {
@@ -242,15 +272,24 @@ mod debug {
                     continue;
                 }
 
-                r_task(|| {
+                let imported = r_task(|| {
+                    // Try again on the next tick if a reload is in progress
+                    let Ok(_guard) = IMPORT_LOCK.try_lock() else {
+                        return false;
+                    };
+
                     let r_main = RMain::get();
                     if let Err(err) =
                         import_file(&path, *src, r_main.positron_ns.as_ref().unwrap().sexp)
                     {
                         log::error!("{err:?}");
                     }
+                    true
                 });
-                *old_modified = new_modified;
+
+                if imported {
+                    *old_modified = new_modified;
+                }
             }
 
             Ok(())
@@ -299,6 +338,9 @@ mod tests {
     use harp::environment::Environment;
     use libr::CLOENV;
 
+    use crate::modules::reload;
+    use crate::modules::ARK_ENVS;
+    use crate::modules::IMPORT_LOCK;
     use crate::r_task;
 
     fn get_namespace(exports: Environment, fun: &str) -> Environment {
@@ -327,4 +369,51 @@ mod tests {
             assert!(rstudio_ns.is_locked());
         })
     }
+
+    #[test]
+    fn test_reload_restores_modified_helpers() {
+        r_task(|| {
+            let is_string = |code: &str| -> String {
+                let out = harp::parse_eval0(code, ARK_ENVS.positron_ns).unwrap();
+                String::try_from(out).unwrap()
+            };
+
+            harp::parse_eval0("is_string <- function(x) 'patched'", ARK_ENVS.positron_ns).unwrap();
+            assert_eq!(is_string("is_string(1L)"), "patched");
+
+            reload().unwrap();
+            assert_eq!(is_string("as.character(is_string(1L))"), "FALSE");
+        })
+    }
+
+    #[test]
+    fn test_reload_preserves_module_state() {
+        r_task(|| {
+            let registered = || -> Vec<String> {
+                let out = harp::parse_eval_global("ls(.ps.internal(the)$setup_chunks)").unwrap();
+                Vec::<String>::try_from(out).unwrap()
+            };
+
+            harp::parse_eval_global(".ps.setup.register('chunk', quote(NULL))").unwrap();
+            harp::parse_eval_global(".ps.rpc.setFileEncoding(tempfile(), 'latin1')").unwrap();
+            let encodings = harp::parse_eval_global(".ps.internal(the)$file_encodings").unwrap();
+
+            reload().unwrap();
+
+            // Registrations made before the reload are still there
+            assert_eq!(registered(), vec![String::from("chunk")]);
+            let same = harp::parse_eval_global(".ps.internal(the)$file_encodings").unwrap();
+            assert_eq!(encodings.sexp, same.sexp);
+
+            harp::parse_eval_global(".ps.setup.clear()").unwrap();
+        })
+    }
+
+    #[test]
+    fn test_reload_is_serialized() {
+        r_task(|| {
+            let _guard = IMPORT_LOCK.lock().unwrap();
+            assert!(reload().is_err());
+        })
+    }
 }
@@ -33,7 +33,9 @@ use amalthea::comm::plot_comm::PlotBackendReply;
 use amalthea::comm::plot_comm::PlotBackendRequest;
 use amalthea::comm::plot_comm::PlotFrontendEvent;
 use amalthea::comm::plot_comm::PlotResult;
+use amalthea::comm::plot_comm::PlotSize;
 use amalthea::comm::plot_comm::RenderFormat;
+use amalthea::socket::comm::CommBackpressure;
 use amalthea::socket::comm::CommInitiator;
 use amalthea::socket::comm::CommSocket;
 use amalthea::socket::iopub::IOPubMessage;
@@ -59,10 +61,16 @@ use stdext::result::ResultOrLog;
 use stdext::unwrap;
 use uuid::Uuid;
 
+use crate::interface::RMain;
 use crate::r_task;
 
 const POSITRON_PLOT_CHANNEL_ID: &str = "positron.plot";
 
+/// Animations and loops can produce plot updates faster than the frontend
+/// renders them. Only the latest updates matter since each one asks for a
+/// rerender of the whole plot, so older ones are dropped.
+const PLOT_BACKPRESSURE: CommBackpressure = CommBackpressure::DropOldest { capacity: 8 };
+
 macro_rules! trace {
     ($($tts:tt)*) => {{
         let message = format!($($tts)*);
@@ -129,14 +137,14 @@ impl DeviceContext {
         self._new_page = true;
     }
 
-    pub fn on_did_execute_request(
+    pub fn process_pending_changes(
         &mut self,
         comm_manager_tx: Sender<CommManagerEvent>,
         iopub_tx: Sender<IOPubMessage>,
         dynamic_plots: bool,
     ) {
-        // After R code has completed execution, we use this to check if any graphics
-        // need to be created
+        // After R code has completed execution, or once a page is complete, we
+        // use this to check if any graphics need to be created
         if self._changes {
             self._changes = false;
             self.process_changes(comm_manager_tx, iopub_tx, dynamic_plots);
@@ -211,6 +219,10 @@ impl DeviceContext {
                     mime_type: mime_type.to_string(),
                 }))
             },
+            PlotBackendRequest::GetPlotSvg(params) => {
+                let svg = self.render_plot_svg(&plot_id, params.size)?;
+                Ok(PlotBackendReply::GetPlotSvgReply(svg))
+            },
         }
     }
 
@@ -258,10 +270,11 @@ impl DeviceContext {
 
     fn process_new_plot_positron(&mut self, id: &str, comm_manager_tx: Sender<CommManagerEvent>) {
         // Let Positron know that we just created a new plot.
-        let socket = CommSocket::new(
+        let socket = CommSocket::new_with_backpressure(
             CommInitiator::BackEnd,
             id.to_string(),
             POSITRON_PLOT_CHANNEL_ID.to_string(),
+            PLOT_BACKPRESSURE,
         );
 
         let event = CommManagerEvent::Opened(socket.clone(), serde_json::Value::Null);
@@ -328,10 +341,9 @@ impl DeviceContext {
         let value = serde_json::to_value(PlotFrontendEvent::Update).unwrap();
 
         // Tell Positron we have an updated plot that it should request a rerender for
-        socket
-            .outgoing_tx
-            .send(CommMsg::Data(value))
-            .or_log_error("Failed to send update message for id {id}.");
+        if !socket.send_outgoing(CommMsg::Data(value)) {
+            log::error!("Failed to send update message for id {id}.");
+        }
     }
 
     fn process_update_plot_jupyter_protocol(&mut self, id: &str, iopub_tx: Sender<IOPubMessage>) {
@@ -415,6 +427,26 @@ impl DeviceContext {
 
         Ok(data)
     }
+
+    fn render_plot_svg(&mut self, plot_id: &str, size: Option<PlotSize>) -> anyhow::Result<String> {
+        self._rendering = true;
+        let svg = r_task(|| unsafe {
+            let mut call = RFunction::from(".ps.graphics.renderPlotSvg");
+            call.param("id", plot_id);
+            if let Some(size) = size {
+                call.param("width", RObject::try_from(size.width)?);
+                call.param("height", RObject::try_from(size.height)?);
+            }
+            call.call()?.to::<String>()
+        });
+        self._rendering = false;
+
+        let svg = unwrap!(svg, Err(error) => {
+            bail!("Failed to render plot with id {plot_id} as SVG due to: {error}.");
+        });
+
+        Ok(svg)
+    }
 }
 
 static mut DEVICE_CONTEXT: Lazy<DeviceContext> = Lazy::new(|| DeviceContext::default());
@@ -458,7 +490,18 @@ pub unsafe fn on_did_execute_request(
     iopub_tx: Sender<IOPubMessage>,
     dynamic_plots: bool,
 ) {
-    DEVICE_CONTEXT.on_did_execute_request(comm_manager_tx, iopub_tx, dynamic_plots);
+    DEVICE_CONTEXT.process_pending_changes(comm_manager_tx, iopub_tx, dynamic_plots);
+}
+
+/// Called between the top-level expressions of an execute request, so that
+/// the plots of a cell are interleaved with its other outputs rather than
+/// emitted all at once at the end.
+pub unsafe fn on_did_execute_expression(
+    comm_manager_tx: Sender<CommManagerEvent>,
+    iopub_tx: Sender<IOPubMessage>,
+    dynamic_plots: bool,
+) {
+    DEVICE_CONTEXT.process_pending_changes(comm_manager_tx, iopub_tx, dynamic_plots);
 }
 
 // NOTE: May be called when rendering a plot to file, since this is done by
@@ -579,7 +622,7 @@ unsafe extern "C" fn ps_graphics_device() -> anyhow::Result<SEXP> {
 }
 
 #[harp::register]
-unsafe extern "C" fn ps_graphics_event(_name: SEXP) -> anyhow::Result<SEXP> {
+unsafe extern "C" fn ps_graphics_event(_name: SEXP, new_page: SEXP) -> anyhow::Result<SEXP> {
     let id = unwrap!(DEVICE_CONTEXT._id.clone(), None => {
         return Ok(Rf_ScalarLogical(0));
     });
@@ -593,5 +636,19 @@ unsafe extern "C" fn ps_graphics_event(_name: SEXP) -> anyhow::Result<SEXP> {
         return Ok(Rf_ScalarLogical(0));
     }
 
+    // The current page is complete when the next plot starts a new page, so
+    // we emit it right away rather than at the end of the execute request.
+    // The panels of a multi-panel figure, e.g. with `par(mfrow =)`, go on the
+    // same page and only complete it once the figure is full.
+    let new_page: bool = RObject::view(new_page).try_into()?;
+    if new_page && RMain::is_initialized() {
+        let main = RMain::get();
+        DEVICE_CONTEXT.process_pending_changes(
+            main.get_comm_manager_tx().clone(),
+            main.get_iopub_tx().clone(),
+            main.is_dynamic_plots(),
+        );
+    }
+
     Ok(Rf_ScalarLogical(1))
 }
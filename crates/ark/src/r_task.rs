@@ -139,6 +139,23 @@ impl RTaskStartInfo {
 // thread. See also `Crossbeam::thread::ScopedThreadBuilder` (from which
 // `r_task()` is adapted) for a similar approach.
 
+/// Like `r_task()`, but restores the RNG state once `f` has run. Use this for
+/// tooling evaluations (completions, variable previews, etc) that might call
+/// code drawing random numbers, so they don't advance the user's RNG stream
+/// and break the reproducibility of their `set.seed()` calls. Tasks running
+/// user code, which is expected to affect the seed, should use `r_task()`.
+pub(crate) fn r_task_preserving_seed<'env, F, T>(f: F) -> T
+where
+    F: FnOnce() -> T,
+    F: 'env + Send,
+    T: 'env + Send,
+{
+    r_task(|| {
+        let _seed = harp::raii::RLocalRandomSeed::new();
+        f()
+    })
+}
+
 pub fn r_task<'env, F, T>(f: F) -> T
 where
     F: FnOnce() -> T,
@@ -317,3 +334,39 @@ fn get_tx(once_tx: &'static OnceLock<Sender<RTask>>) -> &'static Sender<RTask> {
 
 // Tests are tricky because `harp::fixtures::r_test_init()` is very bare bones and
 // doesn't have an `R_MAIN` or `R_MAIN_TASKS_TX`.
+
+#[cfg(test)]
+mod tests {
+    use crate::r_task::r_task;
+    use crate::r_task::r_task_preserving_seed;
+
+    #[test]
+    fn test_r_task_preserving_seed() {
+        r_task(|| {
+            let runif = || -> f64 {
+                harp::parse_eval_global("runif(1)")
+                    .unwrap()
+                    .try_into()
+                    .unwrap()
+            };
+
+            harp::parse_eval_global("set.seed(1)").unwrap();
+            let baseline = runif();
+            harp::parse_eval_global("set.seed(1)").unwrap();
+
+            // Internal evaluations that draw random numbers, e.g. from a
+            // print method, don't perturb the user's RNG stream
+            r_task_preserving_seed(|| {
+                harp::parse_eval_global("sample(10)").unwrap();
+            });
+            assert_eq!(runif(), baseline);
+
+            // Tasks running user code do advance it
+            harp::parse_eval_global("set.seed(1)").unwrap();
+            r_task(|| {
+                harp::parse_eval_global("sample(10)").unwrap();
+            });
+            assert_ne!(runif(), baseline);
+        })
+    }
+}
@@ -12,6 +12,7 @@ use amalthea::socket::comm::CommSocket;
 use amalthea::socket::stdin::StdInRequest;
 use amalthea::wire::complete_reply::CompleteReply;
 use amalthea::wire::complete_request::CompleteRequest;
+use amalthea::wire::exception::Exception;
 use amalthea::wire::execute_reply::ExecuteReply;
 use amalthea::wire::execute_request::ExecuteRequest;
 use amalthea::wire::inspect_reply::InspectReply;
@@ -38,13 +39,15 @@ use log::*;
 use serde_json::json;
 use stdext::unwrap;
 
+use crate::build_output::BuildComm;
 use crate::help::r_help::RHelp;
 use crate::help_proxy;
-use crate::interface::KernelInfo;
+use crate::interface::KernelInit;
 use crate::interface::RMain;
 use crate::r_task;
 use crate::request::KernelRequest;
 use crate::request::RRequest;
+use crate::ui::Heartbeat;
 use crate::ui::UiComm;
 use crate::variables::r_variables::RVariables;
 
@@ -53,8 +56,8 @@ pub struct Shell {
     r_request_tx: Sender<RRequest>,
     stdin_request_tx: Sender<StdInRequest>,
     kernel_request_tx: Sender<KernelRequest>,
-    kernel_init_rx: BusReader<KernelInfo>,
-    kernel_info: Option<KernelInfo>,
+    kernel_init_rx: BusReader<KernelInit>,
+    kernel_info: Option<KernelInit>,
 }
 
 #[derive(Debug)]
@@ -68,7 +71,7 @@ impl Shell {
         comm_manager_tx: Sender<CommManagerEvent>,
         r_request_tx: Sender<RRequest>,
         stdin_request_tx: Sender<StdInRequest>,
-        kernel_init_rx: BusReader<KernelInfo>,
+        kernel_init_rx: BusReader<KernelInit>,
         kernel_request_tx: Sender<KernelRequest>,
     ) -> Self {
         Self {
@@ -117,13 +120,25 @@ impl ShellHandler for Shell {
         //    be sent before they signal that the kernel as ready for use, so
         //    blocking here ensures that it doesn't try to execute code before R is
         //    ready.
+        //
+        // If R hangs during startup, we get a `StartupHang` instead, which we
+        // report as an error so that the launcher can kill the kernel.
         if self.kernel_info.is_none() {
             trace!("Got kernel info request; waiting for R to complete initialization");
             self.kernel_info = Some(self.kernel_init_rx.recv().unwrap());
         } else {
             trace!("R already started, using existing kernel information")
         }
-        let kernel_info = self.kernel_info.as_ref().unwrap();
+        let kernel_info = match self.kernel_info.as_ref().unwrap() {
+            Ok(kernel_info) => kernel_info,
+            Err(hang) => {
+                return Err(amalthea::Error::ShellErrorReply(Exception {
+                    ename: String::from("StartupHang"),
+                    evalue: hang.to_string(),
+                    traceback: vec![],
+                }));
+            },
+        };
 
         let info = LanguageInfo {
             name: String::from("R"),
@@ -225,6 +240,7 @@ impl ShellHandler for Shell {
                 self.kernel_request_tx.clone(),
             ),
             Comm::Help => handle_comm_open_help(comm),
+            Comm::Build => handle_comm_open_build(comm),
             _ => Ok(false),
         }
     }
@@ -248,7 +264,8 @@ fn handle_comm_open_ui(
 ) -> amalthea::Result<bool> {
     // Create a frontend to wrap the comm channel we were just given. This starts
     // a thread that proxies messages to the frontend.
-    let ui_comm_tx = UiComm::start(comm, stdin_request_tx);
+    let heartbeat = r_task(Heartbeat::from_option);
+    let ui_comm_tx = UiComm::start_with_heartbeat(comm, stdin_request_tx, heartbeat);
 
     // Send the frontend event channel to the execution thread so it can emit
     // events to the frontend.
@@ -286,3 +303,8 @@ fn handle_comm_open_help(comm: CommSocket) -> amalthea::Result<bool> {
         Ok(true)
     })
 }
+
+fn handle_comm_open_build(comm: CommSocket) -> amalthea::Result<bool> {
+    BuildComm::start(comm);
+    Ok(true)
+}
@@ -5,7 +5,29 @@
  *
  */
 
+use std::sync::LazyLock;
+
+use crossbeam::channel::bounded;
+use crossbeam::channel::Receiver;
+use crossbeam::channel::Sender;
+
 pub use crate::sys::signals::initialize_signal_block;
 pub use crate::sys::signals::initialize_signal_handlers;
 pub use crate::sys::signals::interrupts_pending;
 pub use crate::sys::signals::set_interrupts_pending;
+
+/// Channel used to wake up the R thread when an interrupt is requested
+/// while it's blocked in `ReadConsole` waiting for input, e.g. from
+/// `readline()`. The capacity of 1 coalesces repeated interrupts.
+static INTERRUPT_NOTIFICATIONS: LazyLock<(Sender<()>, Receiver<()>)> = LazyLock::new(|| bounded(1));
+
+/// Notify the R thread that an interrupt was requested. Must be called
+/// after the interrupt flag has been set (or the signal raised).
+pub fn notify_interrupt() {
+    // If the channel is full, a notification is already pending
+    let _ = INTERRUPT_NOTIFICATIONS.0.try_send(());
+}
+
+pub fn interrupt_notifications() -> Receiver<()> {
+    INTERRUPT_NOTIFICATIONS.1.clone()
+}
@@ -13,11 +13,13 @@ use amalthea::connection_file::ConnectionFile;
 use amalthea::kernel;
 use amalthea::registration_file::RegistrationFile;
 use amalthea::socket::iopub::IOPubMessage;
+use amalthea::socket::shell::ExecuteQueue;
 use amalthea::socket::stdin::StdInRequest;
 use bus::Bus;
 use crossbeam::channel::bounded;
 use crossbeam::channel::unbounded;
 
+use crate::ansi::AnsiOutput;
 use crate::control::Control;
 use crate::dap;
 use crate::interface::SessionMode;
@@ -34,6 +36,8 @@ pub fn start_kernel(
     startup_file: Option<String>,
     session_mode: SessionMode,
     capture_streams: bool,
+    ansi_output: AnsiOutput,
+    execute_queue: ExecuteQueue,
 ) {
     // Create the channels used for communication. These are created here
     // as they need to be shared across different components / threads.
@@ -98,6 +102,7 @@ pub fn start_kernel(
         Some(lsp),
         Some(dap.clone()),
         stream_behavior,
+        execute_queue,
         iopub_tx.clone(),
         iopub_rx,
         comm_manager_tx.clone(),
@@ -122,5 +127,6 @@ pub fn start_kernel(
         kernel_request_rx,
         dap,
         session_mode,
+        ansi_output,
     )
 }
@@ -7,18 +7,172 @@
 
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
+use amalthea::comm::ui_comm::StartupPackageParams;
+use amalthea::comm::ui_comm::StartupPackageStatus;
+use amalthea::comm::ui_comm::UiFrontendEvent;
 use amalthea::socket::iopub::IOPubMessage;
 use amalthea::wire::stream::Stream;
 use amalthea::wire::stream::StreamOutput;
+use bus::Bus;
+use crossbeam::channel::Receiver;
+use crossbeam::channel::RecvTimeoutError;
 use harp::environment::R_ENVS;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use libr::Rf_eval;
 
+use crate::interface::KernelInfo;
+use crate::interface::KernelInit;
 use crate::interface::RMain;
+use crate::modules::ARK_ENVS;
 use crate::sys;
 
+/// How long R has to reach its first prompt before we consider the startup
+/// hung. This is generous on purpose since large `.Rprofile`s can take a
+/// while to run. Can be overridden in seconds with `ARK_STARTUP_TIMEOUT`,
+/// where `0` disables the timeout.
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Reported through the kernel init channel when R hasn't reached its first
+/// prompt within the startup timeout, so that the launcher can kill the
+/// process
+#[derive(Debug, Clone)]
+pub struct StartupHang {
+    pub timeout: Duration,
+}
+
+impl std::fmt::Display for StartupHang {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "R did not reach its first prompt within {} seconds",
+            self.timeout.as_secs()
+        )
+    }
+}
+
+pub(crate) fn startup_timeout() -> Option<Duration> {
+    let Ok(value) = std::env::var("ARK_STARTUP_TIMEOUT") else {
+        return Some(DEFAULT_STARTUP_TIMEOUT);
+    };
+
+    match value.parse::<u64>() {
+        Ok(0) => None,
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(err) => {
+            log::warn!("Ignoring invalid `ARK_STARTUP_TIMEOUT` '{value}': {err}");
+            Some(DEFAULT_STARTUP_TIMEOUT)
+        },
+    }
+}
+
+/// Relays the kernel info sent by the R thread at its first prompt to the
+/// kernel init channel, or broadcasts a `StartupHang` if it doesn't arrive
+/// within `timeout`.
+pub(crate) fn watch_startup(
+    mut kernel_init_tx: Bus<KernelInit>,
+    kernel_info_rx: Receiver<KernelInfo>,
+    timeout: Option<Duration>,
+) {
+    let info = match timeout {
+        Some(timeout) => kernel_info_rx.recv_timeout(timeout),
+        None => kernel_info_rx
+            .recv()
+            .map_err(|_| RecvTimeoutError::Disconnected),
+    };
+
+    match info {
+        Ok(info) => kernel_init_tx.broadcast(Ok(info)),
+        Err(RecvTimeoutError::Timeout) => {
+            let hang = StartupHang {
+                timeout: timeout.unwrap(),
+            };
+            log::error!("{hang}");
+            kernel_init_tx.broadcast(Err(hang));
+        },
+        Err(RecvTimeoutError::Disconnected) => return,
+    }
+
+    // Keep the bus alive while the R thread is, so that readers that haven't
+    // received the init message yet still find it there
+    while kernel_info_rx.recv().is_ok() {}
+}
+
+/// The R startup files to skip, e.g. to bypass a broken `.Rprofile` when
+/// diagnosing startup problems. Safe mode skips the user files, which are the
+/// usual culprits, but keeps the site files that some deployments rely on.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SkipStartupFiles {
+    pub user_r_profile: bool,
+    pub user_r_environ: bool,
+    pub site_r_profile: bool,
+    pub site_r_environ: bool,
+}
+
+impl SkipStartupFiles {
+    pub fn safe_mode() -> Self {
+        Self {
+            user_r_profile: true,
+            user_r_environ: true,
+            ..Default::default()
+        }
+    }
+
+    /// Parses a comma separated list of `user-profile`, `user-environ`,
+    /// `site-profile`, and `site-environ`.
+    pub fn parse(list: &str) -> anyhow::Result<Self> {
+        let mut skip = Self::default();
+
+        for file in list.split(',').map(str::trim) {
+            match file {
+                "user-profile" => skip.user_r_profile = true,
+                "user-environ" => skip.user_r_environ = true,
+                "site-profile" => skip.site_r_profile = true,
+                "site-environ" => skip.site_r_environ = true,
+                other => {
+                    return Err(anyhow::anyhow!(
+                        "Unknown startup file '{other}'. Expected `user-profile`, `user-environ`, `site-profile`, or `site-environ`."
+                    ))
+                },
+            }
+        }
+
+        Ok(skip)
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        self.user_r_profile |= other.user_r_profile;
+        self.user_r_environ |= other.user_r_environ;
+        self.site_r_profile |= other.site_r_profile;
+        self.site_r_environ |= other.site_r_environ;
+    }
+
+    /// Passes the files to skip on to R. The profiles are skipped with R's
+    /// command line arguments. R only has `--no-environ` to skip both
+    /// `.Renviron` files, so these are skipped independently by setting
+    /// `R_ENVIRON_USER` and `R_ENVIRON` to an empty path, which R reads as
+    /// "no file".
+    ///
+    /// Must be called before R starts, and before other threads are spawned
+    /// since this modifies the environment.
+    pub fn apply(&self, r_args: &mut Vec<String>) {
+        if self.user_r_profile && !should_ignore_user_r_profile(r_args) {
+            push_ignore_user_r_profile(r_args);
+        }
+        if self.site_r_profile && !should_ignore_site_r_profile(r_args) {
+            push_ignore_site_r_profile(r_args);
+        }
+        if self.user_r_environ {
+            unsafe { std::env::set_var("R_ENVIRON_USER", "") };
+        }
+        if self.site_r_environ {
+            unsafe { std::env::set_var("R_ENVIRON", "") };
+        }
+    }
+}
+
 pub(crate) fn should_ignore_site_r_profile(args: &Vec<String>) -> bool {
     args.iter()
         .any(|arg| arg == "--no-site-file" || arg == "--vanilla")
@@ -102,6 +256,89 @@ fn source_r_profile(path: &PathBuf) {
     RMain::with(|main| main.get_iopub_tx().send(message).unwrap())
 }
 
+pub(crate) fn run_setup_chunks() {
+    let Err(err) = RFunction::from(".ps.setup.run").call() else {
+        return;
+    };
+
+    log::error!("Error while running setup chunks: {err}");
+
+    // Forward the message on to the frontend to be shown in the console
+    let message = IOPubMessage::Stream(StreamOutput {
+        name: Stream::Stderr,
+        text: format!("Error while running setup chunks:\n{err}\n"),
+    });
+
+    RMain::with(|main| main.get_iopub_tx().send(message).unwrap())
+}
+
+/// Attaches the packages listed in the `ark.startup_packages` option. The
+/// outcome of each attach is sent to the frontend as a `StartupPackage` event,
+/// and packages that aren't installed or fail to attach are also reported in
+/// the console. Startup carries on regardless.
+pub(crate) fn attach_startup_packages() {
+    let results = match startup_packages_results() {
+        Ok(results) => results,
+        Err(err) => {
+            log::error!("Error while attaching startup packages: {err:?}");
+            send_stderr(format!("Error while attaching startup packages:\n{err}\n"));
+            return;
+        },
+    };
+
+    for result in results {
+        match result.status {
+            StartupPackageStatus::Attached => {
+                log::info!("Attached startup package '{}'", result.package);
+            },
+            StartupPackageStatus::NotInstalled => {
+                log::warn!("Startup package '{}' is not installed", result.package);
+                send_stderr(format!(
+                    "Can't attach startup package '{}': it is not installed.\n",
+                    result.package
+                ));
+            },
+            StartupPackageStatus::Failed => {
+                let message = result.message.clone().unwrap_or_default();
+                log::error!(
+                    "Can't attach startup package '{}': {message}",
+                    result.package
+                );
+                send_stderr(format!(
+                    "Can't attach startup package '{}':\n{message}\n",
+                    result.package
+                ));
+            },
+        }
+
+        RMain::with_mut(|main| {
+            main.send_or_defer_ui_event(UiFrontendEvent::StartupPackage(result))
+        });
+    }
+}
+
+fn startup_packages_results() -> anyhow::Result<Vec<StartupPackageParams>> {
+    let results: serde_json::Value = RFunction::new("", "attach_startup_packages")
+        .call_in(ARK_ENVS.positron_ns)?
+        .try_into()?;
+
+    // An empty list converts to `null`
+    if results.is_null() {
+        return Ok(Vec::new());
+    }
+
+    Ok(serde_json::from_value(results)?)
+}
+
+fn send_stderr(text: String) {
+    let message = IOPubMessage::Stream(StreamOutput {
+        name: Stream::Stderr,
+        text,
+    });
+
+    RMain::with(|main| main.get_iopub_tx().send(message).unwrap())
+}
+
 fn find_site_r_profile(r_home: &PathBuf) -> Option<PathBuf> {
     // Try from env var first
     match std::env::var("R_PROFILE") {
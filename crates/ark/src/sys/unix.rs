@@ -10,4 +10,5 @@ pub mod control;
 pub mod interface;
 pub mod path;
 pub mod signals;
+pub mod stack;
 pub mod traps;
@@ -9,7 +9,14 @@ use nix::sys::signal::Signal;
 use nix::sys::signal::{self};
 use nix::unistd::Pid;
 
+use crate::signals::set_interrupts_pending;
+
 pub fn handle_interrupt_request() {
+    // Set the flag right away rather than waiting for the signal handler to
+    // run. This way the R thread sees the interrupt as soon as it is
+    // notified, e.g. while waiting for `readline()` input.
+    set_interrupts_pending(true);
+
     // TODO: Needs to send a SIGINT to the whole process group so that
     // processes started by R will also be interrupted.
     signal::kill(Pid::this(), Signal::SIGINT).unwrap();
@@ -36,6 +36,7 @@ use crate::interface::r_show_message;
 use crate::interface::r_suicide;
 use crate::interface::r_write_console;
 use crate::signals::initialize_signal_handlers;
+use crate::sys::stack::initialize_stack_limits;
 
 pub fn setup_r(mut args: Vec<*mut c_char>) {
     unsafe {
@@ -74,12 +75,16 @@ pub fn setup_r(mut args: Vec<*mut c_char>) {
         // In tests R may be run from various threads. This confuses R's stack
         // overflow checks so we disable those. This should not make it in
         // production builds as it causes stack overflows to crash R instead of
-        // throwing an R error.
+        // throwing an R error. Outside of tests R only ever runs on the thread
+        // that initialised it, but that might not be the main thread, so make
+        // sure R checks the stack it's actually running on.
         //
         // This must be called _after_ `Rf_initialize_R()`, since that's where R
         // detects the stack size and sets the default limit.
         if stdext::IS_TESTING {
             libr::set(libr::R_CStackLimit, usize::MAX);
+        } else {
+            initialize_stack_limits();
         }
 
         // Set up main loop
@@ -0,0 +1,60 @@
+/*
+ * stack.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+/// Point R's C stack checks at the stack of the calling thread.
+///
+/// On Linux, `Rf_initialize_R()` takes the stack base from `__libc_stack_end`,
+/// i.e. the base of the process's main thread. When R runs on another thread,
+/// stack usage is then measured against the wrong stack: R either throws
+/// spurious "C stack usage is too close to the limit" errors, or lets a
+/// runaway recursion overflow the real stack and crash the process instead of
+/// throwing an R error. On macOS, R already queries the calling thread with
+/// `pthread_get_stackaddr_np()` so there is nothing to fix up.
+///
+/// Must be called on the R thread, after `Rf_initialize_R()`.
+#[cfg(target_os = "linux")]
+pub fn initialize_stack_limits() {
+    unsafe {
+        // R's own detection is correct on the main thread
+        if libc::getpid() == libc::gettid() {
+            return;
+        }
+
+        let mut attr: libc::pthread_attr_t = std::mem::zeroed();
+        if libc::pthread_getattr_np(libc::pthread_self(), &mut attr) != 0 {
+            log::warn!("Can't query the stack of the R thread, keeping R's stack limits");
+            return;
+        }
+
+        let mut addr = std::ptr::null_mut();
+        let mut size = 0;
+        let status = libc::pthread_attr_getstack(&attr, &mut addr, &mut size);
+        libc::pthread_attr_destroy(&mut attr);
+
+        if status != 0 {
+            log::warn!("Can't query the stack of the R thread, keeping R's stack limits");
+            return;
+        }
+
+        // The stack grows down from the end of the mapping. Leave some room
+        // below the limit so R can signal the overflow error before we reach
+        // the guard page.
+        let start = addr as usize + size;
+        let limit = size / 100 * 95;
+
+        log::trace!("Setting R stack limits: start {start:#x}, size {limit}");
+
+        libr::set(libr::R_CStackStart, start);
+        libr::set(libr::R_CStackLimit, limit);
+        libr::set(libr::R_CStackDir, 1);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn initialize_stack_limits() {
+    // Nothing to do
+}
@@ -0,0 +1,160 @@
+//
+// capabilities.rs
+//
+// Copyright (C) 2024 by Posit Software, PBC
+//
+//
+
+use amalthea::comm::event::CommManagerEvent;
+use amalthea::comm::event::CommManagerInfoReply;
+use amalthea::comm::event::CommManagerRequest;
+use harp::object::RObject;
+use libr::SEXP;
+use serde::Serialize;
+
+use crate::interface::RMain;
+use crate::interface::SessionMode;
+
+/// The frontend features available to R code, as returned by
+/// `.ps.ui.capabilities()`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct UiCapabilities {
+    /// One of `"console"`, `"notebook"`, or `"background"`.
+    pub session_mode: String,
+
+    /// Whether `display_data` with rich MIME types is rendered.
+    pub rich_display: bool,
+
+    /// Whether plots are shown, either in the Plots pane or inline.
+    pub plots: bool,
+
+    /// Whether data frames can be opened in the Data Viewer.
+    pub data_viewer: bool,
+
+    /// Whether messages and notifications can be shown.
+    pub notifications: bool,
+
+    /// Whether files can be chosen with a native file dialog.
+    pub file_picker: bool,
+
+    /// Whether the user can choose a restart when an error is signalled.
+    pub restarts: bool,
+
+    /// Whether the Help pane is connected.
+    pub help: bool,
+
+    /// Whether the Variables pane is connected.
+    pub variables: bool,
+}
+
+impl UiCapabilities {
+    /// The capabilities of a frontend that opened the comms named `comms`.
+    /// The Data Viewer, notifications, the file picker, and the choice of
+    /// restarts are provided by the UI comm, which only Positron opens.
+    pub fn new(session_mode: &SessionMode, comms: &[String]) -> Self {
+        let has_comm = |name: &str| comms.iter().any(|comm| comm == name);
+        let ui = has_comm("positron.ui");
+
+        let session_mode_name = match session_mode {
+            SessionMode::Console => "console",
+            SessionMode::Notebook => "notebook",
+            SessionMode::Background => "background",
+        };
+        let rich_display = *session_mode != SessionMode::Background;
+
+        Self {
+            session_mode: String::from(session_mode_name),
+            rich_display,
+            plots: ui || *session_mode == SessionMode::Notebook,
+            data_viewer: ui,
+            notifications: ui,
+            file_picker: ui,
+            restarts: ui,
+            help: has_comm("positron.help"),
+            variables: has_comm("positron.variables"),
+        }
+    }
+}
+
+/// The names of the comms currently open. Asked to the comm manager on each
+/// call since the frontend may open its comms again when it reconnects.
+fn open_comm_names(main: &RMain) -> anyhow::Result<Vec<String>> {
+    let (tx, rx) = crossbeam::channel::bounded(1);
+
+    main.get_comm_manager_tx()
+        .send(CommManagerEvent::Request(CommManagerRequest::Info(tx)))?;
+    let CommManagerInfoReply { comms } = rx.recv()?;
+
+    Ok(comms.into_iter().map(|comm| comm.name).collect())
+}
+
+#[harp::register]
+pub unsafe extern "C" fn ps_ui_capabilities() -> anyhow::Result<SEXP> {
+    let main = RMain::get();
+
+    let comms = open_comm_names(main)?;
+    let capabilities = UiCapabilities::new(&main.session_mode, &comms);
+
+    let capabilities = RObject::try_from(serde_json::to_value(capabilities)?)?;
+    Ok(capabilities.sexp)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interface::SessionMode;
+    use crate::ui::capabilities::UiCapabilities;
+
+    #[test]
+    fn test_ui_capabilities() {
+        let comms = vec![
+            String::from("positron.ui"),
+            String::from("positron.help"),
+            String::from("positron.lsp"),
+        ];
+        assert_eq!(
+            UiCapabilities::new(&SessionMode::Console, &comms),
+            UiCapabilities {
+                session_mode: String::from("console"),
+                rich_display: true,
+                plots: true,
+                data_viewer: true,
+                notifications: true,
+                file_picker: true,
+                restarts: true,
+                help: true,
+                variables: false,
+            }
+        );
+
+        // A Jupyter notebook doesn't open any Positron comm
+        assert_eq!(
+            UiCapabilities::new(&SessionMode::Notebook, &[]),
+            UiCapabilities {
+                session_mode: String::from("notebook"),
+                rich_display: true,
+                plots: true,
+                data_viewer: false,
+                notifications: false,
+                file_picker: false,
+                restarts: false,
+                help: false,
+                variables: false,
+            }
+        );
+
+        assert_eq!(
+            UiCapabilities::new(&SessionMode::Background, &[]),
+            UiCapabilities {
+                session_mode: String::from("background"),
+                rich_display: false,
+                plots: false,
+                data_viewer: false,
+                notifications: false,
+                file_picker: false,
+                restarts: false,
+                help: false,
+                variables: false,
+            }
+        );
+    }
+}
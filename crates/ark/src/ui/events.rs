@@ -8,16 +8,23 @@
 use amalthea::comm::ui_comm::OpenEditorParams;
 use amalthea::comm::ui_comm::OpenWorkspaceParams;
 use amalthea::comm::ui_comm::Position;
+use amalthea::comm::ui_comm::ProgressParams;
 use amalthea::comm::ui_comm::Range;
 use amalthea::comm::ui_comm::SetEditorSelectionsParams;
 use amalthea::comm::ui_comm::ShowMessageParams;
+use amalthea::comm::ui_comm::ShowNotificationParams;
 use amalthea::comm::ui_comm::ShowUrlParams;
+use amalthea::comm::ui_comm::StatusEndParams;
+use amalthea::comm::ui_comm::StatusStartParams;
+use amalthea::comm::ui_comm::TestResultParams;
 use amalthea::comm::ui_comm::UiFrontendEvent;
+use harp::object::r_null_or_try_into;
 use harp::object::RObject;
 use libr::R_NilValue;
 use libr::SEXP;
 
 use crate::interface::RMain;
+use crate::ui::send_rpc_event;
 
 #[harp::register]
 pub unsafe extern "C" fn ps_ui_show_message(message: SEXP) -> anyhow::Result<SEXP> {
@@ -36,6 +43,105 @@ pub unsafe extern "C" fn ps_ui_show_message(message: SEXP) -> anyhow::Result<SEX
     Ok(R_NilValue)
 }
 
+#[harp::register]
+pub unsafe extern "C" fn ps_ui_test_result(
+    path: SEXP,
+    file: SEXP,
+    test: SEXP,
+    status: SEXP,
+    message: SEXP,
+    line: SEXP,
+) -> anyhow::Result<SEXP> {
+    let status: String = RObject::view(status).try_into()?;
+    let line: Option<i32> = r_null_or_try_into(RObject::view(line))?;
+
+    let params = TestResultParams {
+        path: RObject::view(path).try_into()?,
+        file: r_null_or_try_into(RObject::view(file))?,
+        test: RObject::view(test).try_into()?,
+        status: serde_json::from_value(serde_json::Value::String(status))?,
+        message: r_null_or_try_into(RObject::view(message))?,
+        line: line.map(i64::from),
+    };
+
+    send_event("ui_test_result", UiFrontendEvent::TestResult(params))?;
+    Ok(R_NilValue)
+}
+
+#[harp::register]
+pub unsafe extern "C" fn ps_ui_show_notification(
+    message: SEXP,
+    severity: SEXP,
+) -> anyhow::Result<SEXP> {
+    let severity: String = RObject::view(severity).try_into()?;
+    let severity =
+        serde_json::from_value(serde_json::Value::String(severity.clone())).map_err(|_| {
+            anyhow::anyhow!(
+                "`severity` must be one of \"info\", \"warning\", or \"error\", not \"{severity}\"."
+            )
+        })?;
+
+    let params = ShowNotificationParams {
+        message: RObject::view(message).try_into()?,
+        severity,
+    };
+
+    send_event(
+        "ui_show_notification",
+        UiFrontendEvent::ShowNotification(params),
+    )?;
+    Ok(R_NilValue)
+}
+
+#[harp::register]
+pub unsafe extern "C" fn ps_ui_status_start(
+    id: SEXP,
+    label: SEXP,
+    parent: SEXP,
+) -> anyhow::Result<SEXP> {
+    let params = StatusStartParams {
+        id: RObject::view(id).try_into()?,
+        label: RObject::view(label).try_into()?,
+        parent: r_null_or_try_into(RObject::view(parent))?,
+    };
+
+    send_event("ui_status_start", UiFrontendEvent::StatusStart(params))?;
+    Ok(R_NilValue)
+}
+
+#[harp::register]
+pub unsafe extern "C" fn ps_ui_status_end(id: SEXP, error: SEXP) -> anyhow::Result<SEXP> {
+    let params = StatusEndParams {
+        id: RObject::view(id).try_into()?,
+        error: RObject::view(error).try_into()?,
+    };
+
+    send_event("ui_status_end", UiFrontendEvent::StatusEnd(params))?;
+    Ok(R_NilValue)
+}
+
+#[harp::register]
+pub unsafe extern "C" fn ps_ui_progress(
+    id: SEXP,
+    label: SEXP,
+    value: SEXP,
+    total: SEXP,
+    units: SEXP,
+    done: SEXP,
+) -> anyhow::Result<SEXP> {
+    let params = ProgressParams {
+        id: RObject::view(id).try_into()?,
+        label: RObject::view(label).try_into()?,
+        value: RObject::view(value).try_into()?,
+        total: r_null_or_try_into(RObject::view(total))?,
+        units: RObject::view(units).try_into()?,
+        done: RObject::view(done).try_into()?,
+    };
+
+    send_event("ui_progress", UiFrontendEvent::Progress(params))?;
+    Ok(R_NilValue)
+}
+
 #[harp::register]
 pub unsafe extern "C" fn ps_ui_open_workspace(
     path: SEXP,
@@ -136,6 +242,23 @@ pub fn ps_ui_robj_as_ranges(ranges: SEXP) -> anyhow::Result<Vec<Range>> {
     Ok(selections)
 }
 
+/// Send an event to the frontend. Events sent while a UI comm RPC is in
+/// progress are streamed before the RPC returns. Otherwise the event goes
+/// through the UI comm thread, which delivers it even if R is busy.
+fn send_event(name: &str, event: UiFrontendEvent) -> anyhow::Result<()> {
+    if send_rpc_event(&event) {
+        return Ok(());
+    }
+
+    let main = RMain::get();
+    let ui_comm_tx = main
+        .get_ui_comm_tx()
+        .ok_or_else(|| ui_comm_not_connected(name))?;
+    ui_comm_tx.send_event(event);
+
+    Ok(())
+}
+
 fn ui_comm_not_connected(name: &str) -> anyhow::Error {
     anyhow::anyhow!("UI comm not connected, can't run `{name}`.")
 }
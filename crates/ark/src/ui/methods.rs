@@ -5,6 +5,7 @@
 //
 //
 
+use amalthea::comm::ui_comm::ChooseRestartParams;
 use amalthea::comm::ui_comm::DebugSleepParams;
 use amalthea::comm::ui_comm::EvaluateWhenClauseParams;
 use amalthea::comm::ui_comm::ExecuteCodeParams;
@@ -12,6 +13,7 @@ use amalthea::comm::ui_comm::ExecuteCommandParams;
 use amalthea::comm::ui_comm::ModifyEditorSelectionsParams;
 use amalthea::comm::ui_comm::NewDocumentParams;
 use amalthea::comm::ui_comm::ShowDialogParams;
+use amalthea::comm::ui_comm::ShowFilePickerParams;
 use amalthea::comm::ui_comm::ShowQuestionParams;
 use amalthea::comm::ui_comm::UiFrontendRequest;
 use harp::object::RObject;
@@ -93,6 +95,40 @@ pub unsafe extern "C" fn ps_ui_show_question(
     Ok(out.sexp)
 }
 
+#[harp::register]
+pub unsafe extern "C" fn ps_ui_show_file_picker(
+    title: SEXP,
+    multiple: SEXP,
+    directory: SEXP,
+    new: SEXP,
+) -> anyhow::Result<SEXP> {
+    let params = ShowFilePickerParams {
+        title: RObject::view(title).try_into()?,
+        multiple: RObject::view(multiple).try_into()?,
+        directory: RObject::view(directory).try_into()?,
+        new: RObject::view(new).try_into()?,
+    };
+
+    let main = RMain::get();
+    let out = main.call_frontend_method(UiFrontendRequest::ShowFilePicker(params))?;
+    Ok(out.sexp)
+}
+
+#[harp::register]
+pub unsafe extern "C" fn ps_ui_choose_restart(
+    message: SEXP,
+    restarts: SEXP,
+) -> anyhow::Result<SEXP> {
+    let params = ChooseRestartParams {
+        message: RObject::view(message).try_into()?,
+        restarts: RObject::view(restarts).try_into()?,
+    };
+
+    let main = RMain::get();
+    let out = main.call_frontend_method(UiFrontendRequest::ChooseRestart(params))?;
+    Ok(out.sexp)
+}
+
 #[harp::register]
 pub unsafe extern "C" fn ps_ui_new_document(
     contents: SEXP,
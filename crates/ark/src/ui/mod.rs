@@ -5,6 +5,7 @@
 //
 //
 
+pub mod capabilities;
 pub mod events;
 pub mod methods;
 
@@ -7,11 +7,14 @@
 
 use std::path::PathBuf;
 
+use amalthea::comm::ui_comm::DevContextParams;
 use amalthea::comm::ui_comm::PromptStateParams;
 use amalthea::comm::ui_comm::UiFrontendEvent;
 use amalthea::comm::ui_comm::WorkingDirectoryParams;
 use amalthea::wire::input_request::UiCommFrontendRequest;
 use crossbeam::channel::Sender;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
 
 use crate::ui::UiCommMessage;
 
@@ -21,10 +24,11 @@ use crate::ui::UiCommMessage;
 /// Adds convenience methods for sending `Event`s and `Request`s.
 ///
 /// Manages a bit of state for performing a state refresh
-/// (the `working_directory`).
+/// (the `working_directory` and the packages under development).
 pub struct UiCommSender {
     ui_comm_tx: Sender<UiCommMessage>,
     working_directory: PathBuf,
+    dev_packages: Vec<String>,
 }
 
 impl UiCommSender {
@@ -35,6 +39,7 @@ impl UiCommSender {
         Self {
             ui_comm_tx,
             working_directory,
+            dev_packages: Vec::new(),
         }
     }
 
@@ -64,6 +69,10 @@ impl UiCommSender {
         if let Err(err) = self.refresh_working_directory() {
             log::error!("Can't refresh working directory: {err:?}");
         }
+
+        if let Err(err) = self.refresh_dev_context() {
+            log::error!("Can't refresh development context: {err:?}");
+        }
     }
 
     fn refresh_prompt_info(&self, input_prompt: String, continuation_prompt: String) {
@@ -100,4 +109,33 @@ impl UiCommSender {
 
         Ok(())
     }
+
+    /// Checks for packages entering or leaving development mode, e.g. through
+    /// `devtools::load_all()`, and sends an event to the frontend for each
+    /// change. Loading the same package again doesn't send a new event.
+    fn refresh_dev_context(&mut self) -> anyhow::Result<()> {
+        let dev_packages: Vec<String> = RFunction::from(".ps.dev_packages").call()?.try_into()?;
+
+        for package in self.dev_packages.iter() {
+            if !dev_packages.contains(package) {
+                self.send_dev_context(package.clone(), false);
+            }
+        }
+
+        for package in dev_packages.iter() {
+            if !self.dev_packages.contains(package) {
+                self.send_dev_context(package.clone(), true);
+            }
+        }
+
+        self.dev_packages = dev_packages;
+        Ok(())
+    }
+
+    fn send_dev_context(&self, package: String, active: bool) {
+        self.send_event(UiFrontendEvent::DevContext(DevContextParams {
+            package,
+            active,
+        }));
+    }
 }
@@ -5,13 +5,21 @@
 //
 //
 
+use std::cell::Cell;
+use std::sync::Mutex;
+use std::time::Duration;
+
 use amalthea::comm::comm_channel::CommMsg;
+use amalthea::comm::ui_comm::CallMethodParams;
+use amalthea::comm::ui_comm::PingParams;
 use amalthea::comm::ui_comm::UiBackendReply;
 use amalthea::comm::ui_comm::UiBackendRequest;
 use amalthea::comm::ui_comm::UiFrontendEvent;
 use amalthea::socket::comm::CommSocket;
 use amalthea::socket::stdin::StdInRequest;
 use amalthea::wire::input_request::UiCommFrontendRequest;
+use crossbeam::channel::never;
+use crossbeam::channel::tick;
 use crossbeam::channel::Receiver;
 use crossbeam::channel::Sender;
 use crossbeam::select;
@@ -24,12 +32,88 @@ use stdext::unwrap;
 
 use crate::r_task;
 
+/// Outgoing channel of the UI comm while R evaluates an RPC method. Events
+/// sent through the UI comm thread could be delivered after the reply of the
+/// RPC, so R code that reports progress during an RPC sends its events
+/// through this channel instead. Set with `RpcOutgoingGuard`.
+static RPC_OUTGOING_TX: Mutex<Option<Sender<CommMsg>>> = Mutex::new(None);
+
+/// Sets `RPC_OUTGOING_TX` for as long as it lives, including when the RPC
+/// method fails or panics. Must be created on the R thread, which runs one
+/// RPC method at a time.
+struct RpcOutgoingGuard;
+
+impl RpcOutgoingGuard {
+    fn new(outgoing_tx: Sender<CommMsg>) -> Self {
+        *RPC_OUTGOING_TX.lock().unwrap() = Some(outgoing_tx);
+        Self
+    }
+}
+
+impl Drop for RpcOutgoingGuard {
+    fn drop(&mut self) {
+        *RPC_OUTGOING_TX.lock().unwrap() = None;
+    }
+}
+
+/// Send an event to the frontend while an RPC is in progress. Returns `false`
+/// when no RPC is being handled.
+pub(crate) fn send_rpc_event(event: &UiFrontendEvent) -> bool {
+    let outgoing_tx = RPC_OUTGOING_TX.lock().unwrap();
+    let Some(outgoing_tx) = outgoing_tx.as_ref() else {
+        return false;
+    };
+
+    let json = serde_json::to_value(event).unwrap();
+    if let Err(err) = outgoing_tx.send(CommMsg::Data(json)) {
+        log::error!("Error sending UI event to frontend: {}", err);
+    }
+    true
+}
+
 #[derive(Debug)]
 pub enum UiCommMessage {
     Event(UiFrontendEvent),
     Request(UiCommFrontendRequest),
 }
 
+/// Heartbeat of the UI comm, to detect a frontend that went away without
+/// closing the comm, e.g. because its socket died silently. A `ping` event is
+/// sent every `interval` and the frontend answers with `pong`. It is
+/// considered gone once more than `max_missed` pings in a row are left
+/// unanswered, so that a slow but alive frontend stays connected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Heartbeat {
+    pub interval: Duration,
+    pub max_missed: u32,
+}
+
+impl Heartbeat {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            max_missed: 2,
+        }
+    }
+
+    /// The heartbeat configured with the `ark.ui.heartbeat_interval` option,
+    /// in seconds. Disabled when unset since frontends only answer pings
+    /// when they support them.
+    ///
+    /// Must be called on the R thread.
+    pub fn from_option() -> Option<Self> {
+        let interval: f64 = harp::get_option("ark.ui.heartbeat_interval")
+            .try_into()
+            .ok()?;
+
+        if !interval.is_finite() || interval <= 0.0 {
+            return None;
+        }
+
+        Some(Self::new(Duration::from_secs_f64(interval)))
+    }
+}
+
 /// UiComm is a wrapper around a comm channel whose lifetime matches
 /// that of the Positron UI frontend. It is used to perform communication with the
 /// frontend that isn't scoped to any particular view.
@@ -37,12 +121,27 @@ pub struct UiComm {
     comm: CommSocket,
     ui_comm_rx: Receiver<UiCommMessage>,
     stdin_request_tx: Sender<StdInRequest>,
+    heartbeat: Option<Heartbeat>,
+
+    /// Sequence number of the last `ping`
+    heartbeat_seq: Cell<i64>,
+
+    /// Number of `ping`s sent since the last `pong`
+    heartbeat_unanswered: Cell<u32>,
 }
 
 impl UiComm {
     pub fn start(
         comm: CommSocket,
         stdin_request_tx: Sender<StdInRequest>,
+    ) -> Sender<UiCommMessage> {
+        Self::start_with_heartbeat(comm, stdin_request_tx, None)
+    }
+
+    pub fn start_with_heartbeat(
+        comm: CommSocket,
+        stdin_request_tx: Sender<StdInRequest>,
+        heartbeat: Option<Heartbeat>,
     ) -> Sender<UiCommMessage> {
         // Create a sender-receiver pair for Positron global events
         let (ui_comm_tx, ui_comm_rx) = crossbeam::channel::unbounded::<UiCommMessage>();
@@ -52,6 +151,9 @@ impl UiComm {
                 comm: comm.clone(),
                 ui_comm_rx: ui_comm_rx.clone(),
                 stdin_request_tx: stdin_request_tx.clone(),
+                heartbeat,
+                heartbeat_seq: Cell::new(0),
+                heartbeat_unanswered: Cell::new(0),
             };
             frontend.execution_thread();
         });
@@ -60,6 +162,11 @@ impl UiComm {
     }
 
     fn execution_thread(&self) {
+        let heartbeat_rx = match self.heartbeat {
+            Some(heartbeat) => tick(heartbeat.interval),
+            None => never(),
+        };
+
         loop {
             // Wait for an event on either the event channel (which forwards
             // Positron events to the frontend) or the comm channel (which
@@ -93,10 +200,44 @@ impl UiComm {
                         },
                     }
                 },
+
+                recv(heartbeat_rx) -> _ => {
+                    if !self.handle_heartbeat() {
+                        // Unlike a `Close` from the frontend, nobody told us
+                        // the comm is gone. Close it on our side too so it
+                        // isn't used anymore.
+                        log::warn!("UI comm {} closing, the frontend stopped answering heartbeats.", self.comm.comm_id);
+                        if let Err(err) = self.comm.outgoing_tx.send(CommMsg::Close) {
+                            log::error!("Error closing UI comm: {err:?}");
+                        }
+                        break;
+                    }
+                },
             }
         }
     }
 
+    /// Sends the next `ping`, unless too many pings went unanswered.
+    ///
+    /// Returns true if the frontend is still considered connected.
+    fn handle_heartbeat(&self) -> bool {
+        let Some(heartbeat) = self.heartbeat else {
+            return true;
+        };
+
+        let unanswered = self.heartbeat_unanswered.get();
+        if unanswered > heartbeat.max_missed {
+            return false;
+        }
+
+        let seq = self.heartbeat_seq.get() + 1;
+        self.heartbeat_seq.set(seq);
+        self.heartbeat_unanswered.set(unanswered + 1);
+
+        self.dispatch_event(&UiFrontendEvent::Ping(PingParams { seq }));
+        true
+    }
+
     fn dispatch_event(&self, event: &UiFrontendEvent) {
         let json = serde_json::to_value(event).unwrap();
 
@@ -118,6 +259,24 @@ impl UiComm {
             return false;
         }
 
+        // Methods are evaluated by R and may take a while, e.g. when running
+        // tests. Handle them on their own thread so this thread keeps
+        // forwarding events and answering heartbeats in the meantime.
+        if let CommMsg::Rpc(_, data) = &message {
+            if let Ok(UiBackendRequest::CallMethod(_)) =
+                serde_json::from_value::<UiBackendRequest>(data.clone())
+            {
+                let comm = self.comm.clone();
+                spawn!("ark-comm-ui-method", move || {
+                    comm.handle_request(message, |req| match req {
+                        UiBackendRequest::CallMethod(request) => call_method(&comm, request),
+                        req => Err(anyhow::anyhow!("Unexpected request: {req:?}")),
+                    });
+                });
+                return true;
+            }
+        }
+
         if self
             .comm
             .handle_request(message.clone(), |req| self.handle_backend_method(req))
@@ -140,44 +299,16 @@ impl UiComm {
     ) -> anyhow::Result<UiBackendReply, anyhow::Error> {
         let request = match request {
             UiBackendRequest::CallMethod(request) => request,
+            UiBackendRequest::Pong(pong) => {
+                // Any answer, even to an older ping, shows that the frontend
+                // is still around
+                log::trace!("Got heartbeat {} from the frontend", pong.seq);
+                self.heartbeat_unanswered.set(0);
+                return Ok(UiBackendReply::PongReply());
+            },
         };
 
-        log::trace!("Handling '{}' frontend RPC method", request.method);
-
-        // Today, all RPCs are fulfilled by R directly. Check to see if an R
-        // method of the appropriate name is defined.
-        //
-        // Consider: In the future, we may want to allow requests to be
-        // fulfilled here on the Rust side, with only some requests forwarded to
-        // R; Rust methods may wish to establish their own RPC handlers.
-
-        // The method name is prefixed with ".ps.rpc.", by convention
-        let method = format!(".ps.rpc.{}", request.method);
-
-        // Use the `exists` function to see if the method exists
-        let exists = r_task(|| unsafe {
-            let exists = RFunction::from("exists")
-                .param("x", method.clone())
-                .call()?;
-            RObject::to::<bool>(exists)
-        })?;
-
-        if !exists {
-            anyhow::bail!("No such method: {}", request.method);
-        }
-
-        // Form an R function call from the request
-        let result = r_task(|| {
-            let mut call = RFunction::from(method);
-            for param in request.params.iter() {
-                let p = RObject::try_from(param.clone())?;
-                call.add(p);
-            }
-            let result = call.call()?;
-            Value::try_from(result)
-        })?;
-
-        Ok(UiBackendReply::CallMethodReply(result))
+        call_method(&self.comm, request)
     }
 
     /**
@@ -190,3 +321,46 @@ impl UiComm {
         Ok(())
     }
 }
+
+/// Evaluates the R method of a `call_method` RPC, named `.ps.rpc.<method>` by
+/// convention
+fn call_method(comm: &CommSocket, request: CallMethodParams) -> anyhow::Result<UiBackendReply> {
+    log::trace!("Handling '{}' frontend RPC method", request.method);
+
+    // Today, all RPCs are fulfilled by R directly. Check to see if an R
+    // method of the appropriate name is defined.
+    //
+    // Consider: In the future, we may want to allow requests to be
+    // fulfilled here on the Rust side, with only some requests forwarded to
+    // R; Rust methods may wish to establish their own RPC handlers.
+
+    // The method name is prefixed with ".ps.rpc.", by convention
+    let method = format!(".ps.rpc.{}", request.method);
+
+    // Use the `exists` function to see if the method exists
+    let exists = r_task(|| unsafe {
+        let exists = RFunction::from("exists")
+            .param("x", method.clone())
+            .call()?;
+        RObject::to::<bool>(exists)
+    })?;
+
+    if !exists {
+        anyhow::bail!("No such method: {}", request.method);
+    }
+
+    // Form an R function call from the request
+    let result = r_task(|| {
+        let _outgoing = RpcOutgoingGuard::new(comm.outgoing_tx.clone());
+
+        let mut call = RFunction::from(method);
+        for param in request.params.iter() {
+            let p = RObject::try_from(param.clone())?;
+            call.add(p);
+        }
+        let result = call.call()?;
+        Value::try_from(result)
+    })?;
+
+    Ok(UiBackendReply::CallMethodReply(result))
+}
@@ -0,0 +1,526 @@
+//
+// methods.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use harp::call::RArgument;
+use harp::environment::r_ns_env;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::utils::r_is_function;
+use harp::utils::r_promise_force;
+use harp::utils::r_typeof;
+use harp::RObject;
+use libr::R_MissingArg;
+use libr::PROMSXP;
+use libr::SEXP;
+
+use crate::modules::ARK_ENVS;
+use crate::r_task;
+
+/// The generics that have a method for a class vector, so that the methods of
+/// an object are looked up once for all generics. Cleared whenever a method
+/// is registered or unregistered.
+static CLASS_GENERICS: LazyLock<Mutex<HashMap<Vec<String>, Vec<ArkGenerics>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Generics that packages can implement to customise how their objects are
+/// shown in the variables pane. Methods are regular functions named
+/// `<generic>.<class>` in a package namespace, they don't need to be exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArkGenerics {
+    VariableDisplayValue,
+    VariableDisplayType,
+    VariableHasChildren,
+    VariableKind,
+    VariableLength,
+}
+
+impl ArkGenerics {
+    pub const ALL: [ArkGenerics; 5] = [
+        ArkGenerics::VariableDisplayValue,
+        ArkGenerics::VariableDisplayType,
+        ArkGenerics::VariableHasChildren,
+        ArkGenerics::VariableKind,
+        ArkGenerics::VariableLength,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArkGenerics::VariableDisplayValue => "ark_variable_display_value",
+            ArkGenerics::VariableDisplayType => "ark_variable_display_type",
+            ArkGenerics::VariableHasChildren => "ark_variable_has_children",
+            ArkGenerics::VariableKind => "ark_variable_kind",
+            ArkGenerics::VariableLength => "ark_variable_length",
+        }
+    }
+
+    /// Call the method registered for the class of `x`, if any.
+    ///
+    /// Returns `Ok(None)` when no method is registered. Errors when the method
+    /// fails or returns something that can't be converted to `T`.
+    pub fn try_dispatch<T>(&self, x: SEXP, args: Vec<RArgument>) -> anyhow::Result<Option<T>>
+    where
+        T: TryFrom<RObject>,
+        <T as TryFrom<RObject>>::Error: std::fmt::Debug,
+    {
+        if !self.has_method(x)? {
+            return Ok(None);
+        }
+
+        let generic = self.as_str();
+        let mut call = RFunction::new("", "call_ark_method");
+        call.add(generic).add(x);
+
+        for RArgument { name, value } in args.into_iter() {
+            call.param(name.as_str(), value);
+        }
+
+        let out = call.call_in(ARK_ENVS.positron_ns)?;
+
+        match T::try_from(out) {
+            Ok(value) => Ok(Some(value)),
+            Err(err) => Err(anyhow!(
+                "Method for `{generic}` returned an unexpected value: {err:?}"
+            )),
+        }
+    }
+
+    pub fn has_method(&self, x: SEXP) -> anyhow::Result<bool> {
+        // Missing arguments can't be passed on to the R side
+        if x == unsafe { R_MissingArg } {
+            return Ok(false);
+        }
+
+        let class: Vec<String> = RFunction::new("base", "class").add(x).call()?.try_into()?;
+
+        if let Some(generics) = CLASS_GENERICS.lock().unwrap().get(&class) {
+            return Ok(generics.contains(self));
+        }
+
+        let generics: Vec<String> = RFunction::new("", "ark_class_generics")
+            .add(RObject::from(class.clone()))
+            .call_in(ARK_ENVS.positron_ns)?
+            .try_into()?;
+        let generics: Vec<ArkGenerics> = generics
+            .iter()
+            .filter_map(|generic| generic.parse().ok())
+            .collect();
+
+        let has_method = generics.contains(self);
+        CLASS_GENERICS.lock().unwrap().insert(class, generics);
+        Ok(has_method)
+    }
+
+    pub fn register_method(&self, class: &str, method: RObject) -> anyhow::Result<()> {
+        self.register_package_method(class, method, "")
+    }
+
+    fn register_package_method(
+        &self,
+        class: &str,
+        method: RObject,
+        package: &str,
+    ) -> anyhow::Result<()> {
+        let generic = self.as_str();
+
+        RFunction::new("", "register_ark_method")
+            .add(generic)
+            .add(class)
+            .add(method)
+            .add(package)
+            .call_in(ARK_ENVS.positron_ns)?;
+
+        clear_class_generics();
+        Ok(())
+    }
+
+    pub fn unregister_method(&self, class: &str) -> anyhow::Result<()> {
+        let generic = self.as_str();
+
+        RFunction::new("", "unregister_ark_method")
+            .add(generic)
+            .add(class)
+            .call_in(ARK_ENVS.positron_ns)?;
+
+        clear_class_generics();
+        Ok(())
+    }
+
+    /// Parse a function name like `ark_variable_kind.foo` into its generic
+    /// and class.
+    pub fn parse_method(name: &str) -> Option<(Self, String)> {
+        for generic in Self::ALL {
+            let Some(class) = name
+                .strip_prefix(generic.as_str())
+                .and_then(|rest| rest.strip_prefix('.'))
+            else {
+                continue;
+            };
+
+            if !class.is_empty() {
+                return Some((generic, class.to_string()));
+            }
+        }
+
+        None
+    }
+}
+
+impl FromStr for ArkGenerics {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|generic| generic.as_str() == name)
+            .ok_or(anyhow!("`{name}` is not an ark generic"))
+    }
+}
+
+/// Register the ark methods defined in the namespace of `package`.
+///
+/// Methods previously registered for that package are removed first so that
+/// a package that is unloaded and then loaded again ends up with the methods
+/// of its current namespace.
+pub fn populate_variable_methods_table(package: &str) -> anyhow::Result<()> {
+    unregister_package_methods(package)?;
+
+    let ns = r_ns_env(package)?;
+
+    for name in ns.names() {
+        let Some((generic, class)) = ArkGenerics::parse_method(&name) else {
+            continue;
+        };
+
+        let mut method = ns.find(name.as_str())?;
+
+        // Namespace bindings are lazy-load promises until first accessed
+        if r_typeof(method) == PROMSXP {
+            method = unsafe { r_promise_force(method)? }.sexp;
+        }
+
+        if !r_is_function(method) {
+            continue;
+        }
+
+        generic.register_package_method(&class, RObject::new(method), package)?;
+    }
+
+    Ok(())
+}
+
+/// Remove all ark methods registered from the namespace of `package`, e.g.
+/// because it is being unloaded. Manually registered methods are kept.
+pub fn unregister_package_methods(package: &str) -> anyhow::Result<()> {
+    RFunction::new("", "unregister_ark_package_methods")
+        .add(package)
+        .call_in(ARK_ENVS.positron_ns)?;
+
+    clear_class_generics();
+    Ok(())
+}
+
+fn clear_class_generics() {
+    CLASS_GENERICS.lock().unwrap().clear();
+}
+
+/// Register the ark methods of namespaces loaded before ark was initialized.
+/// Namespaces loaded later on are populated from the onload hook.
+///
+/// Each namespace is scanned in its own idle task so this doesn't hold up
+/// startup.
+pub(crate) fn populate_methods_from_loaded_namespaces() -> anyhow::Result<()> {
+    let loaded = RFunction::new("base", "loadedNamespaces").call()?;
+    let loaded: Vec<String> = loaded.try_into()?;
+
+    for pkg in loaded.into_iter() {
+        r_task::spawn_idle(|| async move {
+            if let Err(err) = populate_variable_methods_table(&pkg) {
+                log::error!("Can't populate ark methods for `{pkg}`: {err:?}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[harp::register]
+unsafe extern "C" fn ps_register_ark_method(
+    generic: SEXP,
+    class: SEXP,
+    method: SEXP,
+) -> anyhow::Result<SEXP> {
+    let generic: String = RObject::view(generic).try_into()?;
+    let class: String = RObject::view(class).try_into()?;
+
+    let generic: ArkGenerics = generic.parse()?;
+
+    generic.register_method(&class, RObject::new(method))?;
+    Ok(harp::r_null())
+}
+
+#[harp::register]
+unsafe extern "C" fn ps_unregister_ark_method(generic: SEXP, class: SEXP) -> anyhow::Result<SEXP> {
+    let generic: String = RObject::view(generic).try_into()?;
+    let class: String = RObject::view(class).try_into()?;
+
+    let generic: ArkGenerics = generic.parse()?;
+
+    generic.unregister_method(&class)?;
+    Ok(harp::r_null())
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::Binding;
+    use harp::environment::Environment;
+    use harp::environment::R_ENVS;
+    use harp::parse_eval0;
+
+    use crate::modules::ARK_ENVS;
+    use crate::r_task;
+    use crate::variables::methods::populate_variable_methods_table;
+    use crate::variables::methods::unregister_package_methods;
+    use crate::variables::methods::ArkGenerics;
+    use crate::variables::variable::PositronVariable;
+
+    #[test]
+    fn test_parse_method() {
+        assert_eq!(
+            ArkGenerics::parse_method("ark_variable_display_value.foo"),
+            Some((ArkGenerics::VariableDisplayValue, String::from("foo")))
+        );
+        assert_eq!(
+            ArkGenerics::parse_method("ark_variable_kind.data.frame"),
+            Some((ArkGenerics::VariableKind, String::from("data.frame")))
+        );
+        assert_eq!(
+            ArkGenerics::parse_method("ark_variable_length.foo"),
+            Some((ArkGenerics::VariableLength, String::from("foo")))
+        );
+        assert_eq!(ArkGenerics::parse_method("ark_variable_kind."), None);
+        assert_eq!(ArkGenerics::parse_method("ark_variable_kindfoo"), None);
+        assert_eq!(ArkGenerics::parse_method("print.foo"), None);
+    }
+
+    #[test]
+    fn test_populate_methods_of_package_loaded_after_startup() {
+        r_task(|| {
+            // Register a namespace, like `loadNamespace()` would
+            let load = |value: &str| {
+                let code = format!(
+                    "local({{
+                        ns <- new.env(parent = baseenv())
+                        ns$ark_variable_display_value.arkfakepkg <- function(x, ...) '{value}'
+                        assign('arkfakepkg', ns, envir = .Internal(getNamespaceRegistry()))
+                    }})"
+                );
+                parse_eval0(&code, R_ENVS.base).unwrap();
+            };
+            let unload = || {
+                parse_eval0(
+                    "rm('arkfakepkg', envir = .Internal(getNamespaceRegistry()))",
+                    R_ENVS.base,
+                )
+                .unwrap();
+            };
+
+            let x = parse_eval0("structure(1, class = 'arkfakepkg')", R_ENVS.base).unwrap();
+            let dispatch = || -> Option<String> {
+                ArkGenerics::VariableDisplayValue
+                    .try_dispatch(x.sexp, vec![])
+                    .unwrap()
+            };
+
+            assert_eq!(dispatch(), None);
+
+            // Only this package is scanned, as the onload hook does
+            load("one");
+            populate_variable_methods_table("arkfakepkg").unwrap();
+            assert_eq!(dispatch(), Some(String::from("one")));
+
+            // Reloading the package registers its new methods
+            unload();
+            load("two");
+            populate_variable_methods_table("arkfakepkg").unwrap();
+            assert_eq!(dispatch(), Some(String::from("two")));
+
+            // Methods that are no longer defined are unregistered
+            unload();
+            parse_eval0(
+                "assign('arkfakepkg', new.env(), envir = .Internal(getNamespaceRegistry()))",
+                R_ENVS.base,
+            )
+            .unwrap();
+            populate_variable_methods_table("arkfakepkg").unwrap();
+            assert_eq!(dispatch(), None);
+
+            unload();
+        })
+    }
+
+    #[test]
+    fn test_register_method() {
+        r_task(|| {
+            let method = parse_eval0("function(x, ...) 'manual'", R_ENVS.base).unwrap();
+            ArkGenerics::VariableDisplayType
+                .register_method("arkmanualclass", method)
+                .unwrap();
+
+            let x = parse_eval0("structure(1, class = 'arkmanualclass')", R_ENVS.base).unwrap();
+            let out: Option<String> = ArkGenerics::VariableDisplayType
+                .try_dispatch(x.sexp, vec![])
+                .unwrap();
+            assert_eq!(out, Some(String::from("manual")));
+
+            // Unknown generics are rejected on the R side
+            let out = parse_eval0(
+                ".ps.register_ark_method('ark_unknown', 'foo', identity)",
+                ARK_ENVS.positron_ns,
+            );
+            assert!(out.is_err());
+        })
+    }
+    #[test]
+    fn test_unregister_method() {
+        r_task(|| {
+            let method = parse_eval0("function(x, ...) TRUE", R_ENVS.base).unwrap();
+            let x = parse_eval0("structure(1, class = 'arkunregisterclass')", R_ENVS.base).unwrap();
+            let generic = ArkGenerics::VariableHasChildren;
+
+            generic
+                .register_method("arkunregisterclass", method)
+                .unwrap();
+            assert!(generic.has_method(x.sexp).unwrap());
+
+            generic.unregister_method("arkunregisterclass").unwrap();
+            assert!(!generic.has_method(x.sexp).unwrap());
+
+            // Unregistering a method that isn't registered is a no-op
+            generic.unregister_method("arkunregisterclass").unwrap();
+
+            // Same from the R side
+            parse_eval0(
+                ".ps.register_ark_method('ark_variable_has_children', 'arkunregisterclass', function(x, ...) TRUE)",
+                ARK_ENVS.positron_ns,
+            )
+            .unwrap();
+            assert!(generic.has_method(x.sexp).unwrap());
+            parse_eval0(
+                ".ps.unregister_ark_method('ark_variable_has_children', 'arkunregisterclass')",
+                ARK_ENVS.positron_ns,
+            )
+            .unwrap();
+            assert!(!generic.has_method(x.sexp).unwrap());
+        })
+    }
+
+    #[test]
+    fn test_variable_length_method() {
+        r_task(|| {
+            let env = Environment::new(parse_eval0("new.env()", R_ENVS.base).unwrap());
+            parse_eval0(
+                "x <- structure(list(), class = 'arklengthclass')",
+                env.inner.sexp,
+            )
+            .unwrap();
+            let variable = || {
+                let binding = Binding::new(&env, "x".into()).unwrap();
+                PositronVariable::new(&binding).var()
+            };
+
+            // Falls back to the length of the list
+            assert_eq!(variable().length, 0);
+            assert!(!variable().has_children);
+
+            let method = parse_eval0("function(x, ...) 1024", R_ENVS.base).unwrap();
+            ArkGenerics::VariableLength
+                .register_method("arklengthclass", method)
+                .unwrap();
+
+            assert_eq!(variable().length, 1024);
+            assert!(variable().has_children);
+
+            ArkGenerics::VariableLength
+                .unregister_method("arklengthclass")
+                .unwrap();
+        })
+    }
+
+    #[test]
+    fn test_display_value_method_truncation() {
+        r_task(|| {
+            let env = Environment::new(parse_eval0("new.env()", R_ENVS.base).unwrap());
+            parse_eval0(
+                "x <- structure(list(), class = 'arkwideclass')",
+                env.inner.sexp,
+            )
+            .unwrap();
+            let variable = || {
+                let binding = Binding::new(&env, "x".into()).unwrap();
+                PositronVariable::new(&binding).var()
+            };
+
+            // The limit is in characters, not bytes
+            let method =
+                parse_eval0("function(x, ...) strrep('\\u00e9', 100)", R_ENVS.base).unwrap();
+            ArkGenerics::VariableDisplayValue
+                .register_method("arkwideclass", method)
+                .unwrap();
+            assert_eq!(variable().display_value, "\u{e9}".repeat(100));
+            assert!(!variable().is_truncated);
+
+            let method =
+                parse_eval0("function(x, ...) strrep('\\u00e9', 101)", R_ENVS.base).unwrap();
+            ArkGenerics::VariableDisplayValue
+                .register_method("arkwideclass", method)
+                .unwrap();
+            assert_eq!(variable().display_value, "\u{e9}".repeat(100));
+            assert!(variable().is_truncated);
+
+            ArkGenerics::VariableDisplayValue
+                .unregister_method("arkwideclass")
+                .unwrap();
+        })
+    }
+
+    #[test]
+    fn test_unregister_package_methods() {
+        r_task(|| {
+            parse_eval0(
+                "local({
+                    ns <- new.env(parent = baseenv())
+                    ns$ark_variable_kind.arkunloadedpkg <- function(x, ...) 'map'
+                    assign('arkunloadedpkg', ns, envir = .Internal(getNamespaceRegistry()))
+                })",
+                R_ENVS.base,
+            )
+            .unwrap();
+
+            let x = parse_eval0("structure(1, class = 'arkunloadedpkg')", R_ENVS.base).unwrap();
+            let generic = ArkGenerics::VariableKind;
+
+            populate_variable_methods_table("arkunloadedpkg").unwrap();
+            assert!(generic.has_method(x.sexp).unwrap());
+
+            // As the onunload hook does
+            unregister_package_methods("arkunloadedpkg").unwrap();
+            assert!(!generic.has_method(x.sexp).unwrap());
+
+            parse_eval0(
+                "rm('arkunloadedpkg', envir = .Internal(getNamespaceRegistry()))",
+                R_ENVS.base,
+            )
+            .unwrap();
+        })
+    }
+}
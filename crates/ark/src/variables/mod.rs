@@ -5,5 +5,6 @@
 //
 //
 
+pub mod methods;
 pub mod r_variables;
 pub mod variable;
@@ -5,14 +5,21 @@
 //
 //
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::event::CommManagerEvent;
+use amalthea::comm::variables_comm::ClearedParams;
 use amalthea::comm::variables_comm::ClipboardFormatFormat;
+use amalthea::comm::variables_comm::FormattedValue;
 use amalthea::comm::variables_comm::FormattedVariable;
 use amalthea::comm::variables_comm::InspectedVariable;
 use amalthea::comm::variables_comm::RefreshParams;
+use amalthea::comm::variables_comm::SetSortOrderSortOrder;
 use amalthea::comm::variables_comm::UpdateParams;
 use amalthea::comm::variables_comm::Variable;
+use amalthea::comm::variables_comm::VariableDescription;
 use amalthea::comm::variables_comm::VariableList;
 use amalthea::comm::variables_comm::VariablesBackendReply;
 use amalthea::comm::variables_comm::VariablesBackendRequest;
@@ -39,6 +46,7 @@ use crate::data_explorer::r_data_explorer::DataObjectEnvInfo;
 use crate::data_explorer::r_data_explorer::RDataExplorer;
 use crate::lsp::events::EVENTS;
 use crate::r_task;
+use crate::r_task::r_task_preserving_seed;
 use crate::thread::RThreadSafe;
 use crate::variables::variable::PositronVariable;
 
@@ -66,6 +74,12 @@ pub struct RVariables {
     /// thread. Tracked in https://github.com/posit-dev/positron/issues/1812
     current_bindings: RThreadSafe<Vec<Binding>>,
     version: u64,
+    sort_order: SetSortOrderSortOrder,
+    /// The rank of each binding in the order the bindings were first seen,
+    /// used to list them in definition order. R environments don't record
+    /// when their bindings were created.
+    definition_ranks: HashMap<String, u64>,
+    next_definition_rank: u64,
 }
 
 impl RVariables {
@@ -99,6 +113,9 @@ impl RVariables {
                 env,
                 current_bindings,
                 version: 0,
+                sort_order: SetSortOrderSortOrder::Name,
+                definition_ranks: HashMap::new(),
+                next_definition_rank: 0,
             };
             environment.execution_thread();
         });
@@ -193,8 +210,9 @@ impl RVariables {
     #[tracing::instrument(level = "trace", skip_all)]
     fn list_variables(&mut self) -> Vec<Variable> {
         let mut variables: Vec<Variable> = vec![];
-        r_task(|| {
-            self.update_bindings(self.bindings());
+        r_task_preserving_seed(|| {
+            let bindings = self.bindings();
+            self.update_bindings(bindings);
 
             for binding in self.current_bindings.get() {
                 variables.push(PositronVariable::new(binding).var());
@@ -220,7 +238,11 @@ impl RVariables {
             },
             VariablesBackendRequest::Clear(params) => {
                 self.clear(params.include_hidden_objects)?;
-                self.update(None);
+                r_task_preserving_seed(|| {
+                    let bindings = self.bindings();
+                    self.update_bindings(bindings);
+                });
+                self.send_cleared();
                 Ok(VariablesBackendReply::ClearReply())
             },
             VariablesBackendRequest::Delete(params) => {
@@ -241,10 +263,30 @@ impl RVariables {
                     FormattedVariable { content },
                 ))
             },
+            VariablesBackendRequest::FormatValue(params) => {
+                let lines = self.format_value(&params.path, params.width)?;
+                Ok(VariablesBackendReply::FormatValueReply(FormattedValue {
+                    lines,
+                }))
+            },
+            VariablesBackendRequest::Describe(params) => {
+                let descriptions = self.describe(&params.paths);
+                Ok(VariablesBackendReply::DescribeReply(descriptions))
+            },
             VariablesBackendRequest::View(params) => {
                 let viewer_id = self.view(&params.path)?;
                 Ok(VariablesBackendReply::ViewReply(viewer_id))
             },
+            VariablesBackendRequest::SetSortOrder(params) => {
+                self.sort_order = params.sort_order;
+                let list = self.list_variables();
+                let count = list.len() as i64;
+                Ok(VariablesBackendReply::SetSortOrderReply(VariableList {
+                    variables: list,
+                    length: count,
+                    version: Some(self.version as i64),
+                }))
+            },
         }
     }
 
@@ -302,14 +344,39 @@ impl RVariables {
         path: &Vec<String>,
         format: ClipboardFormatFormat,
     ) -> Result<String, harp::error::Error> {
-        r_task(|| {
+        r_task_preserving_seed(|| {
             let env = self.env.get().clone();
             PositronVariable::clip(env, &path, &format)
         })
     }
 
+    /// Capture the output of printing the given variable.
+    ///
+    /// - `path`: The path to the variable to format, as an array of access keys
+    /// - `width`: The console width to print with
+    fn format_value(
+        &mut self,
+        path: &Vec<String>,
+        width: i64,
+    ) -> Result<Vec<String>, harp::error::Error> {
+        r_task_preserving_seed(|| {
+            let env = self.env.get().clone();
+            PositronVariable::format_value(env, &path, width)
+        })
+    }
+
+    /// Describe a batch of variables in a single task on the R thread.
+    ///
+    /// - `paths`: The paths to the variables, each as an array of access keys
+    fn describe(&mut self, paths: &[Vec<String>]) -> Vec<VariableDescription> {
+        r_task_preserving_seed(|| {
+            let env = self.env.get().clone();
+            PositronVariable::describe(env, paths)
+        })
+    }
+
     fn inspect(&mut self, path: &Vec<String>) -> Result<Vec<Variable>, harp::error::Error> {
-        r_task(|| {
+        r_task_preserving_seed(|| {
             let env = self.env.get().clone();
             PositronVariable::inspect(env, &path)
         })
@@ -357,81 +424,71 @@ impl RVariables {
         }
     }
 
+    fn send_cleared(&mut self) {
+        let event = VariablesFrontendEvent::Cleared(ClearedParams {
+            version: self.version as i64,
+        });
+        self.send_event(event, None);
+    }
+
     #[tracing::instrument(level = "trace", skip_all)]
     fn update(&mut self, request_id: Option<String>) {
         let mut assigned: Vec<Variable> = vec![];
         let mut removed: Vec<String> = vec![];
+        let mut cleared = false;
 
-        r_task(|| {
+        r_task_preserving_seed(|| {
             let new_bindings = self.bindings();
 
-            let mut old_iter = self.current_bindings.get().iter();
-            let mut old_next = old_iter.next();
-
-            let mut new_iter = new_bindings.get().iter();
-            let mut new_next = new_iter.next();
-
-            loop {
-                match (old_next, new_next) {
-                    // nothing more to do
-                    (None, None) => break,
-
-                    // No more old, collect last new into added
-                    (None, Some(mut new)) => {
-                        loop {
-                            assigned.push(PositronVariable::new(&new).var());
-
-                            match new_iter.next() {
-                                Some(x) => {
-                                    new = x;
-                                },
-                                None => break,
-                            };
-                        }
-                        break;
-                    },
-
-                    // No more new, collect the last old into removed
-                    (Some(mut old), None) => {
-                        loop {
-                            removed.push(old.name.to_string());
-
-                            match old_iter.next() {
-                                Some(x) => {
-                                    old = x;
-                                },
-                                None => break,
-                            };
-                        }
+            // The bindings may not be sorted by name, so they are matched by
+            // name rather than merged
+            let old_bindings: HashMap<String, &Binding> = self
+                .current_bindings
+                .get()
+                .iter()
+                .map(|binding| (binding.name.to_string(), binding))
+                .collect();
+            let mut new_names: HashSet<String> = HashSet::new();
+
+            for new in new_bindings.get().iter() {
+                let name = new.name.to_string();
+                match old_bindings.get(&name) {
+                    Some(old) if old.value == new.value => {},
+                    _ => assigned.push(PositronVariable::new(new).var()),
+                }
+                new_names.insert(name);
+            }
 
-                        break;
-                    },
-
-                    (Some(old), Some(new)) => {
-                        if old.name == new.name {
-                            if old.value != new.value {
-                                assigned.push(PositronVariable::new(&new).var());
-                            }
-                            old_next = old_iter.next();
-                            new_next = new_iter.next();
-                        } else if old.name < new.name {
-                            removed.push(old.name.to_string());
-                            old_next = old_iter.next();
-                        } else {
-                            assigned.push(PositronVariable::new(&new).var());
-                            new_next = new_iter.next();
-                        }
-                    },
+            for old in self.current_bindings.get().iter() {
+                let name = old.name.to_string();
+                if !new_names.contains(&name) {
+                    removed.push(name);
                 }
             }
 
+            // A mass removal, e.g. `rm(list = ls())`, is reported as a clear.
+            // Removing a single variable is a regular update, even if it was
+            // the last one. The environment is listed again to make sure it's
+            // empty, and not just missing bindings that failed to load.
+            cleared = assigned.is_empty() &&
+                removed.len() > 1 &&
+                removed.len() == self.current_bindings.get().len() &&
+                Environment::new_filtered(
+                    self.env.get().clone(),
+                    EnvironmentFilter::ExcludeHidden,
+                )
+                .names()
+                .is_empty();
+
             // Only update the bindings (and the version) if anything changed
             if assigned.len() > 0 || removed.len() > 0 {
                 self.update_bindings(new_bindings);
             }
         });
 
-        if assigned.len() > 0 || removed.len() > 0 || request_id.is_some() {
+        if cleared && request_id.is_none() {
+            self.send_cleared();
+        } else if assigned.len() > 0 || removed.len() > 0 || request_id.is_some() {
             // Send the message if anything changed or if this came from a request
             let event = VariablesFrontendEvent::Update(UpdateParams {
                 assigned,
@@ -445,14 +502,46 @@ impl RVariables {
 
     // SAFETY: The following methods must be called in an `r_task()`
 
-    fn bindings(&self) -> RThreadSafe<Vec<Binding>> {
+    fn bindings(&mut self) -> RThreadSafe<Vec<Binding>> {
         let env = self.env.get().clone();
         let env = Environment::new_filtered(env, EnvironmentFilter::ExcludeHidden);
 
-        let mut bindings: Vec<Binding> = env.iter().filter_map(|b| b.ok()).collect();
-
-        bindings.sort_by(|a, b| a.name.cmp(&b.name));
+        let bindings: Vec<Binding> = env.iter().filter_map(|b| b.ok()).collect();
+        let bindings = self.sort_bindings(bindings);
 
         RThreadSafe::new(bindings)
     }
+
+    /// Sorts bindings in the current sort order. Names are compared ignoring
+    /// case first, then by code point so that names differing only by case
+    /// are always in the same order, regardless of the locale. In definition
+    /// order, bindings first seen during the same scan are sorted by name.
+    fn sort_bindings(&mut self, bindings: Vec<Binding>) -> Vec<Binding> {
+        let mut bindings: Vec<(String, Binding)> = bindings
+            .into_iter()
+            .map(|binding| (binding.name.to_string(), binding))
+            .collect();
+
+        bindings.sort_by_cached_key(|(name, _)| (name.to_lowercase(), name.clone()));
+
+        // Forget the bindings that were removed so that they are listed last
+        // once defined again
+        let names: HashSet<&str> = bindings.iter().map(|(name, _)| name.as_str()).collect();
+        self.definition_ranks
+            .retain(|name, _| names.contains(name.as_str()));
+
+        for (name, _) in bindings.iter() {
+            if !self.definition_ranks.contains_key(name) {
+                self.definition_ranks
+                    .insert(name.clone(), self.next_definition_rank);
+                self.next_definition_rank += 1;
+            }
+        }
+
+        if let SetSortOrderSortOrder::Definition = self.sort_order {
+            bindings.sort_by_key(|(name, _)| self.definition_ranks[name]);
+        }
+
+        bindings.into_iter().map(|(_, binding)| binding).collect()
+    }
 }
@@ -5,13 +5,16 @@
 //
 //
 
+use std::collections::HashMap;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
 use amalthea::comm::variables_comm::ClipboardFormatFormat;
 use amalthea::comm::variables_comm::Variable;
+use amalthea::comm::variables_comm::VariableDescription;
 use amalthea::comm::variables_comm::VariableKind;
 use anyhow::anyhow;
+use harp::call::RArgument;
 use harp::environment::Binding;
 use harp::environment::BindingValue;
 use harp::environment::Environment;
@@ -36,6 +39,7 @@ use harp::utils::r_is_s4;
 use harp::utils::r_is_simple_vector;
 use harp::utils::r_is_unbound;
 use harp::utils::r_promise_force_with_rollback;
+use harp::utils::r_promise_is_lazy_load_binding;
 use harp::utils::r_typeof;
 use harp::utils::r_vec_is_single_dimension_with_single_value;
 use harp::utils::r_vec_shape;
@@ -51,15 +55,54 @@ use libr::*;
 use stdext::local;
 use stdext::unwrap;
 
+use crate::variables::methods::ArkGenerics;
+
 // Constants.
 const MAX_DISPLAY_VALUE_ENTRIES: usize = 1_000;
 const MAX_DISPLAY_VALUE_LENGTH: usize = 100;
+const DEFAULT_MAX_STRING_LENGTH: usize = 500;
 
 pub struct WorkspaceVariableDisplayValue {
     pub display_value: String,
     pub is_truncated: bool,
 }
 
+/// The `ark.variables.max_string_length` option, or the default when it is
+/// unset or isn't a positive number
+fn max_string_length_option() -> usize {
+    let max_length: Option<i32> =
+        harp::object::r_null_or_try_into(harp::get_option("ark.variables.max_string_length"))
+            .ok()
+            .flatten();
+
+    max_length
+        .and_then(|max_length| usize::try_from(max_length).ok())
+        .filter(|max_length| *max_length > 0)
+        .unwrap_or(DEFAULT_MAX_STRING_LENGTH)
+}
+
+/// Truncates a formatted string element, e.g. `"abc"`, to `max_length`
+/// characters and notes its full length: `"abc…" (1234 chars)`. Returns
+/// whether the element was truncated. Counts characters rather than bytes so
+/// multibyte characters aren't split.
+fn truncate_string_element(formatted: String, max_length: usize) -> (String, bool) {
+    let Some(inner) = formatted
+        .strip_prefix('"')
+        .and_then(|formatted| formatted.strip_suffix('"'))
+    else {
+        // `NA`
+        return (formatted, false);
+    };
+
+    let length = inner.chars().count();
+    if length <= max_length {
+        return (formatted, false);
+    }
+
+    let truncated: String = inner.chars().take(max_length).collect();
+    (format!("\"{truncated}…\" ({length} chars)"), true)
+}
+
 fn plural(text: &str, n: i32) -> String {
     if n == 1 {
         String::from(text)
@@ -70,6 +113,10 @@ fn plural(text: &str, n: i32) -> String {
 
 impl WorkspaceVariableDisplayValue {
     pub fn from(value: SEXP) -> Self {
+        if let Some(display_value) = Self::from_method(value) {
+            return display_value;
+        }
+
         match r_typeof(value) {
             NILSXP => Self::new(String::from("NULL"), false),
             VECSXP if r_inherits(value, "data.frame") => Self::from_data_frame(value),
@@ -85,6 +132,22 @@ impl WorkspaceVariableDisplayValue {
         }
     }
 
+    fn from_method(value: SEXP) -> Option<Self> {
+        let width = RArgument::new("width", RObject::from(MAX_DISPLAY_VALUE_LENGTH as i32));
+        let display_value: String =
+            try_dispatch(ArkGenerics::VariableDisplayValue, value, vec![width])?;
+
+        if display_value.chars().count() > MAX_DISPLAY_VALUE_LENGTH {
+            let display_value = display_value
+                .chars()
+                .take(MAX_DISPLAY_VALUE_LENGTH)
+                .collect();
+            return Some(Self::new(display_value, true));
+        }
+
+        Some(Self::new(display_value, false))
+    }
+
     fn new(display_value: String, is_truncated: bool) -> Self {
         WorkspaceVariableDisplayValue {
             display_value,
@@ -271,7 +334,7 @@ impl WorkspaceVariableDisplayValue {
                 display_value.push_str(display_column.as_str());
                 display_value.push(']');
 
-                if display_value.len() > MAX_DISPLAY_VALUE_LENGTH {
+                if display_value.chars().count() > MAX_DISPLAY_VALUE_LENGTH {
                     is_truncated = true;
                 }
                 if is_truncated {
@@ -292,14 +355,26 @@ impl WorkspaceVariableDisplayValue {
         let mut display_value = String::from("");
         let mut is_truncated = false;
 
+        // Only the default rendering of strings is truncated, display value
+        // methods are in charge of their own output
+        let max_string_length = (r_typeof(value) == STRSXP).then(max_string_length_option);
+
         for x in formatted.iter() {
             if first {
                 first = false;
             } else {
                 display_value.push(' ');
             }
+            let x = match max_string_length {
+                Some(max_length) => {
+                    let (x, truncated) = truncate_string_element(x, max_length);
+                    is_truncated |= truncated;
+                    x
+                },
+                None => x,
+            };
             display_value.push_str(&x);
-            if display_value.len() > MAX_DISPLAY_VALUE_LENGTH {
+            if display_value.chars().count() > MAX_DISPLAY_VALUE_LENGTH {
                 is_truncated = true;
                 break;
             }
@@ -327,6 +402,15 @@ impl WorkspaceVariableDisplayType {
     /// - include_length: Whether to include the length of the object in the
     ///   display type.
     pub fn from(value: SEXP, include_length: bool) -> Self {
+        let include_length_arg = RArgument::new("include_length", RObject::from(include_length));
+        if let Some(display_type) =
+            try_dispatch::<String>(ArkGenerics::VariableDisplayType, value, vec![
+                include_length_arg,
+            ])
+        {
+            return Self::simple(display_type);
+        }
+
         if r_is_null(value) {
             return Self::simple(String::from("NULL"));
         }
@@ -434,6 +518,14 @@ impl WorkspaceVariableDisplayType {
 }
 
 fn has_children(value: SEXP) -> bool {
+    if let Some(has_children) = try_dispatch(ArkGenerics::VariableHasChildren, value, vec![]) {
+        return has_children;
+    }
+
+    if let Some(length) = variable_length_method(value) {
+        return length > 0;
+    }
+
     if RObject::view(value).is_s4() {
         unsafe {
             let names = RFunction::new("methods", ".slotNames")
@@ -459,6 +551,36 @@ fn has_children(value: SEXP) -> bool {
     }
 }
 
+/// Dispatch to the ark method for `x` if there is one. Errors are logged so
+/// that callers can fall back to the default behaviour.
+fn try_dispatch<T>(generic: ArkGenerics, x: SEXP, args: Vec<RArgument>) -> Option<T>
+where
+    T: TryFrom<RObject>,
+    <T as TryFrom<RObject>>::Error: std::fmt::Debug,
+{
+    match generic.try_dispatch(x, args) {
+        Ok(out) => out,
+        Err(err) => {
+            log::error!("Can't dispatch `{}`: {err:?}", generic.as_str());
+            None
+        },
+    }
+}
+
+/// The number of elements reported by the `ark_variable_length` method for
+/// `x`, for classes whose `length()` doesn't reflect what they contain.
+/// Doubles are accepted since R authors rarely bother with integer literals.
+fn variable_length_method(x: SEXP) -> Option<usize> {
+    let length = try_dispatch::<f64>(ArkGenerics::VariableLength, x, vec![])?;
+
+    if !length.is_finite() || length < 0.0 {
+        log::error!("`ark_variable_length` returned an invalid length: {length}");
+        return None;
+    }
+
+    Some(length as usize)
+}
+
 enum EnvironmentVariableNode {
     Concrete { object: RObject },
     Artificial { object: RObject, name: String },
@@ -477,13 +599,16 @@ impl PositronVariable {
     pub fn new(binding: &Binding) -> Self {
         let display_name = binding.name.to_string();
 
-        match &binding.value {
+        let mut variable = match &binding.value {
             BindingValue::Active { .. } => Self::from_active_binding(display_name),
             BindingValue::Promise { promise } => Self::from_promise(display_name, promise.sexp),
             BindingValue::Altrep { object, .. } | BindingValue::Standard { object, .. } => {
                 Self::from(display_name.clone(), display_name, object.sexp)
             },
-        }
+        };
+
+        variable.var.is_locked = binding.locked;
+        variable
     }
 
     /**
@@ -522,6 +647,8 @@ impl PositronVariable {
                 has_children: has_children(x),
                 is_truncated,
                 has_viewer: r_is_data_frame(x) || r_is_matrix(x),
+                is_active_binding: false,
+                is_locked: false,
                 updated_time: Self::update_timestamp(),
             },
         }
@@ -572,6 +699,8 @@ impl PositronVariable {
                 has_children: false,
                 is_truncated: false,
                 has_viewer: false,
+                is_active_binding: false,
+                is_locked: false,
                 updated_time: Self::update_timestamp(),
             },
         }
@@ -591,12 +720,18 @@ impl PositronVariable {
                 has_children: false,
                 is_truncated: false,
                 has_viewer: false,
+                is_active_binding: true,
+                is_locked: false,
                 updated_time: Self::update_timestamp(),
             },
         }
     }
 
     fn variable_length(x: SEXP) -> usize {
+        if let Some(length) = variable_length_method(x) {
+            return length;
+        }
+
         // Check for tabular data
         if let Some(info) = harp::table_info(x) {
             return info.dims.num_cols as usize;
@@ -621,6 +756,13 @@ impl PositronVariable {
     }
 
     fn variable_kind(x: SEXP) -> VariableKind {
+        if let Some(kind) = try_dispatch::<String>(ArkGenerics::VariableKind, x, vec![]) {
+            match serde_json::from_value(serde_json::Value::String(kind.clone())) {
+                Ok(kind) => return kind,
+                Err(_) => log::error!("Unknown variable kind `{kind}` returned by ark method"),
+            }
+        }
+
         if x == unsafe { R_NilValue } {
             return VariableKind::Empty;
         }
@@ -833,6 +975,98 @@ impl PositronVariable {
         }
     }
 
+    /// The display properties of the variables at `paths`. Top-level
+    /// variables are described from their binding and nested ones from the
+    /// inspection of their parent, which is shared by the variables of a
+    /// same parent. A variable that can't be resolved gets an error without
+    /// failing the others.
+    pub fn describe(env: RObject, paths: &[Vec<String>]) -> Vec<VariableDescription> {
+        let mut inspected: HashMap<Vec<String>, harp::Result<Vec<Variable>>> = HashMap::new();
+
+        let mut describe = |path: &Vec<String>| -> anyhow::Result<Variable> {
+            let Some((key, parent)) = path.split_last() else {
+                return Err(anyhow!("Can't describe an empty path."));
+            };
+
+            if parent.is_empty() {
+                let env = Environment::new(env.clone());
+                let binding =
+                    Binding::new(&env, key.as_str().into()).map_err(|err| anyhow!("{err}"))?;
+                return Ok(Self::new(&binding).var());
+            }
+
+            let children = inspected
+                .entry(parent.to_vec())
+                .or_insert_with(|| Self::inspect(env.clone(), &parent.to_vec()));
+
+            match children {
+                Ok(children) => children
+                    .iter()
+                    .find(|child| &child.access_key == key)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("Can't find variable at path {path:?}.")),
+                Err(err) => Err(anyhow!("{err}")),
+            }
+        };
+
+        paths
+            .iter()
+            .map(|path| match describe(path) {
+                Ok(variable) => VariableDescription {
+                    path: path.clone(),
+                    display_value: Some(variable.display_value),
+                    display_type: Some(variable.display_type),
+                    has_children: Some(variable.has_children),
+                    kind: Some(variable.kind),
+                    error: None,
+                },
+                Err(err) => VariableDescription {
+                    path: path.clone(),
+                    display_value: None,
+                    display_type: None,
+                    has_children: None,
+                    kind: None,
+                    error: Some(err.to_string()),
+                },
+            })
+            .collect()
+    }
+
+    pub fn format_value(
+        env: RObject,
+        path: &Vec<String>,
+        width: i64,
+    ) -> Result<Vec<String>, harp::error::Error> {
+        let node = unsafe { Self::resolve_object_from_path(env, &path)? };
+
+        match node {
+            EnvironmentVariableNode::Concrete { object } => {
+                let lines = RFunction::from(".ps.environment.formatValue")
+                    .add(object)
+                    .add(RObject::from(width as i32))
+                    .call()?;
+
+                Ok(lines.try_into()?)
+            },
+            EnvironmentVariableNode::Artificial { .. } => Ok(vec![]),
+            EnvironmentVariableNode::VectorElement { object, index } => {
+                let formatted = FormattedVector::new(*object)?;
+                Ok(vec![formatted.get_unchecked(index)])
+            },
+            EnvironmentVariableNode::Matrixcolumn { object, index } => unsafe {
+                let dim = IntegerVector::new(Rf_getAttrib(*object, R_DimSymbol))?;
+                let n_row = dim.get_unchecked(0).unwrap() as usize;
+
+                let column = FormattedVector::new(*object)?
+                    .iter()
+                    .skip(index as usize * n_row)
+                    .take(n_row)
+                    .collect();
+                Ok(column)
+            },
+        }
+    }
+
     pub fn resolve_data_object(
         env: RObject,
         path: &Vec<String>,
@@ -1015,6 +1249,8 @@ impl PositronVariable {
                     has_children: true,
                     is_truncated: false,
                     has_viewer: false,
+                    is_active_binding: false,
+                    is_locked: false,
                     updated_time: Self::update_timestamp(),
                 });
             }
@@ -1057,6 +1293,8 @@ impl PositronVariable {
                     has_children: false,
                     is_truncated: false,
                     has_viewer: false,
+                    is_active_binding: false,
+                    is_locked: false,
                     updated_time: Self::update_timestamp(),
                 });
             }
@@ -1084,19 +1322,30 @@ impl PositronVariable {
                 VariableKind::Number
             };
 
+            let max_string_length = (r_type == STRSXP).then(max_string_length_option);
+
             for i in 0..n {
+                let (display_value, is_truncated) = match max_string_length {
+                    Some(max_length) => {
+                        truncate_string_element(formatted.get_unchecked(i), max_length)
+                    },
+                    None => (formatted.get_unchecked(i), false),
+                };
+
                 out.push(Variable {
                     access_key: format!("{}", i),
                     display_name: names.get_unchecked(i),
-                    display_value: formatted.get_unchecked(i),
+                    display_value,
                     display_type: String::from(""),
                     type_info: String::from(""),
                     kind: kind.clone(),
                     length: 1,
                     size: 0,
                     has_children: false,
-                    is_truncated: false,
+                    is_truncated,
                     has_viewer: false,
+                    is_active_binding: false,
+                    is_locked: false,
                     updated_time: Self::update_timestamp(),
                 });
             }
@@ -1192,6 +1441,8 @@ impl PositronVariable {
                 has_children: true,
                 is_truncated: false,
                 has_viewer: false,
+                is_active_binding: false,
+                is_locked: false,
                 updated_time: Self::update_timestamp(),
             });
         }
@@ -1209,6 +1460,8 @@ impl PositronVariable {
                 has_children: true,
                 is_truncated: false,
                 has_viewer: false,
+                is_active_binding: false,
+                is_locked: false,
                 updated_time: Self::update_timestamp(),
             });
         }
@@ -1281,3 +1534,79 @@ pub fn plain_binding_force_with_rollback(binding: &Binding) -> anyhow::Result<RO
         _ => Err(anyhow!("Unexpected binding type")),
     }
 }
+
+/// Classify the binding of `name` in `env` without fetching its value.
+///
+/// Returns one of `"function"`, `"data"`, `"promise"` (an unforced promise
+/// that we don't evaluate), or `"active"` (an active binding, which we never
+/// call). Lazy-load promises, e.g. for functions in package namespaces, are
+/// forced since that only deserialises the value and doesn't run user code.
+#[harp::register]
+pub unsafe extern "C" fn ps_binding_kind(name: SEXP, env: SEXP) -> anyhow::Result<SEXP> {
+    let name: String = RObject::view(name).try_into()?;
+    let env = Environment::view(env);
+    let binding = Binding::new(&env, (&name).into())?;
+
+    let object = match binding.value {
+        BindingValue::Active { .. } => return Ok(*RObject::from("active")),
+        BindingValue::Promise { promise } => {
+            if !r_promise_is_lazy_load_binding(promise.sexp) {
+                return Ok(*RObject::from("promise"));
+            }
+            r_promise_force_with_rollback(promise.sexp)?
+        },
+        BindingValue::Altrep { object, .. } => object,
+        BindingValue::Standard { object, .. } => object,
+    };
+
+    let kind = match r_typeof(object.sexp) {
+        CLOSXP | BUILTINSXP | SPECIALSXP => "function",
+        _ => "data",
+    };
+
+    Ok(*RObject::from(kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::Binding;
+    use harp::environment::Environment;
+    use harp::environment::R_ENVS;
+    use harp::object::RObject;
+    use harp::parse_eval0;
+    use harp::raii::RLocalOption;
+
+    use crate::r_task;
+    use crate::variables::variable::PositronVariable;
+
+    #[test]
+    fn test_long_strings_are_truncated() {
+        r_task(|| {
+            let env = Environment::new(parse_eval0("new.env()", R_ENVS.base).unwrap());
+            parse_eval0(
+                "x <- c(strrep('a', 10000), 'b', strrep('é', 30))",
+                env.inner.sexp,
+            )
+            .unwrap();
+            let _max_string_length =
+                RLocalOption::new("ark.variables.max_string_length", RObject::from(20).sexp);
+
+            let binding = Binding::new(&env, "x".into()).unwrap();
+            let variable = PositronVariable::new(&binding).var();
+
+            let a = format!("\"{}…\" (10000 chars)", "a".repeat(20));
+            let e = format!("\"{}…\" (30 chars)", "é".repeat(20));
+            assert_eq!(variable.display_value, format!("{a} \"b\" {e}"));
+            assert!(variable.is_truncated);
+
+            // Elements are truncated too
+            let elements =
+                PositronVariable::inspect(env.inner.clone(), &vec![String::from("x")]).unwrap();
+            assert_eq!(elements[0].display_value, a);
+            assert!(elements[0].is_truncated);
+            assert_eq!(elements[1].display_value, "\"b\"");
+            assert!(!elements[1].is_truncated);
+            assert_eq!(elements[2].display_value, e);
+        })
+    }
+}
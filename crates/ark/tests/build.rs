@@ -0,0 +1,110 @@
+//
+// build.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use amalthea::comm::build_comm::BuildBackendReply;
+use amalthea::comm::build_comm::BuildBackendRequest;
+use amalthea::comm::build_comm::BuildDiagnosticSeverity;
+use amalthea::comm::build_comm::BuildFrontendEvent;
+use amalthea::comm::build_comm::BuildParams;
+use amalthea::comm::comm_channel::CommMsg;
+use amalthea::socket::comm::CommInitiator;
+use amalthea::socket::comm::CommSocket;
+use ark::build_output::BuildComm;
+use ark::r_task::r_task;
+use harp::object::RObject;
+
+#[test]
+fn test_build_comm_diagnostics() {
+    let has_build_tools: bool = r_task(|| {
+        harp::parse_eval_global(".ps.is_installed('pkgbuild') && pkgbuild::has_build_tools()")
+            .unwrap()
+            .try_into()
+            .unwrap()
+    });
+    if !has_build_tools {
+        return;
+    }
+
+    // Create a package whose C code doesn't compile
+    let path: String = r_task(|| unsafe {
+        let path = harp::parse_eval_global(
+            "local({
+                path <- tempfile('arkbuildtest')
+                dir.create(file.path(path, 'src'), recursive = TRUE)
+                writeLines(
+                    c('Package: arkbuildtest', 'Version: 0.0.1', 'Title: Test', 'License: MIT'),
+                    file.path(path, 'DESCRIPTION')
+                )
+                writeLines('useDynLib(arkbuildtest)', file.path(path, 'NAMESPACE'))
+                writeLines(
+                    c('int bad(void) {', '  return undeclared;', '}'),
+                    file.path(path, 'src', 'bad.c')
+                )
+                path
+            })",
+        )
+        .unwrap();
+        RObject::to::<String>(path).unwrap()
+    });
+
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-build-comm-id"),
+        String::from("positron.build"),
+    );
+    BuildComm::start(comm_socket.clone());
+
+    let request = BuildBackendRequest::Build(BuildParams {
+        path,
+        install: false,
+    });
+    let id = String::from("build-id-1");
+    comm_socket
+        .incoming_tx
+        .send(CommMsg::Rpc(
+            id.clone(),
+            serde_json::to_value(request).unwrap(),
+        ))
+        .unwrap();
+
+    // Output is streamed as events until the build replies
+    let mut events = Vec::new();
+    let reply = loop {
+        let msg = comm_socket
+            .outgoing_rx
+            .recv_timeout(std::time::Duration::from_secs(60))
+            .unwrap();
+        match msg {
+            CommMsg::Data(data) => {
+                events.push(serde_json::from_value::<BuildFrontendEvent>(data).unwrap());
+            },
+            CommMsg::Rpc(reply_id, reply) => {
+                assert_eq!(reply_id, id);
+                break serde_json::from_value::<BuildBackendReply>(reply).unwrap();
+            },
+            _ => panic!("Unexpected message: {msg:?}"),
+        }
+    };
+
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, BuildFrontendEvent::Output(_))));
+
+    assert!(events.iter().any(|event| match event {
+        BuildFrontendEvent::Diagnostic(diagnostic) =>
+            diagnostic.file.ends_with("bad.c") &&
+                diagnostic.line == 2 &&
+                diagnostic.severity == BuildDiagnosticSeverity::Error,
+        _ => false,
+    }));
+
+    let BuildBackendReply::BuildReply(result) = reply;
+    assert!(!result.success);
+    assert!(result.errors >= 1);
+
+    comm_socket.incoming_tx.send(CommMsg::Close).unwrap();
+}
@@ -153,7 +153,10 @@ fn default_format_options() -> FormatOptions {
         small_num_digits: 4,
         max_integral_digits: 7,
         thousands_sep: Some(",".to_string()),
+        decimal_sep: None,
         max_value_length: 100,
+        significant_digits: None,
+        date_format: None,
     }
 }
 
@@ -171,6 +174,7 @@ fn get_data_values_request(
                 first_index: row_start_index,
                 last_index: row_start_index + num_rows - 1,
             }),
+            format_options: None,
         })
         .collect();
 
@@ -1705,6 +1709,7 @@ fn test_get_data_values_by_indices() {
                 spec: ArraySelection::SelectIndices(DataSelectionIndices {
                     indices: row_indices.clone(),
                 }),
+                format_options: None,
             })
             .collect();
 
@@ -1733,6 +1738,69 @@ fn test_get_data_values_by_indices() {
     expect_get_data_values(vec![2], vec![0, 10], vec![vec!["0.00"]]); // Ignore oout of bounds
 }
 
+#[test]
+fn test_get_data_values_format_options() {
+    let _lock = r_test_lock();
+
+    let socket = open_data_explorer_from_expression(
+        r#"data.frame(
+            x = c(3.14159, 1234.5678, 0.000012345, 123456789012),
+            y = as.Date(c("2024-01-02", "2024-02-03", NA, "2024-12-31"))
+        )"#,
+        None,
+    )
+    .unwrap();
+
+    let significant = FormatOptions {
+        significant_digits: Some(3),
+        ..default_format_options()
+    };
+    let dates = FormatOptions {
+        date_format: Some(String::from("%d/%m/%Y")),
+        ..default_format_options()
+    };
+
+    // The date column is formatted with its own options
+    let columns = vec![
+        ColumnSelection {
+            column_index: 0,
+            spec: ArraySelection::SelectRange(DataSelectionRange {
+                first_index: 0,
+                last_index: 3,
+            }),
+            format_options: None,
+        },
+        ColumnSelection {
+            column_index: 1,
+            spec: ArraySelection::SelectRange(DataSelectionRange {
+                first_index: 0,
+                last_index: 3,
+            }),
+            format_options: Some(dates),
+        },
+    ];
+    let req = DataExplorerBackendRequest::GetDataValues(GetDataValuesParams {
+        columns,
+        format_options: significant,
+    });
+
+    let formatted = |values: Vec<&str>| -> Vec<ColumnValue> {
+        values
+            .into_iter()
+            .map(|v| ColumnValue::FormattedValue(v.to_string()))
+            .collect()
+    };
+
+    assert_match!(socket_rpc(&socket, req),
+        DataExplorerBackendReply::GetDataValuesReply(data) => {
+            // Very large and very small numbers switch to scientific notation
+            assert_eq!(data.columns[0], formatted(vec!["3.14", "1,230", "1.23e-05", "1.23e+11"]));
+            assert_eq!(data.columns[1][0], ColumnValue::FormattedValue(String::from("02/01/2024")));
+            assert_eq!(data.columns[1][3], ColumnValue::FormattedValue(String::from("31/12/2024")));
+        }
+    );
+}
+
 #[test]
 fn test_data_update_num_rows() {
     let _lock = r_test_lock();
@@ -9,6 +9,11 @@ use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::event::CommManagerEvent;
 use amalthea::comm::variables_comm::ClearParams;
 use amalthea::comm::variables_comm::DeleteParams;
+use amalthea::comm::variables_comm::DescribeParams;
+use amalthea::comm::variables_comm::FormatValueParams;
+use amalthea::comm::variables_comm::SetSortOrderParams;
+use amalthea::comm::variables_comm::SetSortOrderSortOrder;
+use amalthea::comm::variables_comm::VariableKind;
 use amalthea::comm::variables_comm::VariablesBackendReply;
 use amalthea::comm::variables_comm::VariablesBackendRequest;
 use amalthea::comm::variables_comm::VariablesFrontendEvent;
@@ -173,7 +178,7 @@ fn test_environment_list() {
         .send(CommMsg::Rpc(request_id.clone(), data))
         .unwrap();
 
-    // Wait up to 1s for the comm to send us an update message
+    // Wait up to 1s for the comm to send us a cleared message
     let msg = outgoing_rx
         .recv_timeout(std::time::Duration::from_secs(1))
         .unwrap();
@@ -182,15 +187,13 @@ fn test_environment_list() {
         _ => panic!("Expected data message, got {:?}", msg),
     };
 
-    // Ensure we get an event notifying us of the change
+    // Ensure we get a dedicated event rather than an empty update
     let evt: VariablesFrontendEvent = serde_json::from_value(data).unwrap();
     match evt {
-        VariablesFrontendEvent::Update(params) => {
-            assert_eq!(params.assigned.len(), 0);
-            assert_eq!(params.removed.len(), 1);
+        VariablesFrontendEvent::Cleared(params) => {
             assert_eq!(params.version, 4);
         },
-        _ => panic!("Expected update event"),
+        _ => panic!("Expected cleared event"),
     }
 
     // Wait for the success message to be delivered
@@ -279,3 +282,416 @@ fn test_environment_list() {
     // Close the comm. Otherwise the thread panics
     incoming_tx.send(CommMsg::Close).unwrap();
 }
+
+#[test]
+fn test_environment_format_value() {
+    let test_env = r_task(|| {
+        let env =
+            harp::parse_eval_base("local({ x <- matrix(1:60, nrow = 2); environment() })").unwrap();
+        RThreadSafe::new(env)
+    });
+
+    let comm = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-environment-format-value-comm-id"),
+        String::from("positron.environment"),
+    );
+    let (comm_manager_tx, _) = bounded::<CommManagerEvent>(0);
+
+    let incoming_tx = comm.incoming_tx.clone();
+    let outgoing_rx = comm.outgoing_rx.clone();
+    r_task(|| {
+        let test_env = test_env.get().clone();
+        RVariables::start(test_env, comm.clone(), comm_manager_tx.clone());
+    });
+
+    // Skip the initial refresh event
+    let msg = outgoing_rx.recv().unwrap();
+    assert!(matches!(msg, CommMsg::Data(_)));
+
+    let format_value = |width: i64| -> Vec<String> {
+        let request = VariablesBackendRequest::FormatValue(FormatValueParams {
+            path: vec![String::from("x")],
+            width,
+        });
+        let data = serde_json::to_value(request).unwrap();
+        let request_id = format!("format-value-id-{width}");
+        incoming_tx
+            .send(CommMsg::Rpc(request_id.clone(), data))
+            .unwrap();
+
+        let data = match outgoing_rx.recv().unwrap() {
+            CommMsg::Rpc(reply_id, data) => {
+                assert_eq!(request_id, reply_id);
+                data
+            },
+            msg => panic!("Expected RPC message, got {:?}", msg),
+        };
+
+        match serde_json::from_value(data).unwrap() {
+            VariablesBackendReply::FormatValueReply(formatted) => formatted.lines,
+            _ => panic!("Expected format value reply"),
+        }
+    };
+
+    let narrow = format_value(40);
+    let wide = format_value(120);
+
+    // Narrow output wraps the columns over more lines
+    assert!(narrow.len() > wide.len());
+    assert!(narrow.iter().all(|line| line.len() <= 40));
+    assert!(wide.iter().any(|line| line.len() > 40));
+
+    // The width option was restored after printing
+    r_task(|| {
+        let width: i32 = harp::get_option("width").try_into().unwrap();
+        assert_ne!(width, 40);
+        assert_ne!(width, 120);
+    });
+
+    incoming_tx.send(CommMsg::Close).unwrap();
+}
+
+#[test]
+fn test_environment_describe() {
+    let test_env = r_task(|| {
+        let env = harp::parse_eval_base(
+            "local({ x <- 1:3; lst <- list(a = 1, b = 'two'); environment() })",
+        )
+        .unwrap();
+        RThreadSafe::new(env)
+    });
+
+    let comm = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-environment-describe-comm-id"),
+        String::from("positron.environment"),
+    );
+    let (comm_manager_tx, _) = bounded::<CommManagerEvent>(0);
+
+    let incoming_tx = comm.incoming_tx.clone();
+    let outgoing_rx = comm.outgoing_rx.clone();
+    r_task(|| {
+        let test_env = test_env.get().clone();
+        RVariables::start(test_env, comm.clone(), comm_manager_tx.clone());
+    });
+
+    // Skip the initial refresh event
+    let msg = outgoing_rx.recv().unwrap();
+    assert!(matches!(msg, CommMsg::Data(_)));
+
+    let path = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+    let paths = vec![
+        path(&["x"]),
+        path(&["lst"]),
+        path(&["missing"]),
+        path(&["lst", "1"]),
+        path(&["lst", "5"]),
+    ];
+
+    let request = VariablesBackendRequest::Describe(DescribeParams {
+        paths: paths.clone(),
+    });
+    let data = serde_json::to_value(request).unwrap();
+    let request_id = String::from("describe-id");
+    incoming_tx
+        .send(CommMsg::Rpc(request_id.clone(), data))
+        .unwrap();
+
+    let data = match outgoing_rx.recv().unwrap() {
+        CommMsg::Rpc(reply_id, data) => {
+            assert_eq!(request_id, reply_id);
+            data
+        },
+        msg => panic!("Expected RPC message, got {:?}", msg),
+    };
+
+    let descriptions = match serde_json::from_value(data).unwrap() {
+        VariablesBackendReply::DescribeReply(descriptions) => descriptions,
+        _ => panic!("Expected describe reply"),
+    };
+
+    // One description per path, in order
+    assert_eq!(descriptions.len(), paths.len());
+    for (description, path) in descriptions.iter().zip(paths.iter()) {
+        assert_eq!(&description.path, path);
+    }
+
+    let x = &descriptions[0];
+    assert_eq!(x.display_value, Some(String::from("1 2 3")));
+    assert!(x.display_type.is_some());
+    assert!(x.has_children.is_some());
+    assert_eq!(x.kind, Some(VariableKind::Number));
+    assert_eq!(x.error, None);
+
+    let lst = &descriptions[1];
+    assert!(lst.display_value.is_some());
+    assert!(lst.display_type.is_some());
+    assert_eq!(lst.has_children, Some(true));
+    assert!(lst.kind.is_some());
+    assert_eq!(lst.error, None);
+
+    let b = &descriptions[3];
+    assert_eq!(b.display_value, Some(String::from("\"two\"")));
+    assert_eq!(b.has_children, Some(false));
+    assert_eq!(b.kind, Some(VariableKind::String));
+    assert_eq!(b.error, None);
+
+    // Variables that can't be resolved get an error, without failing the
+    // rest of the batch
+    for description in [&descriptions[2], &descriptions[4]] {
+        assert!(description.error.is_some());
+        assert_eq!(description.display_value, None);
+        assert_eq!(description.display_type, None);
+        assert_eq!(description.has_children, None);
+        assert_eq!(description.kind, None);
+    }
+
+    incoming_tx.send(CommMsg::Close).unwrap();
+}
+
+#[test]
+fn test_environment_active_and_locked_bindings() {
+    // The active binding counts its calls and errors when accessed
+    let test_env = r_task(|| {
+        let env = harp::parse_eval_base(
+            "local({
+                ark_test_active_calls <<- 0
+                env <- new.env(parent = emptyenv())
+                makeActiveBinding('active', function() {
+                    ark_test_active_calls <<- ark_test_active_calls + 1
+                    stop('accessed')
+                }, env)
+                env$locked <- 1
+                lockBinding('locked', env)
+                env$plain <- 2
+                env
+            })",
+        )
+        .unwrap();
+        RThreadSafe::new(env)
+    });
+
+    let comm = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-environment-active-bindings-comm-id"),
+        String::from("positron.environment"),
+    );
+    let (comm_manager_tx, _) = bounded::<CommManagerEvent>(0);
+
+    let incoming_tx = comm.incoming_tx.clone();
+    let outgoing_rx = comm.outgoing_rx.clone();
+    r_task(|| {
+        let test_env = test_env.get().clone();
+        RVariables::start(test_env, comm.clone(), comm_manager_tx.clone());
+    });
+
+    let data = match outgoing_rx.recv().unwrap() {
+        CommMsg::Data(data) => data,
+        msg => panic!("Expected data message, got {:?}", msg),
+    };
+    let variables = match serde_json::from_value(data).unwrap() {
+        VariablesFrontendEvent::Refresh(params) => params.variables,
+        _ => panic!("Expected refresh event"),
+    };
+
+    let flags: Vec<(String, bool, bool)> = variables
+        .iter()
+        .map(|var| {
+            (
+                var.display_name.clone(),
+                var.is_active_binding,
+                var.is_locked,
+            )
+        })
+        .collect();
+    assert_eq!(flags, vec![
+        (String::from("active"), true, false),
+        (String::from("locked"), false, true),
+        (String::from("plain"), false, false),
+    ]);
+
+    // Listing the variables didn't call the active binding
+    r_task(|| {
+        let calls: f64 = harp::parse_eval_global("ark_test_active_calls")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(calls, 0.0);
+        harp::parse_eval_global("rm(ark_test_active_calls)").unwrap();
+    });
+
+    incoming_tx.send(CommMsg::Close).unwrap();
+}
+
+#[test]
+fn test_environment_cleared_on_mass_removal() {
+    let test_env = r_task(|| {
+        let env = harp::parse_eval_base(
+            "local({
+            env <- new.env(parent = emptyenv())
+            env$a <- 1
+            env$b <- 2
+            env$c <- 3
+            env
+        })",
+        )
+        .unwrap();
+        RThreadSafe::new(env)
+    });
+
+    let comm = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-environment-cleared-comm-id"),
+        String::from("positron.environment"),
+    );
+    let (comm_manager_tx, _) = bounded::<CommManagerEvent>(0);
+
+    let incoming_tx = comm.incoming_tx.clone();
+    let outgoing_rx = comm.outgoing_rx.clone();
+    r_task(|| {
+        let test_env = test_env.get().clone();
+        RVariables::start(test_env, comm.clone(), comm_manager_tx.clone());
+    });
+
+    let recv_event = || -> VariablesFrontendEvent {
+        match outgoing_rx.recv().unwrap() {
+            CommMsg::Data(data) => serde_json::from_value(data).unwrap(),
+            msg => panic!("Expected data message, got {:?}", msg),
+        }
+    };
+
+    match recv_event() {
+        VariablesFrontendEvent::Refresh(params) => assert_eq!(params.length, 3),
+        _ => panic!("Expected refresh event"),
+    }
+
+    // Removing a single variable is a regular update
+    r_task(|| unsafe {
+        r_envir_remove("c", *test_env.get().clone());
+    });
+    EVENTS.console_prompt.emit(());
+
+    match recv_event() {
+        VariablesFrontendEvent::Update(params) => assert_eq!(params.removed, ["c"]),
+        _ => panic!("Expected update event"),
+    }
+
+    // Removing all of the variables at once is a clear
+    r_task(|| unsafe {
+        let test_env = test_env.get().clone();
+        r_envir_remove("a", *test_env);
+        r_envir_remove("b", *test_env);
+    });
+    EVENTS.console_prompt.emit(());
+
+    match recv_event() {
+        VariablesFrontendEvent::Cleared(params) => assert_eq!(params.version, 3),
+        _ => panic!("Expected cleared event"),
+    }
+
+    incoming_tx.send(CommMsg::Close).unwrap();
+}
+
+#[test]
+fn test_environment_sort_order() {
+    let test_env = r_task(|| {
+        let env = harp::parse_eval_base(
+            "local({
+                env <- new.env(parent = emptyenv())
+                env$b <- 1
+                env$B <- 2
+                env$a <- 3
+                assign('\\u00e9', 4, envir = env)
+                env
+            })",
+        )
+        .unwrap();
+        RThreadSafe::new(env)
+    });
+
+    let comm = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-environment-sort-order-comm-id"),
+        String::from("positron.environment"),
+    );
+    let (comm_manager_tx, _) = bounded::<CommManagerEvent>(0);
+
+    let incoming_tx = comm.incoming_tx.clone();
+    let outgoing_rx = comm.outgoing_rx.clone();
+    r_task(|| {
+        let test_env = test_env.get().clone();
+        RVariables::start(test_env, comm.clone(), comm_manager_tx.clone());
+    });
+
+    let names = |variables: Vec<amalthea::comm::variables_comm::Variable>| -> Vec<String> {
+        variables.into_iter().map(|var| var.display_name).collect()
+    };
+
+    // Names are sorted ignoring case first
+    let data = match outgoing_rx.recv().unwrap() {
+        CommMsg::Data(data) => data,
+        msg => panic!("Expected data message, got {:?}", msg),
+    };
+    let variables = match serde_json::from_value(data).unwrap() {
+        VariablesFrontendEvent::Refresh(params) => params.variables,
+        _ => panic!("Expected refresh event"),
+    };
+    assert_eq!(names(variables), vec!["a", "B", "b", "\u{e9}"]);
+
+    let request = |request: VariablesBackendRequest, id: &str| -> Vec<String> {
+        let data = serde_json::to_value(request).unwrap();
+        incoming_tx
+            .send(CommMsg::Rpc(String::from(id), data))
+            .unwrap();
+
+        let data = match outgoing_rx.recv().unwrap() {
+            CommMsg::Rpc(reply_id, data) => {
+                assert_eq!(reply_id, id);
+                data
+            },
+            msg => panic!("Expected RPC message, got {:?}", msg),
+        };
+
+        match serde_json::from_value(data).unwrap() {
+            VariablesBackendReply::ListReply(list) => names(list.variables),
+            VariablesBackendReply::SetSortOrderReply(list) => names(list.variables),
+            _ => panic!("Expected list of variables"),
+        }
+    };
+
+    let set_sort_order = |sort_order: SetSortOrderSortOrder| {
+        VariablesBackendRequest::SetSortOrder(SetSortOrderParams { sort_order })
+    };
+
+    // Bindings seen in the same scan are sorted by name
+    assert_eq!(
+        request(
+            set_sort_order(SetSortOrderSortOrder::Definition),
+            "sort-id-1"
+        ),
+        vec!["a", "B", "b", "\u{e9}"]
+    );
+
+    // Bindings defined in the meantime are listed after the unchanged ones
+    r_task(|| unsafe {
+        let test_env = test_env.get().clone();
+        r_envir_set("c", Rf_ScalarInteger(5), *test_env);
+        r_envir_set("A", Rf_ScalarInteger(6), *test_env);
+    });
+    assert_eq!(request(VariablesBackendRequest::List, "list-id-1"), vec![
+        "a", "B", "b", "\u{e9}", "A", "c"
+    ]);
+
+    // Listing again preserves the order
+    assert_eq!(request(VariablesBackendRequest::List, "list-id-2"), vec![
+        "a", "B", "b", "\u{e9}", "A", "c"
+    ]);
+
+    assert_eq!(
+        request(set_sort_order(SetSortOrderSortOrder::Name), "sort-id-2"),
+        vec!["A", "a", "B", "b", "c", "\u{e9}"]
+    );
+
+    incoming_tx.send(CommMsg::Close).unwrap();
+}
@@ -10,9 +10,14 @@ use core::panic;
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::help_comm::HelpBackendReply;
 use amalthea::comm::help_comm::HelpBackendRequest;
+use amalthea::comm::help_comm::HelpFrontendEvent;
+use amalthea::comm::help_comm::ShowHelpKind;
+use amalthea::comm::help_comm::ShowHelpTopicContentFormat;
 use amalthea::comm::help_comm::ShowHelpTopicParams;
 use amalthea::socket::comm::CommInitiator;
 use amalthea::socket::comm::CommSocket;
+use ark::help::message::HelpEvent;
+use ark::help::message::ShowHelpUrlParams;
 use ark::help::r_help::RHelp;
 use ark::help_proxy;
 use ark::r_task::r_task;
@@ -46,6 +51,8 @@ fn test_help_comm() {
         // Send a request for the help topic
         let request = HelpBackendRequest::ShowHelpTopic(ShowHelpTopicParams {
             topic: String::from(topic),
+            content_format: None,
+            asset_links: None,
         });
         let data = serde_json::to_value(request).unwrap();
         let request_id = String::from(id);
@@ -65,6 +72,7 @@ fn test_help_comm() {
                         assert!(found);
                         assert_eq!(id, request_id);
                     },
+                    _ => panic!("Unexpected reply from help comm: {response:?}"),
                 }
             },
             _ => {
@@ -98,3 +106,112 @@ fn test_help_comm() {
     );
     assert!(RHelp::is_help_url(url.as_str(), r_help_port));
 }
+
+#[test]
+fn test_help_comm_markdown_content() {
+    let comm = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-help-comm-markdown-id"),
+        String::from("positron.help"),
+    );
+
+    let incoming_tx = comm.incoming_tx.clone();
+    let outgoing_rx = comm.outgoing_rx.clone();
+
+    let r_port = r_task(|| RHelp::r_start_or_reconnect_to_help_server().unwrap());
+    let proxy_port = help_proxy::start(r_port).unwrap();
+    let _help_event_tx = RHelp::start(comm, r_port, proxy_port).unwrap();
+
+    let request = HelpBackendRequest::ShowHelpTopic(ShowHelpTopicParams {
+        topic: String::from("library"),
+        content_format: Some(ShowHelpTopicContentFormat::Markdown),
+        asset_links: None,
+    });
+    let data = serde_json::to_value(request).unwrap();
+    incoming_tx
+        .send(CommMsg::Rpc(String::from("help-markdown-id"), data))
+        .unwrap();
+
+    let duration = std::time::Duration::from_secs(5);
+
+    // The rendered help is delivered through a Show Help notification
+    let event = outgoing_rx.recv_timeout(duration).unwrap();
+    let CommMsg::Data(event) = event else {
+        panic!("Unexpected message from help comm: {event:?}");
+    };
+    let HelpFrontendEvent::ShowHelp(params) = serde_json::from_value(event).unwrap();
+    assert_eq!(params.kind, ShowHelpKind::Markdown);
+    assert!(params
+        .content
+        .starts_with("# Loading/Attaching and Listing of Packages"));
+    assert!(params.content.contains("\n## Usage\n\n```r\nlibrary("));
+
+    let response = outgoing_rx.recv_timeout(duration).unwrap();
+    let CommMsg::Rpc(id, val) = response else {
+        panic!("Unexpected response from help comm: {response:?}");
+    };
+    assert_eq!(id, "help-markdown-id");
+    let HelpBackendReply::ShowHelpTopicReply(found) = serde_json::from_value(val).unwrap() else {
+        panic!("Unexpected reply from help comm");
+    };
+    assert!(found);
+}
+
+#[test]
+fn test_help_comm_restart_help_server() {
+    let comm = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-help-comm-restart-id"),
+        String::from("positron.help"),
+    );
+
+    let incoming_tx = comm.incoming_tx.clone();
+    let outgoing_rx = comm.outgoing_rx.clone();
+
+    let r_port = r_task(|| RHelp::r_start_or_reconnect_to_help_server().unwrap());
+    let proxy_port = help_proxy::start(r_port).unwrap();
+    let help_event_tx = RHelp::start(comm, r_port, proxy_port).unwrap();
+
+    let data = serde_json::to_value(HelpBackendRequest::RestartHelpServer).unwrap();
+    incoming_tx
+        .send(CommMsg::Rpc(String::from("help-restart-id"), data))
+        .unwrap();
+
+    let duration = std::time::Duration::from_secs(5);
+
+    let response = outgoing_rx.recv_timeout(duration).unwrap();
+    let CommMsg::Rpc(id, val) = response else {
+        panic!("Unexpected response from help comm: {response:?}");
+    };
+    assert_eq!(id, "help-restart-id");
+    let HelpBackendReply::RestartHelpServerReply(port) = serde_json::from_value(val).unwrap()
+    else {
+        panic!("Unexpected reply from help comm");
+    };
+
+    // The new port is rediscovered from R
+    let r_help_port = r_task(|| unsafe {
+        RFunction::new_internal("tools", "httpdPort")
+            .call()?
+            .to::<u16>()
+    })
+    .unwrap();
+    assert_eq!(port, r_help_port as i64);
+
+    // Help URLs of the restarted server are still handled
+    let url = format!("http://127.0.0.1:{r_help_port}/library/base/html/plot.html");
+    help_event_tx
+        .send(HelpEvent::ShowHelpUrl(ShowHelpUrlParams { url }))
+        .unwrap();
+
+    let event = outgoing_rx.recv_timeout(duration).unwrap();
+    let CommMsg::Data(event) = event else {
+        panic!("Unexpected message from help comm: {event:?}");
+    };
+    let HelpFrontendEvent::ShowHelp(params) = serde_json::from_value(event).unwrap();
+    assert_eq!(params.kind, ShowHelpKind::Url);
+    assert_eq!(
+        params.content,
+        format!("http://127.0.0.1:{proxy_port}/library/base/html/plot.html")
+    );
+}
@@ -0,0 +1,35 @@
+use amalthea::fixtures::dummy_frontend::ExecuteRequestOptions;
+use ark::fixtures::DummyArkFrontend;
+
+// Runs in its own process so that the idle timeout doesn't affect other tests
+
+#[test]
+fn test_idle_timeout_runs_gc() {
+    let frontend = DummyArkFrontend::lock();
+
+    // The finalizer of an unreachable environment runs at the next garbage
+    // collection
+    let code = "
+options(ark.idle_timeout = 0.5, ark.idle_action = 'gc')
+ark_test_gc_ran <- FALSE
+invisible(reg.finalizer(new.env(), function(e) ark_test_gc_ran <<- TRUE))";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+    frontend.recv_iopub_execute_input();
+    frontend.recv_iopub_idle();
+    frontend.recv_shell_execute_reply();
+
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    let code = "options(ark.idle_timeout = NULL); ark_test_gc_ran";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] TRUE");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
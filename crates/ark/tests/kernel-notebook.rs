@@ -180,3 +180,67 @@ fn test_notebook_stdin_followed_by_an_expression_on_the_next_line() {
 
     assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
 }
+
+#[test]
+fn test_notebook_execute_request_interleaved_plots() {
+    let frontend = DummyArkFrontendNotebook::lock();
+
+    // The two panels of the second figure complete a single plot
+    let code = "cat('one\\n')
+plot(1:10)
+cat('two\\n')
+old <- par(mfrow = c(1, 2)); plot(1); plot(2); par(old)
+cat('three\\n')";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    frontend.recv_iopub_stream_stdout("one\n");
+    assert!(frontend.recv_iopub_display_data()["image/png"].is_string());
+    frontend.recv_iopub_stream_stdout("two\n");
+    assert!(frontend.recv_iopub_display_data()["image/png"].is_string());
+    frontend.recv_iopub_stream_stdout("three\n");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
+#[test]
+fn test_notebook_quit_is_blocked() {
+    let frontend = DummyArkFrontendNotebook::lock();
+
+    // The kernel survives `q()` and evaluates the rest of the cell
+    let code = "q()\n1";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    frontend.recv_iopub_stream_stderr("Warning: quit ignored in notebook mode\n");
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] 1");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+
+    // A sourced script stops at `q()`
+    let code = r#"source(textConnection("cat('one\\n'); q(); cat('two\\n')"))
+2"#;
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    frontend.recv_iopub_stream_stdout("one\n");
+    frontend.recv_iopub_stream_stderr("Warning: quit ignored in notebook mode\n");
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] 2");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
@@ -0,0 +1,160 @@
+use amalthea::comm::ui_comm::ChooseRestartParams;
+use amalthea::comm::ui_comm::ShowFilePickerParams;
+use amalthea::comm::ui_comm::UiFrontendRequest;
+use amalthea::fixtures::dummy_frontend::ExecuteRequestOptions;
+use ark::fixtures::DummyArkFrontendUi;
+use serde_json::json;
+use stdext::assert_match;
+
+// Runs in its own process so that the UI comm, which changes how R interacts
+// with the frontend, is only open for these tests
+
+#[test]
+fn test_error_restarts_are_offered() {
+    let frontend = DummyArkFrontendUi::lock();
+
+    let code = "withRestarts(
+        stop('Oops'),
+        retry = function() 'retried',
+        skip = function(value) value
+    )";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    // `skip` requires an argument and can't be invoked
+    assert_eq!(
+        frontend.recv_stdin_comm_request(),
+        UiFrontendRequest::ChooseRestart(ChooseRestartParams {
+            message: String::from("Oops"),
+            restarts: vec![String::from("retry")],
+        })
+    );
+    frontend.send_stdin_comm_reply(json!("retry"));
+
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] \"retried\"");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
+#[test]
+fn test_error_restarts_declined() {
+    let frontend = DummyArkFrontendUi::lock();
+
+    let code = "withRestarts(stop('Oops'), retry = function() 'retried')";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    assert!(matches!(
+        frontend.recv_stdin_comm_request(),
+        UiFrontendRequest::ChooseRestart(_)
+    ));
+    frontend.send_stdin_comm_reply(json!(null));
+
+    // The error is handled as usual
+    assert!(frontend.recv_iopub_execute_error().contains("Oops"));
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(
+        frontend.recv_shell_execute_reply_exception(),
+        input.execution_count
+    );
+}
+
+#[test]
+fn test_error_restarts_left_to_inner_handlers() {
+    let frontend = DummyArkFrontendUi::lock();
+
+    // The inner handler had the chance to invoke the restart, the user isn't
+    // asked
+    let code = "withCallingHandlers(
+        withRestarts(stop('Oops'), retry = function() 'retried'),
+        error = function(cnd) NULL
+    )";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    assert!(frontend.recv_iopub_execute_error().contains("Oops"));
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(
+        frontend.recv_shell_execute_reply_exception(),
+        input.execution_count
+    );
+}
+
+#[test]
+fn test_file_choose_shows_file_picker() {
+    let frontend = DummyArkFrontendUi::lock();
+
+    let code = "file.choose()";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    assert_eq!(
+        frontend.recv_stdin_comm_request(),
+        UiFrontendRequest::ShowFilePicker(ShowFilePickerParams {
+            title: String::from("Choose a File"),
+            multiple: false,
+            directory: false,
+            new: false,
+        })
+    );
+    frontend.send_stdin_comm_reply(json!(["/path/to/chosen.R"]));
+
+    assert_eq!(
+        frontend.recv_iopub_execute_result(),
+        "[1] \"/path/to/chosen.R\""
+    );
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
+#[test]
+fn test_file_choose_cancelled() {
+    let frontend = DummyArkFrontendUi::lock();
+
+    let code = "file.choose(new = TRUE)";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    assert_match!(
+        frontend.recv_stdin_comm_request(),
+        UiFrontendRequest::ShowFilePicker(params) => {
+            assert!(params.new);
+        }
+    );
+    frontend.send_stdin_comm_reply(json!(null));
+
+    // As in R, cancelling is an error
+    assert!(frontend
+        .recv_iopub_execute_error()
+        .contains("file choice cancelled"));
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(
+        frontend.recv_shell_execute_reply_exception(),
+        input.execution_count
+    );
+}
@@ -1,6 +1,10 @@
 use amalthea::fixtures::dummy_frontend::ExecuteRequestOptions;
+use amalthea::wire::execute_reply::AssignmentTargets;
+use amalthea::wire::execute_request::ExecuteRequest;
 use amalthea::wire::jupyter_message::Message;
 use amalthea::wire::kernel_info_request::KernelInfoRequest;
+use amalthea::wire::status::ExecutionState;
+use amalthea::wire::stream::Stream;
 use ark::fixtures::DummyArkFrontend;
 use stdext::assert_match;
 
@@ -38,6 +42,320 @@ fn test_execute_request() {
     assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
 }
 
+#[test]
+fn test_execute_request_execute_input() {
+    let frontend = DummyArkFrontend::lock();
+
+    // The input is echoed with its execution count before any output
+    let code = "cat('hello\\n')";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+    frontend.recv_iopub_stream_stdout("hello\n");
+
+    frontend.recv_iopub_idle();
+    let execution_count = frontend.recv_shell_execute_reply();
+    assert_eq!(execution_count, input.execution_count);
+
+    // Silent requests are not echoed and don't increment the count
+    frontend.send_shell(ExecuteRequest {
+        code: String::from("cat('hidden\\n')"),
+        silent: true,
+        store_history: false,
+        user_expressions: serde_json::Value::Null,
+        allow_stdin: false,
+        stop_on_error: false,
+        supported_mimetypes: None,
+        package_context: None,
+    });
+    frontend.recv_iopub_busy();
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), execution_count);
+
+    frontend.send_execute_request("1", ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.execution_count, execution_count + 1);
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] 1");
+
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
+#[test]
+fn test_execute_request_package_startup_messages() {
+    let frontend = DummyArkFrontend::lock();
+
+    let code = "packageStartupMessage('Attaching arkfake'); message('note'); invisible()";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    // Other messages are streamed as usual, startup messages follow once
+    // the input is evaluated
+    frontend.recv_iopub_stream_stderr("note\n");
+    assert_match!(frontend.recv_iopub(), Message::DisplayData(data) => {
+        assert_eq!(data.content.data["text/plain"], "Attaching arkfake\n");
+        assert_eq!(data.content.metadata["package_startup_message"], true);
+    });
+
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+
+    // Suppressed startup messages aren't sent
+    let code = "suppressPackageStartupMessages(packageStartupMessage('Attaching arkfake'))";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+    let input = frontend.recv_iopub_execute_input();
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
+#[test]
+fn test_execute_request_knit_print() {
+    let frontend = DummyArkFrontend::lock();
+
+    let code = ".ps.is_installed('knitr')";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+    let input = frontend.recv_iopub_execute_input();
+    let has_knitr = frontend.recv_iopub_execute_result() == "[1] TRUE";
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+
+    if !has_knitr {
+        return;
+    }
+
+    // The method's own output isn't streamed
+    let code = "local({
+        loadNamespace('knitr')
+        method <- function(x, ...) {
+            cat('knit_print noise\\n')
+            ark_knit_print_calls <<- ark_knit_print_calls + 1
+            knitr::asis_output('<b>rich</b>')
+        }
+        registerS3method('knit_print', 'ark_rich', method, envir = asNamespace('knitr'))
+        ark_knit_print_calls <<- 0
+        ark_rich <<- structure(list(), class = 'ark_rich')
+    })";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+    let input = frontend.recv_iopub_execute_input();
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+
+    // The `knit_print()` output comes along with the printed output
+    for _ in 0..2 {
+        let code = "ark_rich";
+        frontend.send_execute_request(code, ExecuteRequestOptions::default());
+        frontend.recv_iopub_busy();
+
+        let input = frontend.recv_iopub_execute_input();
+        assert_eq!(input.code, code);
+
+        assert_match!(frontend.recv_iopub(), Message::ExecuteResult(data) => {
+            assert!(data.content.data["text/plain"].is_string());
+            assert_eq!(data.content.data["text/html"], "<b>rich</b>");
+        });
+
+        frontend.recv_iopub_idle();
+        assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+    }
+
+    // The output of the object auto-printed again is cached
+    let code = "ark_knit_print_calls";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] 1");
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
+#[test]
+fn test_execute_request_supported_mimetypes() {
+    let frontend = DummyArkFrontend::lock();
+
+    let code = "data.frame(x = 1)";
+
+    let execute = |supported_mimetypes: Option<Vec<String>>| -> Vec<String> {
+        frontend.send_shell(ExecuteRequest {
+            code: String::from(code),
+            silent: false,
+            store_history: true,
+            user_expressions: serde_json::Value::Null,
+            allow_stdin: false,
+            stop_on_error: false,
+            supported_mimetypes,
+            package_context: None,
+        });
+        frontend.recv_iopub_busy();
+
+        let input = frontend.recv_iopub_execute_input();
+        assert_eq!(input.code, code);
+
+        let mimetypes = assert_match!(frontend.recv_iopub(), Message::ExecuteResult(data) => {
+            assert_match!(data.content.data, serde_json::Value::Object(map) => {
+                map.keys().cloned().collect::<Vec<String>>()
+            })
+        });
+
+        frontend.recv_iopub_idle();
+        assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+
+        mimetypes
+    };
+
+    // By default, data frames come with an HTML representation
+    assert_eq!(execute(None), vec!["text/plain", "text/html"]);
+
+    // A text-only client only gets the plain text representation
+    let text_only = Some(vec![String::from("text/plain")]);
+    assert_eq!(execute(text_only), vec!["text/plain"]);
+}
+
+#[test]
+fn test_execute_request_sink() {
+    let frontend = DummyArkFrontend::lock();
+
+    let execute = |code: &str| {
+        frontend.send_execute_request(code, ExecuteRequestOptions::default());
+        frontend.recv_iopub_busy();
+
+        let input = frontend.recv_iopub_execute_input();
+        assert_eq!(input.code, code);
+        input.execution_count
+    };
+
+    // Sunk output goes to the file only, and comes back to IOPub once the
+    // sink is removed
+    let code = "ark_sink_file <- tempfile()
+sink(ark_sink_file)
+cat('sunk\\n')
+sink()
+cat('visible\\n')";
+    let count = execute(code);
+    frontend.recv_iopub_stream_stdout("visible\n");
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), count);
+
+    // Nested sinks, and messages sunk to a connection
+    let code = "ark_sink_outer <- tempfile()
+ark_sink_inner <- tempfile()
+ark_sink_messages <- tempfile()
+ark_sink_con <- file(ark_sink_messages, open = 'w')
+sink(ark_sink_outer)
+sink(ark_sink_inner)
+cat('inner\\n')
+sink()
+cat('outer\\n')
+sink(ark_sink_con, type = 'message')
+message('sunk message')
+sink(type = 'message')
+close(ark_sink_con)
+sink()";
+    let count = execute(code);
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), count);
+
+    let code = "writeLines(readLines(ark_sink_file))
+writeLines(readLines(ark_sink_inner))
+writeLines(readLines(ark_sink_outer))
+writeLines(readLines(ark_sink_messages))";
+    let count = execute(code);
+    frontend.recv_iopub_stream_stdout("sunk\ninner\nouter\nsunk message\n");
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), count);
+
+    let count = execute("rm(list = ls(pattern = '^ark_sink_'))");
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), count);
+}
+
+#[test]
+fn test_execute_request_package_context() {
+    let frontend = DummyArkFrontend::lock();
+
+    let execute = |code: &str, package_context: Option<&str>| {
+        frontend.send_shell(ExecuteRequest {
+            code: String::from(code),
+            silent: false,
+            store_history: true,
+            user_expressions: serde_json::Value::Null,
+            allow_stdin: false,
+            stop_on_error: false,
+            supported_mimetypes: None,
+            package_context: package_context.map(String::from),
+        });
+        frontend.recv_iopub_busy();
+
+        let input = frontend.recv_iopub_execute_input();
+        assert_eq!(input.code, code);
+        input.execution_count
+    };
+
+    // A global that collides with the internal `stats:::Pillai()`
+    let count = execute("Pillai <- 'global'", None);
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), count);
+
+    // The internal function is callable unqualified and shadows the global.
+    // The last value is the result of the request.
+    let count = execute("is.function(Pillai)\nPillai(1, 1, 1)[[1]]", Some("stats"));
+    frontend.recv_iopub_stream_stdout("[1] TRUE\n");
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] 0.5");
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), count);
+
+    // The code is passed verbatim, including quotes, backslashes, and
+    // control characters
+    let count = execute("nchar('a\tb\\\\\"\r')", Some("stats"));
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] 6");
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), count);
+
+    // Invisible values aren't printed
+    let count = execute("invisible(Pillai)", Some("stats"));
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), count);
+
+    // Tracebacks start at the user's code
+    let count = execute(
+        "options(positron.error_entrace = FALSE)\nf <- function() stop('boom')\nf()",
+        Some("stats"),
+    );
+    let traceback = assert_match!(frontend.recv_iopub(), Message::ExecuteError(data) => {
+        data.content.exception.traceback
+    });
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply_exception(), count);
+
+    assert!(traceback[0].contains("f()"), "{traceback:?}");
+    assert!(traceback
+        .iter()
+        .all(|entry| !entry.contains("eval_in_package") && !entry.contains("withVisible")));
+
+    let count = execute("options(positron.error_entrace = NULL)", None);
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), count);
+
+    // Only within that request
+    let count = execute("Pillai", None);
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] \"global\"");
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), count);
+
+    let count = execute("rm(Pillai)", None);
+    frontend.recv_iopub_idle();
+    assert_eq!(frontend.recv_shell_execute_reply(), count);
+}
+
 #[test]
 fn test_execute_request_empty() {
     let frontend = DummyArkFrontend::lock();
@@ -129,6 +447,79 @@ fn test_execute_request_incomplete_multiple_lines() {
     )
 }
 
+#[test]
+fn test_execute_request_incomplete_nested_braces() {
+    let frontend = DummyArkFrontend::lock();
+
+    let code = "incomplete_fn <- function(x) {\n  if (x) {\n    1\n  }";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    assert!(frontend
+        .recv_iopub_execute_error()
+        .contains("Can't execute incomplete input"));
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(
+        frontend.recv_shell_execute_reply_exception(),
+        input.execution_count
+    );
+
+    // R is not left waiting for the closing brace, which would consume the
+    // next request
+    let code = "exists('incomplete_fn')";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] FALSE");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
+#[test]
+fn test_execute_request_incomplete_string() {
+    let frontend = DummyArkFrontend::lock();
+
+    let code = "x <- 'unterminated\n1 + 1";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    assert!(frontend
+        .recv_iopub_execute_error()
+        .contains("Can't execute incomplete input"));
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(
+        frontend.recv_shell_execute_reply_exception(),
+        input.execution_count
+    );
+
+    // R is not left waiting for the closing quote
+    let code = "'terminated'";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] \"terminated\"");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
 #[test]
 fn test_execute_request_browser() {
     let frontend = DummyArkFrontend::lock();
@@ -277,8 +668,9 @@ fn test_execute_request_browser_stdin() {
     let input = frontend.recv_iopub_execute_input();
     assert_eq!(input.code, code);
 
-    let prompt = frontend.recv_stdin_input_request();
-    assert_eq!(prompt, String::from("prompt>"));
+    let request = frontend.recv_stdin_input_request_content();
+    assert_eq!(request.prompt, String::from("prompt>"));
+    assert_eq!(request.choices, None);
 
     frontend.send_stdin_input_reply(String::from("hi"));
 
@@ -317,6 +709,54 @@ fn test_execute_request_error() {
     );
 }
 
+#[test]
+fn test_execute_request_error_traceback_truncated() {
+    let frontend = DummyArkFrontend::lock();
+
+    // Returns the traceback entry of the call to `f()` that errors
+    let traceback_entry = |call: &str| -> String {
+        let code = format!(
+            "local({{
+                old <- options(deparse.max.lines = 2, positron.error_entrace = FALSE)
+                on.exit(options(old))
+                f <- function(...) stop('deep')
+                eval({call})
+            }})"
+        );
+        frontend.send_execute_request(&code, ExecuteRequestOptions::default());
+        frontend.recv_iopub_busy();
+
+        let input = frontend.recv_iopub_execute_input();
+        let traceback = assert_match!(frontend.recv_iopub(), Message::ExecuteError(data) => {
+            data.content.exception.traceback
+        });
+
+        frontend.recv_iopub_idle();
+        assert_eq!(
+            frontend.recv_shell_execute_reply_exception(),
+            input.execution_count
+        );
+
+        traceback
+            .into_iter()
+            .find(|entry| entry.contains(". f("))
+            .unwrap()
+    };
+
+    // A deeply nested call is truncated after two lines
+    let entry = traceback_entry(
+        "local({ x <- quote(x); for (i in 1:30) x <- call('list', x, i); call('f', x) })",
+    );
+    assert!(entry.ends_with(" ..."));
+    assert_eq!(entry.lines().count(), 2);
+
+    // A call deparsed to a single long line is truncated by characters
+    let entry = traceback_entry("call('f', strrep('x', 1000))");
+    assert!(entry.ends_with(" ..."));
+    assert_eq!(entry.lines().count(), 1);
+    assert!(entry.len() < 1000);
+}
+
 #[test]
 fn test_execute_request_error_multiple_expressions() {
     let frontend = DummyArkFrontend::lock();
@@ -362,14 +802,91 @@ fn test_execute_request_multiple_expressions() {
     assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
 }
 
+#[test]
+fn test_execute_request_streams() {
+    let frontend = DummyArkFrontend::lock();
+
+    // Messages go to stderr and `cat()` to stdout. Consecutive writes on the
+    // same stream are coalesced into a single message.
+    let code = "message('x'); cat('y'); cat('z\\n')";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    assert_match!(frontend.recv_iopub(), Message::Stream(data) => {
+        assert_eq!(data.content.name, Stream::Stderr);
+        assert_eq!(data.content.text, "x\n");
+    });
+    assert_match!(frontend.recv_iopub(), Message::Stream(data) => {
+        assert_eq!(data.content.name, Stream::Stdout);
+        assert_eq!(data.content.text, "yz\n");
+    });
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
+#[test]
+fn test_execute_request_assignment_targets() {
+    let frontend = DummyArkFrontend::lock();
+
+    let code = "x <- 1; df = data.frame(a = 1)\nassign('z', 3)\nassign(paste0('w', 1), 4)";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    frontend.recv_iopub_idle();
+
+    // The dynamic `assign()` makes the targets incomplete
+    assert_match!(frontend.recv_shell(), Message::ExecuteReply(data) => {
+        assert_eq!(data.content.execution_count, input.execution_count);
+        assert_eq!(data.content.assignment_targets, Some(AssignmentTargets {
+            names: vec![String::from("x"), String::from("df"), String::from("z")],
+            unknown: true,
+        }));
+    });
+}
+
+#[test]
+fn test_execute_request_output_originator() {
+    let frontend = DummyArkFrontend::lock();
+
+    let code = "cat('hello\\n'); 1";
+    let id = frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    let originator = |metadata: &serde_json::Value| metadata["originator"].clone();
+    let expected = serde_json::json!({ "scope": "request", "request_id": id });
+
+    assert_match!(frontend.recv_iopub(), Message::Stream(data) => {
+        assert_eq!(data.content.text, "hello\n");
+        assert_eq!(originator(&data.metadata), expected);
+    });
+    assert_match!(frontend.recv_iopub(), Message::ExecuteResult(data) => {
+        assert_eq!(originator(&data.metadata), expected);
+    });
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
 #[test]
 fn test_execute_request_single_line_buffer_overflow() {
     let frontend = DummyArkFrontend::lock();
 
     // The newlines do matter for what we are testing here,
     // due to how we internally split by newlines. We want
-    // to test that the `aaa`s result in an immediate R error,
-    // not in text written to the R buffer that calls `stop()`.
+    // to test that the `aaa`s, which don't fit in R's buffer,
+    // are passed on to R across several `ReadConsole` calls.
     let aaa = "a".repeat(4096);
     let code = format!("quote(\n{aaa}\n)");
     frontend.send_execute_request(code.as_str(), ExecuteRequestOptions::default());
@@ -378,16 +895,35 @@ fn test_execute_request_single_line_buffer_overflow() {
     let input = frontend.recv_iopub_execute_input();
     assert_eq!(input.code, code);
 
-    assert!(frontend
-        .recv_iopub_execute_error()
-        .contains("Can't pass console input on to R"));
+    assert_eq!(frontend.recv_iopub_execute_result(), aaa);
 
     frontend.recv_iopub_idle();
 
-    assert_eq!(
-        frontend.recv_shell_execute_reply_exception(),
-        input.execution_count
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
+#[test]
+fn test_execute_request_large_string_literal() {
+    let frontend = DummyArkFrontend::lock();
+
+    // A 1MB line, with multibyte characters so that some chunks end in the
+    // middle of a UTF-8 sequence
+    let aaa = "aé".repeat(1024 * 1024 / 3);
+    let code = format!(
+        "x <- '{aaa}'; identical(x, strrep('aé', {}))",
+        1024 * 1024 / 3
     );
+    frontend.send_execute_request(code.as_str(), ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] TRUE");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
 }
 
 #[test]
@@ -440,6 +976,77 @@ fn test_stdin_followed_by_an_expression_on_the_same_line() {
     assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
 }
 
+#[test]
+fn test_execute_request_resources() {
+    let frontend = DummyArkFrontend::lock();
+
+    let code = "Sys.sleep(0.5)";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    frontend.recv_iopub_idle();
+
+    assert_match!(frontend.recv_shell(), Message::ExecuteReply(data) => {
+        assert_eq!(data.content.execution_count, input.execution_count);
+        let resources = data.content.resources.unwrap();
+        assert!(resources.elapsed_ms >= 500);
+
+        // Allocations are only tracked on request
+        assert_eq!(resources.allocation_delta_bytes, None);
+    });
+}
+
+#[test]
+fn test_execute_request_resources_exclude_input_wait() {
+    let frontend = DummyArkFrontend::lock();
+
+    let options = ExecuteRequestOptions { allow_stdin: true };
+
+    let code = "options(ark.execution.track_allocations = TRUE)\nval <- readline('prompt>')";
+    frontend.send_execute_request(code, options);
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    let prompt = frontend.recv_stdin_input_request();
+    assert_eq!(prompt, String::from("prompt>"));
+
+    // Keep the user thinking for longer than the code takes to run
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    frontend.send_stdin_input_reply(String::from("hi"));
+
+    frontend.recv_iopub_idle();
+
+    assert_match!(frontend.recv_shell(), Message::ExecuteReply(data) => {
+        assert_eq!(data.content.execution_count, input.execution_count);
+        let resources = data.content.resources.unwrap();
+        assert!(resources.elapsed_ms < 2000);
+        assert!(resources.allocation_delta_bytes.is_none());
+    });
+
+    let code = "x <- numeric(1e6)";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+    frontend.recv_iopub_execute_input();
+    frontend.recv_iopub_idle();
+
+    assert_match!(frontend.recv_shell(), Message::ExecuteReply(data) => {
+        let resources = data.content.resources.unwrap();
+        assert!(resources.allocation_delta_bytes.unwrap() >= 8e6);
+    });
+
+    let code = "options(ark.execution.track_allocations = NULL); rm(x)";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+    frontend.recv_iopub_execute_input();
+    frontend.recv_iopub_idle();
+    frontend.recv_shell_execute_reply();
+}
+
 #[test]
 fn test_stdin_followed_by_an_expression_on_the_next_line() {
     let frontend = DummyArkFrontend::lock();
@@ -501,6 +1108,108 @@ fn test_stdin_single_line_buffer_overflow() {
     );
 }
 
+#[test]
+fn test_stdin_interrupt() {
+    let frontend = DummyArkFrontend::lock();
+
+    let options = ExecuteRequestOptions { allow_stdin: true };
+
+    let code = "readline('prompt>')";
+    let request_id = frontend.send_execute_request(code, options);
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    let prompt = frontend.recv_stdin_input_request();
+    assert_eq!(prompt, String::from("prompt>"));
+
+    // Interrupt while R is blocked waiting for the input reply
+    frontend.send_interrupt_request();
+    frontend.recv_control_interrupt_reply();
+
+    // The `readline()` call is unwound without any input
+    recv_iopub_idle_after_interrupt(&frontend, &request_id);
+
+    assert_match!(frontend.recv_shell(),
+        Message::ExecuteReply(data) => {
+            assert_eq!(data.content.execution_count, input.execution_count);
+        }
+    );
+
+    // We're back at a clean top-level prompt
+    let code = "1";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] 1");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
+#[test]
+fn test_execute_request_interrupt() {
+    let frontend = DummyArkFrontend::lock();
+
+    let code = "Sys.sleep(100)";
+    let request_id = frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    let start = std::time::Instant::now();
+    frontend.send_interrupt_request();
+    frontend.recv_control_interrupt_reply();
+
+    recv_iopub_idle_after_interrupt(&frontend, &request_id);
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+    assert_match!(frontend.recv_shell(),
+        Message::ExecuteReply(data) => {
+            assert_eq!(data.content.execution_count, input.execution_count);
+        }
+    );
+
+    // We're back at a clean top-level prompt
+    let code = "1";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] 1");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
+
+/// The Control socket emits its own busy/idle statuses around the interrupt
+/// request, so wait for the idle status of the interrupted execute request
+/// specifically.
+fn recv_iopub_idle_after_interrupt(frontend: &DummyArkFrontend, request_id: &str) {
+    loop {
+        match frontend.recv_iopub() {
+            Message::Status(data) => {
+                let parent = data.parent_header.unwrap();
+                if parent.msg_id == request_id {
+                    assert_eq!(data.content.execution_state, ExecutionState::Idle);
+                    break;
+                }
+            },
+            Message::Stream(_) => continue,
+            msg => panic!("Unexpected IOPub message: {msg:?}"),
+        }
+    }
+}
+
 #[test]
 fn test_stdin_from_menu() {
     let frontend = DummyArkFrontend::lock();
@@ -523,8 +1232,13 @@ fn test_stdin_from_menu() {
 ",
     );
 
-    let prompt = frontend.recv_stdin_input_request();
-    assert_eq!(prompt, String::from("Selection: "));
+    // The choices are forwarded so frontends can present a picker
+    let request = frontend.recv_stdin_input_request_content();
+    assert_eq!(request.prompt, String::from("Selection: "));
+    assert_eq!(
+        request.choices,
+        Some(vec![String::from("a"), String::from("b")])
+    );
 
     frontend.send_stdin_input_reply(String::from("b"));
 
@@ -537,3 +1251,56 @@ fn test_stdin_from_menu() {
 
     assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
 }
+
+#[test]
+fn test_stdin_from_file_choose() {
+    let frontend = DummyArkFrontend::lock();
+
+    let options = ExecuteRequestOptions { allow_stdin: true };
+
+    // Without a native file dialog, `file.choose()` asks for the path on stdin
+    let code = "file.choose()";
+    frontend.send_execute_request(code, options);
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    let prompt = frontend.recv_stdin_input_request();
+    assert_eq!(prompt, String::from("Enter file name: "));
+
+    frontend.send_stdin_input_reply(String::from("/tmp/data.csv"));
+
+    assert_eq!(
+        frontend.recv_iopub_execute_result(),
+        "[1] \"/tmp/data.csv\""
+    );
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+
+    // Cancelling is an error, as in R
+    let code = "file.choose()";
+    frontend.send_execute_request(code, ExecuteRequestOptions { allow_stdin: true });
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    let prompt = frontend.recv_stdin_input_request();
+    assert_eq!(prompt, String::from("Enter file name: "));
+
+    frontend.send_stdin_input_reply(String::from(""));
+
+    assert!(frontend
+        .recv_iopub_execute_error()
+        .contains("file choice cancelled"));
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(
+        frontend.recv_shell_execute_reply_exception(),
+        input.execution_count
+    );
+}
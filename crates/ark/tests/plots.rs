@@ -0,0 +1,58 @@
+//
+// plots.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use ark::r_task::r_task;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+
+#[test]
+fn test_plot_svg() {
+    let has_svg_device: bool = r_task(|| {
+        harp::parse_eval_global("capabilities('cairo') || .ps.is_installed('svglite')")
+            .unwrap()
+            .try_into()
+            .unwrap()
+    });
+    if !has_svg_device {
+        return;
+    }
+
+    // Record a plot with lines and a raster element
+    r_task(|| {
+        harp::parse_eval_global(
+            "local({
+                grDevices::pdf(NULL)
+                grDevices::dev.control('enable')
+                plot(1:10, type = 'l')
+                rasterImage(as.raster(matrix(0:1, 2, 2)), 2, 2, 4, 4)
+                .ps.graphics.createSnapshot('test-plot-svg-id')
+                grDevices::dev.off()
+            })",
+        )
+        .unwrap();
+    });
+
+    let svg: String = r_task(|| unsafe {
+        RFunction::from(".ps.graphics.renderPlotSvg")
+            .param("id", "test-plot-svg-id")
+            .param("width", 400)
+            .param("height", 300)
+            .call()
+            .unwrap()
+            .to::<String>()
+            .unwrap()
+    });
+
+    let svg = svg.trim();
+    assert!(svg.starts_with("<?xml"), "Unexpected SVG: {svg}");
+    assert!(svg.contains("<svg"));
+    assert!(svg.ends_with("</svg>"));
+
+    // Lines are drawn as vector elements, the raster is embedded
+    assert!(svg.contains("<path") || svg.contains("<polyline"));
+    assert!(svg.contains("<image"));
+}
@@ -0,0 +1,37 @@
+use std::io::Write;
+
+use amalthea::fixtures::dummy_frontend::ExecuteRequestOptions;
+use ark::fixtures::DummyArkFrontendSafeMode;
+
+// SAFETY:
+// Do not write any other tests in this integration test file. We can only
+// start R up once per process, and the startup files are only read then.
+
+#[test]
+fn test_safe_mode_skips_user_startup_files() {
+    // The trailing `\n` is critical, otherwise R's `source()` silently fails
+    let mut profile = tempfile::NamedTempFile::new().unwrap();
+    write!(profile, "ark_safe_mode_profile <- TRUE\n").unwrap();
+
+    let mut environ = tempfile::NamedTempFile::new().unwrap();
+    write!(environ, "ARK_SAFE_MODE_ENVIRON=TRUE\n").unwrap();
+
+    unsafe {
+        std::env::set_var("R_PROFILE_USER", profile.path().to_str().unwrap());
+        std::env::set_var("R_ENVIRON_USER", environ.path().to_str().unwrap());
+    };
+
+    let frontend = DummyArkFrontendSafeMode::lock();
+
+    let code = "c(exists('ark_safe_mode_profile'), nzchar(Sys.getenv('ARK_SAFE_MODE_ENVIRON')))";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] FALSE FALSE");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
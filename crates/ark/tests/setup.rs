@@ -0,0 +1,51 @@
+use ark::r_task::r_task;
+use harp::object::RObject;
+
+#[test]
+fn test_setup_chunks_dependency_order() {
+    r_task(|| {
+        harp::parse_eval_global("ark_test_setup_log <- character()").unwrap();
+
+        // Registered out of order, `c` depends on `b` which depends on `a`
+        harp::parse_eval_global(
+            ".ps.setup.register('c', quote(ark_test_setup_log <- c(ark_test_setup_log, 'c')), depends = 'b')",
+        )
+        .unwrap();
+        harp::parse_eval_global(
+            ".ps.setup.register('a', quote(ark_test_setup_log <- c(ark_test_setup_log, 'a')))",
+        )
+        .unwrap();
+        harp::parse_eval_global(
+            ".ps.setup.register('b', quote(ark_test_setup_log <- c(ark_test_setup_log, 'b')), depends = 'a')",
+        )
+        .unwrap();
+
+        harp::parse_eval_global(".ps.setup.run()").unwrap();
+
+        let log: Vec<String> = harp::parse_eval_global("ark_test_setup_log")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(log, vec!["a", "b", "c"]);
+
+        harp::parse_eval_global(".ps.setup.clear(); rm(ark_test_setup_log)").unwrap();
+    })
+}
+
+#[test]
+fn test_setup_chunks_cycle_is_rejected() {
+    r_task(|| {
+        harp::parse_eval_global(".ps.setup.register('x', quote(NULL), depends = 'y')").unwrap();
+
+        // Closing the cycle fails and leaves the registry untouched
+        let result = harp::parse_eval_global(".ps.setup.register('y', quote(NULL), depends = 'x')");
+        assert!(result.is_err());
+
+        let registered: RObject =
+            harp::parse_eval_global("ls(.ps.internal(the)$setup_chunks)").unwrap();
+        let registered: Vec<String> = registered.try_into().unwrap();
+        assert_eq!(registered, vec!["x"]);
+
+        harp::parse_eval_global(".ps.setup.clear()").unwrap();
+    })
+}
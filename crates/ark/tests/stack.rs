@@ -38,3 +38,38 @@ fn test_stack_info_current() {
 
     assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count)
 }
+
+#[test]
+fn test_stack_overflow_is_an_error() {
+    let frontend = DummyArkFrontend::lock();
+
+    // Runaway recursion fails with an R error rather than crashing the kernel
+    let code = "f <- function() f(); f()";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+
+    let error = frontend.recv_iopub_execute_error();
+    assert!(error.contains("infinite recursion") || error.contains("C stack"));
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(
+        frontend.recv_shell_execute_reply_exception(),
+        input.execution_count
+    );
+
+    // The kernel is still responsive
+    let code = "1";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] 1");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count)
+}
@@ -0,0 +1,43 @@
+use std::io::Write;
+
+use amalthea::wire::kernel_info_request::KernelInfoRequest;
+use amalthea::wire::wire_message::WireMessage;
+use ark::fixtures::DummyArkFrontendRprofile;
+
+// SAFETY:
+// Do not write any other tests in this integration test file. R can only be
+// started once per process and this test deliberately hangs its startup.
+
+#[test]
+fn test_startup_hang_is_reported() {
+    // The trailing `\n` is critical, otherwise R's `source()` silently fails
+    let contents = "Sys.sleep(60)\n";
+
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    write!(file, "{contents}").unwrap();
+
+    let path = file.path();
+    let path = path.to_str().unwrap();
+
+    unsafe {
+        std::env::set_var("R_PROFILE_USER", path);
+        std::env::set_var("ARK_STARTUP_TIMEOUT", "1");
+    };
+
+    let frontend = DummyArkFrontendRprofile::lock();
+
+    frontend.send_shell(KernelInfoRequest {});
+    frontend.recv_iopub_busy();
+
+    // The reply is an error reply, which doesn't deserialize as a typed
+    // `KernelInfoReply`, so read it as a raw wire message. Allow for the
+    // startup timeout to elapse.
+    assert!(frontend.shell_socket.poll_incoming(10000).unwrap());
+    let reply = WireMessage::read_from_socket(&frontend.shell_socket).unwrap();
+
+    assert_eq!(reply.message_type(), "kernel_info_reply");
+    assert_eq!(reply.content["status"], "error");
+    assert_eq!(reply.content["ename"], "StartupHang");
+
+    frontend.recv_iopub_idle();
+}
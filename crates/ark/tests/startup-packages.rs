@@ -0,0 +1,45 @@
+use std::io::Write;
+
+use amalthea::fixtures::dummy_frontend::ExecuteRequestOptions;
+use ark::fixtures::DummyArkFrontendRprofile;
+
+// SAFETY:
+// Do not write any other tests related to `.Rprofile` in
+// this integration test file. We can only start R up once
+// per process, so we can only run one `.Rprofile`. Use a
+// separate integration test (i.e. separate process) if you
+// need to test more details related to `.Rprofile` usage.
+
+#[test]
+fn test_startup_packages_are_attached() {
+    // `tools` is installed with R but not attached by default. The missing
+    // package is reported without aborting startup.
+    // The `\n` is critical, otherwise R's `source()` silently fails
+    let contents = "options(ark.startup_packages = c('arkNotAPackage', 'tools'))\n";
+
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    write!(file, "{contents}").unwrap();
+
+    let path = file.path();
+    let path = path.to_str().unwrap();
+
+    unsafe { std::env::set_var("R_PROFILE_USER", path) };
+
+    let frontend = DummyArkFrontendRprofile::lock();
+
+    frontend.recv_iopub_stream_stderr(
+        "Can't attach startup package 'arkNotAPackage': it is not installed.\n",
+    );
+
+    let code = "'package:tools' %in% search()";
+    frontend.send_execute_request(code, ExecuteRequestOptions::default());
+    frontend.recv_iopub_busy();
+
+    let input = frontend.recv_iopub_execute_input();
+    assert_eq!(input.code, code);
+    assert_eq!(frontend.recv_iopub_execute_result(), "[1] TRUE");
+
+    frontend.recv_iopub_idle();
+
+    assert_eq!(frontend.recv_shell_execute_reply(), input.execution_count);
+}
@@ -5,25 +5,51 @@
 //
 //
 
+use std::time::Duration;
+use std::time::Instant;
+
 use amalthea::comm::base_comm::JsonRpcError;
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::ui_comm::BusyParams;
 use amalthea::comm::ui_comm::CallMethodParams;
+use amalthea::comm::ui_comm::DevContextParams;
+use amalthea::comm::ui_comm::NotificationSeverity;
+use amalthea::comm::ui_comm::PongParams;
+use amalthea::comm::ui_comm::ShowNotificationParams;
+use amalthea::comm::ui_comm::TestResultParams;
+use amalthea::comm::ui_comm::TestResultStatus;
 use amalthea::comm::ui_comm::UiBackendReply;
 use amalthea::comm::ui_comm::UiBackendRequest;
 use amalthea::comm::ui_comm::UiFrontendEvent;
 use amalthea::socket::comm::CommInitiator;
 use amalthea::socket::comm::CommSocket;
 use amalthea::socket::stdin::StdInRequest;
+use ark::fixtures::socket_rpc_request;
 use ark::r_task::r_task;
+use ark::ui::Heartbeat;
 use ark::ui::UiComm;
 use ark::ui::UiCommMessage;
+use ark::ui::UiCommSender;
 use crossbeam::channel::bounded;
+use crossbeam::channel::unbounded;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::object::RObject;
+use serde_json::json;
 use serde_json::Value;
 
+/// Calls `method` through the UI comm and returns the value of its reply
+fn call_ui_method(comm_socket: &CommSocket, method: &str, params: Vec<Value>) -> Value {
+    let request = UiBackendRequest::CallMethod(CallMethodParams {
+        method: String::from(method),
+        params,
+    });
+    match socket_rpc_request::<UiBackendRequest, UiBackendReply>(comm_socket, request) {
+        UiBackendReply::CallMethodReply(value) => value,
+        reply => panic!("Unexpected reply: {reply:?}"),
+    }
+}
+
 /**
  * Basic tests for the UI comm.
  */
@@ -139,3 +165,1292 @@ fn test_ui_comm() {
         })))
         .unwrap();
 }
+
+#[test]
+fn test_ui_comm_resolve_symbol() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-resolve-symbol-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    r_task(|| {
+        harp::parse_eval_global("ark_test_resolve_data <- 1:3").unwrap();
+        harp::parse_eval_global("delayedAssign('ark_test_resolve_promise', stop('forced'))")
+            .unwrap();
+    });
+
+    let resolve = |name: &str| -> Value {
+        call_ui_method(&comm_socket, "resolveSymbol", vec![Value::from(name)])
+    };
+
+    assert_eq!(
+        resolve("mean"),
+        json!({"found": true, "environment": "package:base", "type": "function"})
+    );
+    assert_eq!(
+        resolve("ark_test_resolve_data"),
+        json!({"found": true, "environment": "global", "type": "data"})
+    );
+    assert_eq!(
+        resolve("ark_test_resolve_undefined"),
+        json!({"found": false})
+    );
+
+    // Promises are reported without being forced
+    assert_eq!(
+        resolve("ark_test_resolve_promise"),
+        json!({"found": true, "environment": "global", "type": "promise"})
+    );
+
+    r_task(|| {
+        harp::parse_eval_global("rm(ark_test_resolve_data, ark_test_resolve_promise)").unwrap();
+    });
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_evaluate_with_output() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-evaluate-with-output-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    let evaluate = |code: &str| -> Value {
+        call_ui_method(&comm_socket, "evaluateWithOutput", vec![Value::from(code)])
+    };
+
+    let result = evaluate("{ cat('hi\\n'); 42 }");
+    assert_eq!(result["output"], json!("hi"));
+    assert_eq!(result["value"], json!(42.0));
+    assert_eq!(result["error"], Value::Null);
+
+    let result = evaluate("{ warning('careful'); message('note'); TRUE }");
+    assert_eq!(result["warnings"], json!(["careful"]));
+    assert_eq!(result["messages"], json!(["note\n"]));
+    assert_eq!(result["value"], json!(true));
+
+    // Package startup messages, such as attach banners, are kept apart from
+    // other conditions
+    let result = evaluate(
+        "{ packageStartupMessage('Attaching arkfake'); message('note'); warning('careful'); TRUE }",
+    );
+    assert_eq!(result["startupMessages"], json!(["Attaching arkfake\n"]));
+    assert_eq!(result["messages"], json!(["note\n"]));
+    assert_eq!(result["warnings"], json!(["careful"]));
+
+    let result = evaluate(
+        "suppressPackageStartupMessages({ packageStartupMessage('Attaching arkfake'); TRUE })",
+    );
+    // Empty lists are serialised as `null`
+    assert_eq!(result["startupMessages"], Value::Null);
+    assert_eq!(result["messages"], Value::Null);
+
+    // Output produced before an error is kept
+    let result = evaluate("{ cat('before\\n'); stop('boom'); cat('after\\n') }");
+    assert_eq!(result["output"], json!("before"));
+    assert_eq!(result["error"], json!("boom"));
+
+    // Deprecation warnings are also reported in structured form
+    let result = evaluate(
+        "{ ark_test_old <- function() { .Deprecated('ark_test_new'); 1 }; x <- ark_test_old(); rm(ark_test_old); x }",
+    );
+    assert_eq!(result["value"], json!(1.0));
+    assert_eq!(result["deprecations"][0]["function"], json!("ark_test_old"));
+    assert_eq!(
+        result["deprecations"][0]["replacement"],
+        json!("ark_test_new")
+    );
+    assert_eq!(result["deprecations"][0]["soft"], json!(false));
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_evaluate_with_warnings() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-evaluate-with-warnings-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    let result = call_ui_method(&comm_socket, "evaluateWithWarnings", vec![Value::from(
+        "local({
+                old <- options(warn = 0)
+                on.exit(options(old))
+                f <- function(i) warning(paste('warning', i))
+                for (i in 1:3) f(i)
+                'done'
+            })",
+    )]);
+
+    // All warnings are collected in order, even though `warn = 0` defers
+    // them to the end of the top-level call
+    assert_eq!(
+        result["warnings"],
+        json!([
+            { "message": "warning 1", "call": "f(i)" },
+            { "message": "warning 2", "call": "f(i)" },
+            { "message": "warning 3", "call": "f(i)" },
+        ])
+    );
+    assert_eq!(result["value"], json!("done"));
+    assert_eq!(result["error"], Value::Null);
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_evaluate_with_data() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-evaluate-with-data-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    let evaluate = |name: &str, code: &str| {
+        call_ui_method(&comm_socket, "evaluateWithData", vec![
+            Value::from(name),
+            Value::from(code),
+        ])
+    };
+
+    // A global of the same name as a column is shadowed by the column
+    r_task(|| {
+        harp::parse_eval_global("mpg <- 'global'").unwrap();
+    });
+
+    let result = evaluate("mtcars", "mean(mpg)");
+    assert_eq!(result["value"], json!(20.090625));
+    assert_eq!(result["error"], Value::Null);
+
+    // Assignments don't leak into the global environment
+    let result = evaluate("mtcars", "mpg <- mpg * 2; max(mpg)");
+    assert_eq!(result["value"], json!(67.8));
+    let global = r_task(|| String::try_from(harp::parse_eval_global("mpg").unwrap()).unwrap());
+    assert_eq!(global, "global");
+
+    r_task(|| {
+        harp::parse_eval_global("rm(mpg)").unwrap();
+    });
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_evaluate_with_rollback() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-evaluate-with-rollback-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    let evaluate = |code: &str| -> Value {
+        call_ui_method(&comm_socket, "evaluateWithRollback", vec![json!(code)])
+    };
+    let eval_global = |code: &str| -> String {
+        r_task(|| String::try_from(harp::parse_eval_global(code).unwrap()).unwrap())
+    };
+
+    r_task(|| {
+        harp::parse_eval_global("ark_test_rollback_x <- 1").unwrap();
+        harp::parse_eval_global("ark_test_rollback_gone <- 1").unwrap();
+        harp::parse_eval_global(
+            "ark_test_rollback_f <- function() {
+                ark_test_rollback_x <<- 10
+                rm(ark_test_rollback_gone, envir = globalenv())
+                ark_test_rollback_x
+            }",
+        )
+        .unwrap();
+    });
+
+    // Assignments that can be detected statically
+    let result =
+        evaluate("{ ark_test_rollback_x <- 2; ark_test_rollback_new <- 3; ark_test_rollback_x }");
+    assert_eq!(result["value"], json!(2.0));
+    assert_eq!(
+        eval_global("paste(ark_test_rollback_x, exists('ark_test_rollback_new'))"),
+        "1 FALSE"
+    );
+
+    // Side effects of functions defined in the global environment
+    let result = evaluate("ark_test_rollback_f()");
+    assert_eq!(result["value"], json!(10.0));
+    assert_eq!(
+        eval_global("paste(ark_test_rollback_x, exists('ark_test_rollback_gone'))"),
+        "1 TRUE"
+    );
+
+    // Changes are rolled back even when evaluation fails
+    let result = evaluate("{ ark_test_rollback_x <- 3; stop('boom') }");
+    assert_eq!(result["error"], json!("boom"));
+    assert_eq!(eval_global("as.character(ark_test_rollback_x)"), "1");
+
+    r_task(|| {
+        harp::parse_eval_global(
+            "rm(ark_test_rollback_x, ark_test_rollback_gone, ark_test_rollback_f)",
+        )
+        .unwrap();
+    });
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_diff_objects() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-diff-objects-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    r_task(|| {
+        harp::parse_eval_global("ark_test_diff_a <- list(x = 1, y = 'a', z = TRUE)").unwrap();
+        harp::parse_eval_global("ark_test_diff_b <- list(x = 1, y = 'b', z = TRUE)").unwrap();
+        harp::parse_eval_global("ark_test_diff_c <- 1:3").unwrap();
+    });
+
+    let diff = |a: &str, b: &str| -> Value {
+        call_ui_method(&comm_socket, "diffObjects", vec![
+            Value::from(a),
+            Value::from(b),
+        ])
+    };
+
+    // A single differing element is reported, whichever method is used
+    let result = diff("ark_test_diff_a", "ark_test_diff_b");
+    assert_eq!(result["identical"], json!(false));
+    let differences = result["differences"].as_array().unwrap();
+    assert_eq!(differences.len(), 1);
+    assert!(differences[0].as_str().unwrap().contains("y"));
+
+    let result = diff("ark_test_diff_a", "ark_test_diff_a");
+    assert_eq!(result["identical"], json!(true));
+
+    // Incompatible types are reported as such
+    let result = diff("ark_test_diff_a", "ark_test_diff_c");
+    assert_eq!(result["method"], json!("type"));
+    assert!(result["differences"][0]
+        .as_str()
+        .unwrap()
+        .starts_with("Type mismatch"));
+
+    r_task(|| {
+        harp::parse_eval_global("rm(ark_test_diff_a, ark_test_diff_b, ark_test_diff_c)").unwrap();
+    });
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_deparse_with_tokens() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-deparse-with-tokens-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    r_task(|| {
+        harp::parse_eval_global("ark_test_deparse <- function(x) if (x) foo(x) else NULL").unwrap();
+    });
+
+    let result = call_ui_method(&comm_socket, "deparseWithTokens", vec![json!(
+        "ark_test_deparse"
+    )]);
+
+    assert_eq!(result["valid"], json!(true));
+
+    // Look up the text of each token in the deparsed code
+    let text = result["text"].as_str().unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    let tokens: Vec<(String, String)> = result["tokens"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|token| {
+            let start = &token["range"]["start"];
+            let end = &token["range"]["end"];
+            let line = lines[start["line"].as_u64().unwrap() as usize];
+            let start = start["character"].as_u64().unwrap() as usize;
+            let end = end["character"].as_u64().unwrap() as usize;
+            (
+                line[start..end].to_string(),
+                token["tokenType"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+
+    let expected: Vec<(String, String)> = vec![
+        ("function", "keyword"),
+        ("x", "parameter"),
+        ("if", "keyword"),
+        ("x", "variable"),
+        ("foo", "function"),
+        ("x", "variable"),
+        ("else", "keyword"),
+        ("NULL", "keyword"),
+    ]
+    .into_iter()
+    .map(|(text, kind)| (text.to_string(), kind.to_string()))
+    .collect();
+    assert_eq!(tokens, expected);
+
+    r_task(|| {
+        harp::parse_eval_global("rm(ark_test_deparse)").unwrap();
+    });
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_capture_print_output() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-capture-print-output-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    // The print method doesn't end its output with a newline
+    r_task(|| {
+        harp::parse_eval_global(
+            "print.ark_test_printed <- function(x, ...) {
+                cat('<printed>\n')
+                cat('no newline')
+                invisible(x)
+            }",
+        )
+        .unwrap();
+        harp::parse_eval_global(
+            "ark_test_printed <- structure(list(), class = 'ark_test_printed')",
+        )
+        .unwrap();
+    });
+
+    let result = call_ui_method(&comm_socket, "capturePrintOutput", vec![json!(
+        "ark_test_printed"
+    )]);
+
+    assert_eq!(
+        result,
+        json!({
+            "output": "<printed>\nno newline",
+            "lines": ["<printed>", "no newline"],
+        })
+    );
+
+    r_task(|| {
+        harp::parse_eval_global("rm(print.ark_test_printed, ark_test_printed)").unwrap();
+    });
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_get_and_set_function_body() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-function-body-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    r_task(|| {
+        harp::parse_eval_global("ark_test_edit <- function(x) x + 1").unwrap();
+    });
+
+    let call = |method: &str, params: Vec<Value>| -> Value {
+        call_ui_method(&comm_socket, method, params)
+    };
+
+    let result = call("getFunctionBody", vec![Value::from("ark_test_edit")]);
+    assert_eq!(result["environment"], json!("global"));
+    assert!(result["code"].as_str().unwrap().contains("x + 1"));
+
+    // The new code is kept as the function's source
+    let code = "function(x) {\n  x * 10\n}";
+    let result = call("setFunctionBody", vec![
+        Value::from("ark_test_edit"),
+        Value::from(code),
+    ]);
+    assert_eq!(result, json!({"environment": "global"}));
+
+    let result = call("getFunctionBody", vec![Value::from("ark_test_edit")]);
+    assert_eq!(result["code"], json!(code));
+    assert_eq!(result["srcref"], json!(true));
+
+    r_task(|| {
+        let value: i32 = harp::parse_eval_global("as.integer(ark_test_edit(2))")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(value, 20);
+        harp::parse_eval_global("rm(ark_test_edit)").unwrap();
+    });
+
+    // Functions in locked package environments can't be replaced
+    let id = String::from("test-id-locked");
+    let request = UiBackendRequest::CallMethod(CallMethodParams {
+        method: String::from("setFunctionBody"),
+        params: vec![Value::from("median"), Value::from("function(x, ...) 0")],
+    });
+    comm_socket
+        .incoming_tx
+        .send(CommMsg::Rpc(id, serde_json::to_value(request).unwrap()))
+        .unwrap();
+
+    let response = comm_socket
+        .outgoing_rx
+        .recv_timeout(std::time::Duration::from_secs(1))
+        .unwrap();
+    match response {
+        CommMsg::Rpc(id, result) => {
+            assert_eq!(id, "test-id-locked");
+            let reply = serde_json::from_value::<JsonRpcError>(result).unwrap();
+            assert!(reply.error.message.contains("is locked"));
+        },
+        _ => panic!("Unexpected response: {:?}", response),
+    }
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_session_info() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-session-info-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    let result = call_ui_method(&comm_socket, "sessionInfo", vec![]);
+
+    let r_version: String = r_task(|| {
+        harp::parse_eval_global("paste(R.version$major, R.version$minor, sep = '.')")
+            .unwrap()
+            .try_into()
+            .unwrap()
+    });
+    assert_eq!(result["r_version"], json!(r_version));
+
+    let packages = result["packages"].as_array().unwrap();
+    assert!(!packages.is_empty());
+    for package in packages {
+        assert!(package["package"].is_string());
+        assert!(package["version"].is_string());
+    }
+
+    let base = packages
+        .iter()
+        .find(|package| package["package"] == json!("base"))
+        .unwrap();
+    assert_eq!(base["attached"], json!(true));
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_show_notification() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-show-notification-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    // Notify from the middle of a computation that keeps R busy
+    r_task(|| {
+        harp::parse_eval_global(
+            ".ps.rpc.arkTestNotify <- function() {
+                .ps.ui.showNotification('Long job finished', 'warning')
+                Sys.sleep(0.1)
+                TRUE
+            }",
+        )
+        .unwrap();
+    });
+
+    let request = UiBackendRequest::CallMethod(CallMethodParams {
+        method: String::from("arkTestNotify"),
+        params: vec![],
+    });
+    comm_socket
+        .incoming_tx
+        .send(CommMsg::Rpc(
+            String::from("test-id-notify"),
+            serde_json::to_value(request).unwrap(),
+        ))
+        .unwrap();
+
+    // The notification reaches the frontend before the computation returns
+    let msg = comm_socket
+        .outgoing_rx
+        .recv_timeout(std::time::Duration::from_secs(1))
+        .unwrap();
+    match msg {
+        CommMsg::Data(data) => {
+            let event = serde_json::from_value::<UiFrontendEvent>(data).unwrap();
+            assert_eq!(
+                event,
+                UiFrontendEvent::ShowNotification(ShowNotificationParams {
+                    message: String::from("Long job finished"),
+                    severity: NotificationSeverity::Warning,
+                })
+            );
+        },
+        _ => panic!("Unexpected message: {msg:?}"),
+    }
+
+    let msg = comm_socket
+        .outgoing_rx
+        .recv_timeout(std::time::Duration::from_secs(1))
+        .unwrap();
+    assert!(matches!(msg, CommMsg::Rpc(id, _) if id == "test-id-notify"));
+
+    r_task(|| {
+        harp::parse_eval_global("rm(.ps.rpc.arkTestNotify)").unwrap();
+    });
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_run_tests() {
+    let has_testthat: bool = r_task(|| {
+        harp::parse_eval_global(".ps.is_installed('testthat')")
+            .unwrap()
+            .try_into()
+            .unwrap()
+    });
+    if !has_testthat {
+        return;
+    }
+
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-run-tests-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    // Fixture test files, one with a passing and a failing test and one that
+    // errors before any test can run
+    let (path, broken_path): (String, String) = r_task(|| {
+        let paths = harp::parse_eval_global(
+            r#"local({
+                path <- tempfile("test-ark-", fileext = ".R")
+                writeLines(
+                    c(
+                        "test_that('passes', expect_true(TRUE))",
+                        "test_that('fails', {",
+                        "  expect_equal(1, 2)",
+                        "})"
+                    ),
+                    path
+                )
+                broken_path <- tempfile("test-ark-broken-", fileext = ".R")
+                writeLines("test_that('never runs', {", broken_path)
+                c(path, broken_path)
+            })"#,
+        )
+        .unwrap();
+        let paths: Vec<String> = paths.try_into().unwrap();
+        (paths[0].clone(), paths[1].clone())
+    });
+
+    // Returns the streamed `test_result` events and the RPC result
+    let run = |path: &str| -> (Vec<TestResultParams>, Value) {
+        let id = String::from("test-id-run-tests");
+        let request = UiBackendRequest::CallMethod(CallMethodParams {
+            method: String::from("runTests"),
+            params: vec![Value::from(path)],
+        });
+        comm_socket
+            .incoming_tx
+            .send(CommMsg::Rpc(id, serde_json::to_value(request).unwrap()))
+            .unwrap();
+
+        let mut events = vec![];
+        loop {
+            let msg = comm_socket
+                .outgoing_rx
+                .recv_timeout(std::time::Duration::from_secs(30))
+                .unwrap();
+            match msg {
+                CommMsg::Data(data) => {
+                    if let Ok(UiFrontendEvent::TestResult(params)) = serde_json::from_value(data) {
+                        events.push(params);
+                    }
+                },
+                CommMsg::Rpc(_, result) => {
+                    let reply = serde_json::from_value::<UiBackendReply>(result).unwrap();
+                    let value = match reply {
+                        UiBackendReply::CallMethodReply(value) => value,
+                        reply => panic!("Unexpected reply: {reply:?}"),
+                    };
+                    return (events, value);
+                },
+                _ => panic!("Unexpected message: {msg:?}"),
+            }
+        }
+    };
+
+    let (events, result) = run(&path);
+    assert_eq!(events.len(), 2);
+
+    assert_eq!(events[0].test, "passes");
+    assert_eq!(events[0].status, TestResultStatus::Passed);
+    assert_eq!(events[0].message, None);
+
+    assert_eq!(events[1].test, "fails");
+    assert_eq!(events[1].status, TestResultStatus::Failed);
+    assert!(events[1].message.is_some());
+    assert_eq!(events[1].line, Some(3));
+
+    assert_eq!(result["passed"], json!(1));
+    assert_eq!(result["failed"], json!(1));
+    assert_eq!(result["load_error"], Value::Null);
+
+    // A file that doesn't parse reports a load error
+    let (events, result) = run(&broken_path);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].status, TestResultStatus::LoadError);
+    assert!(result["load_error"].is_string());
+
+    r_task(|| {
+        harp::parse_eval_global(&format!("unlink(c({path:?}, {broken_path:?}))")).unwrap();
+    });
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_dev_context() {
+    let has_pkgload: bool = r_task(|| {
+        harp::parse_eval_global(".ps.is_installed('pkgload')")
+            .unwrap()
+            .try_into()
+            .unwrap()
+    });
+    if !has_pkgload {
+        return;
+    }
+
+    let (ui_comm_tx, ui_comm_rx) = unbounded::<UiCommMessage>();
+    let mut sender = UiCommSender::new(ui_comm_tx);
+
+    let mut refresh = || -> Vec<DevContextParams> {
+        r_task(|| sender.send_refresh(String::from("> "), String::from("+ ")));
+        ui_comm_rx
+            .try_iter()
+            .filter_map(|msg| match msg {
+                UiCommMessage::Event(UiFrontendEvent::DevContext(params)) => Some(params),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // Nothing is under development yet
+    assert!(refresh().is_empty());
+
+    // Create a fixture package and load it in development mode
+    r_task(|| {
+        harp::parse_eval_global(
+            r#"local({
+                path <- file.path(tempdir(), "arkdevcontext")
+                dir.create(file.path(path, "R"), recursive = TRUE, showWarnings = FALSE)
+                writeLines(
+                    c(
+                        "Package: arkdevcontext",
+                        "Version: 0.0.1",
+                        "Title: Test",
+                        "Description: Test.",
+                        "License: MIT"
+                    ),
+                    file.path(path, "DESCRIPTION")
+                )
+                writeLines("f <- function() 1", file.path(path, "R", "f.R"))
+                pkgload::load_all(path, quiet = TRUE)
+            })"#,
+        )
+        .unwrap();
+    });
+
+    assert_eq!(refresh(), vec![DevContextParams {
+        package: String::from("arkdevcontext"),
+        active: true,
+    }]);
+
+    // Loading the package again doesn't notify the frontend again
+    r_task(|| {
+        harp::parse_eval_global(
+            "pkgload::load_all(file.path(tempdir(), 'arkdevcontext'), quiet = TRUE)",
+        )
+        .unwrap();
+    });
+    assert!(refresh().is_empty());
+
+    // Unloading the package exits the development context
+    r_task(|| {
+        harp::parse_eval_global("pkgload::unload('arkdevcontext')").unwrap();
+    });
+    assert_eq!(refresh(), vec![DevContextParams {
+        package: String::from("arkdevcontext"),
+        active: false,
+    }]);
+}
+
+#[test]
+fn test_ui_comm_source_file_encoding() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-source-file-encoding-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    let call = |method: &str, params: Vec<Value>| call_ui_method(&comm_socket, method, params);
+
+    // A Latin-1 file, and a UTF-8 file with a byte order mark
+    let (latin1, bom): (String, String) = r_task(|| {
+        let latin1: String = harp::parse_eval_global(
+            "local({
+                path <- tempfile(fileext = '.R')
+                code <- iconv('ark_latin1 <- \"caf\\u00e9\"', 'UTF-8', 'latin1', toRaw = TRUE)
+                writeBin(code[[1]], path)
+                path
+            })",
+        )
+        .unwrap()
+        .try_into()
+        .unwrap();
+        let bom: String = harp::parse_eval_global(
+            "local({
+                path <- tempfile(fileext = '.R')
+                code <- charToRaw(enc2utf8('ark_bom <- \"na\\u00efve\"'))
+                writeBin(c(as.raw(c(0xEF, 0xBB, 0xBF)), code), path)
+                path
+            })",
+        )
+        .unwrap()
+        .try_into()
+        .unwrap();
+        (latin1, bom)
+    });
+
+    assert_eq!(
+        call("detectFileEncoding", vec![json!(latin1)]),
+        json!("latin1")
+    );
+    assert_eq!(call("sourceFile", vec![json!(latin1)]), json!("latin1"));
+    assert_eq!(call("sourceFile", vec![json!(bom)]), json!("UTF-8-BOM"));
+
+    let values: Vec<String> = r_task(|| {
+        harp::parse_eval_global("c(ark_latin1, ark_bom)")
+            .unwrap()
+            .try_into()
+            .unwrap()
+    });
+    assert_eq!(values, vec!["café", "naïve"]);
+
+    // An overridden encoding wins over the detected one
+    call("setFileEncoding", vec![json!(latin1), json!("CP1252")]);
+    assert_eq!(call("sourceFile", vec![json!(latin1)]), json!("CP1252"));
+    call("setFileEncoding", vec![json!(latin1), Value::Null]);
+    assert_eq!(call("sourceFile", vec![json!(latin1)]), json!("latin1"));
+
+    r_task(|| {
+        harp::parse_eval_global("rm(ark_latin1, ark_bom)").unwrap();
+    });
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_with_status() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-with-status-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    r_task(|| {
+        harp::parse_eval_global(
+            ".ps.rpc.arkTestStatus <- function(fail) {
+                .ps.ui.withStatus('Fitting model...', {
+                    .ps.ui.withStatus('Downloading data...', if (fail) stop('boom') else 1)
+                })
+            }",
+        )
+        .unwrap();
+    });
+
+    // Pretend the frontend can show statuses
+    let set_frontend = |enabled: &str| {
+        r_task(|| {
+            let code = format!(
+                "assign('has_progress_frontend', function() {enabled}, envir = .ps.internal(ark_ns))"
+            );
+            harp::parse_eval_global(&code).unwrap();
+        })
+    };
+    r_task(|| {
+        harp::parse_eval_global("ark_old_frontend <- .ps.internal(has_progress_frontend)").unwrap();
+    });
+    set_frontend("TRUE");
+
+    // Collect the events emitted while the method runs, along with its reply
+    let call = |fail: bool| {
+        let request = UiBackendRequest::CallMethod(CallMethodParams {
+            method: String::from("arkTestStatus"),
+            params: vec![json!(fail)],
+        });
+        comm_socket
+            .incoming_tx
+            .send(CommMsg::Rpc(
+                String::from("test-id-status"),
+                serde_json::to_value(request).unwrap(),
+            ))
+            .unwrap();
+
+        let mut events = vec![];
+        loop {
+            let msg = comm_socket
+                .outgoing_rx
+                .recv_timeout(std::time::Duration::from_secs(1))
+                .unwrap();
+            match msg {
+                CommMsg::Data(data) => {
+                    events.push(serde_json::from_value::<UiFrontendEvent>(data).unwrap())
+                },
+                CommMsg::Rpc(_, reply) => return (events, reply),
+                _ => panic!("Unexpected message: {msg:?}"),
+            }
+        }
+    };
+
+    let check = |events: Vec<UiFrontendEvent>, error: bool| {
+        let (outer, inner, inner_end, outer_end) = match events.as_slice() {
+            [UiFrontendEvent::StatusStart(outer), UiFrontendEvent::StatusStart(inner), UiFrontendEvent::StatusEnd(inner_end), UiFrontendEvent::StatusEnd(outer_end)] => {
+                (outer, inner, inner_end, outer_end)
+            },
+            _ => panic!("Unexpected events: {events:?}"),
+        };
+
+        assert_eq!(outer.label, "Fitting model...");
+        assert_eq!(outer.parent, None);
+        assert_eq!(inner.label, "Downloading data...");
+        assert_eq!(inner.parent, Some(outer.id.clone()));
+        assert_ne!(inner.id, outer.id);
+
+        assert_eq!(inner_end.id, inner.id);
+        assert_eq!(outer_end.id, outer.id);
+        assert_eq!(inner_end.error, error);
+        assert_eq!(outer_end.error, error);
+    };
+
+    let (events, reply) = call(false);
+    check(events, false);
+    assert!(serde_json::from_value::<JsonRpcError>(reply).is_err());
+
+    // The end events are still emitted when the expression fails
+    let (events, reply) = call(true);
+    check(events, true);
+    serde_json::from_value::<JsonRpcError>(reply).unwrap();
+
+    // Without a frontend showing statuses, the expression is evaluated
+    // without emitting events
+    set_frontend("FALSE");
+    let (events, reply) = call(false);
+    assert!(events.is_empty());
+    match serde_json::from_value::<UiBackendReply>(reply).unwrap() {
+        UiBackendReply::CallMethodReply(value) => assert_eq!(value, json!(1)),
+        reply => panic!("Unexpected reply: {reply:?}"),
+    }
+
+    r_task(|| {
+        harp::parse_eval_global(
+            "assign('has_progress_frontend', ark_old_frontend, envir = .ps.internal(ark_ns))",
+        )
+        .unwrap();
+        harp::parse_eval_global("rm(.ps.rpc.arkTestStatus, ark_old_frontend)").unwrap();
+    });
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_download_progress() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-download-progress-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    // Simulate a download of known size followed by a chunked download of
+    // unknown size
+    r_task(|| {
+        harp::parse_eval_global(
+            ".ps.rpc.arkTestDownload <- function() {
+                sized <- .ps.internal(download_progress('Downloading data.csv', interval = 0))
+                sized$update(0)
+                sized$update(40, 100)
+                sized$update(100, 100)
+                sized$finish()
+
+                chunked <- .ps.internal(download_progress('Downloading stream', interval = 0))
+                chunked$update(512)
+                chunked$finish()
+
+                # The hook reports the progress of the original function,
+                # which gets the arguments of the call
+                ns <- .ps.internal(ark_ns)
+                the <- .ps.internal(the)
+                old_download <- the$download_file
+                old_frontend <- ns$has_progress_frontend
+                dest <- tempfile()
+                on.exit({
+                    the$download_file <- old_download
+                    assign('has_progress_frontend', old_frontend, envir = ns)
+                    unlink(dest)
+                })
+
+                assign('has_progress_frontend', function() TRUE, envir = ns)
+                the$download_file <- function(url, destfile, method, quiet = FALSE, mode = 'w', ...) {
+                    stopifnot(missing(method), isTRUE(quiet), identical(mode, 'wb'))
+                    writeBin(as.raw(1:10), destfile)
+                    invisible(0L)
+                }
+
+                status <- .ps.internal(download_file_hook('https://example.com/data.bin', dest, mode = 'wb'))
+                identical(status, 0L)
+            }",
+        )
+        .unwrap();
+    });
+
+    let request = UiBackendRequest::CallMethod(CallMethodParams {
+        method: String::from("arkTestDownload"),
+        params: vec![],
+    });
+    comm_socket
+        .incoming_tx
+        .send(CommMsg::Rpc(
+            String::from("test-id-download"),
+            serde_json::to_value(request).unwrap(),
+        ))
+        .unwrap();
+
+    let mut updates = vec![];
+    loop {
+        let msg = comm_socket
+            .outgoing_rx
+            .recv_timeout(std::time::Duration::from_secs(1))
+            .unwrap();
+        match msg {
+            CommMsg::Data(data) => match serde_json::from_value(data).unwrap() {
+                UiFrontendEvent::Progress(params) => updates.push(params),
+                event => panic!("Unexpected event: {event:?}"),
+            },
+            CommMsg::Rpc(id, data) if id == "test-id-download" => {
+                assert_eq!(data["result"], json!(true));
+                break;
+            },
+            _ => panic!("Unexpected message: {msg:?}"),
+        }
+    }
+
+    let summary: Vec<_> = updates
+        .iter()
+        .map(|update| (update.value, update.total, update.done))
+        .collect();
+    assert_eq!(summary, vec![
+        (0.0, None, false),
+        (40.0, Some(100.0), false),
+        (100.0, Some(100.0), true),
+        // Unknown total, reported without a percentage
+        (512.0, None, false),
+        (512.0, None, true),
+        // Reported around the original `download.file()`
+        (0.0, None, false),
+        (10.0, Some(10.0), true),
+    ]);
+
+    assert!(updates[..3].iter().all(|update| update.id == updates[0].id));
+    assert!(updates[3..5]
+        .iter()
+        .all(|update| update.id == updates[3].id));
+    assert_ne!(updates[0].id, updates[3].id);
+    assert_eq!(updates[0].label, "Downloading data.csv");
+    assert_eq!(updates[3].label, "Downloading stream");
+    assert_eq!(updates[5].label, "Downloading https://example.com/data.bin");
+    assert!(updates.iter().all(|update| update.units == "bytes"));
+
+    r_task(|| {
+        harp::parse_eval_global("rm(.ps.rpc.arkTestDownload)").unwrap();
+    });
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_get_viewable_info() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-get-viewable-info-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+    let ui_comm_tx = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+    r_task(|| {
+        harp::parse_eval_global(
+            "ark_test_viewable_df <- data.frame(x = 1:3, y = c('a', 'b', 'c'))
+             ark_test_viewable_scalar <- 1",
+        )
+        .unwrap();
+    });
+
+    let info =
+        |name: &str| call_ui_method(&comm_socket, "getViewableInfo", vec![Value::from(name)]);
+
+    let result = info("ark_test_viewable_df");
+    assert_eq!(result["tabular"], json!(true));
+    assert_eq!(result["class"], json!(["data.frame"]));
+    assert_eq!(result["dims"], json!([3, 2]));
+    assert_eq!(result["column_types"], json!(["integer", "character"]));
+    assert!(result["size"].as_f64().unwrap() > 0.0);
+
+    let result = info("ark_test_viewable_scalar");
+    assert_eq!(result["tabular"], json!(false));
+    assert_eq!(result["dims"], Value::Null);
+
+    r_task(|| {
+        harp::parse_eval_global("rm(ark_test_viewable_df, ark_test_viewable_scalar)").unwrap();
+    });
+
+    ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .unwrap();
+}
+
+#[test]
+fn test_ui_comm_heartbeat() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-heartbeat-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+
+    let heartbeat = Heartbeat::new(Duration::from_millis(100));
+    let ui_comm_tx =
+        UiComm::start_with_heartbeat(comm_socket.clone(), stdin_request_tx, Some(heartbeat));
+
+    let recv_ping = || -> i64 {
+        let msg = comm_socket
+            .outgoing_rx
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap();
+        match msg {
+            CommMsg::Data(data) => match serde_json::from_value(data).unwrap() {
+                UiFrontendEvent::Ping(params) => params.seq,
+                event => panic!("Unexpected event: {event:?}"),
+            },
+            _ => panic!("Unexpected message: {msg:?}"),
+        }
+    };
+
+    // A slow frontend that only answers every other ping stays connected
+    for _ in 0..3 {
+        recv_ping();
+        let seq = recv_ping();
+        let request = UiBackendRequest::Pong(PongParams { seq });
+        let reply = socket_rpc_request::<UiBackendRequest, UiBackendReply>(&comm_socket, request);
+        assert_eq!(reply, UiBackendReply::PongReply());
+    }
+
+    // A dead frontend never answers. The missed beats are tolerated, then the
+    // comm closes.
+    for _ in 0..=heartbeat.max_missed {
+        recv_ping();
+    }
+    let msg = comm_socket
+        .outgoing_rx
+        .recv_timeout(Duration::from_secs(1))
+        .unwrap();
+    assert!(matches!(msg, CommMsg::Close), "Unexpected message: {msg:?}");
+
+    // The thread has exited and no longer receives events
+    let start = Instant::now();
+    while ui_comm_tx
+        .send(UiCommMessage::Event(UiFrontendEvent::Busy(BusyParams {
+            busy: false,
+        })))
+        .is_ok()
+    {
+        assert!(start.elapsed() < Duration::from_secs(1));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn test_ui_comm_heartbeat_during_method() {
+    let comm_socket = CommSocket::new(
+        CommInitiator::FrontEnd,
+        String::from("test-ui-comm-heartbeat-method-id"),
+        String::from("positron.UI"),
+    );
+    let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+
+    let heartbeat = Heartbeat::new(Duration::from_millis(50));
+    let _ui_comm_tx =
+        UiComm::start_with_heartbeat(comm_socket.clone(), stdin_request_tx, Some(heartbeat));
+
+    r_task(|| {
+        harp::parse_eval_global(".ps.rpc.arkTestSlow <- function() { Sys.sleep(0.5); 1 }").unwrap();
+    });
+
+    let request = UiBackendRequest::CallMethod(CallMethodParams {
+        method: String::from("arkTestSlow"),
+        params: vec![],
+    });
+    comm_socket
+        .incoming_tx
+        .send(CommMsg::Rpc(
+            String::from("test-id-slow"),
+            serde_json::to_value(request).unwrap(),
+        ))
+        .unwrap();
+
+    // The comm keeps pinging, and answering pongs, while the method runs
+    let mut pings = 0;
+    let reply = loop {
+        let msg = comm_socket
+            .outgoing_rx
+            .recv_timeout(Duration::from_secs(2))
+            .unwrap();
+        match msg {
+            CommMsg::Data(data) => match serde_json::from_value(data).unwrap() {
+                UiFrontendEvent::Ping(params) => {
+                    pings += 1;
+                    let pong = UiBackendRequest::Pong(PongParams { seq: params.seq });
+                    comm_socket
+                        .incoming_tx
+                        .send(CommMsg::Rpc(
+                            format!("test-id-pong-{}", params.seq),
+                            serde_json::to_value(pong).unwrap(),
+                        ))
+                        .unwrap();
+                },
+                event => panic!("Unexpected event: {event:?}"),
+            },
+            CommMsg::Rpc(id, reply) if id == "test-id-slow" => break reply,
+            CommMsg::Rpc(_, reply) => {
+                let reply = serde_json::from_value::<UiBackendReply>(reply).unwrap();
+                assert_eq!(reply, UiBackendReply::PongReply());
+            },
+            _ => panic!("Unexpected message: {msg:?}"),
+        }
+    };
+
+    assert!(pings >= 2, "Only got {pings} pings");
+    let reply = serde_json::from_value::<UiBackendReply>(reply).unwrap();
+    assert_eq!(reply, UiBackendReply::CallMethodReply(json!(1)));
+
+    r_task(|| {
+        harp::parse_eval_global("rm(.ps.rpc.arkTestSlow)").unwrap();
+    });
+
+    comm_socket.incoming_tx.send(CommMsg::Close).unwrap();
+}
@@ -20,6 +20,7 @@ use amalthea::kernel::StreamBehavior;
 use amalthea::kernel_spec::KernelSpec;
 use amalthea::registration_file::RegistrationFile;
 use amalthea::socket::iopub::IOPubMessage;
+use amalthea::socket::shell::ExecuteQueue;
 use amalthea::socket::stdin::StdInRequest;
 use crossbeam::channel::bounded;
 use crossbeam::channel::unbounded;
@@ -55,6 +56,7 @@ fn start_kernel(connection_file: ConnectionFile, registration_file: Option<Regis
         None,
         None,
         StreamBehavior::None,
+        ExecuteQueue::default(),
         iopub_tx,
         iopub_rx,
         comm_manager_tx,
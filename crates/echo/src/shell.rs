@@ -179,6 +179,8 @@ impl ShellHandler for Shell {
             status: Status::Ok,
             execution_count: self.execution_count,
             user_expressions: serde_json::Value::Null,
+            assignment_targets: None,
+            resources: None,
         })
     }
 
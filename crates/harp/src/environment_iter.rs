@@ -17,6 +17,8 @@ pub struct EnvironmentIter {
 pub struct Binding {
     pub name: RSymbol,
     pub value: BindingValue,
+    /// Whether the binding is locked, e.g. with `lockBinding()`
+    pub locked: bool,
 }
 
 // What is this used for? Do we still need it?
@@ -65,58 +67,64 @@ impl Iterator for EnvironmentIter {
 
 impl Binding {
     pub fn new(env: &Environment, name: RSymbol) -> harp::Result<Self> {
+        let value = Self::new_value(env, name)?;
+        let locked = env.is_locked_binding(name);
+        Ok(Self {
+            name,
+            value,
+            locked,
+        })
+    }
+
+    fn new_value(env: &Environment, name: RSymbol) -> harp::Result<BindingValue> {
         unsafe {
             if env.is_active(name)? {
                 let fun = libr::R_ActiveBindingFunction(name.sexp, env.inner.sexp);
-                let value = BindingValue::Active {
+                return Ok(BindingValue::Active {
                     fun: RObject::from(fun),
-                };
-                return Ok(Self { name, value });
+                });
             };
 
             let value = env.find(name)?;
 
             if r_is_altrep(value) {
-                let value = BindingValue::Altrep {
+                return Ok(BindingValue::Altrep {
                     object: RObject::from(value),
                     data1: RObject::from(R_altrep_data1(value)),
                     data2: RObject::from(R_altrep_data2(value)),
                     has_nested_environment: BindingNestedEnvironment::new(value),
-                };
-                return Ok(Self { name, value });
+                });
             }
 
             if r_typeof(value) == PROMSXP {
                 let pr_value = PRVALUE(value);
                 if pr_value != R_UnboundValue {
                     // Forced promise
-                    return Self::new_standard(name, pr_value);
+                    return Ok(Self::new_standard(pr_value));
                 }
 
                 let code = PRCODE(value);
 
                 if let LANGSXP | SYMSXP = r_typeof(code) {
                     // Promise to a symbolic expression
-                    let value = BindingValue::Promise {
+                    return Ok(BindingValue::Promise {
                         promise: RObject::from(value),
-                    };
-                    return Ok(Self { name, value });
+                    });
                 }
 
                 // Promise to a literal expression
-                return Self::new_standard(name, code);
+                return Ok(Self::new_standard(code));
             }
 
-            Self::new_standard(name, value)
+            Ok(Self::new_standard(value))
         }
     }
 
-    fn new_standard(name: RSymbol, value: SEXP) -> harp::Result<Self> {
-        let value = BindingValue::Standard {
+    fn new_standard(value: SEXP) -> BindingValue {
+        BindingValue::Standard {
             object: RObject::from(value),
             has_nested_environment: BindingNestedEnvironment::new(value),
-        };
-        Ok(Self { name, value })
+        }
     }
 
     pub fn is_hidden(&self) -> bool {
@@ -45,6 +45,13 @@ pub struct RLocalShowErrorMessageOption {
     _raii: RLocalOptionBoolean,
 }
 
+/// Restores the RNG state, `.Random.seed` in the global environment, when
+/// dropped. If the seed didn't exist, i.e. no random numbers were drawn yet,
+/// it is removed instead.
+pub struct RLocalRandomSeed {
+    old_value: Option<crate::RObject>,
+}
+
 impl<T> RLocal<T>
 where
     T: Copy,
@@ -148,9 +155,32 @@ impl RLocalShowErrorMessageOption {
     }
 }
 
+impl RLocalRandomSeed {
+    pub fn new() -> Self {
+        let global = crate::environment::Environment::view(crate::environment::R_ENVS.global);
+        let old_value = global.find(".Random.seed").ok().map(crate::RObject::from);
+
+        Self { old_value }
+    }
+}
+
+impl Drop for RLocalRandomSeed {
+    fn drop(&mut self) {
+        let global = crate::environment::R_ENVS.global;
+
+        unsafe {
+            match &self.old_value {
+                Some(value) => crate::utils::r_envir_set(".Random.seed", value.sexp, global),
+                None => crate::utils::r_envir_remove(".Random.seed", global),
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::raii::RLocalInteractive;
+    use crate::raii::RLocalRandomSeed;
     use crate::raii::RLocalShowErrorMessageOption;
 
     #[test]
@@ -196,4 +226,41 @@ mod tests {
             assert_eq!(get(), old);
         })
     }
+
+    #[test]
+    fn test_local_random_seed() {
+        crate::r_task(|| {
+            let runif = || -> f64 {
+                harp::parse_eval_global("runif(1)")
+                    .unwrap()
+                    .try_into()
+                    .unwrap()
+            };
+            let has_seed = || -> bool {
+                harp::parse_eval_global("exists('.Random.seed', envir = globalenv())")
+                    .unwrap()
+                    .try_into()
+                    .unwrap()
+            };
+
+            harp::parse_eval_global("set.seed(1)").unwrap();
+            let baseline = runif();
+
+            harp::parse_eval_global("set.seed(1)").unwrap();
+            {
+                let _guard = RLocalRandomSeed::new();
+                runif();
+            }
+            assert_eq!(runif(), baseline);
+
+            // The seed is removed again if it didn't exist
+            harp::parse_eval_global("rm(.Random.seed, envir = globalenv())").unwrap();
+            {
+                let _guard = RLocalRandomSeed::new();
+                runif();
+                assert!(has_seed());
+            }
+            assert!(!has_seed());
+        })
+    }
 }
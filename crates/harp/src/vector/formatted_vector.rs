@@ -19,6 +19,7 @@ use libr::STRSXP;
 use crate::error::Error;
 use crate::error::Result;
 use crate::r_format_vec;
+use crate::utils::get_option;
 use crate::utils::r_assert_type;
 use crate::utils::r_inherits;
 use crate::utils::r_is_null;
@@ -45,6 +46,7 @@ pub enum FormattedVector {
     },
     Numeric {
         vector: NumericVector,
+        decimal_sep: String,
     },
     Character {
         vector: CharacterVector,
@@ -52,6 +54,7 @@ pub enum FormattedVector {
     },
     Complex {
         vector: ComplexVector,
+        decimal_sep: String,
     },
     // special
     Factor {
@@ -100,6 +103,7 @@ impl FormattedVector {
                     }),
                     REALSXP => Ok(Self::Numeric {
                         vector: NumericVector::new_unchecked(vector),
+                        decimal_sep: r_decimal_sep(),
                     }),
                     STRSXP => Ok(Self::Character {
                         vector: CharacterVector::new_unchecked(vector),
@@ -107,6 +111,7 @@ impl FormattedVector {
                     }),
                     CPLXSXP => Ok(Self::Complex {
                         vector: ComplexVector::new_unchecked(vector),
+                        decimal_sep: r_decimal_sep(),
                     }),
 
                     _ => Err(Error::UnexpectedType(r_typeof(vector), vec![
@@ -136,11 +141,17 @@ impl FormattedVector {
             FormattedVector::Raw { vector } => vector.format_elt_unchecked(index, None),
             FormattedVector::Logical { vector } => vector.format_elt_unchecked(index, None),
             FormattedVector::Integer { vector } => vector.format_elt_unchecked(index, None),
-            FormattedVector::Numeric { vector } => vector.format_elt_unchecked(index, None),
+            FormattedVector::Numeric {
+                vector,
+                decimal_sep,
+            } => apply_decimal_sep(vector.format_elt_unchecked(index, None), decimal_sep),
             FormattedVector::Character { vector, options } => {
                 vector.format_elt_unchecked(index, Some(options))
             },
-            FormattedVector::Complex { vector } => vector.format_elt_unchecked(index, None),
+            FormattedVector::Complex {
+                vector,
+                decimal_sep,
+            } => apply_decimal_sep(vector.format_elt_unchecked(index, None), decimal_sep),
             FormattedVector::Factor { vector } => vector.format_elt_unchecked(index, None),
             FormattedVector::FormattedVector { vector, options } => {
                 vector.format_elt_unchecked(index, Some(options))
@@ -157,15 +168,31 @@ impl FormattedVector {
             FormattedVector::Raw { vector } => vector.data(),
             FormattedVector::Logical { vector } => vector.data(),
             FormattedVector::Integer { vector } => vector.data(),
-            FormattedVector::Numeric { vector } => vector.data(),
+            FormattedVector::Numeric { vector, .. } => vector.data(),
             FormattedVector::Character { vector, options: _ } => vector.data(),
-            FormattedVector::Complex { vector } => vector.data(),
+            FormattedVector::Complex { vector, .. } => vector.data(),
             FormattedVector::Factor { vector } => vector.data(),
             FormattedVector::FormattedVector { vector, options: _ } => vector.data(),
         }
     }
 }
 
+/// The decimal separator used by R when printing numbers, from the `OutDec`
+/// option
+fn r_decimal_sep() -> String {
+    get_option("OutDec")
+        .try_into()
+        .unwrap_or_else(|_| String::from("."))
+}
+
+fn apply_decimal_sep(x: String, decimal_sep: &str) -> String {
+    if decimal_sep == "." {
+        x
+    } else {
+        x.replace('.', decimal_sep)
+    }
+}
+
 pub struct FormattedVectorIter<'a> {
     formatted: &'a FormattedVector,
     index: isize,
@@ -228,9 +255,11 @@ mod tests {
     use crate::fixtures::r_task;
     use crate::modules::HARP_ENV;
     use crate::r_assert_type;
+    use crate::raii::RLocalOption;
     use crate::vector::formatted_vector::FormattedVector;
     use crate::vector::formatted_vector::FormattedVectorOptions;
     use crate::vector::FormatOptions;
+    use crate::RObject;
 
     #[test]
     fn test_unconforming_format_method() {
@@ -259,6 +288,20 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_formatting_decimal_sep() {
+        r_task(|| {
+            let x = harp::parse_eval_base("c(1.5, NA, 2)").unwrap();
+
+            let formatted = FormattedVector::new(x.sexp).unwrap();
+            assert_eq!(formatted.iter().join(" "), String::from("1.5 NA 2"));
+
+            let _out_dec = RLocalOption::new("OutDec", RObject::from(",").sexp);
+            let formatted = FormattedVector::new(x.sexp).unwrap();
+            assert_eq!(formatted.iter().join(" "), String::from("1,5 NA 2"));
+        })
+    }
+
     #[test]
     fn test_formatting_option() {
         r_task(|| {
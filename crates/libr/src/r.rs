@@ -673,6 +673,10 @@ mutable_globals::generate! {
 
     pub static mut R_CStackLimit: usize;
 
+    pub static mut R_CStackStart: usize;
+
+    pub static mut R_CStackDir: std::ffi::c_int;
+
     pub static mut R_Srcref: SEXP;
 
     // -----------------------------------------------------------------------------------